@@ -0,0 +1,44 @@
+//Copyright (c) 2023 X. Gillard, A. Dubray
+//
+//Permission is hereby granted, free of charge, to any person obtaining a copy
+//of this software and associated documentation files (the "Software"), to deal
+//in the Software without restriction, including without limitation the rights
+//to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+//copies of the Software, and to permit persons to whom the Software is
+//furnished to do so, subject to the following conditions:
+//
+//The above copyright notice and this permission notice shall be included in all
+//copies or substantial portions of the Software.
+//
+//THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+//IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+//AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+//OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+//SOFTWARE.
+
+
+//! Sets the `numbers` cfg when at least one numeric type is managed, and the `managed` cfg when
+//! at least one type (numeric or boolean) is managed. The booleans are packed in words and do not go
+//! through the generic helpers of the manager, so these helpers are only compiled when there is a
+//! numeric store to use them.
+
+const NUMBERS: [&str; 14] = [
+    "U8", "U16", "U32", "U64", "U128", "USIZE", "I8", "I16", "I32", "I64", "I128", "ISIZE", "F32", "F64",
+];
+
+fn enabled(feature: &str) -> bool {
+    std::env::var_os(format!("CARGO_FEATURE_{feature}")).is_some()
+}
+
+fn main() {
+    println!("cargo::rustc-check-cfg=cfg(numbers, managed)");
+    let numbers = NUMBERS.iter().any(|name| enabled(name));
+    if numbers {
+        println!("cargo::rustc-cfg=numbers");
+    }
+    if numbers || enabled("BOOL") {
+        println!("cargo::rustc-cfg=managed");
+    }
+}
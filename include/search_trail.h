@@ -0,0 +1,137 @@
+#ifndef SEARCH_TRAIL_H
+#define SEARCH_TRAIL_H
+
+/* Generated by cbindgen from src/ffi.rs, do not edit by hand. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+typedef struct StateManager StateManager;
+
+// Returned when the operation succeeded
+#define ST_OK 0
+
+// Returned when the handle is not the handle of a resource of the manager
+#define ST_INVALID_HANDLE -1
+
+// The manager seen from C
+typedef StateManager SearchTrailManager;
+
+// Creates a new manager, to be freed with `st_manager_free`
+SearchTrailManager *st_manager_new(void);
+
+// Frees a manager created by `st_manager_new`. Does nothing if the pointer is null.
+//
+// # Safety
+//
+// The pointer must be null or returned by `st_manager_new`, and not freed yet
+void st_manager_free(SearchTrailManager *mgr);
+
+// Saves the state of the manager
+//
+// # Safety
+//
+// The manager must be valid
+void st_save_state(SearchTrailManager *mgr);
+
+// Restores the last saved state of the manager
+//
+// # Safety
+//
+// The manager must be valid, and have a saved state
+void st_restore_state(SearchTrailManager *mgr);
+
+// Returns the number of saved states
+//
+// # Safety
+//
+// The manager must be valid
+size_t st_depth(const SearchTrailManager *mgr);
+
+// Creates a new managed `size_t` and returns its handle
+//
+// # Safety
+//
+// The manager must be valid
+size_t st_manage_usize(SearchTrailManager *mgr, size_t value);
+
+// Writes the value of a managed `size_t` in `out`
+//
+// # Safety
+//
+// The manager must be valid, and `out` must be a valid pointer
+int st_get_usize(const SearchTrailManager *mgr, size_t handle, size_t *out);
+
+// Sets the value of a managed `size_t`
+//
+// # Safety
+//
+// The manager must be valid
+int st_set_usize(SearchTrailManager *mgr, size_t handle, size_t value);
+
+// Creates a new managed `int64_t` and returns its handle
+//
+// # Safety
+//
+// The manager must be valid
+size_t st_manage_i64(SearchTrailManager *mgr, int64_t value);
+
+// Writes the value of a managed `int64_t` in `out`
+//
+// # Safety
+//
+// The manager must be valid, and `out` must be a valid pointer
+int st_get_i64(const SearchTrailManager *mgr, size_t handle, int64_t *out);
+
+// Sets the value of a managed `int64_t`
+//
+// # Safety
+//
+// The manager must be valid
+int st_set_i64(SearchTrailManager *mgr, size_t handle, int64_t value);
+
+// Creates a new managed `double` and returns its handle
+//
+// # Safety
+//
+// The manager must be valid
+size_t st_manage_f64(SearchTrailManager *mgr, double value);
+
+// Writes the value of a managed `double` in `out`
+//
+// # Safety
+//
+// The manager must be valid, and `out` must be a valid pointer
+int st_get_f64(const SearchTrailManager *mgr, size_t handle, double *out);
+
+// Sets the value of a managed `double`
+//
+// # Safety
+//
+// The manager must be valid
+int st_set_f64(SearchTrailManager *mgr, size_t handle, double value);
+
+// Creates a new managed `bool` and returns its handle
+//
+// # Safety
+//
+// The manager must be valid
+size_t st_manage_bool(SearchTrailManager *mgr, bool value);
+
+// Writes the value of a managed `bool` in `out`
+//
+// # Safety
+//
+// The manager must be valid, and `out` must be a valid pointer
+int st_get_bool(const SearchTrailManager *mgr, size_t handle, bool *out);
+
+// Sets the value of a managed `bool`
+//
+// # Safety
+//
+// The manager must be valid
+int st_set_bool(SearchTrailManager *mgr, size_t handle, bool value);
+
+#endif  /* SEARCH_TRAIL_H */
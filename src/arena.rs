@@ -0,0 +1,92 @@
+//Copyright (c) 2023 X. Gillard, A. Dubray
+//
+//Permission is hereby granted, free of charge, to any person obtaining a copy
+//of this software and associated documentation files (the "Software"), to deal
+//in the Software without restriction, including without limitation the rights
+//to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+//copies of the Software, and to permit persons to whom the Software is
+//furnished to do so, subject to the following conditions:
+//
+//The above copyright notice and this permission notice shall be included in all
+//copies or substantial portions of the Software.
+//
+//THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+//IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+//AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+//OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+//SOFTWARE.
+
+
+//! Reusable storage for the trail. Solvers that create many short-lived managers (e.g. one per
+//! subproblem) can recycle the memory of the trail, the levels and the managed resources instead of
+//! going through the global allocator each time.
+
+use crate::StateManager;
+
+/// Memory used by a state manager. An arena is obtained back from a manager with
+/// `StateManager::into_arena` and keeps its capacity, so that the next manager built with
+/// `StateManager::with_arena` does not need to allocate until it grows past the previous high-water mark.
+#[derive(Debug, Default)]
+pub struct TrailArena {
+    manager: Option<StateManager>,
+}
+
+impl TrailArena {
+    /// Creates an arena able to hold `levels` levels without reallocating
+    pub fn with_capacity(levels: usize) -> Self {
+        let mut manager = StateManager::default();
+        manager.levels.reserve(levels);
+        Self {
+            manager: Some(manager),
+        }
+    }
+
+    /// Returns the number of levels the arena can hold without reallocating
+    pub fn levels_capacity(&self) -> usize {
+        self.manager.as_ref().map_or(0, |mgr| mgr.levels.capacity())
+    }
+}
+
+impl StateManager {
+    /// Creates an empty manager whose memory comes from the given arena
+    pub fn with_arena(arena: TrailArena) -> Self {
+        match arena.manager {
+            Some(mut manager) => {
+                manager.clear();
+                manager
+            }
+            None => StateManager::default(),
+        }
+    }
+
+    /// Consumes the manager and returns its memory so that it can be reused
+    pub fn into_arena(self) -> TrailArena {
+        TrailArena { manager: Some(self) }
+    }
+}
+
+#[cfg(all(test, feature = "usize"))]
+mod test_arena {
+    use crate::{SaveAndRestore, StateManager, TrailArena, UsizeManager};
+
+    #[test]
+    fn recycled_arena_keeps_capacity() {
+        let mut mgr = StateManager::with_arena(TrailArena::with_capacity(4));
+        let n = mgr.manage_usize(0);
+        for i in 0..10 {
+            mgr.save_state();
+            mgr.set_usize(n, i + 1);
+        }
+        let arena = mgr.into_arena();
+        assert!(arena.levels_capacity() >= 11);
+
+        let mut mgr = StateManager::with_arena(arena);
+        let n = mgr.manage_usize(3);
+        mgr.save_state();
+        mgr.set_usize(n, 5);
+        mgr.restore_state();
+        assert_eq!(3, mgr.get_usize(n));
+    }
+}
@@ -0,0 +1,156 @@
+//Copyright (c) 2023 X. Gillard, A. Dubray
+//
+//Permission is hereby granted, free of charge, to any person obtaining a copy
+//of this software and associated documentation files (the "Software"), to deal
+//in the Software without restriction, including without limitation the rights
+//to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+//copies of the Software, and to permit persons to whom the Software is
+//furnished to do so, subject to the following conditions:
+//
+//The above copyright notice and this permission notice shall be included in all
+//copies or substantial portions of the Software.
+//
+//THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+//IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+//AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+//OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+//SOFTWARE.
+
+
+//! A stack of assignments with their reasons, as maintained by clause-learning solvers. The
+//! assignments pushed in a level are popped when the level is restored, so that the stack always
+//! holds the assignments of the current branch, in the order in which they were made.
+
+use crate::{ReversibleUsize, StateManager, TrailBackend, UsizeManager};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// An assignment of a value to a variable, with the reason of the assignment (e.g., the clause that
+/// propagated it, or none for a decision) and the depth of the manager when it was made
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Assignment<V, T, R> {
+    /// The assigned variable
+    pub variable: V,
+    /// The value given to the variable
+    pub value: T,
+    /// Why the variable has been assigned
+    pub reason: R,
+    /// The number of saved levels when the assignment was made
+    pub level: usize,
+}
+
+/// A stack of assignments whose pushes are undone by restoring the state of the manager used to
+/// modify it.
+///
+/// #Example
+///
+/// ```
+/// use search_trail::{AssignmentStack, SaveAndRestore, StateManager};
+///
+/// let mut mgr = StateManager::default();
+/// let mut trail = AssignmentStack::new(&mut mgr);
+/// mgr.save_state();
+/// trail.push(&mut mgr, 3, true, None);
+/// trail.push(&mut mgr, 1, false, Some("c1"));
+/// assert_eq!(vec![3, 1], trail.iter(&mgr).map(|a| a.variable).collect::<Vec<_>>());
+/// mgr.restore_state();
+/// assert!(trail.is_empty(&mgr));
+/// ```
+#[derive(Debug, Clone)]
+pub struct AssignmentStack<V, T, R> {
+    /// The assignments, including the ones beyond `len` that have been popped by a restoration
+    assignments: Vec<Assignment<V, T, R>>,
+    /// Number of assignments in the current state
+    len: ReversibleUsize,
+}
+
+impl<V, T, R> AssignmentStack<V, T, R> {
+    /// Creates an empty stack, trailed by the given manager
+    pub fn new<B: TrailBackend>(mgr: &mut StateManager<B>) -> Self {
+        Self {
+            assignments: vec![],
+            len: mgr.manage_usize(0),
+        }
+    }
+
+    /// Pushes an assignment made in the current level
+    pub fn push<B: TrailBackend>(&mut self, mgr: &mut StateManager<B>, variable: V, value: T, reason: R) {
+        let len = mgr.get_usize(self.len);
+        // The assignments beyond the current length have been popped by restorations
+        self.assignments.truncate(len);
+        self.assignments.push(Assignment {
+            variable,
+            value,
+            reason,
+            level: mgr.depth,
+        });
+        mgr.set_usize(self.len, len + 1);
+    }
+
+    /// Returns the number of assignments of the current branch
+    pub fn len<B: TrailBackend>(&self, mgr: &StateManager<B>) -> usize {
+        mgr.get_usize(self.len)
+    }
+
+    /// Returns true if there is no assignment in the current branch
+    pub fn is_empty<B: TrailBackend>(&self, mgr: &StateManager<B>) -> bool {
+        self.len(mgr) == 0
+    }
+
+    /// Returns the assignments of the current branch, in the order in which they were made
+    pub fn as_slice<B: TrailBackend>(&self, mgr: &StateManager<B>) -> &[Assignment<V, T, R>] {
+        &self.assignments[..self.len(mgr)]
+    }
+
+    /// Iterates over the assignments of the current branch, in the order in which they were made
+    pub fn iter<'a, B: TrailBackend>(&'a self, mgr: &StateManager<B>) -> std::slice::Iter<'a, Assignment<V, T, R>> {
+        self.as_slice(mgr).iter()
+    }
+
+    /// Returns the most recent assignment
+    pub fn last<B: TrailBackend>(&self, mgr: &StateManager<B>) -> Option<&Assignment<V, T, R>> {
+        self.as_slice(mgr).last()
+    }
+
+    /// Returns the assignments made at the given level of the current branch
+    pub fn at_level<B: TrailBackend>(&self, mgr: &StateManager<B>, level: usize) -> &[Assignment<V, T, R>] {
+        // The levels of the assignments are increasing along the branch
+        let assignments = self.as_slice(mgr);
+        let start = assignments.partition_point(|assignment| assignment.level < level);
+        let end = assignments.partition_point(|assignment| assignment.level <= level);
+        &assignments[start..end]
+    }
+}
+
+#[cfg(test)]
+mod test_assignments {
+    use crate::{AssignmentStack, SaveAndRestore, StateManager};
+
+    #[test]
+    fn assignments_are_popped_on_restore() {
+        let mut mgr = StateManager::default();
+        let mut trail = AssignmentStack::new(&mut mgr);
+        trail.push(&mut mgr, 0, 1, "unit");
+        mgr.save_state();
+        trail.push(&mut mgr, 1, 0, "decision");
+        trail.push(&mut mgr, 2, 1, "c0");
+        mgr.save_state();
+        mgr.save_state();
+        trail.push(&mut mgr, 3, 1, "decision");
+        assert_eq!(vec![0, 1, 2, 3], trail.iter(&mgr).map(|a| a.variable).collect::<Vec<_>>());
+        assert_eq!(vec!["decision", "c0"], trail.at_level(&mgr, 1).iter().map(|a| a.reason).collect::<Vec<_>>());
+        assert!(trail.at_level(&mgr, 2).is_empty());
+        assert_eq!(3, trail.last(&mgr).unwrap().level);
+        mgr.restore_state();
+        mgr.restore_state();
+        assert_eq!(3, trail.len(&mgr));
+        trail.push(&mut mgr, 4, 0, "decision");
+        assert_eq!(vec![0, 1, 2, 4], trail.iter(&mgr).map(|a| a.variable).collect::<Vec<_>>());
+        mgr.restore_state();
+        assert_eq!(1, trail.len(&mgr));
+        assert_eq!("unit", trail.as_slice(&mgr)[0].reason);
+    }
+}
@@ -0,0 +1,149 @@
+//Copyright (c) 2023 X. Gillard, A. Dubray
+//
+//Permission is hereby granted, free of charge, to any person obtaining a copy
+//of this software and associated documentation files (the "Software"), to deal
+//in the Software without restriction, including without limitation the rights
+//to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+//copies of the Software, and to permit persons to whom the Software is
+//furnished to do so, subject to the following conditions:
+//
+//The above copyright notice and this permission notice shall be included in all
+//copies or substantial portions of the Software.
+//
+//THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+//IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+//AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+//OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+//SOFTWARE.
+
+
+//! The strategy used by a `StateManager` to save and restore the managed values. The manager delegates
+//! the storage of the values of each managed type to a `Store` provided by its `TrailBackend`. The
+//! crate provides the following backends:
+//!
+//! - `Trailing` (the default) records the previous value of a resource the first time it is modified
+//!   in a level;
+//! - `CopyOnWrite` trails like `Trailing`, but shares its stores with the clones of the manager until
+//!   they are modified;
+//! - `Copying` copies all the values at each `save_state()`;
+//! - `Recomputing` records the writes done in each level and replays them from a copy made every few
+//!   levels;
+//! - `Persistent` (feature `persistent`) trails like `Trailing`, but in persistent vectors that make
+//!   the manager cheap to clone.
+//!
+//! Custom backends can be plugged by implementing these two traits, and a solver written against
+//! `StateManager<B>` can be benchmarked with each of them.
+
+use std::fmt::Debug;
+
+use crate::StoreMemory;
+
+/// Storage of all the managed resources of a given type.
+///
+/// The levels are identified by a clock. Each call to `save_state()` creates a new level with a fresh
+/// clock, and after a call to `restore_state()` the clock of the manager is back to the clock of the
+/// restored level.
+pub trait Store<T>: Debug + Clone + Default + Send {
+    /// Creates a new resource with the given value, in the level identified by `clock`, and returns
+    /// its index
+    fn manage(&mut self, clock: usize, value: T) -> usize;
+    /// Returns the number of managed resources
+    fn len(&self) -> usize;
+    /// Returns true if the store does not manage any resource
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    /// Reserves the memory for at least `additional` more resources. Stores that can not reserve their
+    /// memory in advance do nothing.
+    fn reserve(&mut self, _additional: usize) {}
+    /// Returns the value of the resource at the given index
+    fn get(&self, id: usize) -> T;
+    /// Returns the value the resource at the given index will have once all the saved levels are
+    /// restored. Stores that can not find it without restoring return the current value.
+    fn root_value(&self, id: usize) -> T {
+        self.get(id)
+    }
+    /// Returns a reference to the value of the resource at the given index
+    fn get_ref(&self, id: usize) -> &T;
+    /// Sets the value of the resource at the given index, in the level identified by `clock`
+    fn set(&mut self, clock: usize, id: usize, value: T);
+    /// Sets the values of the resources `first..first + values.len()`, in the level identified by
+    /// `clock`. Stores can save the previous values of the whole range at once.
+    fn set_range(&mut self, clock: usize, first: usize, values: &[T])
+    where
+        T: Copy,
+    {
+        for (i, value) in values.iter().copied().enumerate() {
+            self.set(clock, first + i, value);
+        }
+    }
+    /// Called when a new level, identified by `clock`, is saved. `depth` is the number of levels saved
+    /// before this one.
+    fn save(&mut self, clock: usize, depth: usize);
+    /// Restores the values as they were before the level identified by `clock` was saved
+    fn restore(&mut self, clock: usize);
+    /// Returns the number of values that `restore(clock)` will restore. This is used to decide if the
+    /// stores are worth restoring in parallel.
+    fn restore_size(&self, _clock: usize) -> usize {
+        0
+    }
+    /// Returns the number of values currently saved on the trail. Stores that do not trail their
+    /// resources return 0.
+    fn trail_len(&self) -> usize {
+        0
+    }
+    /// Removes all the resources and saved levels, keeping the allocated memory
+    fn clear(&mut self);
+    /// Returns a store managing the current values of the resources, created in the root level (whose
+    /// clock is 0) and without any saved level
+    fn fork(&self) -> Self {
+        let mut store = Self::default();
+        for id in 0..self.len() {
+            store.manage(0, self.get(id));
+        }
+        store
+    }
+    /// Returns the memory used by the store
+    fn memory_usage(&self) -> StoreMemory;
+    /// Panics if an internal invariant of the store is violated. `clock` is the clock of the current
+    /// level of the manager.
+    fn debug_validate(&self, _clock: usize) {}
+    /// Returns the resources saved in the level identified by `clock`, with their saved value. This is
+    /// only used to format the manager, and the backends without a trail return nothing.
+    fn trailed(&self, _clock: usize) -> Vec<(usize, T)> {
+        vec![]
+    }
+    /// Returns, for each resource, the number of entries it has pushed on the trail. Stores that do
+    /// not trail their resources return an empty slice.
+    #[cfg(feature = "instrument")]
+    fn trail_counts(&self) -> &[u64] {
+        &[]
+    }
+    /// Same as `get`, without bounds checking
+    ///
+    /// # Safety
+    ///
+    /// `id` must be the index of a managed resource
+    #[cfg(feature = "unchecked")]
+    unsafe fn get_unchecked(&self, id: usize) -> T {
+        self.get(id)
+    }
+    /// Same as `set`, without bounds checking
+    ///
+    /// # Safety
+    ///
+    /// `id` must be the index of a managed resource
+    #[cfg(feature = "unchecked")]
+    unsafe fn set_unchecked(&mut self, clock: usize, id: usize, value: T) {
+        self.set(clock, id, value)
+    }
+}
+
+/// A strategy to save and restore the state of a manager, given by the kind of store used for each
+/// managed type
+pub trait TrailBackend: Debug + Clone + Default + Send {
+    /// The storage of the resources of type `T`
+    type Store<T: Copy + PartialEq + Debug + Send + Sync>: Store<T>;
+}
@@ -0,0 +1,111 @@
+//Copyright (c) 2023 X. Gillard, A. Dubray
+//
+//Permission is hereby granted, free of charge, to any person obtaining a copy
+//of this software and associated documentation files (the "Software"), to deal
+//in the Software without restriction, including without limitation the rights
+//to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+//copies of the Software, and to permit persons to whom the Software is
+//furnished to do so, subject to the following conditions:
+//
+//The above copyright notice and this permission notice shall be included in all
+//copies or substantial portions of the Software.
+//
+//THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+//IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+//AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+//OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+//SOFTWARE.
+
+//! Batches of writes. The trail already records at most one entry per resource and level, however many
+//! times it is written. A batch also defers the work that only needs the final values of a fixpoint: the
+//! refresh of the mirrors and the paranoid checks are done once, when the batch ends, instead of after
+//! every write.
+//!
+#![cfg_attr(feature = "usize", doc = "```")]
+#![cfg_attr(not(feature = "usize"), doc = "```ignore")]
+//! use search_trail::{SaveAndRestore, StateManager};
+//!
+//! let mut mgr = StateManager::default();
+//! let lb = mgr.manage(0usize);
+//! mgr.save_state();
+//! mgr.batch(|mgr| {
+//!     for bound in 1..=10 {
+//!         mgr.set(lb, bound);
+//!     }
+//! });
+//! assert_eq!(1, mgr.trail_len());
+//! mgr.restore_state();
+//! assert_eq!(0, mgr.get(lb));
+//! ```
+
+use crate::*;
+
+impl<B: TrailBackend> StateManager<B> {
+    /// Starts a batch of writes. The state can not be saved or restored until the batch is ended by
+    /// `end_batch()`.
+    pub fn begin_batch(&mut self) {
+        assert!(!self.batch, "A batch of writes is already started");
+        self.batch = true;
+    }
+
+    /// Ends the current batch of writes
+    pub fn end_batch(&mut self) {
+        assert!(self.batch, "No batch of writes is started");
+        self.batch = false;
+        self.after_write();
+    }
+
+    /// Returns true if a batch of writes is started
+    pub fn in_batch(&self) -> bool {
+        self.batch
+    }
+
+    /// Runs the given closure in a batch of writes and returns its result
+    pub fn batch<R>(&mut self, f: impl FnOnce(&mut Self) -> R) -> R {
+        self.begin_batch();
+        let result = f(self);
+        self.end_batch();
+        result
+    }
+
+    /// Does the work following each write, unless it is deferred to the end of the current batch
+    pub(crate) fn after_write(&self) {
+        if !self.batch {
+            #[cfg(feature = "mirror")]
+            self.refresh_mirrors();
+            self.paranoid_check();
+        }
+    }
+}
+
+#[cfg(all(test, feature = "usize", feature = "mirror"))]
+mod test_batch {
+    use crate::*;
+
+    #[test]
+    fn mirrors_are_refreshed_at_the_end_of_the_batch() {
+        let mut mgr = StateManager::default();
+        let n = mgr.manage(1usize);
+        let mirror = mgr.mirror(n);
+        mgr.save_state();
+        mgr.begin_batch();
+        mgr.set(n, 2);
+        mgr.set(n, 3);
+        assert!(mgr.in_batch());
+        assert_eq!(1, mirror.get());
+        mgr.end_batch();
+        assert_eq!(3, mirror.get());
+        assert_eq!(4, mgr.batch(|mgr| mgr.set(n, 4)));
+        mgr.restore_state();
+        assert_eq!(1, mirror.get());
+    }
+
+    #[test]
+    #[should_panic(expected = "already started")]
+    fn batches_can_not_be_nested() {
+        let mut mgr = StateManager::default();
+        mgr.batch(|mgr| mgr.begin_batch());
+    }
+}
@@ -0,0 +1,253 @@
+//Copyright (c) 2023 X. Gillard, A. Dubray
+//
+//Permission is hereby granted, free of charge, to any person obtaining a copy
+//of this software and associated documentation files (the "Software"), to deal
+//in the Software without restriction, including without limitation the rights
+//to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+//copies of the Software, and to permit persons to whom the Software is
+//furnished to do so, subject to the following conditions:
+//
+//The above copyright notice and this permission notice shall be included in all
+//copies or substantial portions of the Software.
+//
+//THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+//IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+//AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+//OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+//SOFTWARE.
+
+
+//! Packed storage of the managed booleans. The booleans are stored as bits of managed words, so that
+//! a boolean uses a single bit instead of a whole managed value, and the words are saved and restored
+//! by the store of the backend like any other managed value.
+
+#[cfg(feature = "snapshot")]
+use crate::snapshot::{read_column, read_usize, write_usize, Snapshot};
+#[cfg(feature = "snapshot")]
+use crate::SnapshotError;
+use crate::Store;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Managed flags of `WIDTH` bits, packed in words of 64 bits stored in `S`
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub(crate) struct PackedBools<S, const WIDTH: usize> {
+    /// The managed words
+    pub words: S,
+    /// Index of the next flag to be created
+    len: usize,
+    /// Clock of the level in which the last word was created. Flags are only added to a word created
+    /// in the current level, because setting their initial value in an older word would save it.
+    clock: usize,
+    /// Ranges of flags left unused at the end of a word, because the next flag was created in a new word
+    unused: Vec<(usize, usize)>,
+}
+
+impl<S: Default, const WIDTH: usize> Default for PackedBools<S, WIDTH> {
+    fn default() -> Self {
+        Self {
+            words: S::default(),
+            len: 0,
+            clock: 0,
+            unused: vec![],
+        }
+    }
+}
+
+impl<S: Store<u64>, const WIDTH: usize> PackedBools<S, WIDTH> {
+    /// Number of flags packed in a word
+    pub const PER_WORD: usize = 64 / WIDTH;
+    /// Mask of the bits of a flag
+    const MASK: u64 = (1 << WIDTH) - 1;
+
+    /// Creates a new flag with the given bits, in the level identified by `clock`, and returns its index
+    pub fn manage(&mut self, clock: usize, bits: u64) -> usize {
+        if self.len.is_multiple_of(Self::PER_WORD) || self.clock != clock {
+            let next = self.len.next_multiple_of(Self::PER_WORD);
+            if next > self.len {
+                self.unused.push((self.len, next));
+            }
+            self.len = next;
+            self.words.manage(clock, bits);
+            self.clock = clock;
+        } else {
+            let word = self.len / Self::PER_WORD;
+            let value = self.words.get(word) | bits << Self::shift(self.len);
+            self.words.set(clock, word, value);
+        }
+        self.len += 1;
+        self.len - 1
+    }
+
+    /// Reserves the memory for at least `additional` more flags
+    pub fn reserve(&mut self, additional: usize) {
+        self.words.reserve(additional.div_ceil(Self::PER_WORD));
+    }
+
+    /// Returns the bits of the given flag
+    pub fn get(&self, id: usize) -> u64 {
+        Self::unpack(self.words.get(id / Self::PER_WORD), id)
+    }
+
+    /// Returns the bits the given flag will have once all the saved levels are restored
+    pub fn root(&self, id: usize) -> u64 {
+        Self::unpack(self.words.root_value(id / Self::PER_WORD), id)
+    }
+
+    /// Returns the bits of the given flag in a value of its word
+    pub fn unpack(word: u64, id: usize) -> u64 {
+        (word >> Self::shift(id)) & Self::MASK
+    }
+
+    /// Returns the index of the word holding the given flag, and the value of this word after the
+    /// bits of the flag are replaced by `bits`. Returns `None` if the flag already has these bits.
+    pub fn replace(&self, id: usize, bits: u64) -> Option<(usize, u64)> {
+        let word = id / Self::PER_WORD;
+        let value = self.words.get(word);
+        let shift = Self::shift(id);
+        let new_value = (value & !(Self::MASK << shift)) | bits << shift;
+        (new_value != value).then_some((word, new_value))
+    }
+
+    /// Returns the number of flags, including the unused ones at the end of the words
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Sets the number of flags after words have been added by another manager. The next flag is
+    /// created in a new word.
+    pub fn extend_to(&mut self, len: usize) {
+        self.len = len;
+        self.clock = usize::MAX;
+    }
+
+    /// Iterates over the flags that have been created, skipping the unused ones
+    pub fn ids(&self) -> impl Iterator<Item = usize> + '_ {
+        let mut unused = self.unused.iter().peekable();
+        (0..self.len).filter(move |id| {
+            while unused.next_if(|(_, end)| end <= id).is_some() {}
+            unused.peek().is_none_or(|(start, _)| start > id)
+        })
+    }
+
+    /// Returns the flags with their current bits, created in the root level and without any saved level
+    pub fn fork(&self) -> Self {
+        Self {
+            words: self.words.fork(),
+            len: self.len,
+            clock: 0,
+            unused: self.unused.clone(),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.words.clear();
+        self.len = 0;
+        self.clock = 0;
+        self.unused.clear();
+    }
+
+    fn shift(id: usize) -> usize {
+        (id % Self::PER_WORD) * WIDTH
+    }
+}
+
+#[cfg(feature = "snapshot")]
+impl<S: Snapshot, const WIDTH: usize> Snapshot for PackedBools<S, WIDTH> {
+    fn write_snapshot(&self, out: &mut Vec<u8>) {
+        self.words.write_snapshot(out);
+        write_usize(self.len, out);
+        write_usize(self.clock, out);
+        write_usize(self.unused.len(), out);
+        self.unused.iter().for_each(|(start, _)| write_usize(*start, out));
+        self.unused.iter().for_each(|(_, end)| write_usize(*end, out));
+    }
+
+    fn read_snapshot(input: &mut &[u8]) -> Result<Self, SnapshotError> {
+        let words = S::read_snapshot(input)?;
+        let len = read_usize(input)?;
+        let clock = read_usize(input)?;
+        let unused = read_usize(input)?;
+        let starts = read_column(input, unused, read_usize)?;
+        let ends = read_column(input, unused, read_usize)?;
+        Ok(Self {
+            words,
+            len,
+            clock,
+            unused: starts.into_iter().zip(ends).collect(),
+        })
+    }
+}
+
+/// Booleans use a single bit
+pub(crate) type Bools<S> = PackedBools<S, 1>;
+/// Optional booleans use a bit telling if they are some, followed by a bit for their value
+pub(crate) type OptionBools<S> = PackedBools<S, 2>;
+
+#[cfg(test)]
+mod test_packed_bools {
+    use crate::{BoolManager, OptionBoolManager, SaveAndRestore, StateManager};
+
+    #[test]
+    fn booleans_share_words() {
+        let mut mgr = StateManager::default();
+        let flags = (0..1000).map(|i| mgr.manage_bool(i % 3 == 0)).collect::<Vec<_>>();
+        assert_eq!(15, flags[999].0 / 64);
+        mgr.save_state();
+        for (i, flag) in flags.iter().copied().enumerate() {
+            if i % 2 == 0 {
+                mgr.flip_bool(flag);
+            }
+        }
+        for (i, flag) in flags.iter().copied().enumerate() {
+            assert_eq!((i % 3 == 0) != (i % 2 == 0), mgr.get_bool(flag));
+        }
+        mgr.restore_state();
+        for (i, flag) in flags.iter().copied().enumerate() {
+            assert_eq!(i % 3 == 0, mgr.get_bool(flag));
+        }
+    }
+
+    #[test]
+    fn optional_booleans() {
+        let mut mgr = StateManager::default();
+        let values = [None, Some(false), Some(true)];
+        let flags = (0..100).map(|i| mgr.manage_option_bool(values[i % 3])).collect::<Vec<_>>();
+        mgr.save_state();
+        for flag in flags.iter().copied() {
+            match mgr.get_option_bool(flag) {
+                None => mgr.set_option_bool(flag, true),
+                Some(_) => {
+                    mgr.set_option_bool_none(flag);
+                    false
+                }
+            };
+        }
+        assert_eq!(Some(true), mgr.get_option_bool(flags[0]));
+        assert!(mgr.is_option_bool_none(flags[1]));
+        mgr.restore_state();
+        for (i, flag) in flags.iter().copied().enumerate() {
+            assert_eq!(values[i % 3], mgr.get_option_bool(flag));
+        }
+    }
+
+    #[test]
+    fn booleans_created_in_a_level_keep_their_value() {
+        let mut mgr = StateManager::default();
+        let a = mgr.manage_bool(false);
+        mgr.save_state();
+        let b = mgr.manage_bool(true);
+        mgr.save_state();
+        let c = mgr.manage_bool(true);
+        mgr.set_bool(a, true);
+        mgr.restore_state();
+        assert!(!mgr.get_bool(a));
+        assert!(mgr.get_bool(b));
+        assert!(mgr.get_bool(c));
+        mgr.restore_state();
+        assert!(mgr.get_bool(b));
+    }
+}
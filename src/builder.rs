@@ -0,0 +1,252 @@
+//Copyright (c) 2023 X. Gillard, A. Dubray
+//
+//Permission is hereby granted, free of charge, to any person obtaining a copy
+//of this software and associated documentation files (the "Software"), to deal
+//in the Software without restriction, including without limitation the rights
+//to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+//copies of the Software, and to permit persons to whom the Software is
+//furnished to do so, subject to the following conditions:
+//
+//The above copyright notice and this permission notice shall be included in all
+//copies or substantial portions of the Software.
+//
+//THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+//IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+//AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+//OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+//SOFTWARE.
+
+
+//! A builder configuring a manager before its creation: the memory reserved for each managed type and for
+//! the levels, the epsilon of the floating point values, the instrumentation enabled from the start, and
+//! the backend.
+//!
+#![cfg_attr(all(feature = "usize", feature = "bool"), doc = "```")]
+#![cfg_attr(not(all(feature = "usize", feature = "bool")), doc = "```ignore")]
+//! use search_trail::{AnyHandle, Copying, SaveAndRestore, StateManager, UsizeManager};
+//!
+//! let mut mgr = StateManager::builder()
+//!     .capacity::<usize>(1000)
+//!     .capacity::<Option<bool>>(64)
+//!     .levels_capacity(100)
+//!     .epsilon(1e-9)
+//!     .track_dirty()
+//!     .backend::<Copying>()
+//!     .build();
+//! let n = mgr.manage_usize(0);
+//! mgr.save_state();
+//! mgr.set_usize(n, 1);
+//! assert_eq!(vec![AnyHandle::Usize(n)], mgr.drain_dirty());
+//! ```
+
+use std::any::TypeId;
+use std::collections::HashMap;
+use std::marker::PhantomData;
+
+use paste::paste;
+
+use crate::*;
+
+/// A builder of `StateManager<B>`. The manager is created by `build()`, with the configured options.
+#[derive(Debug, Clone)]
+pub struct StateManagerBuilder<B: TrailBackend = Trailing> {
+    /// The number of resources to reserve for each managed type
+    capacities: HashMap<TypeId, usize>,
+    /// The number of levels to reserve
+    levels: usize,
+    epsilon: f64,
+    search_tree: bool,
+    dirty: bool,
+    state_hash: bool,
+    /// The width of the bands of depths of the statistics, if they are recorded
+    stats: Option<usize>,
+    #[cfg(feature = "journal")]
+    journal: bool,
+    backend: PhantomData<B>,
+}
+
+impl<B: TrailBackend> Default for StateManagerBuilder<B> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StateManager {
+    /// Returns a builder of a manager, using the default backend unless another one is given with
+    /// `StateManagerBuilder::backend()`
+    pub fn builder() -> StateManagerBuilder {
+        StateManagerBuilder::new()
+    }
+}
+
+impl<B: TrailBackend> StateManagerBuilder<B> {
+    /// Creates a builder of a manager with the backend `B`. Without any option, the built manager is the
+    /// same as `StateManager::<B>::new()`.
+    pub fn new() -> Self {
+        Self {
+            capacities: HashMap::new(),
+            levels: 0,
+            epsilon: 0.0,
+            search_tree: false,
+            dirty: false,
+            state_hash: false,
+            stats: None,
+            #[cfg(feature = "journal")]
+            journal: false,
+            backend: PhantomData,
+        }
+    }
+
+    /// Uses the backend `C` instead of `B`, keeping the other options
+    pub fn backend<C: TrailBackend>(self) -> StateManagerBuilder<C> {
+        StateManagerBuilder {
+            capacities: self.capacities,
+            levels: self.levels,
+            epsilon: self.epsilon,
+            search_tree: self.search_tree,
+            dirty: self.dirty,
+            state_hash: self.state_hash,
+            stats: self.stats,
+            #[cfg(feature = "journal")]
+            journal: self.journal,
+            backend: PhantomData,
+        }
+    }
+
+    /// Reserves the memory for `resources` resources of type `T` (e.g., `u32` or `Option<bool>`), so that
+    /// creating them does not reallocate the store of the type
+    pub fn capacity<T: ScopedValue + 'static>(mut self, resources: usize) -> Self {
+        self.capacities.insert(TypeId::of::<T>(), resources);
+        self
+    }
+
+    /// Reserves the memory for `levels` saved levels
+    pub fn levels_capacity(mut self, levels: usize) -> Self {
+        self.levels = levels;
+        self
+    }
+
+    /// Sets the epsilon under which the changes of floating point values are ignored (see
+    /// `StateManager::set_epsilon()`)
+    pub fn epsilon(mut self, epsilon: f64) -> Self {
+        self.epsilon = epsilon;
+        self
+    }
+
+    /// Records the tree of the explored levels (see `StateManager::record_search_tree()`)
+    pub fn record_search_tree(mut self) -> Self {
+        self.search_tree = true;
+        self
+    }
+
+    /// Tracks the modified resources (see `StateManager::track_dirty()`)
+    pub fn track_dirty(mut self) -> Self {
+        self.dirty = true;
+        self
+    }
+
+    /// Maintains the hash of the state (see `StateManager::track_state_hash()`)
+    pub fn track_state_hash(mut self) -> Self {
+        self.state_hash = true;
+        self
+    }
+
+    /// Records the statistics of the search, by bands of `band_width` depths (see
+    /// `StateManager::record_stats()`)
+    pub fn record_stats(mut self, band_width: usize) -> Self {
+        self.stats = Some(band_width);
+        self
+    }
+
+    /// Records the journal of the mutating calls (see `StateManager::record_journal()`)
+    #[cfg(feature = "journal")]
+    pub fn record_journal(mut self) -> Self {
+        self.journal = true;
+        self
+    }
+
+    /// Returns the number of resources reserved for the type `T`
+    fn capacity_of<T: 'static>(&self) -> usize {
+        self.capacities.get(&TypeId::of::<T>()).copied().unwrap_or(0)
+    }
+
+    /// Creates the manager
+    pub fn build(&self) -> StateManager<B> {
+        let mut mgr = StateManager::<B>::new();
+        mgr.levels.reserve(self.levels);
+        self.reserve(&mut mgr);
+        #[cfg(feature = "journal")]
+        if self.journal {
+            mgr.record_journal();
+        }
+        if self.epsilon != 0.0 {
+            mgr.set_epsilon(self.epsilon);
+        }
+        if self.search_tree {
+            mgr.record_search_tree();
+        }
+        if self.dirty {
+            mgr.track_dirty();
+        }
+        if self.state_hash {
+            mgr.track_state_hash();
+        }
+        if let Some(band_width) = self.stats {
+            mgr.record_stats(band_width);
+        }
+        mgr
+    }
+}
+
+macro_rules! reserve_stores {
+    ($($u:ty => $feature:literal),*) => {
+        paste! {
+            impl<B: TrailBackend> StateManagerBuilder<B> {
+                /// Reserves the memory of the stores of the manager
+                fn reserve(&self, mgr: &mut StateManager<B>) {
+                    $(
+                        #[cfg(feature = $feature)]
+                        {
+                            mgr.[<numbers _ $u>].reserve(self.capacity_of::<$u>());
+                            mgr.[<numbers_option_ $u>].reserve(self.capacity_of::<Option<$u>>());
+                        }
+                    )*
+                    #[cfg(feature = "bool")]
+                    {
+                        mgr.bools.reserve(self.capacity_of::<bool>());
+                        mgr.option_bools.reserve(self.capacity_of::<Option<bool>>());
+                    }
+                }
+            }
+        }
+    };
+}
+
+for_each_managed_type!(reserve_stores);
+
+#[cfg(all(test, feature = "usize", feature = "f64"))]
+mod test_builder {
+    use crate::*;
+
+    #[test]
+    fn built_manager_has_the_options() {
+        let builder = StateManager::builder().capacity::<f64>(100).epsilon(0.5).record_stats(2).track_state_hash();
+        let mut mgr = builder.build();
+        let x = mgr.manage_f64(1.0);
+        // The change is smaller than the epsilon
+        assert_eq!(1.0, mgr.set_f64(x, 1.2));
+        mgr.save_state();
+        assert_eq!(1, mgr.stats().unwrap().saves);
+        assert!(mgr.state_hash().is_some());
+
+        let mut copying = builder.backend::<Copying>().build();
+        let n = copying.manage_usize(0);
+        copying.save_state();
+        copying.set_usize(n, 3);
+        copying.restore_state();
+        assert_eq!((0, 0.5), (copying.get_usize(n), copying.epsilon()));
+        assert!(copying.state_hash().is_some());
+    }
+}
@@ -0,0 +1,388 @@
+//Copyright (c) 2023 X. Gillard, A. Dubray
+//
+//Permission is hereby granted, free of charge, to any person obtaining a copy
+//of this software and associated documentation files (the "Software"), to deal
+//in the Software without restriction, including without limitation the rights
+//to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+//copies of the Software, and to permit persons to whom the Software is
+//furnished to do so, subject to the following conditions:
+//
+//The above copyright notice and this permission notice shall be included in all
+//copies or substantial portions of the Software.
+//
+//THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+//IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+//AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+//OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+//SOFTWARE.
+
+
+//! Incremental checkpoints streamed to a file, for the runs that must survive a crash. A stream starts
+//! with a snapshot of the manager, and each call to `checkpoint()` appends the calls done on the manager
+//! since the previous checkpoint (recorded in its journal). A manager is resumed by replaying the
+//! complete frames of the stream on the snapshot: a frame torn by a crash is ignored, so that the
+//! resumed manager is the one of the last complete checkpoint.
+//!
+//! ```
+//! use search_trail::{Checkpointer, SaveAndRestore, StateManager, UsizeManager};
+//!
+//! let mut mgr = StateManager::default();
+//! let x = mgr.manage_usize(0);
+//! let mut stream = Checkpointer::new(Vec::new(), &mut mgr).unwrap();
+//! mgr.save_state();
+//! mgr.set_usize(x, 1);
+//! stream.checkpoint(&mut mgr).unwrap();
+//! mgr.set_usize(x, 2);
+//! // Crash before the next checkpoint
+//! let mut resumed = StateManager::resume(stream.get_ref()).unwrap();
+//! assert_eq!(1, resumed.get_usize(x));
+//! resumed.restore_state();
+//! assert_eq!(0, resumed.get_usize(x));
+//! ```
+//!
+//! The stream grows with the number of calls. To compact it, start a new stream from the resumed
+//! manager (or from the running one) in another file.
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use paste::paste;
+
+use crate::snapshot::{read_usize, write_usize, SnapshotValue};
+use crate::*;
+
+/// Identifies the checkpoint streams
+const MAGIC: &[u8; 4] = b"STRC";
+/// Version of the layout of the streams
+const VERSION: u8 = 1;
+
+/// Returns the FNV-1a hash of the bytes, used to detect the torn frames
+fn checksum(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0x811c9dc5u32, |hash, &byte| (hash ^ byte as u32).wrapping_mul(0x01000193))
+}
+
+/// Writes a frame: its length, its payload and the checksum of the payload
+fn write_frame(payload: &[u8], out: &mut impl Write) -> io::Result<()> {
+    let mut header = vec![];
+    write_usize(payload.len(), &mut header);
+    out.write_all(&header)?;
+    out.write_all(payload)?;
+    out.write_all(&checksum(payload).to_le_bytes())
+}
+
+/// Reads a frame, or returns `None` if the frame is incomplete or corrupted
+fn read_frame<'a>(input: &mut &'a [u8]) -> Option<&'a [u8]> {
+    let len = read_usize(input).ok()?;
+    if input.len() < len.checked_add(4)? {
+        return None;
+    }
+    let (payload, rest) = input.split_at(len);
+    let (sum, rest) = rest.split_at(4);
+    if checksum(payload).to_le_bytes() != sum {
+        return None;
+    }
+    *input = rest;
+    Some(payload)
+}
+
+macro_rules! journal_values {
+    ($($u:ty => $feature:literal),*) => {
+        paste! {
+            /// The names of all the types that can be managed, whose positions identify the values in the
+            /// streams, whatever the enabled features
+            const TYPES: &[&str] = &[$(stringify!($u)),*];
+
+            /// Returns the tag of the values of the given type
+            fn tag_of(name: &str, option: bool) -> u8 {
+                let position = TYPES.iter().position(|t| *t == name).unwrap_or(TYPES.len());
+                (2 * position + option as usize) as u8
+            }
+
+            impl Value {
+                /// Writes the value, preceded by the tag of its type
+                fn encode(&self, out: &mut Vec<u8>) {
+                    match self {
+                        $(
+                            #[cfg(feature = $feature)]
+                            Value::[<$u:camel>](v) => {
+                                out.push(tag_of(stringify!($u), false));
+                                v.write(out);
+                            }
+                            #[cfg(feature = $feature)]
+                            Value::[<Option $u:camel>](v) => {
+                                out.push(tag_of(stringify!($u), true));
+                                v.write(out);
+                            }
+                        )*
+                        #[cfg(feature = "bool")]
+                        Value::Bool(v) => {
+                            out.push(tag_of("bool", false));
+                            v.write(out);
+                        }
+                        #[cfg(feature = "bool")]
+                        Value::OptionBool(v) => {
+                            out.push(tag_of("bool", true));
+                            v.write(out);
+                        }
+                    }
+                }
+
+                /// Reads a value written by `encode`
+                fn decode(input: &mut &[u8]) -> Result<Self, SnapshotError> {
+                    let tag = u8::read(input)?;
+                    $(
+                        #[cfg(feature = $feature)]
+                        {
+                            if tag == tag_of(stringify!($u), false) {
+                                return Ok(Value::[<$u:camel>](SnapshotValue::read(input)?));
+                            }
+                            if tag == tag_of(stringify!($u), true) {
+                                return Ok(Value::[<Option $u:camel>](SnapshotValue::read(input)?));
+                            }
+                        }
+                    )*
+                    #[cfg(feature = "bool")]
+                    {
+                        if tag == tag_of("bool", false) {
+                            return Ok(Value::Bool(SnapshotValue::read(input)?));
+                        }
+                        if tag == tag_of("bool", true) {
+                            return Ok(Value::OptionBool(SnapshotValue::read(input)?));
+                        }
+                    }
+                    Err(SnapshotError::LayoutMismatch)
+                }
+            }
+        }
+    };
+}
+
+for_each_managed_type!(journal_values);
+
+impl JournalEntry {
+    /// Writes the entry, preceded by the tag of its kind
+    fn encode(&self, out: &mut Vec<u8>) {
+        match self {
+            JournalEntry::SaveState => out.push(0),
+            JournalEntry::RestoreState => out.push(1),
+            JournalEntry::Clear => out.push(2),
+            JournalEntry::SetEpsilon(epsilon) => {
+                out.push(3);
+                epsilon.write(out);
+            }
+            JournalEntry::Manage(value) => {
+                out.push(4);
+                value.encode(out);
+            }
+            JournalEntry::Set { id, value } => {
+                out.push(5);
+                write_usize(*id, out);
+                value.encode(out);
+            }
+            JournalEntry::SetRange { first, values } => {
+                out.push(6);
+                write_usize(*first, out);
+                write_usize(values.len(), out);
+                values.iter().for_each(|value| value.encode(out));
+            }
+            JournalEntry::SetBoolWord { option, word, value } => {
+                out.push(7);
+                option.write(out);
+                write_usize(*word, out);
+                value.write(out);
+            }
+            JournalEntry::ApplyDelta(delta) => {
+                out.push(8);
+                write_usize(delta.len(), out);
+                out.extend_from_slice(delta);
+            }
+            JournalEntry::Release { id, value } => {
+                out.push(9);
+                write_usize(*id, out);
+                value.encode(out);
+            }
+        }
+    }
+
+    /// Reads an entry written by `encode`
+    fn decode(input: &mut &[u8]) -> Result<Self, SnapshotError> {
+        Ok(match u8::read(input)? {
+            0 => JournalEntry::SaveState,
+            1 => JournalEntry::RestoreState,
+            2 => JournalEntry::Clear,
+            3 => JournalEntry::SetEpsilon(f64::read(input)?),
+            4 => JournalEntry::Manage(Value::decode(input)?),
+            5 => JournalEntry::Set { id: read_usize(input)?, value: Value::decode(input)? },
+            6 => {
+                let first = read_usize(input)?;
+                let len = read_usize(input)?;
+                let values = crate::snapshot::read_column(input, len, Value::decode)?;
+                JournalEntry::SetRange { first, values }
+            }
+            7 => JournalEntry::SetBoolWord { option: bool::read(input)?, word: read_usize(input)?, value: u64::read(input)? },
+            8 => {
+                let len = read_usize(input)?;
+                if input.len() < len {
+                    return Err(SnapshotError::Truncated);
+                }
+                let (delta, rest) = input.split_at(len);
+                *input = rest;
+                JournalEntry::ApplyDelta(delta.to_vec())
+            }
+            9 => JournalEntry::Release { id: read_usize(input)?, value: Value::decode(input)? },
+            _ => return Err(SnapshotError::Corrupted),
+        })
+    }
+}
+
+/// Streams the checkpoints of a manager to a writer
+#[derive(Debug)]
+pub struct Checkpointer<W: Write> {
+    out: W,
+}
+
+impl<W: Write> Checkpointer<W> {
+    /// Starts a stream with a snapshot of the manager, and records the journal of the manager for
+    /// the next checkpoints. The journal recorded so far, if any, is discarded.
+    pub fn new(mut out: W, mgr: &mut StateManager) -> io::Result<Self> {
+        out.write_all(MAGIC)?;
+        out.write_all(&[VERSION])?;
+        write_frame(&mgr.to_bytes(), &mut out)?;
+        out.flush()?;
+        mgr.take_journal();
+        mgr.record_journal();
+        Ok(Self { out })
+    }
+
+    /// Appends the calls done on the manager since the previous checkpoint to the stream, and flushes
+    /// it
+    pub fn checkpoint(&mut self, mgr: &mut StateManager) -> io::Result<()> {
+        let journal = mgr.take_journal().unwrap_or_default();
+        mgr.record_journal();
+        let mut payload = vec![];
+        write_usize(journal.entries.len(), &mut payload);
+        journal.entries.iter().for_each(|entry| entry.encode(&mut payload));
+        write_frame(&payload, &mut self.out)?;
+        self.out.flush()
+    }
+
+    /// Returns the writer of the stream
+    pub fn get_ref(&self) -> &W {
+        &self.out
+    }
+
+    /// Returns the writer of the stream
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+}
+
+impl Checkpointer<BufWriter<File>> {
+    /// Starts a stream in a new file, replacing the file if it exists
+    pub fn create(path: impl AsRef<Path>, mgr: &mut StateManager) -> io::Result<Self> {
+        Self::new(BufWriter::new(File::create(path)?), mgr)
+    }
+
+    /// Waits until the checkpoints written so far are stored on the disk, and not only handed to the
+    /// operating system
+    pub fn sync(&mut self) -> io::Result<()> {
+        self.out.flush()?;
+        self.out.get_ref().sync_data()
+    }
+}
+
+impl StateManager<Trailing> {
+    /// Resumes a manager from a stream written by a `Checkpointer`, in the state of the last complete
+    /// checkpoint of the stream. The handles of the checkpointed manager are valid for the resumed one.
+    pub fn resume(mut stream: &[u8]) -> Result<Self, SnapshotError> {
+        let input = &mut stream;
+        if input.len() < MAGIC.len() + 1 || &input[..MAGIC.len()] != MAGIC || input[MAGIC.len()] != VERSION {
+            return Err(SnapshotError::InvalidHeader);
+        }
+        *input = &input[MAGIC.len() + 1..];
+        let mut mgr = Self::from_bytes(read_frame(input).ok_or(SnapshotError::Truncated)?)?;
+        while let Some(mut payload) = read_frame(input) {
+            let payload = &mut payload;
+            let len = read_usize(payload)?;
+            let entries = crate::snapshot::read_column(payload, len, JournalEntry::decode)?;
+            Journal { entries }.replay_on(&mut mgr);
+        }
+        Ok(mgr)
+    }
+
+    /// Resumes a manager from a file written by a `Checkpointer`
+    pub fn resume_file(path: impl AsRef<Path>) -> io::Result<Self> {
+        let stream = std::fs::read(path)?;
+        Self::resume(&stream).map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+    }
+}
+
+#[cfg(all(test, feature = "usize", feature = "i32", feature = "f64", feature = "bool"))]
+mod test_checkpoint {
+    use crate::*;
+
+    #[test]
+    fn last_complete_checkpoint_is_resumed() {
+        let mut mgr = StateManager::default();
+        let xs = (0..10).map(|i| mgr.manage_usize(i)).collect::<Vec<_>>();
+        let mut stream = Checkpointer::new(vec![], &mut mgr).unwrap();
+        let mut expected = vec![];
+        for (depth, x) in xs.iter().copied().enumerate().take(5) {
+            mgr.save_state();
+            mgr.set_usize(x, 100);
+            let y = mgr.manage_i32(depth as i32);
+            mgr.set_i32_slice(y, &[-(depth as i32)]);
+            mgr.manage_option_f64(None);
+            mgr.manage_bool(true);
+            if depth == 2 {
+                mgr.restore_state();
+            }
+            stream.checkpoint(&mut mgr).unwrap();
+            expected.push((stream.get_ref().len(), format!("{:?}", mgr)));
+        }
+        let bytes = stream.into_inner();
+        for (len, debug) in expected.iter() {
+            assert_eq!(*debug, format!("{:?}", StateManager::resume(&bytes[..*len]).unwrap()));
+            // A torn frame is ignored
+            let torn = StateManager::resume(&bytes[..(*len + 3).min(bytes.len())]).unwrap();
+            assert_eq!(*debug, format!("{:?}", torn));
+        }
+        let mut resumed = StateManager::resume(&bytes).unwrap();
+        assert_eq!(100, resumed.get_usize(xs[4]));
+        while resumed.depth > 0 {
+            resumed.restore_state();
+        }
+        assert_eq!(4, resumed.get_usize(xs[4]));
+    }
+
+    #[test]
+    fn corrupted_frames_are_ignored() {
+        let mut mgr = StateManager::default();
+        let x = mgr.manage_usize(0);
+        let mut stream = Checkpointer::new(vec![], &mut mgr).unwrap();
+        mgr.set_usize(x, 1);
+        stream.checkpoint(&mut mgr).unwrap();
+        let mut bytes = stream.into_inner();
+        let last = bytes.len() - 5;
+        bytes[last] ^= 0xff;
+        assert_eq!(0, StateManager::resume(&bytes).unwrap().get_usize(x));
+        assert_eq!(Err(SnapshotError::InvalidHeader), StateManager::resume(b"STRL").map(|_| ()));
+    }
+
+    #[test]
+    fn files_are_resumed() {
+        let path = std::env::temp_dir().join(format!("search_trail_checkpoint_{}", std::process::id()));
+        let mut mgr = StateManager::default();
+        let x = mgr.manage_f64(0.5);
+        let mut stream = Checkpointer::create(&path, &mut mgr).unwrap();
+        mgr.set_f64(x, 1.5);
+        stream.checkpoint(&mut mgr).unwrap();
+        stream.sync().unwrap();
+        let resumed = StateManager::resume_file(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(1.5, resumed.get_f64(x));
+    }
+}
@@ -0,0 +1,227 @@
+//Copyright (c) 2023 X. Gillard, A. Dubray
+//
+//Permission is hereby granted, free of charge, to any person obtaining a copy
+//of this software and associated documentation files (the "Software"), to deal
+//in the Software without restriction, including without limitation the rights
+//to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+//copies of the Software, and to permit persons to whom the Software is
+//furnished to do so, subject to the following conditions:
+//
+//The above copyright notice and this permission notice shall be included in all
+//copies or substantial portions of the Software.
+//
+//THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+//IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+//AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+//OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+//SOFTWARE.
+
+
+//! A manager that can be shared between threads. The managers are `Send` (and `Sync`) with all the
+//! backends of the crate, so that they can be moved to the worker threads of a parallel search. When
+//! several threads must access the same manager, the `ConcurrentStateManager` lets the readers work
+//! concurrently, and gives the writers an exclusive access.
+//!
+#![cfg_attr(feature = "usize", doc = "```")]
+#![cfg_attr(not(feature = "usize"), doc = "```ignore")]
+//! use search_trail::{ConcurrentStateManager, StateManager};
+//!
+//! let mgr = ConcurrentStateManager::new(StateManager::default());
+//! let x = mgr.manage_usize(3);
+//! std::thread::scope(|s| {
+//!     s.spawn(|| assert_eq!(3, mgr.get_usize(x)));
+//!     s.spawn(|| assert_eq!(3, mgr.get_usize(x)));
+//! });
+//! mgr.save_state();
+//! mgr.set_usize(x, 4);
+//! mgr.restore_state();
+//! assert_eq!(3, mgr.get_usize(x));
+//! ```
+
+use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use paste::paste;
+
+use crate::*;
+
+/// A manager behind a reader/writer lock. The getters share the lock, while the setters, the
+/// creations of resources and the saves and restorations take it exclusively. A sequence of accesses
+/// that must see a consistent state (e.g., a heuristic reading many resources) should hold the guard
+/// returned by `read()` (or `write()`) instead of taking the lock for each access.
+#[derive(Debug)]
+pub struct ConcurrentStateManager<B: TrailBackend = Trailing> {
+    mgr: RwLock<StateManager<B>>,
+}
+
+impl<B: TrailBackend> ConcurrentStateManager<B> {
+    /// Shares the given manager
+    pub fn new(mgr: StateManager<B>) -> Self {
+        Self { mgr: RwLock::new(mgr) }
+    }
+
+    /// Locks the manager for reading, and returns a guard giving access to it
+    pub fn read(&self) -> RwLockReadGuard<'_, StateManager<B>> {
+        self.mgr.read().expect("A thread panicked while modifying the manager")
+    }
+
+    /// Locks the manager for writing, and returns a guard giving access to it
+    pub fn write(&self) -> RwLockWriteGuard<'_, StateManager<B>> {
+        self.mgr.write().expect("A thread panicked while modifying the manager")
+    }
+
+    /// Returns the manager
+    pub fn into_inner(self) -> StateManager<B> {
+        self.mgr.into_inner().expect("A thread panicked while modifying the manager")
+    }
+
+    /// Saves the current state of all managed resources
+    pub fn save_state(&self) {
+        self.write().save_state();
+    }
+
+    /// Restores the previous state of all managed resources
+    pub fn restore_state(&self) {
+        self.write().restore_state();
+    }
+}
+
+impl Default for ConcurrentStateManager<Trailing> {
+    fn default() -> Self {
+        Self::new(StateManager::default())
+    }
+}
+
+macro_rules! concurrent_accessors {
+    ($($u:ty => $feature:literal),*) => {
+        paste! {
+            impl<B: TrailBackend> ConcurrentStateManager<B> {
+                $(
+                    #[cfg(feature = $feature)]
+                    #[doc="Creates a new managed resource"]
+                    pub fn [<manage_ $u>](&self, value: $u) -> [<Reversible $u:camel>] {
+                        self.write().[<manage_ $u>](value)
+                    }
+
+                    #[cfg(feature = $feature)]
+                    #[doc="Returns the value of the resource"]
+                    pub fn [<get_ $u>](&self, id: [<Reversible $u:camel>]) -> $u {
+                        self.read().[<get_ $u>](id)
+                    }
+
+                    #[cfg(feature = $feature)]
+                    #[doc="Sets the value of the resource and returns the new value"]
+                    pub fn [<set_ $u>](&self, id: [<Reversible $u:camel>], value: $u) -> $u {
+                        self.write().[<set_ $u>](id, value)
+                    }
+
+                    #[cfg(feature = $feature)]
+                    #[doc="Creates a new managed resource"]
+                    pub fn [<manage_option_ $u>](&self, value: Option<$u>) -> [<ReversibleOption $u:camel>] {
+                        self.write().[<manage_option_ $u>](value)
+                    }
+
+                    #[cfg(feature = $feature)]
+                    #[doc="Returns the value of the resource"]
+                    pub fn [<get_option_ $u>](&self, id: [<ReversibleOption $u:camel>]) -> Option<$u> {
+                        self.read().[<get_option_ $u>](id)
+                    }
+
+                    #[cfg(feature = $feature)]
+                    #[doc="Sets the value of the resource and returns the new value"]
+                    pub fn [<set_option_ $u>](&self, id: [<ReversibleOption $u:camel>], value: Option<$u>) -> Option<$u> {
+                        self.write().[<set_option_ $u>](id, value)
+                    }
+                )*
+
+                #[cfg(feature = "bool")]
+                /// Creates a new managed boolean
+                pub fn manage_bool(&self, value: bool) -> ReversibleBool {
+                    self.write().manage_bool(value)
+                }
+
+                #[cfg(feature = "bool")]
+                /// Returns the value of the boolean
+                pub fn get_bool(&self, id: ReversibleBool) -> bool {
+                    self.read().get_bool(id)
+                }
+
+                #[cfg(feature = "bool")]
+                /// Sets the value of the boolean and returns the new value
+                pub fn set_bool(&self, id: ReversibleBool, value: bool) -> bool {
+                    self.write().set_bool(id, value)
+                }
+            }
+        }
+    };
+}
+
+for_each_managed_type!(concurrent_accessors);
+
+// The managers of all the backends can be moved to, and shared with, other threads
+const _: () = {
+    const fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<StateManager<Trailing>>();
+    assert_send_sync::<StateManager<Copying>>();
+    assert_send_sync::<StateManager<Recomputing<4>>>();
+    assert_send_sync::<StateManager<CopyOnWrite>>();
+    #[cfg(feature = "persistent")]
+    assert_send_sync::<StateManager<Persistent>>();
+    assert_send_sync::<ConcurrentStateManager>();
+};
+
+#[cfg(all(test, feature = "usize", feature = "bool"))]
+mod test_concurrent {
+    use crate::*;
+
+    #[test]
+    fn readers_see_consistent_states() {
+        let mgr = ConcurrentStateManager::new(StateManager::default());
+        let xs = (0..8).map(|_| mgr.manage_usize(0)).collect::<Vec<_>>();
+        let done = mgr.manage_bool(false);
+        std::thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| loop {
+                    // All the resources are modified at once by the writer
+                    let mgr = mgr.read();
+                    let first = mgr.get_usize(xs[0]);
+                    assert!(xs.iter().all(|&x| mgr.get_usize(x) == first));
+                    if mgr.get_bool(done) {
+                        break;
+                    }
+                });
+            }
+            for i in 1..200 {
+                let mut mgr = mgr.write();
+                mgr.save_state();
+                xs.iter().for_each(|&x| {
+                    mgr.set_usize(x, i);
+                });
+            }
+            mgr.set_bool(done, true);
+        });
+        let mut mgr = mgr.into_inner();
+        assert_eq!(199, mgr.get_usize(xs[3]));
+        while mgr.depth > 0 {
+            mgr.restore_state();
+        }
+        assert_eq!(0, mgr.get_usize(xs[3]));
+    }
+
+    #[test]
+    fn managers_are_moved_to_threads() {
+        let mut mgr = CowStateManager::new();
+        let x = mgr.manage_usize(1);
+        mgr.save_state();
+        let workers = (0..4).map(|i| {
+            let mut worker = mgr.share();
+            std::thread::spawn(move || {
+                worker.set_usize(x, i);
+                worker.restore_state();
+                worker.get_usize(x)
+            })
+        });
+        assert!(workers.collect::<Vec<_>>().into_iter().all(|worker| worker.join().unwrap() == 1));
+    }
+}
@@ -0,0 +1,205 @@
+//Copyright (c) 2023 X. Gillard, A. Dubray
+//
+//Permission is hereby granted, free of charge, to any person obtaining a copy
+//of this software and associated documentation files (the "Software"), to deal
+//in the Software without restriction, including without limitation the rights
+//to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+//copies of the Software, and to permit persons to whom the Software is
+//furnished to do so, subject to the following conditions:
+//
+//The above copyright notice and this permission notice shall be included in all
+//copies or substantial portions of the Software.
+//
+//THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+//IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+//AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+//OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+//SOFTWARE.
+
+
+//! A backend that saves the state of the manager by copying all the managed values, instead of
+//! recording the changes on a trail. When there are few managed resources that are modified very often,
+//! copying the values at each `save_state()` is cheaper than trailing every change.
+
+use std::fmt::Debug;
+
+use crate::memory::bytes;
+use crate::{StateManager, Store, StoreMemory, TrailBackend};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// A backend saving the state by copying the managed values at each call to `save_state()`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Copying;
+
+impl TrailBackend for Copying {
+    type Store<T: Copy + PartialEq + Debug + Send + Sync> = CopyStore<T>;
+}
+
+/// A state manager implementing the same traits as `StateManager`, but saving its state by copying the
+/// managed values at each call to `save_state()`. This is faster than trailing for models with few
+/// resources and a very high write rate.
+///
+/// #Example
+///
+#[cfg_attr(feature = "usize", doc = "```")]
+#[cfg_attr(not(feature = "usize"), doc = "```ignore")]
+/// use search_trail::{CopyingStateManager, SaveAndRestore, UsizeManager};
+///
+/// let mut mgr = CopyingStateManager::new();
+/// let n = mgr.manage_usize(0);
+/// mgr.save_state();
+/// mgr.set_usize(n, 42);
+/// assert_eq!(42, mgr.get_usize(n));
+/// mgr.restore_state();
+/// assert_eq!(0, mgr.get_usize(n));
+/// ```
+pub type CopyingStateManager = StateManager<Copying>;
+
+/// The values of all the managed resources of a given type, as well as copies of these values for each
+/// saved level
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct CopyStore<T> {
+    values: Vec<T>,
+    /// The copies of `values`, one for each call to `save_state()`
+    saved: Vec<Vec<T>>,
+    /// Buffers of restored copies, reused by the next calls to `save_state()`
+    #[cfg_attr(feature = "serde", serde(skip))]
+    spare: Vec<Vec<T>>,
+}
+
+impl<T> Default for CopyStore<T> {
+    fn default() -> Self {
+        Self {
+            values: vec![],
+            saved: vec![],
+            spare: vec![],
+        }
+    }
+}
+
+impl<T: Copy + PartialEq + Debug + Send + Sync> Store<T> for CopyStore<T> {
+    fn manage(&mut self, _clock: usize, value: T) -> usize {
+        self.values.push(value);
+        self.values.len() - 1
+    }
+
+    fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    fn reserve(&mut self, additional: usize) {
+        self.values.reserve(additional);
+    }
+
+    fn get(&self, id: usize) -> T {
+        self.values[id]
+    }
+
+    fn get_ref(&self, id: usize) -> &T {
+        &self.values[id]
+    }
+
+    fn root_value(&self, id: usize) -> T {
+        // Resources created after a copy are restored by the first copy that includes them
+        self.saved.iter().find(|copy| id < copy.len()).map_or(self.values[id], |copy| copy[id])
+    }
+
+    fn set(&mut self, _clock: usize, id: usize, value: T) {
+        self.values[id] = value;
+    }
+
+    fn save(&mut self, _clock: usize, _depth: usize) {
+        let mut copy = self.spare.pop().unwrap_or_default();
+        copy.clear();
+        copy.extend_from_slice(&self.values);
+        self.saved.push(copy);
+    }
+
+    fn restore(&mut self, _clock: usize) {
+        let copy = self.saved.pop().expect("Can not pop the root level of the state manager");
+        // Resources created after the copy keep their current value, as with the trail
+        self.values[..copy.len()].copy_from_slice(&copy);
+        self.spare.push(copy);
+    }
+
+    fn restore_size(&self, _clock: usize) -> usize {
+        self.saved.last().map_or(0, |copy| copy.len())
+    }
+
+    fn clear(&mut self) {
+        self.values.clear();
+        self.spare.append(&mut self.saved);
+    }
+
+    fn memory_usage(&self) -> StoreMemory {
+        let copies = self.saved.iter().chain(self.spare.iter()).map(|copy| bytes::<T>(copy.capacity())).sum::<usize>();
+        StoreMemory {
+            values: bytes::<T>(self.values.capacity()),
+            saved: copies + bytes::<Vec<T>>(self.saved.capacity() + self.spare.capacity()),
+        }
+    }
+
+    #[cfg(feature = "unchecked")]
+    unsafe fn get_unchecked(&self, id: usize) -> T {
+        *self.values.get_unchecked(id)
+    }
+
+    #[cfg(feature = "unchecked")]
+    unsafe fn set_unchecked(&mut self, _clock: usize, id: usize, value: T) {
+        *self.values.get_unchecked_mut(id) = value;
+    }
+}
+
+#[cfg(all(test, feature = "bool", feature = "f64", feature = "usize"))]
+mod test_copying_manager {
+    use crate::{BoolManager, CopyingStateManager, F64Manager, OptionUsizeManager, SaveAndRestore, StateManager, UsizeManager};
+
+    /// A small search that only relies on the manager traits
+    fn run<M: SaveAndRestore + UsizeManager + F64Manager + BoolManager>(mgr: &mut M) {
+        let n = mgr.manage_usize(1);
+        let x = mgr.manage_f64(0.5);
+        let b = mgr.manage_bool(false);
+        mgr.save_state();
+        mgr.increment_usize(n);
+        mgr.set_f64(x, 2.0);
+        mgr.save_state();
+        mgr.flip_bool(b);
+        mgr.set_usize(n, 10);
+        assert_eq!(10, mgr.get_usize(n));
+        assert!(mgr.get_bool(b));
+        mgr.restore_state();
+        assert_eq!(2, mgr.get_usize(n));
+        assert_eq!(2.0, mgr.get_f64(x));
+        assert!(!mgr.get_bool(b));
+        mgr.restore_state();
+        assert_eq!(1, mgr.get_usize(n));
+        assert_eq!(0.5, mgr.get_f64(x));
+    }
+
+    #[test]
+    fn same_behaviour_as_trailing() {
+        run(&mut StateManager::default());
+        run(&mut CopyingStateManager::new());
+    }
+
+    #[test]
+    fn resources_created_after_save_are_kept() {
+        let mut mgr = CopyingStateManager::new();
+        mgr.save_state();
+        let n = mgr.manage_option_usize(None);
+        mgr.set_option_usize(n, Some(4));
+        mgr.restore_state();
+        assert_eq!(Some(4), mgr.get_option_usize(n));
+    }
+
+    #[test]
+    #[should_panic]
+    fn can_not_pop_root_level() {
+        let mut mgr = CopyingStateManager::new();
+        mgr.restore_state();
+    }
+}
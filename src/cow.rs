@@ -0,0 +1,217 @@
+//Copyright (c) 2023 X. Gillard, A. Dubray
+//
+//Permission is hereby granted, free of charge, to any person obtaining a copy
+//of this software and associated documentation files (the "Software"), to deal
+//in the Software without restriction, including without limitation the rights
+//to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+//copies of the Software, and to permit persons to whom the Software is
+//furnished to do so, subject to the following conditions:
+//
+//The above copyright notice and this permission notice shall be included in all
+//copies or substantial portions of the Software.
+//
+//THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+//IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+//AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+//OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+//SOFTWARE.
+
+
+//! A trailing backend whose stores are shared between a manager and its clones until one of them
+//! writes. When a parallel search forks the manager at every split, most of the managed values are
+//! never modified by some of the workers and do not need to be copied for them.
+
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use crate::{NumberStore, StateManager, Store, StoreMemory, TrailBackend};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// A trailing backend whose stores are copied on the first write following a clone
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CopyOnWrite;
+
+impl TrailBackend for CopyOnWrite {
+    type Store<T: Copy + PartialEq + Debug + Send + Sync> = CowStore<T>;
+}
+
+/// A state manager that can be shared in constant time (in the number of managed resources). The
+/// managed values and the trail of each type are shared with the copy until either side modifies them.
+/// Unlike `fork()`, the copy keeps the saved levels of the manager.
+///
+/// #Example
+///
+#[cfg_attr(feature = "usize", doc = "```")]
+#[cfg_attr(not(feature = "usize"), doc = "```ignore")]
+/// use search_trail::{CowStateManager, SaveAndRestore, UsizeManager};
+///
+/// let mut mgr = CowStateManager::new();
+/// let n = mgr.manage_usize(0);
+/// mgr.save_state();
+/// let mut worker = mgr.share();
+/// worker.set_usize(n, 42);
+/// assert_eq!(0, mgr.get_usize(n));
+/// worker.restore_state();
+/// assert_eq!(0, worker.get_usize(n));
+/// ```
+pub type CowStateManager = StateManager<CopyOnWrite>;
+
+impl StateManager<CopyOnWrite> {
+    /// Returns a copy of the manager sharing its stores with this one. A store is copied the first time
+    /// it is modified by one of the managers.
+    pub fn share(&self) -> Self {
+        self.clone()
+    }
+}
+
+/// A `NumberStore` that is shared with the clones of its manager, and copied on the first write
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct CowStore<T>(Arc<NumberStore<T>>);
+
+impl<T> Default for CowStore<T> {
+    fn default() -> Self {
+        Self(Arc::new(NumberStore::default()))
+    }
+}
+
+impl<T: Copy + PartialEq + Debug + Send + Sync> CowStore<T> {
+    /// Returns the store, after copying it if it is shared with another manager
+    fn owned(&mut self) -> &mut NumberStore<T> {
+        Arc::make_mut(&mut self.0)
+    }
+}
+
+impl<T: Copy + PartialEq + Debug + Send + Sync> Store<T> for CowStore<T> {
+    fn manage(&mut self, clock: usize, value: T) -> usize {
+        self.owned().manage(clock, value)
+    }
+
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    fn reserve(&mut self, additional: usize) {
+        self.owned().reserve(additional);
+    }
+
+    fn get(&self, id: usize) -> T {
+        self.0.get(id)
+    }
+
+    fn get_ref(&self, id: usize) -> &T {
+        self.0.get_ref(id)
+    }
+
+    fn root_value(&self, id: usize) -> T {
+        self.0.root_value(id)
+    }
+
+    fn set(&mut self, clock: usize, id: usize, value: T) {
+        if self.0.get(id) != value {
+            self.owned().set(clock, id, value);
+        }
+    }
+
+    fn set_range(&mut self, clock: usize, first: usize, values: &[T]) {
+        self.owned().set_range(clock, first, values);
+    }
+
+    fn save(&mut self, _clock: usize, _depth: usize) {}
+
+    fn restore(&mut self, clock: usize) {
+        if self.0.restore_size(clock) > 0 {
+            self.owned().restore(clock);
+        }
+    }
+
+    fn restore_size(&self, clock: usize) -> usize {
+        self.0.restore_size(clock)
+    }
+
+    fn trail_len(&self) -> usize {
+        self.0.trail_len()
+    }
+
+    fn clear(&mut self) {
+        match Arc::get_mut(&mut self.0) {
+            Some(store) => store.clear(),
+            None => *self = Self::default(),
+        }
+    }
+
+    fn memory_usage(&self) -> StoreMemory {
+        self.0.memory_usage()
+    }
+
+    fn debug_validate(&self, clock: usize) {
+        self.0.debug_validate(clock)
+    }
+
+    fn trailed(&self, clock: usize) -> Vec<(usize, T)> {
+        self.0.trailed(clock)
+    }
+
+    #[cfg(feature = "instrument")]
+    fn trail_counts(&self) -> &[u64] {
+        self.0.trail_counts()
+    }
+
+    #[cfg(feature = "unchecked")]
+    unsafe fn get_unchecked(&self, id: usize) -> T {
+        self.0.get_unchecked(id)
+    }
+
+    #[cfg(feature = "unchecked")]
+    unsafe fn set_unchecked(&mut self, clock: usize, id: usize, value: T) {
+        if self.0.get_unchecked(id) != value {
+            self.owned().set_unchecked(clock, id, value);
+        }
+    }
+}
+
+#[cfg(all(test, feature = "usize", feature = "u8"))]
+mod test_cow {
+    use std::sync::Arc;
+
+    use crate::{CowStateManager, SaveAndRestore, U8Manager, UsizeManager};
+
+    #[test]
+    fn stores_are_shared_until_written() {
+        let mut mgr = CowStateManager::new();
+        let n = mgr.manage_usize(3);
+        let b = mgr.manage_u8(1);
+        mgr.save_state();
+        let mut worker = mgr.share();
+        worker.set_usize(n, 4);
+        worker.set_u8(b, 1);
+        assert!(!Arc::ptr_eq(&mgr.numbers_usize.0, &worker.numbers_usize.0));
+        assert!(Arc::ptr_eq(&mgr.numbers_u8.0, &worker.numbers_u8.0));
+        assert_eq!(3, mgr.get_usize(n));
+        assert_eq!(4, worker.get_usize(n));
+        worker.restore_state();
+        mgr.restore_state();
+        assert_eq!(3, worker.get_usize(n));
+        assert!(Arc::ptr_eq(&mgr.numbers_u8.0, &worker.numbers_u8.0));
+    }
+
+    #[test]
+    fn shares_restore_independently() {
+        let mut mgr = CowStateManager::new();
+        let n = mgr.manage_usize(0);
+        mgr.save_state();
+        mgr.set_usize(n, 1);
+        let mut worker = mgr.share();
+        mgr.set_usize(n, 2);
+        worker.save_state();
+        worker.set_usize(n, 5);
+        worker.restore_state();
+        assert_eq!(1, worker.get_usize(n));
+        worker.restore_state();
+        assert_eq!(0, worker.get_usize(n));
+        assert_eq!(2, mgr.get_usize(n));
+    }
+}
@@ -0,0 +1,337 @@
+//Copyright (c) 2023 X. Gillard, A. Dubray
+//
+//Permission is hereby granted, free of charge, to any person obtaining a copy
+//of this software and associated documentation files (the "Software"), to deal
+//in the Software without restriction, including without limitation the rights
+//to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+//copies of the Software, and to permit persons to whom the Software is
+//furnished to do so, subject to the following conditions:
+//
+//The above copyright notice and this permission notice shall be included in all
+//copies or substantial portions of the Software.
+//
+//THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+//IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+//AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+//OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+//SOFTWARE.
+
+
+//! Managed numbers of the types defined outside of this crate. Any `ManagedNumber`, i.e. any copyable type
+//! implementing the numeric operations of `num-traits`, can be managed with `manage_number()`. Its values
+//! are kept in a store of the backend of the manager, created the first time the type is managed, and are
+//! saved and restored with the values of the built-in types.
+//!
+//! The handles of these numbers are not `AnyHandle`s: they can not be named, frozen, listened to or tracked
+//! by the dirty set, and they are not written in the snapshots, journals and serialized managers.
+//!
+//! ```
+//! use std::num::Wrapping;
+//! use search_trail::{SaveAndRestore, StateManager};
+//!
+//! let mut mgr = StateManager::default();
+//! let x = mgr.manage_number(Wrapping(254u8));
+//! mgr.save_state();
+//! mgr.set_number(x, Wrapping(255));
+//! assert_eq!(Wrapping(0), mgr.increment_number(x));
+//! mgr.restore_state();
+//! assert_eq!(Wrapping(254), mgr.get_number(x));
+//! ```
+
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::marker::PhantomData;
+
+use crate::*;
+
+/// Index of a managed number of type `T`
+#[derive(Debug)]
+pub struct ReversibleNumber<T>(usize, PhantomData<T>);
+
+impl<T> Clone for ReversibleNumber<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for ReversibleNumber<T> {}
+
+impl<T> PartialEq for ReversibleNumber<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl<T> Eq for ReversibleNumber<T> {}
+
+/// The store of the numbers of a type, whose type is erased so that the stores of all the types are kept
+/// in the same map
+trait NumberColumn: Debug + Send + Sync {
+    fn save(&mut self, clock: usize, depth: usize);
+    fn restore(&mut self, clock: usize);
+    fn trail_len(&self) -> usize;
+    fn clear(&mut self);
+    fn fork(&self) -> Box<dyn NumberColumn>;
+    fn boxed_clone(&self) -> Box<dyn NumberColumn>;
+    fn debug_validate(&self, clock: usize);
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+/// The store of the numbers of type `T`
+#[derive(Debug, Clone)]
+struct Column<T, S> {
+    store: S,
+    values: PhantomData<T>,
+}
+
+impl<T: ManagedNumber, S: Store<T> + Sync + 'static> NumberColumn for Column<T, S> {
+    fn save(&mut self, clock: usize, depth: usize) {
+        self.store.save(clock, depth);
+    }
+
+    fn restore(&mut self, clock: usize) {
+        self.store.restore(clock);
+    }
+
+    fn trail_len(&self) -> usize {
+        self.store.trail_len()
+    }
+
+    fn clear(&mut self) {
+        self.store.clear();
+    }
+
+    fn fork(&self) -> Box<dyn NumberColumn> {
+        Box::new(Self {
+            store: self.store.fork(),
+            values: PhantomData,
+        })
+    }
+
+    fn boxed_clone(&self) -> Box<dyn NumberColumn> {
+        Box::new(self.clone())
+    }
+
+    fn debug_validate(&self, clock: usize) {
+        self.store.debug_validate(clock);
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// The stores of the managed numbers of the types defined outside of this crate, by type
+#[derive(Debug, Default)]
+pub(crate) struct CustomNumbers {
+    columns: HashMap<TypeId, Box<dyn NumberColumn>>,
+}
+
+impl Clone for CustomNumbers {
+    fn clone(&self) -> Self {
+        Self {
+            columns: self.columns.iter().map(|(&id, column)| (id, column.boxed_clone())).collect(),
+        }
+    }
+}
+
+impl CustomNumbers {
+    fn store<T: ManagedNumber, S: Store<T> + Sync + 'static>(&self) -> &S {
+        let column = self.columns.get(&TypeId::of::<T>()).expect("No number of this type is managed");
+        &column.as_any().downcast_ref::<Column<T, S>>().unwrap().store
+    }
+
+    fn store_mut<T: ManagedNumber, S: Store<T> + Sync + 'static>(&mut self) -> &mut S {
+        let column = self.columns.entry(TypeId::of::<T>()).or_insert_with(|| {
+            Box::new(Column::<T, S> {
+                store: S::default(),
+                values: PhantomData,
+            })
+        });
+        &mut column.as_any_mut().downcast_mut::<Column<T, S>>().unwrap().store
+    }
+
+    pub fn save(&mut self, clock: usize, depth: usize) {
+        for column in self.columns.values_mut() {
+            column.save(clock, depth);
+        }
+    }
+
+    pub fn restore(&mut self, clock: usize) {
+        for column in self.columns.values_mut() {
+            column.restore(clock);
+        }
+    }
+
+    pub fn trail_len(&self) -> usize {
+        self.columns.values().map(|column| column.trail_len()).sum()
+    }
+
+    pub fn clear(&mut self) {
+        for column in self.columns.values_mut() {
+            column.clear();
+        }
+    }
+
+    /// Returns the stores of the current values, without their saved levels
+    pub fn fork(&self) -> Self {
+        Self {
+            columns: self.columns.iter().map(|(&id, column)| (id, column.fork())).collect(),
+        }
+    }
+
+    pub fn debug_validate(&self, clock: usize) {
+        for column in self.columns.values() {
+            column.debug_validate(clock);
+        }
+    }
+}
+
+impl<B: TrailBackend> StateManager<B> {
+    /// Creates a new managed number of a type that is not managed by a dedicated method
+    pub fn manage_number<T: ManagedNumber>(&mut self, value: T) -> ReversibleNumber<T>
+    where
+        B::Store<T>: Sync + 'static,
+    {
+        self.materialize();
+        let clock = self.clock;
+        let id = self.custom.store_mut::<T, B::Store<T>>().manage(clock, value);
+        self.paranoid_check();
+        ReversibleNumber(id, PhantomData)
+    }
+
+    /// Returns the value of a managed number
+    pub fn get_number<T: ManagedNumber>(&self, handle: ReversibleNumber<T>) -> T
+    where
+        B::Store<T>: Sync + 'static,
+    {
+        self.custom.store::<T, B::Store<T>>().get(handle.0)
+    }
+
+    /// Sets the value of a managed number and returns the new value
+    pub fn set_number<T: ManagedNumber>(&mut self, handle: ReversibleNumber<T>, value: T) -> T
+    where
+        B::Store<T>: Sync + 'static,
+    {
+        if self.get_number(handle) != value {
+            self.materialize();
+            let clock = self.clock;
+            self.custom.store_mut::<T, B::Store<T>>().set(clock, handle.0, value);
+            self.after_write();
+        }
+        value
+    }
+
+    /// Adds one to a managed number and returns the new value
+    pub fn increment_number<T: ManagedNumber>(&mut self, handle: ReversibleNumber<T>) -> T
+    where
+        B::Store<T>: Sync + 'static,
+    {
+        self.set_number(handle, self.get_number(handle) + T::one())
+    }
+
+    /// Subtracts one from a managed number and returns the new value
+    pub fn decrement_number<T: ManagedNumber>(&mut self, handle: ReversibleNumber<T>) -> T
+    where
+        B::Store<T>: Sync + 'static,
+    {
+        self.set_number(handle, self.get_number(handle) - T::one())
+    }
+}
+
+#[cfg(test)]
+mod test_custom {
+    use std::ops::{Add, Div, Mul, Rem, Sub};
+
+    use num_traits::{Num, One, Zero};
+
+    use crate::*;
+
+    /// A fixed point number with two decimals, defined outside of the managed types
+    #[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+    struct Cents(i64);
+
+    macro_rules! cents_ops {
+        ($($op:ident $f:ident $e:expr),*) => {
+            $(
+                impl $op for Cents {
+                    type Output = Cents;
+
+                    fn $f(self, rhs: Cents) -> Cents {
+                        let f: fn(i64, i64) -> i64 = $e;
+                        Cents(f(self.0, rhs.0))
+                    }
+                }
+            )*
+        };
+    }
+
+    cents_ops!(Add add |a, b| a + b, Sub sub |a, b| a - b, Mul mul |a, b| a * b / 100, Div div |a, b| a * 100 / b, Rem rem |a, b| a % b);
+
+    impl Zero for Cents {
+        fn zero() -> Self {
+            Cents(0)
+        }
+
+        fn is_zero(&self) -> bool {
+            self.0 == 0
+        }
+    }
+
+    impl One for Cents {
+        fn one() -> Self {
+            Cents(100)
+        }
+    }
+
+    impl Num for Cents {
+        type FromStrRadixErr = std::num::ParseIntError;
+
+        fn from_str_radix(s: &str, radix: u32) -> Result<Self, Self::FromStrRadixErr> {
+            i64::from_str_radix(s, radix).map(Cents)
+        }
+    }
+
+    fn user_numbers_are_restored_with<B: TrailBackend>()
+    where
+        B::Store<Cents>: Sync + 'static,
+        B::Store<i128>: Sync + 'static,
+    {
+        let mut mgr = StateManager::<B>::new();
+        let x = mgr.manage_number(Cents(150));
+        let y = mgr.manage_number(-3i128);
+        mgr.save_state();
+        assert_eq!(Cents(250), mgr.increment_number(x));
+        mgr.set_number(y, 7);
+        mgr.save_state();
+        let z = mgr.manage_number(Cents(0));
+        mgr.set_number(x, Cents(1000));
+        mgr.set_number(z, Cents(5));
+        mgr.restore_state();
+        assert_eq!((Cents(250), 7), (mgr.get_number(x), mgr.get_number(y)));
+        let mut fork = mgr.fork();
+        assert_eq!(Cents(150), fork.decrement_number(x));
+        mgr.restore_state();
+        assert_eq!((Cents(150), -3), (mgr.get_number(x), mgr.get_number(y)));
+        assert_eq!(Cents(150), fork.get_number(x));
+        mgr.debug_validate();
+    }
+
+    #[test]
+    fn user_numbers_are_restored() {
+        user_numbers_are_restored_with::<Trailing>();
+        user_numbers_are_restored_with::<Copying>();
+        user_numbers_are_restored_with::<CopyOnWrite>();
+        user_numbers_are_restored_with::<Recomputing<2>>();
+        #[cfg(feature = "persistent")]
+        user_numbers_are_restored_with::<Persistent>();
+    }
+}
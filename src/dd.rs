@@ -0,0 +1,192 @@
+//Copyright (c) 2023 X. Gillard, A. Dubray
+//
+//Permission is hereby granted, free of charge, to any person obtaining a copy
+//of this software and associated documentation files (the "Software"), to deal
+//in the Software without restriction, including without limitation the rights
+//to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+//copies of the Software, and to permit persons to whom the Software is
+//furnished to do so, subject to the following conditions:
+//
+//The above copyright notice and this permission notice shall be included in all
+//copies or substantial portions of the Software.
+//
+//THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+//IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+//AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+//OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+//SOFTWARE.
+
+
+//! An adapter for the branch-and-bound solvers based on decision diagrams, such as ddo. These solvers
+//! compile restricted and relaxed diagrams from the subproblems of a fringe, which is not explored in
+//! depth-first order: the state of each node is thus a fork of the manager rather than a level of its
+//! trail. With the `CopyOnWrite` backend (the default) or the `Persistent` one, the forks share the
+//! stores that their transitions do not modify.
+//!
+#![cfg_attr(feature = "usize", doc = "```")]
+#![cfg_attr(not(feature = "usize"), doc = "```ignore")]
+//! use search_trail::{CowStateManager, DdState, UsizeManager};
+//!
+//! let mut mgr = CowStateManager::new();
+//! let capacity = mgr.manage_usize(10);
+//! let root = DdState::fork(&mgr);
+//! // Restriction: the children of the root, one per decision
+//! let take = root.transition(|mgr| { mgr.set_usize(capacity, 4); });
+//! let skip = root.transition(|_| ());
+//! assert_eq!(1, take.layer());
+//! // Relaxation: the merged node keeps the largest capacity
+//! let merged = DdState::merge([&take, &skip], |into, other| {
+//!     let best = into.get_usize(capacity).max(other.get_usize(capacity));
+//!     into.set_usize(capacity, best);
+//! }).unwrap();
+//! assert_eq!(10, merged.manager().get_usize(capacity));
+//! // The solver resumes its search from a node of the cutset
+//! take.restore(&mut mgr);
+//! assert_eq!(4, mgr.get_usize(capacity));
+//! ```
+
+use crate::{CopyOnWrite, StateManager, TrailBackend};
+
+/// The state of a node of a decision diagram, holding a fork of the manager
+#[derive(Debug, Clone)]
+pub struct DdState<B: TrailBackend = CopyOnWrite> {
+    /// The values of the managed resources in this node
+    mgr: StateManager<B>,
+    /// The layer of the node in the diagram, counted from the state it has been forked from
+    layer: usize,
+}
+
+impl<B: TrailBackend> DdState<B> {
+    /// Returns the state of the root of a diagram, at the current state of the manager
+    pub fn fork(mgr: &StateManager<B>) -> Self {
+        Self {
+            mgr: mgr.clone(),
+            layer: 0,
+        }
+    }
+
+    /// Sets the manager to this state, for instance to explore a node of the cutset
+    pub fn restore(&self, mgr: &mut StateManager<B>) {
+        mgr.clone_from(&self.mgr);
+    }
+
+    /// Returns the state reached by applying the given transition to this state, in the next layer
+    pub fn transition(&self, transition: impl FnOnce(&mut StateManager<B>)) -> Self {
+        let mut mgr = self.mgr.clone();
+        transition(&mut mgr);
+        Self {
+            mgr,
+            layer: self.layer + 1,
+        }
+    }
+
+    /// Returns the merge of the given states, used by the relaxations. The merged state starts from the
+    /// first one, and `merge` is called with each of the others. Returns `None` if there is no state.
+    pub fn merge<'a>(
+        states: impl IntoIterator<Item = &'a Self>,
+        mut merge: impl FnMut(&mut StateManager<B>, &StateManager<B>),
+    ) -> Option<Self>
+    where
+        B: 'a,
+    {
+        let mut states = states.into_iter();
+        let mut merged = states.next()?.clone();
+        for state in states {
+            merge(&mut merged.mgr, &state.mgr);
+            merged.layer = merged.layer.max(state.layer);
+        }
+        Some(merged)
+    }
+
+    /// Returns the manager holding the values of this state
+    pub fn manager(&self) -> &StateManager<B> {
+        &self.mgr
+    }
+
+    /// Returns the manager holding the values of this state, to use it as the root of a subproblem
+    pub fn into_manager(self) -> StateManager<B> {
+        self.mgr
+    }
+
+    /// Returns the layer of the state, counted from the state it has been forked from
+    pub fn layer(&self) -> usize {
+        self.layer
+    }
+}
+
+#[cfg(all(test, feature = "usize"))]
+mod test_dd {
+    use crate::*;
+
+    const WEIGHTS: [usize; 5] = [4, 3, 5, 2, 6];
+    const PROFITS: [usize; 5] = [10, 7, 12, 3, 13];
+
+    /// Compiles a diagram for a knapsack of the given capacity, with at most `width` nodes per layer,
+    /// and returns the best profit found in its last layer. The nodes beyond the width are merged when
+    /// `relaxed`, and dropped otherwise.
+    fn compile(capacity: usize, width: usize, relaxed: bool) -> usize {
+        let mut mgr = CowStateManager::new();
+        let remaining = mgr.manage_usize(capacity);
+        let profit = mgr.manage_usize(0);
+        let mut layer = vec![DdState::fork(&mgr)];
+        for (&weight, &gain) in WEIGHTS.iter().zip(PROFITS.iter()) {
+            let mut next = vec![];
+            for node in layer.iter() {
+                next.push(node.transition(|_| ()));
+                let left = node.manager().get_usize(remaining);
+                if left >= weight {
+                    next.push(node.transition(|mgr| {
+                        mgr.set_usize(remaining, left - weight);
+                        let p = mgr.get_usize(profit);
+                        mgr.set_usize(profit, p + gain);
+                    }));
+                }
+            }
+            next.sort_by_key(|node| std::cmp::Reverse(node.manager().get_usize(profit)));
+            if next.len() > width {
+                let dropped = next.split_off(width - 1);
+                if relaxed {
+                    next.push(DdState::merge(&dropped, |into, other| {
+                        for handle in [remaining, profit] {
+                            let best = into.get_usize(handle).max(other.get_usize(handle));
+                            into.set_usize(handle, best);
+                        }
+                    }).unwrap());
+                } else {
+                    next.push(dropped[0].clone());
+                }
+            }
+            layer = next;
+        }
+        assert!(layer.iter().all(|node| node.layer() == WEIGHTS.len()));
+        layer.iter().map(|node| node.manager().get_usize(profit)).max().unwrap()
+    }
+
+    #[test]
+    fn bounds_enclose_the_optimum() {
+        let exact = compile(10, usize::MAX, false);
+        assert_eq!(23, exact);
+        for width in 1..4 {
+            assert!(compile(10, width, false) <= exact);
+            assert!(compile(10, width, true) >= exact);
+        }
+    }
+
+    #[test]
+    fn forks_do_not_modify_the_manager() {
+        let mut mgr = CowStateManager::new();
+        let x = mgr.manage_usize(1);
+        let root = DdState::fork(&mgr);
+        let child = root.transition(|mgr| {
+            mgr.set_usize(x, 2);
+        });
+        assert_eq!(1, root.manager().get_usize(x));
+        assert_eq!(1, mgr.get_usize(x));
+        child.restore(&mut mgr);
+        assert_eq!(2, mgr.get_usize(x));
+        assert_eq!(2, child.into_manager().get_usize(x));
+        assert!(DdState::<CopyOnWrite>::merge([], |_, _| ()).is_none());
+    }
+}
@@ -0,0 +1,687 @@
+//Copyright (c) 2023 X. Gillard, A. Dubray
+//
+//Permission is hereby granted, free of charge, to any person obtaining a copy
+//of this software and associated documentation files (the "Software"), to deal
+//in the Software without restriction, including without limitation the rights
+//to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+//copies of the Software, and to permit persons to whom the Software is
+//furnished to do so, subject to the following conditions:
+//
+//The above copyright notice and this permission notice shall be included in all
+//copies or substantial portions of the Software.
+//
+//THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+//IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+//AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+//OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+//SOFTWARE.
+
+
+//! Incremental synchronization of managers with the same layout. The modifications done by a manager
+//! since a level are encoded in a compact byte stream, that can be applied to another manager (e.g.,
+//! a worker of a distributed search in another process) which is in the state of this level. This
+//! avoids shipping the whole manager every time a subproblem is sent to a worker.
+//!
+//! A `NodeDescriptor` encodes in the same way the path from the root to an open level of the search,
+//! with one delta per level. A work-stealing worker can thus take an open node from another worker,
+//! and rebuild it on its own copy of the root state, with the same levels to backtrack to.
+//!
+//! Conversely, a child thawed from a frozen state of its parent can send back the values it has
+//! changed (e.g., the bounds it has proven), which are merged in the parent at a chosen level.
+
+use std::collections::{BTreeSet, HashMap};
+
+use paste::paste;
+
+use crate::{FrozenState, SaveAndRestore, StateManager, Store, Trailing};
+
+/// A token identifying a level of a manager, used to encode the modifications done since this level
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LevelToken {
+    /// Clock of the most recent level materialized when the token was taken
+    clock: usize,
+}
+
+/// The errors that can occur when applying a delta to a manager
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeltaError {
+    /// The delta ends in the middle of an entry
+    Truncated,
+    /// The delta creates resources that do not follow the resources of the manager, which has not the
+    /// same layout as the sender
+    LayoutMismatch,
+}
+
+impl std::fmt::Display for DeltaError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DeltaError::Truncated => write!(f, "the delta is truncated"),
+            DeltaError::LayoutMismatch => write!(f, "the delta does not match the layout of the manager"),
+        }
+    }
+}
+
+impl std::error::Error for DeltaError {}
+
+/// The path from the root of a search to one of its open levels. It holds, for each level, a delta
+/// encoding the values of the resources modified in it, as they were when the next level was saved.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NodeDescriptor {
+    /// The delta of each level, empty for the levels in which nothing was modified
+    levels: Vec<Vec<u8>>,
+}
+
+impl NodeDescriptor {
+    /// Returns the depth of the node
+    pub fn depth(&self) -> usize {
+        self.levels.len()
+    }
+
+    /// Encodes the descriptor, to send it to another worker
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = vec![];
+        encode_index(self.levels.len(), &mut out);
+        for delta in self.levels.iter() {
+            encode_index(delta.len(), &mut out);
+            out.extend_from_slice(delta);
+        }
+        out
+    }
+
+    /// Decodes a descriptor encoded by `to_bytes`
+    pub fn from_bytes(mut bytes: &[u8]) -> Result<Self, DeltaError> {
+        let input = &mut bytes;
+        let depth = decode_index(input)?;
+        let mut levels = Vec::with_capacity(depth.min(input.len()));
+        for _ in 0..depth {
+            let len = decode_index(input)?;
+            if input.len() < len {
+                return Err(DeltaError::Truncated);
+            }
+            let (delta, rest) = input.split_at(len);
+            levels.push(delta.to_vec());
+            *input = rest;
+        }
+        Ok(Self { levels })
+    }
+}
+
+/// A value that can be written in a delta
+trait Encode: Sized {
+    fn encode(&self, out: &mut Vec<u8>);
+    fn decode(input: &mut &[u8]) -> Result<Self, DeltaError>;
+}
+
+macro_rules! encode_primitives {
+    ($($t:ty),*) => {
+        $(
+            impl Encode for $t {
+                fn encode(&self, out: &mut Vec<u8>) {
+                    out.extend_from_slice(&self.to_le_bytes());
+                }
+
+                fn decode(input: &mut &[u8]) -> Result<Self, DeltaError> {
+                    let size = std::mem::size_of::<$t>();
+                    if input.len() < size {
+                        return Err(DeltaError::Truncated);
+                    }
+                    let (bytes, rest) = input.split_at(size);
+                    *input = rest;
+                    Ok(<$t>::from_le_bytes(bytes.try_into().unwrap()))
+                }
+            }
+        )*
+    };
+}
+
+encode_primitives!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize, f32, f64);
+
+impl<T: Encode> Encode for Option<T> {
+    fn encode(&self, out: &mut Vec<u8>) {
+        match self {
+            None => out.push(0),
+            Some(value) => {
+                out.push(1);
+                value.encode(out);
+            }
+        }
+    }
+
+    fn decode(input: &mut &[u8]) -> Result<Self, DeltaError> {
+        match u8::decode(input)? {
+            0 => Ok(None),
+            _ => Ok(Some(T::decode(input)?)),
+        }
+    }
+}
+
+/// Writes an index or a count as a LEB128 variable-length integer
+fn encode_index(mut index: usize, out: &mut Vec<u8>) {
+    while index >= 0x80 {
+        out.push((index as u8) | 0x80);
+        index >>= 7;
+    }
+    out.push(index as u8);
+}
+
+fn decode_index(input: &mut &[u8]) -> Result<usize, DeltaError> {
+    let mut index = 0;
+    let mut shift = 0;
+    loop {
+        let byte = u8::decode(input)?;
+        index |= ((byte & 0x7f) as usize) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(index);
+        }
+        shift += 7;
+    }
+}
+
+/// Writes the given entries, preceded by their number
+fn encode_entries<T: Encode>(entries: impl Iterator<Item = (usize, T)>, out: &mut Vec<u8>) {
+    let entries = entries.collect::<Vec<(usize, T)>>();
+    encode_index(entries.len(), out);
+    for (id, value) in entries.iter() {
+        encode_index(*id, out);
+        value.encode(out);
+    }
+}
+
+/// Writes the resources of `store` whose value differs from the one in `base`, ignoring the resources
+/// that are not in `base`
+fn encode_changes<T: Copy + PartialEq + Encode, S: Store<T>>(store: &S, base: &S, out: &mut Vec<u8>) {
+    let changes = (0..store.len().min(base.len())).map(|id| (id, store.get(id))).filter(|&(id, value)| value != base.get(id));
+    encode_entries(changes, out);
+}
+
+/// Reads the entries written by `encode_entries`, and gives each of them to `apply`
+fn decode_entries<T: Encode>(input: &mut &[u8], mut apply: impl FnMut(usize, T) -> Result<(), DeltaError>) -> Result<(), DeltaError> {
+    let count = decode_index(input)?;
+    for _ in 0..count {
+        let id = decode_index(input)?;
+        apply(id, T::decode(input)?)?;
+    }
+    Ok(())
+}
+
+/// Returns, for each level of `levels`, the resources of the store modified in this level with their
+/// value at the end of the level. `deeper` are the clocks of the levels saved after them.
+fn level_values<T: Copy, S: Store<T>>(store: &S, levels: &[usize], deeper: &[usize]) -> Vec<Vec<(usize, T)>> {
+    // The value of a resource at the end of a level is the value saved by the first deeper level in
+    // which it is modified, or its current value if it is not modified deeper
+    let mut later = HashMap::new();
+    let save = |later: &mut HashMap<usize, T>, clock| {
+        // A resource saved several times in a level (on the trail and in ranges) is first saved with
+        // its oldest value
+        for (id, value) in store.trailed(clock).into_iter().rev() {
+            later.insert(id, value);
+        }
+    };
+    deeper.iter().rev().for_each(|&clock| save(&mut later, clock));
+    let mut values = vec![vec![]; levels.len()];
+    for (i, &clock) in levels.iter().enumerate().rev() {
+        let ids = store.trailed(clock).into_iter().map(|(id, _)| id).collect::<BTreeSet<_>>();
+        values[i] = ids.into_iter().map(|id| (id, later.get(&id).copied().unwrap_or_else(|| store.get(id)))).collect();
+        save(&mut later, clock);
+    }
+    values
+}
+
+/// Returns a function checking that the entries of a delta are in the first `len` resources
+fn check_index<T>(len: usize) -> impl FnMut(usize, T) -> Result<(), DeltaError> {
+    move |id, _| if id < len { Ok(()) } else { Err(DeltaError::LayoutMismatch) }
+}
+
+macro_rules! delta_sync {
+    ($($u:ty => $feature:literal),*) => {
+        paste! {
+            impl StateManager<Trailing> {
+                /// Returns a token identifying the current level. The modifications done from now on are
+                /// encoded by `delta_since`, as long as the level is not restored.
+                pub fn level_token(&self) -> LevelToken {
+                    LevelToken { clock: self.clock }
+                }
+
+                /// Encodes the values of the resources created or modified since the level identified by
+                /// the token. Applying the delta to a manager in the state of this level puts it in the
+                /// current state of this manager. Some resources modified in the level of the token just
+                /// before it was taken might also be encoded, which is harmless.
+                pub fn delta_since(&self, token: LevelToken) -> Vec<u8> {
+                    let mut delta = vec![];
+                    $(
+                        #[cfg(feature = $feature)]
+                        {
+                            encode_entries(self.[<numbers _ $u>].modified_since(token.clock), &mut delta);
+                            encode_entries(self.[<numbers_option_ $u>].modified_since(token.clock), &mut delta);
+                        }
+                    )*
+                    #[cfg(feature = "bool")]
+                    {
+                        encode_index(self.bools.len(), &mut delta);
+                        encode_entries(self.bools.words.modified_since(token.clock), &mut delta);
+                        encode_index(self.option_bools.len(), &mut delta);
+                        encode_entries(self.option_bools.words.modified_since(token.clock), &mut delta);
+                    }
+                    delta
+                }
+
+                /// Applies a delta encoded by `delta_since` on a manager with the same layout. The values
+                /// are set in the current level, so that restoring it undoes the delta. The resources
+                /// created by the delta get the same handles as in the sender, except for the booleans
+                /// created after the delta is applied, which may get other handles. If an error is returned,
+                /// the delta might have been partially applied, and the current level should be restored.
+                pub fn apply_delta(&mut self, delta: &[u8]) -> Result<(), DeltaError> {
+                    // The delta is journaled as a whole, rather than the writes it does
+                    #[cfg(feature = "journal")]
+                    {
+                        let journal = self.journal.take();
+                        let result = self.apply_delta_entries(delta);
+                        self.journal = journal;
+                        self.journal_push(|| crate::JournalEntry::ApplyDelta(delta.to_vec()));
+                        result
+                    }
+                    #[cfg(not(feature = "journal"))]
+                    self.apply_delta_entries(delta)
+                }
+
+                /// Returns the path from the root to the level at the given depth, which must not be deeper
+                /// than the current level. The values of a level in the descriptor are the ones it had when
+                /// the next level was saved (or the current ones for the current level).
+                ///
+                /// The resources modified by the levels of the path must have been created at the root.
+                pub fn node_descriptor(&self, depth: usize) -> NodeDescriptor {
+                    assert!(depth <= self.depth, "Can not describe the level {} of a manager at depth {}", depth, self.depth);
+                    // The depth of each materialized level, below the root, and its clock
+                    let mut levels = vec![];
+                    let mut level_depth = 0;
+                    for pair in self.levels.windows(2) {
+                        level_depth += pair[0].empties + 1;
+                        levels.push((level_depth, pair[1].clock));
+                    }
+                    let split = levels.partition_point(|&(level_depth, _)| level_depth <= depth);
+                    let clocks = levels.iter().map(|&(_, clock)| clock).collect::<Vec<_>>();
+                    let (path, deeper) = clocks.split_at(split);
+                    $(
+                        #[cfg(feature = $feature)]
+                        let mut [<values_ $u>] = level_values(&self.[<numbers _ $u>], path, deeper);
+                        #[cfg(feature = $feature)]
+                        let mut [<values_option_ $u>] = level_values(&self.[<numbers_option_ $u>], path, deeper);
+                    )*
+                    #[cfg(feature = "bool")]
+                    let (mut bools, mut option_bools) = (level_values(&self.bools.words, path, deeper), level_values(&self.option_bools.words, path, deeper));
+                    let mut deltas = vec![vec![]; depth];
+                    for (i, &(level_depth, _)) in levels[..split].iter().enumerate() {
+                        let delta = &mut deltas[level_depth - 1];
+                        $(
+                            #[cfg(feature = $feature)]
+                            {
+                                encode_entries(std::mem::take(&mut [<values_ $u>][i]).into_iter(), delta);
+                                encode_entries(std::mem::take(&mut [<values_option_ $u>][i]).into_iter(), delta);
+                            }
+                        )*
+                        #[cfg(feature = "bool")]
+                        {
+                            encode_index(self.bools.len(), delta);
+                            encode_entries(std::mem::take(&mut bools[i]).into_iter(), delta);
+                            encode_index(self.option_bools.len(), delta);
+                            encode_entries(std::mem::take(&mut option_bools[i]).into_iter(), delta);
+                        }
+                    }
+                    NodeDescriptor { levels: deltas }
+                }
+
+                /// Rebuilds the node described by the descriptor, on a manager in the state of the root of
+                /// the search that produced it. A level is saved for each level of the path, so that the
+                /// manager can backtrack to the nodes of the path. If an error is returned, the levels
+                /// saved so far are not restored.
+                ///
+                #[cfg_attr(feature = "usize", doc = "```")]
+                #[cfg_attr(not(feature = "usize"), doc = "```ignore")]
+                /// use search_trail::{NodeDescriptor, SaveAndRestore, StateManager, UsizeManager};
+                ///
+                /// let mut mgr = StateManager::default();
+                /// let x = mgr.manage_usize(0);
+                /// let mut thief = mgr.fork();
+                /// mgr.save_state();
+                /// mgr.set_usize(x, 1);
+                /// mgr.save_state();
+                /// mgr.set_usize(x, 2);
+                /// let node = NodeDescriptor::from_bytes(&mgr.node_descriptor(1).to_bytes()).unwrap();
+                /// thief.enter_node(&node).unwrap();
+                /// assert_eq!(1, thief.get_usize(x));
+                /// ```
+                pub fn enter_node(&mut self, node: &NodeDescriptor) -> Result<(), DeltaError> {
+                    for delta in node.levels.iter() {
+                        self.save_state();
+                        if !delta.is_empty() {
+                            self.apply_delta(delta)?;
+                        }
+                    }
+                    Ok(())
+                }
+
+                /// Encodes the values of the resources that differ from the given frozen state, typically the
+                /// state of the parent from which this manager has been thawed. The resources created after
+                /// the state was frozen are ignored. The delta can be merged in the parent by `merge_delta`.
+                pub fn delta_from(&self, base: &FrozenState) -> Vec<u8> {
+                    let base = &base.mgr;
+                    let mut delta = vec![];
+                    $(
+                        #[cfg(feature = $feature)]
+                        {
+                            encode_changes(&self.[<numbers _ $u>], &base.[<numbers _ $u>], &mut delta);
+                            encode_changes(&self.[<numbers_option_ $u>], &base.[<numbers_option_ $u>], &mut delta);
+                        }
+                    )*
+                    #[cfg(feature = "bool")]
+                    {
+                        encode_index(self.bools.len().min(base.bools.len()), &mut delta);
+                        encode_changes(&self.bools.words, &base.bools.words, &mut delta);
+                        encode_index(self.option_bools.len().min(base.option_bools.len()), &mut delta);
+                        encode_changes(&self.option_bools.words, &base.option_bools.words, &mut delta);
+                    }
+                    delta
+                }
+
+                /// Merges a delta computed by `delta_from` in this manager, at the level of the given depth:
+                /// the values of the delta are set in this level, and the deeper levels are rebuilt on top of
+                /// it, so that the values they modified keep their current value. Restoring the level of the
+                /// given depth undoes the merge. The tokens of the rebuilt levels are not valid anymore.
+                ///
+                /// The delta is checked before anything is modified: if it contains resources that are not
+                /// managed by this manager, `DeltaError::LayoutMismatch` is returned and the manager is left
+                /// untouched.
+                ///
+                #[cfg_attr(feature = "usize", doc = "```")]
+                #[cfg_attr(not(feature = "usize"), doc = "```ignore")]
+                /// use search_trail::{SaveAndRestore, StateManager, UsizeManager};
+                ///
+                /// let mut parent = StateManager::default();
+                /// let bound = parent.manage_usize(100);
+                /// parent.save_state();
+                /// let base = parent.freeze();
+                /// let mut child = base.thaw();
+                /// child.set_usize(bound, 42);
+                /// parent.save_state();
+                /// parent.merge_delta(&child.delta_from(&base), 0).unwrap();
+                /// parent.restore_state();
+                /// parent.restore_state();
+                /// assert_eq!(42, parent.get_usize(bound));
+                /// ```
+                pub fn merge_delta(&mut self, delta: &[u8], depth: usize) -> Result<(), DeltaError> {
+                    assert!(depth <= self.depth, "Can not merge a delta in the level {} of a manager at depth {}", depth, self.depth);
+                    self.check_delta(delta)?;
+                    let path = self.node_descriptor(self.depth);
+                    while self.depth > depth {
+                        self.restore_state();
+                    }
+                    self.apply_delta(delta)?;
+                    for level in path.levels[depth..].iter() {
+                        self.save_state();
+                        if !level.is_empty() {
+                            self.apply_delta(level)?;
+                        }
+                    }
+                    Ok(())
+                }
+
+                /// Returns an error if the delta is truncated, or if it modifies or creates resources that are
+                /// not managed by this manager
+                fn check_delta(&self, mut delta: &[u8]) -> Result<(), DeltaError> {
+                    let input = &mut delta;
+                    $(
+                        #[cfg(feature = $feature)]
+                        {
+                            decode_entries(input, check_index::<$u>(self.[<numbers _ $u>].len()))?;
+                            decode_entries(input, check_index::<Option<$u>>(self.[<numbers_option_ $u>].len()))?;
+                        }
+                    )*
+                    #[cfg(feature = "bool")]
+                    {
+                        if decode_index(input)? > self.bools.len() {
+                            return Err(DeltaError::LayoutMismatch);
+                        }
+                        decode_entries(input, check_index::<u64>(self.bools.words.len()))?;
+                        if decode_index(input)? > self.option_bools.len() {
+                            return Err(DeltaError::LayoutMismatch);
+                        }
+                        decode_entries(input, check_index::<u64>(self.option_bools.words.len()))?;
+                    }
+                    Ok(())
+                }
+
+                /// Applies the entries of a delta
+                fn apply_delta_entries(&mut self, mut delta: &[u8]) -> Result<(), DeltaError> {
+                    let input = &mut delta;
+                    $(
+                        #[cfg(feature = $feature)]
+                        {
+                            decode_entries(input, |id, value: $u| self.apply_entry(id, value))?;
+                            decode_entries(input, |id, value: Option<$u>| self.apply_entry(id, value))?;
+                        }
+                    )*
+                    #[cfg(feature = "bool")]
+                    {
+                        let len = decode_index(input)?;
+                        decode_entries(input, |id, word: u64| self.apply_bool_word(false, id, word))?;
+                        if len > self.bools.len() {
+                            self.bools.extend_to(len);
+                        }
+                        let len = decode_index(input)?;
+                        decode_entries(input, |id, word: u64| self.apply_bool_word(true, id, word))?;
+                        if len > self.option_bools.len() {
+                            self.option_bools.extend_to(len);
+                        }
+                    }
+                    Ok(())
+                }
+            }
+        }
+    };
+}
+
+for_each_managed_type!(delta_sync);
+
+impl StateManager<Trailing> {
+    /// Sets the value of a resource from a delta, creating it if it follows the last resource
+    #[cfg(numbers)]
+    fn apply_entry<T: crate::Managed>(&mut self, id: usize, value: T) -> Result<(), DeltaError> {
+        match id.cmp(&T::store(self).len()) {
+            std::cmp::Ordering::Less => {
+                self.set_value(id, value);
+            }
+            std::cmp::Ordering::Equal => {
+                self.manage_value(value);
+            }
+            std::cmp::Ordering::Greater => return Err(DeltaError::LayoutMismatch),
+        }
+        Ok(())
+    }
+
+    /// Sets a word of the packed booleans from a delta, creating it if it follows the last word
+    #[cfg(feature = "bool")]
+    fn apply_bool_word(&mut self, option: bool, id: usize, word: u64) -> Result<(), DeltaError> {
+        let words = if option { &self.option_bools.words } else { &self.bools.words };
+        match id.cmp(&words.len()) {
+            std::cmp::Ordering::Less => {
+                if words.get(id) != word {
+                    self.set_bool_word(option, id, word);
+                }
+            }
+            std::cmp::Ordering::Equal => {
+                self.materialize();
+                let clock = self.clock;
+                let words = if option { &mut self.option_bools.words } else { &mut self.bools.words };
+                words.manage(clock, word);
+            }
+            std::cmp::Ordering::Greater => return Err(DeltaError::LayoutMismatch),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(all(test, feature = "usize", feature = "f64", feature = "bool"))]
+mod test_delta {
+    use crate::{BoolManager, DeltaError, F64Manager, NodeDescriptor, OptionUsizeManager, SaveAndRestore, StateManager, UsizeManager};
+
+    #[test]
+    fn delta_reproduces_the_state() {
+        let mut sender = StateManager::default();
+        let mut receiver = StateManager::default();
+        let layout = |mgr: &mut StateManager| {
+            let numbers = (0..100).map(|i| mgr.manage_usize(i)).collect::<Vec<_>>();
+            let x = mgr.manage_f64(0.5);
+            let y = mgr.manage_option_usize(None);
+            let b = mgr.manage_bool(false);
+            (numbers, x, y, b)
+        };
+        let (numbers, _, y, b) = layout(&mut sender);
+        sender.save_state();
+        sender.set_usize(numbers[3], 42);
+        let token = sender.level_token();
+        sender.save_state();
+        sender.set_option_usize(y, Some(7));
+        sender.flip_bool(b);
+        sender.manage_usize(1000);
+        sender.save_state();
+        sender.set_usize(numbers[50], 0);
+        sender.save_state();
+        sender.set_usize(numbers[60], 0);
+        sender.restore_state();
+        let delta = sender.delta_since(token);
+
+        let (numbers, x, y, b) = layout(&mut receiver);
+        receiver.save_state();
+        receiver.set_usize(numbers[3], 42);
+        receiver.save_state();
+        receiver.apply_delta(&delta).unwrap();
+        assert_eq!(Some(7), receiver.get_option_usize(y));
+        assert!(receiver.get_bool(b));
+        assert_eq!(1000, receiver.get_usize(crate::ReversibleUsize::new(&receiver, 100)));
+        assert_eq!(0, receiver.get_usize(numbers[50]));
+        assert_eq!(60, receiver.get_usize(numbers[60]));
+        assert_eq!(0.5, receiver.get_f64(x));
+        receiver.restore_state();
+        assert_eq!(None, receiver.get_option_usize(y));
+        assert_eq!(42, receiver.get_usize(numbers[3]));
+    }
+
+    #[test]
+    fn invalid_deltas_are_rejected() {
+        let mut sender = StateManager::default();
+        let handles = (0..3).map(|i| sender.manage_usize(i)).collect::<Vec<_>>();
+        sender.save_state();
+        sender.set_usize(handles[0], 5);
+        let token = sender.level_token();
+        sender.save_state();
+        sender.set_usize(handles[2], 10);
+        let delta = sender.delta_since(token);
+        let mut receiver = StateManager::default();
+        assert_eq!(Err(DeltaError::LayoutMismatch), receiver.apply_delta(&delta));
+        (0..3).for_each(|i| {
+            receiver.manage_usize(i);
+        });
+        assert_eq!(Err(DeltaError::Truncated), receiver.apply_delta(&delta[..delta.len() - 1]));
+    }
+
+    #[test]
+    fn node_descriptors_rebuild_the_open_levels() {
+        let mut worker = StateManager::default();
+        let numbers = (0..20).map(|i| worker.manage_usize(i)).collect::<Vec<_>>();
+        let option = worker.manage_option_usize(None);
+        let bools = (0..10).map(|_| worker.manage_bool(false)).collect::<Vec<_>>();
+        let values = |mgr: &StateManager| {
+            let numbers = numbers.iter().map(|&x| mgr.get_usize(x)).collect::<Vec<_>>();
+            let bools = bools.iter().map(|&b| mgr.get_bool(b)).collect::<Vec<_>>();
+            (numbers, mgr.get_option_usize(option), bools)
+        };
+        let root = worker.fork();
+        let mut states = vec![values(&worker)];
+        for depth in 1..=8 {
+            worker.save_state();
+            // Some levels are left empty, and some are modified by ranges
+            if depth % 3 != 0 {
+                worker.set_usize(numbers[depth], 100 + depth);
+                worker.set_usize(numbers[depth * 2], depth);
+                worker.set_option_usize(option, Some(depth));
+                worker.flip_bool(bools[depth]);
+            }
+            if depth % 4 == 0 {
+                worker.set_usize_slice(numbers[4], &[depth; 10]);
+            }
+            states.push(values(&worker));
+        }
+        for depth in 0..=8 {
+            let node = NodeDescriptor::from_bytes(&worker.node_descriptor(depth).to_bytes()).unwrap();
+            assert_eq!(depth, node.depth());
+            let mut thief = root.fork();
+            thief.enter_node(&node).unwrap();
+            for state in states[..=depth].iter().rev() {
+                assert_eq!(*state, values(&thief));
+                if thief.depth > 0 {
+                    thief.restore_state();
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn child_changes_are_merged_at_a_level() {
+        let mut parent = StateManager::default();
+        let bounds = (0..4).map(|i| parent.manage_usize(100 + i)).collect::<Vec<_>>();
+        let fixed = parent.manage_bool(false);
+        let values = |mgr: &StateManager| bounds.iter().map(|&bound| mgr.get_usize(bound)).collect::<Vec<_>>();
+        parent.save_state();
+        parent.set_usize(bounds[0], 90);
+        let base = parent.freeze();
+        let mut child = base.thaw();
+        child.set_usize(bounds[0], 50);
+        child.set_usize(bounds[1], 60);
+        child.set_bool(fixed, true);
+        child.save_state();
+        child.set_usize(bounds[2], 0);
+        child.restore_state();
+        child.manage_usize(1000);
+        let delta = child.delta_from(&base);
+
+        parent.save_state();
+        parent.set_usize(bounds[1], 80);
+        parent.save_state();
+        parent.set_usize(bounds[3], 70);
+        parent.merge_delta(&delta, 1).unwrap();
+        assert_eq!(3, parent.depth);
+        assert_eq!(vec![50, 80, 102, 70], values(&parent));
+        assert!(parent.get_bool(fixed));
+        parent.restore_state();
+        assert_eq!(vec![50, 80, 102, 103], values(&parent));
+        parent.restore_state();
+        assert_eq!(vec![50, 60, 102, 103], values(&parent));
+        assert!(parent.get_bool(fixed));
+        parent.restore_state();
+        assert_eq!(vec![100, 101, 102, 103], values(&parent));
+        assert!(!parent.get_bool(fixed));
+    }
+
+    #[test]
+    fn incompatible_deltas_are_not_merged() {
+        let mut other = StateManager::default();
+        let handles = (0..5).map(|i| other.manage_usize(i)).collect::<Vec<_>>();
+        let base = other.freeze();
+        other.set_usize(handles[4], 0);
+        let delta = other.delta_from(&base);
+        let mut parent = StateManager::default();
+        let handles = (0..4).map(|i| parent.manage_usize(i)).collect::<Vec<_>>();
+        parent.save_state();
+        parent.set_usize(handles[0], 10);
+        assert_eq!(Err(DeltaError::LayoutMismatch), parent.merge_delta(&delta, 0));
+        let base = parent.freeze();
+        let mut child = base.thaw();
+        child.set_usize(handles[1], 0);
+        let delta = child.delta_from(&base);
+        assert_eq!(Err(DeltaError::Truncated), parent.merge_delta(&delta[..delta.len() - 1], 0));
+        assert_eq!(1, parent.depth);
+        assert_eq!(10, parent.get_usize(handles[0]));
+    }
+}
@@ -0,0 +1,533 @@
+//Copyright (c) 2023 X. Gillard, A. Dubray
+//
+//Permission is hereby granted, free of charge, to any person obtaining a copy
+//of this software and associated documentation files (the "Software"), to deal
+//in the Software without restriction, including without limitation the rights
+//to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+//copies of the Software, and to permit persons to whom the Software is
+//furnished to do so, subject to the following conditions:
+//
+//The above copyright notice and this permission notice shall be included in all
+//copies or substantial portions of the Software.
+//
+//THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+//IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+//AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+//OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+//SOFTWARE.
+
+
+//! Tracking of the resources modified since the last propagation. When the tracking is enabled, the
+//! handles of the resources changed by the setters are collected in a deduplicated set, which a
+//! propagation loop drains to know which propagators to wake up.
+//!
+//! The set follows the saved levels: restoring a level also restores the set as it was when the level
+//! was saved, so that the changes undone by the restoration are not reported.
+//!
+//! Subscribers (e.g., the propagators of a solver) can be registered on the resources with a priority.
+//! Draining the set with `drain_subscribers()` returns the subscribers of the modified resources,
+//! ordered by priority, so that the cheap propagators are scheduled before the expensive ones.
+//!
+//! Each modified resource is also associated with its value at the last drain, so that
+//! `drain_events()` reports a resource once even if it was changed several times, with the kind of the
+//! change from this value to its current value.
+
+use std::collections::HashMap;
+
+use crate::*;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// The kind of the change of a resource, from its value at the last drain to its current value
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum EventKind {
+    /// The value changed, and the resource is assigned (or empty) as it was before
+    Changed,
+    /// The optional resource was empty, and has now a value
+    Fixed,
+    /// The optional resource had a value, and is now empty
+    Unfixed,
+}
+
+impl EventKind {
+    /// Returns the kind of the change from the value `before` to the value `after`, or `None` if they
+    /// are equal. Only these two values matter, not the changes made in between: e.g., an empty option
+    /// that is fixed and then emptied again is not changed.
+    fn between(before: Value, after: Value) -> Option<Self> {
+        if before == after {
+            return None;
+        }
+        Some(match (before.is_assigned(), after.is_assigned()) {
+            (false, true) => EventKind::Fixed,
+            (true, false) => EventKind::Unfixed,
+            _ => EventKind::Changed,
+        })
+    }
+}
+
+/// A modification of the set, undone when the level in which it was done is restored
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+enum DirtyChange {
+    /// A resource was added at the end of the set
+    Inserted,
+    /// The set was drained while it contained the given resources
+    Drained(Vec<(AnyHandle, Value)>),
+    /// The resource, with its value at the last drain, was removed from the given position when it was
+    /// released
+    #[cfg(numbers)]
+    Removed(usize, AnyHandle, Value),
+}
+
+/// The handles of the resources modified since the last drain
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub(crate) struct DirtySet {
+    /// The modified resources, in the order of their first modification
+    handles: Vec<AnyHandle>,
+    /// The value of each modified resource at the last drain
+    members: HashMap<AnyHandle, Value>,
+    /// The modifications of the set done since the first saved level. Nothing is recorded at the root,
+    /// which is never restored.
+    trail: Vec<DirtyChange>,
+    /// The length of the trail when each level was saved
+    saved: Vec<usize>,
+    /// The subscribers of each resource, with their priority. They are not serialized.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    subscriptions: HashMap<AnyHandle, Vec<(usize, u32)>>,
+}
+
+impl DirtySet {
+    /// Adds the resource, whose value before its modification is `before`, to the set. The value of a
+    /// resource already in the set is the one it had at the last drain, and is kept.
+    fn insert(&mut self, handle: AnyHandle, before: Value) {
+        if let std::collections::hash_map::Entry::Vacant(entry) = self.members.entry(handle) {
+            entry.insert(before);
+            self.handles.push(handle);
+            if !self.saved.is_empty() {
+                self.trail.push(DirtyChange::Inserted);
+            }
+        }
+    }
+
+    /// Removes the resource, which has been released, from the set, and drops its subscribers
+    #[cfg(numbers)]
+    pub fn remove(&mut self, handle: AnyHandle) {
+        self.subscriptions.remove(&handle);
+        if let Some(before) = self.members.remove(&handle) {
+            let index = self.handles.iter().position(|&h| h == handle).unwrap();
+            self.handles.remove(index);
+            if !self.saved.is_empty() {
+                self.trail.push(DirtyChange::Removed(index, handle, before));
+            }
+        }
+    }
+
+    fn save(&mut self) {
+        self.saved.push(self.trail.len());
+    }
+
+    fn restore(&mut self) {
+        let Some(len) = self.saved.pop() else {
+            // The tracking started after the level was saved
+            self.handles.clear();
+            self.members.clear();
+            return;
+        };
+        // The modifications are undone in the reverse order, so that each inserted resource is at the end
+        // of the set when its insertion is undone
+        for change in self.trail.drain(len..).rev() {
+            match change {
+                DirtyChange::Inserted => {
+                    let handle = self.handles.pop().unwrap();
+                    self.members.remove(&handle);
+                }
+                #[cfg(numbers)]
+                DirtyChange::Removed(index, handle, before) => {
+                    self.handles.insert(index, handle);
+                    self.members.insert(handle, before);
+                }
+                DirtyChange::Drained(drained) => {
+                    self.handles = drained.iter().map(|&(handle, _)| handle).collect();
+                    self.members = drained.into_iter().collect();
+                }
+            }
+        }
+    }
+
+    /// Empties the set and returns the modified resources with their value at the previous drain
+    fn drain(&mut self) -> Vec<(AnyHandle, Value)> {
+        let handles = std::mem::take(&mut self.handles);
+        let drained = handles.into_iter().map(|handle| (handle, self.members.remove(&handle).unwrap())).collect::<Vec<_>>();
+        if !self.saved.is_empty() && !drained.is_empty() {
+            self.trail.push(DirtyChange::Drained(drained.clone()));
+        }
+        drained
+    }
+
+    /// Returns an empty set with the same subscribers
+    pub fn fork(&self) -> Self {
+        Self {
+            subscriptions: self.subscriptions.clone(),
+            ..Self::default()
+        }
+    }
+}
+
+impl<B: TrailBackend> StateManager<B> {
+    /// Starts collecting the handles of the resources modified by the setters, discarding the ones
+    /// collected so far. The subscribers are kept.
+    ///
+    #[cfg_attr(feature = "usize", doc = "```")]
+    #[cfg_attr(not(feature = "usize"), doc = "```ignore")]
+    /// use search_trail::{AnyHandle, SaveAndRestore, StateManager, UsizeManager};
+    ///
+    /// let mut mgr = StateManager::default();
+    /// let x = mgr.manage_usize(0);
+    /// let y = mgr.manage_usize(0);
+    /// mgr.track_dirty();
+    /// mgr.save_state();
+    /// mgr.set_usize(x, 1);
+    /// mgr.set_usize(y, 1);
+    /// mgr.set_usize(x, 2);
+    /// assert_eq!(vec![AnyHandle::Usize(x), AnyHandle::Usize(y)], mgr.drain_dirty());
+    /// mgr.set_usize(y, 0);
+    /// mgr.restore_state();
+    /// assert!(mgr.drain_dirty().is_empty());
+    /// ```
+    pub fn track_dirty(&mut self) {
+        self.dirty = Some(self.dirty.as_ref().map(DirtySet::fork).unwrap_or_default());
+    }
+
+    /// Stops collecting the modified resources, and removes the subscribers
+    pub fn stop_tracking_dirty(&mut self) {
+        self.dirty = None;
+    }
+
+    /// Returns the handles of the resources modified since the previous call, in the order of their
+    /// first modification, and empties the set. Returns nothing if the tracking is not enabled.
+    pub fn drain_dirty(&mut self) -> Vec<AnyHandle> {
+        match self.dirty.as_mut() {
+            Some(dirty) => dirty.drain().into_iter().map(|(handle, _)| handle).collect(),
+            None => vec![],
+        }
+    }
+
+    /// Same as `drain_dirty()`, but also returns the kind of the change of each resource since the
+    /// previous drain. A resource changed several times is returned once, with the kind of the change
+    /// from its value at the previous drain to its current value: e.g., an optional resource modified and
+    /// then set to none is `Unfixed`. A resource whose current value is its value at the previous drain
+    /// (e.g., an empty option that is fixed and then emptied again) is not returned.
+    ///
+    #[cfg_attr(feature = "usize", doc = "```")]
+    #[cfg_attr(not(feature = "usize"), doc = "```ignore")]
+    /// use search_trail::{AnyHandle, EventKind, OptionUsizeManager, StateManager, UsizeManager};
+    ///
+    /// let mut mgr = StateManager::default();
+    /// let x = mgr.manage_usize(0);
+    /// let y = mgr.manage_option_usize(None);
+    /// mgr.track_dirty();
+    /// mgr.set_usize(x, 1);
+    /// mgr.set_option_usize(y, Some(1));
+    /// mgr.set_usize(x, 2);
+    /// mgr.set_option_usize(y, Some(2));
+    /// assert_eq!(vec![(AnyHandle::Usize(x), EventKind::Changed), (AnyHandle::OptionUsize(y), EventKind::Fixed)], mgr.drain_events());
+    /// ```
+    pub fn drain_events(&mut self) -> Vec<(AnyHandle, EventKind)> {
+        let Some(dirty) = self.dirty.as_mut() else {
+            return vec![];
+        };
+        let drained = dirty.drain();
+        drained.into_iter().filter_map(|(handle, before)| Some((handle, EventKind::between(before, self.any_value(handle))?))).collect()
+    }
+
+    /// Subscribes `subscriber` (e.g., the index of a propagator) to the modifications of the resource,
+    /// and starts tracking the modified resources if they were not tracked. The subscribers with the
+    /// lowest priority are returned first by `drain_subscribers()`.
+    ///
+    #[cfg_attr(feature = "usize", doc = "```")]
+    #[cfg_attr(not(feature = "usize"), doc = "```ignore")]
+    /// use search_trail::{StateManager, UsizeManager};
+    ///
+    /// let mut mgr = StateManager::default();
+    /// let x = mgr.manage_usize(0);
+    /// let y = mgr.manage_usize(0);
+    /// mgr.subscribe(x, 0, 10);
+    /// mgr.subscribe(y, 1, 1);
+    /// mgr.subscribe(y, 2, 5);
+    /// mgr.set_usize(x, 1);
+    /// mgr.set_usize(y, 1);
+    /// assert_eq!(vec![1, 2, 0], mgr.drain_subscribers());
+    /// ```
+    pub fn subscribe(&mut self, handle: impl Into<AnyHandle>, subscriber: usize, priority: u32) {
+        let dirty = self.dirty.get_or_insert_with(DirtySet::default);
+        let subscribers = dirty.subscriptions.entry(handle.into()).or_default();
+        subscribers.retain(|&(s, _)| s != subscriber);
+        subscribers.push((subscriber, priority));
+    }
+
+    /// Removes a subscriber of the resource
+    pub fn unsubscribe(&mut self, handle: impl Into<AnyHandle>, subscriber: usize) {
+        if let Some(subscribers) = self.dirty.as_mut().and_then(|dirty| dirty.subscriptions.get_mut(&handle.into())) {
+            subscribers.retain(|&(s, _)| s != subscriber);
+        }
+    }
+
+    /// Drains the modified resources, and returns their subscribers by increasing priority. A
+    /// subscriber of several modified resources is returned once, with its lowest priority. The
+    /// subscribers with the same priority are ordered by the first modification of their resources.
+    pub fn drain_subscribers(&mut self) -> Vec<usize> {
+        let handles = self.drain_dirty();
+        let Some(dirty) = self.dirty.as_ref() else {
+            return vec![];
+        };
+        let mut scheduled = HashMap::new();
+        for (order, handle) in handles.iter().enumerate() {
+            for &(subscriber, priority) in dirty.subscriptions.get(handle).into_iter().flatten() {
+                let entry = scheduled.entry(subscriber).or_insert((priority, order));
+                *entry = (*entry).min((priority, order));
+            }
+        }
+        let mut scheduled = scheduled.into_iter().collect::<Vec<_>>();
+        scheduled.sort_unstable_by_key(|&(subscriber, key)| (key, subscriber));
+        scheduled.into_iter().map(|(subscriber, _)| subscriber).collect()
+    }
+
+    /// Returns true if the resource has been modified since the last drain
+    pub fn is_dirty(&self, handle: impl Into<AnyHandle>) -> bool {
+        self.dirty.as_ref().is_some_and(|dirty| dirty.members.contains_key(&handle.into()))
+    }
+
+    /// Records a call to `save_state()` in the dirty set, if it is tracked
+    pub(crate) fn save_dirty(&mut self) {
+        if let Some(dirty) = self.dirty.as_mut() {
+            dirty.save();
+        }
+    }
+
+    /// Records a call to `restore_state()` in the dirty set, if it is tracked
+    pub(crate) fn restore_dirty(&mut self) {
+        if let Some(dirty) = self.dirty.as_mut() {
+            dirty.restore();
+        }
+    }
+
+    /// Marks the resource at the given index, changed from `old`, as modified, if the dirty set is
+    /// tracked
+    #[inline]
+    #[cfg(numbers)]
+    pub(crate) fn mark_dirty<T: Managed>(&mut self, id: usize, old: T) {
+        if let Some(mut dirty) = self.dirty.take() {
+            dirty.insert(T::handle(self, id), old.into_value());
+            self.dirty = Some(dirty);
+        }
+    }
+
+    /// Marks the resources of `first..first + values.len()` whose value is not the given one as
+    /// modified, if the dirty set is tracked
+    #[cfg(numbers)]
+    pub(crate) fn mark_dirty_range<T: Managed>(&mut self, first: usize, values: &[T]) {
+        if let Some(mut dirty) = self.dirty.take() {
+            for (i, value) in values.iter().enumerate() {
+                let old = self.get_value::<T>(first + i);
+                if old != *value {
+                    dirty.insert(T::handle(self, first + i), old.into_value());
+                }
+            }
+            self.dirty = Some(dirty);
+        }
+    }
+
+    /// Marks the packed booleans of a word whose value changed from `old` to `new` as modified, if the
+    /// dirty set is tracked
+    #[cfg(feature = "bool")]
+    pub(crate) fn mark_dirty_bools(&mut self, option: bool, word: usize, old: u64, new: u64) {
+        if let Some(mut dirty) = self.dirty.take() {
+            if option {
+                let per_word = OptionBools::<B::Store<u64>>::PER_WORD;
+                for id in word * per_word..(word + 1) * per_word {
+                    let old = OptionBools::<B::Store<u64>>::unpack(old, id);
+                    if old != OptionBools::<B::Store<u64>>::unpack(new, id) {
+                        dirty.insert(AnyHandle::OptionBool(ReversibleOptionBool::new(self, id)), Value::OptionBool(Self::option_bool_value(old)));
+                    }
+                }
+            } else {
+                let per_word = Bools::<B::Store<u64>>::PER_WORD;
+                for id in word * per_word..(word + 1) * per_word {
+                    let old = Bools::<B::Store<u64>>::unpack(old, id);
+                    if old != Bools::<B::Store<u64>>::unpack(new, id) {
+                        dirty.insert(AnyHandle::Bool(ReversibleBool::new(self, id)), Value::Bool(old != 0));
+                    }
+                }
+            }
+            self.dirty = Some(dirty);
+        }
+    }
+}
+
+#[cfg(all(test, feature = "usize", feature = "u32", feature = "bool"))]
+mod test_dirty {
+    use crate::*;
+
+    #[test]
+    fn dirty_set_follows_the_levels() {
+        let mut mgr = StateManager::default();
+        let xs = (0..10).map(|i| mgr.manage_u32(i)).collect::<Vec<_>>();
+        let b = (0..40).map(|_| mgr.manage_option_bool(None)).collect::<Vec<_>>();
+        let n = mgr.manage_usize(0);
+        assert!(mgr.drain_dirty().is_empty());
+        mgr.track_dirty();
+        mgr.set_u32(xs[0], 10);
+        mgr.set_u32(xs[1], 1);
+        assert!(mgr.is_dirty(xs[0]) && !mgr.is_dirty(xs[1]));
+        mgr.save_state();
+        mgr.set_u32_slice(xs[2], &[2, 0, 0]);
+        mgr.set_option_bool(b[33], true);
+        mgr.set_usize(n, 1);
+        mgr.set_u32(xs[0], 0);
+        assert_eq!(
+            vec![AnyHandle::U32(xs[0]), AnyHandle::U32(xs[3]), AnyHandle::U32(xs[4]), AnyHandle::OptionBool(b[33]), AnyHandle::Usize(n)],
+            mgr.drain_dirty()
+        );
+        mgr.save_state();
+        mgr.set_usize(n, 2);
+        mgr.restore_state();
+        assert!(mgr.drain_dirty().is_empty());
+        mgr.set_option_bool_none(b[33]);
+        mgr.restore_state();
+        // The changes of the restored level are forgotten, but not the ones done before it was saved
+        assert_eq!(vec![AnyHandle::U32(xs[0])], mgr.drain_dirty());
+        mgr.stop_tracking_dirty();
+        mgr.set_usize(n, 5);
+        assert!(!mgr.is_dirty(n));
+    }
+
+    #[test]
+    fn only_the_changes_of_the_set_are_saved() {
+        let mut mgr = StateManager::default();
+        let xs = (0..100).map(|i| mgr.manage_u32(i)).collect::<Vec<_>>();
+        let n = mgr.manage_usize(0);
+        mgr.track_dirty();
+        for &x in xs.iter() {
+            mgr.set_u32(x, 100);
+        }
+        for _ in 0..10 {
+            mgr.save_state();
+            mgr.set_u32(xs[0], 0);
+        }
+        // Saving does not copy the set, and the resources already in the set are not saved again
+        assert!(mgr.dirty.as_ref().unwrap().trail.is_empty());
+        mgr.set_usize(n, 1);
+        assert_eq!(1, mgr.dirty.as_ref().unwrap().trail.len());
+        assert_eq!(101, mgr.drain_dirty().len());
+        for _ in 0..10 {
+            mgr.restore_state();
+        }
+        assert!(mgr.dirty.as_ref().unwrap().trail.is_empty());
+        assert_eq!(xs.iter().map(|&x| AnyHandle::U32(x)).collect::<Vec<_>>(), mgr.drain_dirty());
+    }
+
+    #[test]
+    fn subscribers_are_scheduled_by_priority() {
+        let mut mgr = StateManager::default();
+        let xs = (0..4).map(|i| mgr.manage_u32(i)).collect::<Vec<_>>();
+        let b = mgr.manage_bool(false);
+        // Subscriber i is a propagator of priority 10 - i, watching xs[i] and xs[i + 1]
+        for i in 0..3 {
+            mgr.subscribe(xs[i], i, 10 - i as u32);
+            mgr.subscribe(xs[i + 1], i, 10 - i as u32);
+        }
+        mgr.subscribe(b, 7, 0);
+        mgr.subscribe(b, 8, 0);
+        mgr.set_u32(xs[0], 5);
+        mgr.set_u32(xs[3], 5);
+        mgr.set_bool(b, true);
+        assert_eq!(vec![7, 8, 2, 0], mgr.drain_subscribers());
+        assert!(mgr.drain_subscribers().is_empty());
+
+        mgr.save_state();
+        mgr.unsubscribe(b, 7);
+        mgr.set_u32(xs[1], 0);
+        mgr.set_bool(b, false);
+        let mut fork = mgr.fork();
+        assert_eq!(vec![8, 1, 0], mgr.drain_subscribers());
+        mgr.set_u32(xs[2], 0);
+        mgr.restore_state();
+        assert!(mgr.drain_subscribers().is_empty());
+        fork.set_u32(xs[2], 1);
+        assert_eq!(vec![2, 1], fork.drain_subscribers());
+    }
+
+    #[test]
+    fn events_are_coalesced() {
+        let mut mgr = StateManager::default();
+        let x = mgr.manage_option_u32(Some(0));
+        let y = mgr.manage_option_u32(None);
+        let zs = (0..3).map(|_| mgr.manage_option_u32(None)).collect::<Vec<_>>();
+        let b = mgr.manage_option_bool(None);
+        mgr.track_dirty();
+        mgr.set_option_u32(x, Some(1));
+        mgr.set_option_u32(x, None);
+        mgr.set_option_u32(y, Some(1));
+        mgr.set_option_u32(y, Some(2));
+        mgr.set_option_u32(zs[0], Some(1));
+        mgr.set_option_u32(zs[2], Some(2));
+        mgr.set_option_bool(b, true);
+        mgr.save_state();
+        mgr.set_option_u32(x, Some(2));
+        mgr.set_option_u32(zs[1], Some(1));
+        mgr.set_option_bool_none(b);
+        assert_eq!(
+            vec![
+                (AnyHandle::OptionU32(x), EventKind::Changed),
+                (AnyHandle::OptionU32(y), EventKind::Fixed),
+                (AnyHandle::OptionU32(zs[0]), EventKind::Fixed),
+                (AnyHandle::OptionU32(zs[2]), EventKind::Fixed),
+                (AnyHandle::OptionU32(zs[1]), EventKind::Fixed),
+            ],
+            mgr.drain_events()
+        );
+        mgr.restore_state();
+        // The kinds are restored with the level
+        assert_eq!(
+            vec![
+                (AnyHandle::OptionU32(x), EventKind::Unfixed),
+                (AnyHandle::OptionU32(y), EventKind::Fixed),
+                (AnyHandle::OptionU32(zs[0]), EventKind::Fixed),
+                (AnyHandle::OptionU32(zs[2]), EventKind::Fixed),
+                (AnyHandle::OptionBool(b), EventKind::Fixed),
+            ],
+            mgr.drain_events()
+        );
+        assert!(mgr.drain_events().is_empty());
+    }
+
+    #[test]
+    fn events_depend_on_the_values_at_the_drains() {
+        let mut mgr = StateManager::default();
+        let fixed_unfixed = mgr.manage_option_u32(None);
+        let unfixed_fixed = mgr.manage_option_u32(Some(1));
+        let unfixed_refixed = mgr.manage_option_u32(Some(1));
+        let b = mgr.manage_bool(false);
+        mgr.track_dirty();
+        mgr.set_option_u32(fixed_unfixed, Some(1));
+        mgr.set_option_u32(fixed_unfixed, None);
+        mgr.set_option_u32(unfixed_fixed, None);
+        mgr.set_option_u32(unfixed_fixed, Some(2));
+        mgr.set_option_u32(unfixed_refixed, None);
+        mgr.set_option_u32(unfixed_refixed, Some(1));
+        mgr.flip_bool(b);
+        mgr.flip_bool(b);
+        // Only the resources whose value differs from the one at the previous drain are reported
+        assert_eq!(vec![(AnyHandle::OptionU32(unfixed_fixed), EventKind::Changed)], mgr.drain_events());
+        mgr.set_option_u32(fixed_unfixed, Some(3));
+        mgr.set_option_u32(unfixed_fixed, None);
+        assert_eq!(
+            vec![(AnyHandle::OptionU32(fixed_unfixed), EventKind::Fixed), (AnyHandle::OptionU32(unfixed_fixed), EventKind::Unfixed)],
+            mgr.drain_events()
+        );
+    }
+}
@@ -0,0 +1,364 @@
+//Copyright (c) 2023 X. Gillard, A. Dubray
+//
+//Permission is hereby granted, free of charge, to any person obtaining a copy
+//of this software and associated documentation files (the "Software"), to deal
+//in the Software without restriction, including without limitation the rights
+//to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+//copies of the Software, and to permit persons to whom the Software is
+//furnished to do so, subject to the following conditions:
+//
+//The above copyright notice and this permission notice shall be included in all
+//copies or substantial portions of the Software.
+//
+//THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+//IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+//AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+//OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+//SOFTWARE.
+
+
+//! A human-readable dump of a manager, listing the current value of every managed resource and the
+//! levels that can be restored. It is meant for debugging, not for saving a manager.
+
+use std::fmt;
+
+use paste::paste;
+
+#[cfg(feature = "bool")]
+use crate::bools::{Bools, OptionBools};
+use crate::*;
+
+/// The current value of a managed resource
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResourceDump {
+    /// The handle of the resource
+    pub handle: AnyHandle,
+    /// The managed type, e.g. `usize` or `Option<bool>`
+    pub type_name: &'static str,
+    /// The index of the resource among the resources of its type
+    pub index: usize,
+    /// The name of the resource, if it has one
+    pub name: Option<String>,
+    /// The current value of the resource, formatted with `Debug`
+    pub value: String,
+}
+
+/// A level of the manager that can be restored
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LevelDump {
+    /// The clock of the level
+    pub clock: usize,
+    /// The number of levels saved on top of this one without any modification
+    pub empties: usize,
+}
+
+/// The state of a manager, returned by `StateManager::dump_state`
+#[derive(Debug, Clone, PartialEq)]
+pub struct StateDump {
+    /// The number of saved levels
+    pub depth: usize,
+    /// The levels in which a resource has been created or modified, from the root
+    pub levels: Vec<LevelDump>,
+    /// The managed resources, grouped by type
+    pub resources: Vec<ResourceDump>,
+}
+
+impl fmt::Display for StateDump {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "depth {}", self.depth)?;
+        for level in self.levels.iter() {
+            writeln!(f, "level {} (+{} empty)", level.clock, level.empties)?;
+        }
+        for resource in self.resources.iter() {
+            match &resource.name {
+                Some(name) => writeln!(f, "{} ({} #{}) = {}", name, resource.type_name, resource.index, resource.value)?,
+                None => writeln!(f, "{} #{} = {}", resource.type_name, resource.index, resource.value)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Writes the string as a JSON string
+#[cfg(feature = "json")]
+fn write_json_string(s: &str, out: &mut String) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+#[cfg(feature = "json")]
+impl StateDump {
+    /// Returns the dump as a JSON object. The values of the resources are written as strings.
+    pub fn to_json(&self) -> String {
+        let mut out = format!("{{\"depth\":{},\"levels\":[", self.depth);
+        for (i, level) in self.levels.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            out.push_str(&format!("{{\"clock\":{},\"empties\":{}}}", level.clock, level.empties));
+        }
+        out.push_str("],\"resources\":[");
+        for (i, resource) in self.resources.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            out.push_str("{\"type\":");
+            write_json_string(resource.type_name, &mut out);
+            out.push_str(&format!(",\"index\":{},\"name\":", resource.index));
+            match &resource.name {
+                Some(name) => write_json_string(name, &mut out),
+                None => out.push_str("null"),
+            }
+            out.push_str(",\"value\":");
+            write_json_string(&resource.value, &mut out);
+            out.push('}');
+        }
+        out.push_str("]}");
+        out
+    }
+}
+
+macro_rules! dump_state {
+    ($($u:ty => $feature:literal),*) => {
+        paste! {
+            impl<B: TrailBackend> StateManager<B> {
+                /// Returns the current value of every managed resource, and the levels that can be restored
+                pub fn dump_state(&self) -> StateDump {
+                    let mut resources = vec![];
+                    $(
+                        #[cfg(feature = $feature)]
+                        {
+                            for index in 0..self.[<numbers _ $u>].len() {
+                                let handle = [<Reversible $u:camel>]::new(self, index);
+                                resources.push(self.dump_resource(handle.into(), stringify!($u), index, self.[<get_ $u>](handle)));
+                            }
+                            for index in 0..self.[<numbers_option_ $u>].len() {
+                                let handle = [<ReversibleOption $u:camel>]::new(self, index);
+                                resources.push(self.dump_resource(handle.into(), concat!("Option<", stringify!($u), ">"), index, self.[<get_option_ $u>](handle)));
+                            }
+                        }
+                    )*
+                    #[cfg(feature = "bool")]
+                    {
+                        for index in self.bools.ids() {
+                            let handle = ReversibleBool::new(self, index);
+                            resources.push(self.dump_resource(handle.into(), "bool", index, self.get_bool(handle)));
+                        }
+                        for index in self.option_bools.ids() {
+                            let handle = ReversibleOptionBool::new(self, index);
+                            resources.push(self.dump_resource(handle.into(), "Option<bool>", index, self.get_option_bool(handle)));
+                        }
+                    }
+                    StateDump {
+                        depth: self.depth,
+                        levels: self.levels.iter().map(|level| LevelDump { clock: level.clock, empties: level.empties }).collect(),
+                        resources,
+                    }
+                }
+
+                /// Returns the resources saved in the level identified by `clock`, with their saved value
+                /// and their current value. For the booleans, only the flags whose value changed are given.
+                fn trailed_in(&self, clock: usize) -> Vec<TrailedResource> {
+                    let mut trailed = vec![];
+                    $(
+                        #[cfg(feature = $feature)]
+                        {
+                            for (index, old) in self.[<numbers _ $u>].trailed(clock) {
+                                let handle = [<Reversible $u:camel>]::new(self, index);
+                                trailed.push(self.trailed_resource(handle.into(), stringify!($u), index, old, self.[<get_ $u>](handle)));
+                            }
+                            for (index, old) in self.[<numbers_option_ $u>].trailed(clock) {
+                                let handle = [<ReversibleOption $u:camel>]::new(self, index);
+                                let type_name = concat!("Option<", stringify!($u), ">");
+                                trailed.push(self.trailed_resource(handle.into(), type_name, index, old, self.[<get_option_ $u>](handle)));
+                            }
+                        }
+                    )*
+                    #[cfg(feature = "bool")]
+                    {
+                        type Flags<B> = Bools<<B as TrailBackend>::Store<u64>>;
+                        type OptionFlags<B> = OptionBools<<B as TrailBackend>::Store<u64>>;
+                        for (word, old) in self.bools.words.trailed(clock) {
+                            let flags = word * Flags::<B>::PER_WORD..(word + 1) * Flags::<B>::PER_WORD;
+                            for index in flags.filter(|index| *index < self.bools.len()) {
+                                let old = Flags::<B>::unpack(old, index);
+                                if old != self.bools.get(index) {
+                                    let handle = ReversibleBool::new(self, index);
+                                    trailed.push(self.trailed_resource(handle.into(), "bool", index, old != 0, self.get_bool(handle)));
+                                }
+                            }
+                        }
+                        for (word, old) in self.option_bools.words.trailed(clock) {
+                            let flags = word * OptionFlags::<B>::PER_WORD..(word + 1) * OptionFlags::<B>::PER_WORD;
+                            for index in flags.filter(|index| *index < self.option_bools.len()) {
+                                let old = OptionFlags::<B>::unpack(old, index);
+                                if old != self.option_bools.get(index) {
+                                    let handle = ReversibleOptionBool::new(self, index);
+                                    let old = (old & 1 != 0).then_some(old & 2 != 0);
+                                    trailed.push(self.trailed_resource(handle.into(), "Option<bool>", index, old, self.get_option_bool(handle)));
+                                }
+                            }
+                        }
+                    }
+                    trailed
+                }
+
+                /// Returns the number of managed resources
+                fn resource_count(&self) -> usize {
+                    #[allow(unused_mut)]
+                    let mut count = 0;
+                    $(
+                        #[cfg(feature = $feature)]
+                        {
+                            count += self.[<numbers _ $u>].len() + self.[<numbers_option_ $u>].len();
+                        }
+                    )*
+                    #[cfg(feature = "bool")]
+                    {
+                        count += self.bools.ids().count() + self.option_bools.ids().count();
+                    }
+                    count
+                }
+            }
+        }
+    };
+}
+
+for_each_managed_type!(dump_state);
+
+/// A resource saved in a level, used to format the manager
+struct TrailedResource {
+    resource: ResourceDump,
+    old: String,
+}
+
+impl fmt::Display for TrailedResource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let resource = &self.resource;
+        match &resource.name {
+            Some(name) => write!(f, "{} ({} #{})", name, resource.type_name, resource.index)?,
+            None => write!(f, "{} #{}", resource.type_name, resource.index)?,
+        }
+        write!(f, ": {} -> {}", self.old, resource.value)
+    }
+}
+
+/// Shows the levels of the manager, from the root, with the resources saved in each of them and
+/// their saved and current values
+impl<B: TrailBackend> fmt::Debug for StateManager<B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "StateManager (depth {}, {} resources)", self.depth, self.resource_count())?;
+        for level in self.levels.iter() {
+            writeln!(f, "  level {} (+{} empty)", level.clock, level.empties)?;
+            for trailed in self.trailed_in(level.clock) {
+                writeln!(f, "    {}", trailed)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Shows a summary of the manager
+impl<B: TrailBackend> fmt::Display for StateManager<B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let trailed = self.levels.iter().map(|level| self.trailed_in(level.clock).len()).sum::<usize>();
+        write!(f, "StateManager(depth {}, {} resources, {} saved)", self.depth, self.resource_count(), trailed)
+    }
+}
+
+impl<B: TrailBackend> StateManager<B> {
+    fn trailed_resource(&self, handle: AnyHandle, type_name: &'static str, index: usize, old: impl fmt::Debug, value: impl fmt::Debug) -> TrailedResource {
+        TrailedResource {
+            resource: self.dump_resource(handle, type_name, index, value),
+            old: format!("{:?}", old),
+        }
+    }
+
+    fn dump_resource(&self, handle: AnyHandle, type_name: &'static str, index: usize, value: impl fmt::Debug) -> ResourceDump {
+        ResourceDump {
+            handle,
+            type_name,
+            index,
+            name: self.name_of(handle).map(String::from),
+            value: format!("{:?}", value),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "usize", feature = "bool"))]
+mod test_format {
+    use crate::{BoolManager, OptionUsizeManager, SaveAndRestore, StateManager, UsizeManager};
+
+    #[test]
+    fn levels_are_shown_with_their_saved_resources() {
+        let mut mgr = StateManager::default();
+        let numbers = (0..4).map(|i| mgr.manage_usize(i)).collect::<Vec<_>>();
+        let y = mgr.manage_option_usize_named(None, "best");
+        let flags = (0..3).map(|_| mgr.manage_bool(false)).collect::<Vec<_>>();
+        mgr.save_state();
+        mgr.set_usize(numbers[1], 10);
+        mgr.set_bool(flags[2], true);
+        mgr.save_state();
+        mgr.save_state();
+        mgr.set_usize_slice(numbers[2], &[20, 30]);
+        mgr.set_option_usize(y, Some(4));
+        let expected = "StateManager (depth 3, 8 resources)
+  level 0 (+0 empty)
+  level 1 (+1 empty)
+    usize #1: 1 -> 10
+    bool #2: false -> true
+  level 2 (+0 empty)
+    usize #2: 2 -> 20
+    usize #3: 3 -> 30
+    best (Option<usize> #0): None -> Some(4)
+";
+        assert_eq!(expected, format!("{:?}", mgr));
+        assert_eq!("StateManager(depth 3, 8 resources, 5 saved)", mgr.to_string());
+    }
+}
+
+#[cfg(all(test, feature = "usize", feature = "bool"))]
+mod test_dump {
+    use crate::{BoolManager, OptionUsizeManager, SaveAndRestore, StateManager, UsizeManager};
+
+    #[test]
+    fn resources_and_levels_are_dumped() {
+        let mut mgr = StateManager::default();
+        let x = mgr.manage_usize(3);
+        mgr.manage_option_usize(None);
+        mgr.manage_bool(true);
+        mgr.save_state();
+        mgr.set_usize(x, 7);
+        mgr.save_state();
+        // This flag is created in a new word
+        mgr.manage_bool(false);
+        mgr.save_state();
+        let dump = mgr.dump_state();
+        assert_eq!(3, dump.depth);
+        assert_eq!(vec![0, 1, 2], dump.levels.iter().map(|level| level.clock).collect::<Vec<_>>());
+        assert_eq!(1, dump.levels[2].empties);
+        let resources = dump.resources.iter().map(|r| (r.type_name, r.index, r.value.as_str())).collect::<Vec<_>>();
+        assert_eq!(vec![("usize", 0, "7"), ("Option<usize>", 0, "None"), ("bool", 0, "true"), ("bool", 64, "false")], resources);
+        assert_eq!("depth 3\nlevel 0 (+0 empty)\nlevel 1 (+0 empty)\nlevel 2 (+1 empty)\nusize #0 = 7\nOption<usize> #0 = None\nbool #0 = true\nbool #64 = false\n", dump.to_string());
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn dump_is_written_in_json() {
+        let mut mgr = StateManager::default();
+        mgr.manage_usize(3);
+        mgr.save_state();
+        let json = mgr.dump_state().to_json();
+        assert_eq!("{\"depth\":1,\"levels\":[{\"clock\":0,\"empties\":1}],\"resources\":[{\"type\":\"usize\",\"index\":0,\"name\":null,\"value\":\"3\"}]}", json);
+    }
+}
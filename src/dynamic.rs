@@ -0,0 +1,172 @@
+//Copyright (c) 2023 X. Gillard, A. Dubray
+//
+//Permission is hereby granted, free of charge, to any person obtaining a copy
+//of this software and associated documentation files (the "Software"), to deal
+//in the Software without restriction, including without limitation the rights
+//to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+//copies of the Software, and to permit persons to whom the Software is
+//furnished to do so, subject to the following conditions:
+//
+//The above copyright notice and this permission notice shall be included in all
+//copies or substantial portions of the Software.
+//
+//THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+//IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+//AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+//OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+//SOFTWARE.
+
+
+//! A type-erased interface of the managers. `DynStateManager` is object safe: the resources are identified by
+//! an `AnyHandle` and their values are `Value`s, so that code compiled separately (e.g. a plugin loaded at
+//! runtime) can manipulate the state through a `&mut dyn DynStateManager`, whatever the backend of the
+//! manager.
+//!
+#![cfg_attr(feature = "i32", doc = "```")]
+#![cfg_attr(not(feature = "i32"), doc = "```ignore")]
+//! use search_trail::{DynStateManager, SaveAndRestore, StateManager, Value};
+//!
+//! fn plugin(mgr: &mut dyn DynStateManager) {
+//!     let x = mgr.manage_any(Value::I32(1));
+//!     mgr.save_state();
+//!     mgr.set_any(x, Value::I32(2)).unwrap();
+//!     assert_eq!(Value::I32(2), mgr.get_any(x));
+//!     mgr.restore_state();
+//!     assert_eq!(Value::I32(1), mgr.get_any(x));
+//! }
+//!
+//! plugin(&mut StateManager::default());
+//! ```
+
+use std::mem::discriminant;
+
+use crate::*;
+
+/// The operations of a manager that do not depend on the types of the resources nor on its backend. The
+/// levels are saved and restored with the methods of `SaveAndRestore`.
+pub trait DynStateManager: SaveAndRestore {
+    /// Returns the number of saved levels
+    fn depth(&self) -> usize;
+    /// Creates a new managed resource of the type of the value, and returns its handle
+    fn manage_any(&mut self, value: Value) -> AnyHandle;
+    /// Returns the value of the resource. Panics if the handle is not valid.
+    fn get_any(&self, handle: AnyHandle) -> Value;
+    /// Sets the value of the resource and returns the new value. Returns an error, without modifying the
+    /// resource, if the value is not of the type of the resource or if the resource is frozen.
+    fn set_any(&mut self, handle: AnyHandle, value: Value) -> Result<Value, TrailError>;
+}
+
+impl<B: TrailBackend> DynStateManager for StateManager<B> {
+    fn depth(&self) -> usize {
+        self.depth
+    }
+
+    fn manage_any(&mut self, value: Value) -> AnyHandle {
+        value.manage(self)
+    }
+
+    fn get_any(&self, handle: AnyHandle) -> Value {
+        self.any_value(handle)
+    }
+
+    fn set_any(&mut self, handle: AnyHandle, value: Value) -> Result<Value, TrailError> {
+        if discriminant(&self.any_value(handle)) != discriminant(&value) {
+            return Err(TrailError::TypeMismatch);
+        }
+        if self.is_frozen(handle) {
+            return Err(TrailError::Frozen);
+        }
+        self.set_any_value(handle, value);
+        Ok(self.any_value(handle))
+    }
+}
+
+/// A value to give to a resource of any type
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TypedAssignment {
+    /// The assigned resource
+    pub handle: AnyHandle,
+    /// The value given to the resource
+    pub value: Value,
+}
+
+impl From<(AnyHandle, Value)> for TypedAssignment {
+    fn from((handle, value): (AnyHandle, Value)) -> Self {
+        Self { handle, value }
+    }
+}
+
+impl<B: TrailBackend> StateManager<B> {
+    /// Gives their value to all the assigned resources. The assignments are all checked before any of
+    /// them is applied: if a handle is not valid, a value is not of the type of its resource, or a
+    /// resource is frozen, an error is returned and the state is left untouched.
+    ///
+    #[cfg_attr(all(feature = "usize", feature = "bool"), doc = "```")]
+    #[cfg_attr(not(all(feature = "usize", feature = "bool")), doc = "```ignore")]
+    /// use search_trail::{AnyHandle, StateManager, TrailError, TypedAssignment, Value};
+    ///
+    /// let mut mgr = StateManager::default();
+    /// let x = mgr.manage(0usize);
+    /// let b = mgr.manage(false);
+    /// let warm_start = [(AnyHandle::Usize(x), Value::Usize(3)), (AnyHandle::Bool(b), Value::Usize(1))];
+    /// assert_eq!(Err(TrailError::TypeMismatch), mgr.assign_all(warm_start.map(TypedAssignment::from)));
+    /// assert_eq!(0, mgr.get(x));
+    /// ```
+    pub fn assign_all(&mut self, assignments: impl IntoIterator<Item = TypedAssignment>) -> Result<(), TrailError> {
+        let assignments = assignments.into_iter().collect::<Vec<_>>();
+        for assignment in assignments.iter() {
+            self.check_any_handle(assignment.handle)?;
+            if discriminant(&self.any_value(assignment.handle)) != discriminant(&assignment.value) {
+                return Err(TrailError::TypeMismatch);
+            }
+            if self.is_frozen(assignment.handle) {
+                return Err(TrailError::Frozen);
+            }
+        }
+        for assignment in assignments {
+            self.set_any_value(assignment.handle, assignment.value);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(all(test, feature = "u64", feature = "bool"))]
+mod test_dynamic {
+    use crate::*;
+
+    #[test]
+    fn managers_of_any_backend_are_used_through_the_trait() {
+        let mut managers: Vec<Box<dyn DynStateManager>> = vec![Box::new(StateManager::default()), Box::new(CopyingStateManager::new())];
+        for mgr in managers.iter_mut() {
+            let x = mgr.manage_any(Value::U64(1));
+            let b = mgr.manage_any(Value::OptionBool(None));
+            mgr.save_state();
+            assert_eq!(Ok(Value::U64(5)), mgr.set_any(x, Value::U64(5)));
+            assert_eq!(Ok(Value::OptionBool(Some(true))), mgr.set_any(b, Value::OptionBool(Some(true))));
+            assert_eq!(Err(TrailError::TypeMismatch), mgr.set_any(x, Value::Bool(true)));
+            assert_eq!(1, mgr.depth());
+            mgr.restore_state();
+            assert_eq!((Value::U64(1), Value::OptionBool(None)), (mgr.get_any(x), mgr.get_any(b)));
+        }
+    }
+
+    #[test]
+    fn failed_assignments_leave_the_state_untouched() {
+        let mut mgr = StateManager::default();
+        let x = mgr.manage(1u64);
+        let b = mgr.manage(Some(false));
+        let assignments = vec![TypedAssignment::from((x.into(), Value::U64(2))), TypedAssignment::from((b.into(), Value::OptionBool(None)))];
+        let mut other = StateManager::default();
+        other.manage(0u64);
+        let unknown = TypedAssignment { handle: other.manage(9u64).into(), value: Value::U64(0) };
+        assert!(mgr.assign_all(assignments.iter().copied().chain([unknown])).is_err());
+        mgr.freeze_resource(b);
+        assert_eq!(Err(TrailError::Frozen), mgr.assign_all(assignments.clone()));
+        assert_eq!((1, Some(false)), (mgr.get(x), mgr.get(b)));
+        mgr.unfreeze_resource(b);
+        assert_eq!(Ok(()), mgr.assign_all(assignments));
+        assert_eq!((2, None), (mgr.get(x), mgr.get(b)));
+    }
+}
@@ -0,0 +1,62 @@
+//Copyright (c) 2023 X. Gillard, A. Dubray
+//
+//Permission is hereby granted, free of charge, to any person obtaining a copy
+//of this software and associated documentation files (the "Software"), to deal
+//in the Software without restriction, including without limitation the rights
+//to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+//copies of the Software, and to permit persons to whom the Software is
+//furnished to do so, subject to the following conditions:
+//
+//The above copyright notice and this permission notice shall be included in all
+//copies or substantial portions of the Software.
+//
+//THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+//IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+//AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+//OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+//SOFTWARE.
+
+
+//! The errors returned by the fallible accessors of the managers
+
+/// The errors that can occur when accessing a managed resource through a handle
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrailError {
+    /// The handle does not refer to a resource of the manager. It might have been created by another
+    /// manager, or before the manager was cleared.
+    InvalidHandle {
+        /// The index of the handle
+        id: usize,
+        /// The number of resources of this type in the manager
+        len: usize,
+    },
+    /// The handle has been created by another manager (feature `checked-handles`)
+    ForeignHandle,
+    /// The handle has been created before its manager was reset (feature `checked-handles`)
+    StaleHandle,
+    /// A manager with a fixed capacity is full
+    CapacityExceeded,
+    /// The resource is frozen and can not be modified
+    Frozen,
+    /// The value is not of the type of the resource
+    TypeMismatch,
+}
+
+impl std::fmt::Display for TrailError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TrailError::InvalidHandle { id, len } => {
+                write!(f, "invalid handle {} for a manager with {} resources of this type", id, len)
+            }
+            TrailError::ForeignHandle => write!(f, "the handle has been created by another manager"),
+            TrailError::StaleHandle => write!(f, "the handle has been created before the manager was reset"),
+            TrailError::CapacityExceeded => write!(f, "the capacity of the manager is exceeded"),
+            TrailError::Frozen => write!(f, "the resource is frozen"),
+            TrailError::TypeMismatch => write!(f, "the value is not of the type of the resource"),
+        }
+    }
+}
+
+impl std::error::Error for TrailError {}
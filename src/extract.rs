@@ -0,0 +1,86 @@
+//Copyright (c) 2023 X. Gillard, A. Dubray
+//
+//Permission is hereby granted, free of charge, to any person obtaining a copy
+//of this software and associated documentation files (the "Software"), to deal
+//in the Software without restriction, including without limitation the rights
+//to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+//copies of the Software, and to permit persons to whom the Software is
+//furnished to do so, subject to the following conditions:
+//
+//The above copyright notice and this permission notice shall be included in all
+//copies or substantial portions of the Software.
+//
+//THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+//IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+//AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+//OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+//SOFTWARE.
+
+
+//! Extraction of solutions. The current values of a set of resources are copied in owned data, e.g. to keep
+//! the incumbent solution before the search backtracks away from it.
+//!
+#![cfg_attr(feature = "usize", doc = "```")]
+#![cfg_attr(not(feature = "usize"), doc = "```ignore")]
+//! use search_trail::{AnyHandle, SaveAndRestore, StateManager, UsizeManager, Value};
+//!
+//! let mut mgr = StateManager::default();
+//! let xs = [0, 0, 0].map(|x| mgr.manage_usize(x));
+//! mgr.save_state();
+//! mgr.set_usize(xs[1], 4);
+//! let solution = mgr.extract_usize(&xs);
+//! let any = mgr.extract(&[AnyHandle::Usize(xs[1])]);
+//! mgr.restore_state();
+//! assert_eq!(vec![0, 4, 0], solution);
+//! assert_eq!(vec![Value::Usize(4)], any);
+//! ```
+
+use paste::paste;
+
+use crate::*;
+
+macro_rules! extract_values {
+    ($($u:ty => $feature:literal),*) => {
+        paste! {
+            impl<B: TrailBackend> StateManager<B> {
+                $(
+                    #[doc="Returns the current values of the given resources of type `" $u "`"]
+                    #[cfg(feature = $feature)]
+                    pub fn [<extract_ $u>](&self, handles: &[[<Reversible $u:camel>]]) -> Vec<$u> {
+                        handles.iter().map(|&handle| self.[<get_ $u>](handle)).collect()
+                    }
+
+                    #[doc="Returns the current values of the given resources of type `Option<" $u ">`"]
+                    #[cfg(feature = $feature)]
+                    pub fn [<extract_option_ $u>](&self, handles: &[[<ReversibleOption $u:camel>]]) -> Vec<Option<$u>> {
+                        handles.iter().map(|&handle| self.[<get_option_ $u>](handle)).collect()
+                    }
+                )*
+            }
+        }
+    };
+}
+
+for_each_managed_type!(extract_values);
+
+#[cfg(feature = "bool")]
+impl<B: TrailBackend> StateManager<B> {
+    /// Returns the current values of the given booleans
+    pub fn extract_bool(&self, handles: &[ReversibleBool]) -> Vec<bool> {
+        handles.iter().map(|&handle| self.get_bool(handle)).collect()
+    }
+
+    /// Returns the current values of the given optional booleans
+    pub fn extract_option_bool(&self, handles: &[ReversibleOptionBool]) -> Vec<Option<bool>> {
+        handles.iter().map(|&handle| self.get_option_bool(handle)).collect()
+    }
+}
+
+impl<B: TrailBackend> StateManager<B> {
+    /// Returns the current values of the given resources, of any type
+    pub fn extract(&self, handles: &[AnyHandle]) -> Vec<Value> {
+        handles.iter().map(|&handle| self.any_value(handle)).collect()
+    }
+}
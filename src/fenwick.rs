@@ -0,0 +1,167 @@
+//Copyright (c) 2023 X. Gillard, A. Dubray
+//
+//Permission is hereby granted, free of charge, to any person obtaining a copy
+//of this software and associated documentation files (the "Software"), to deal
+//in the Software without restriction, including without limitation the rights
+//to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+//copies of the Software, and to permit persons to whom the Software is
+//furnished to do so, subject to the following conditions:
+//
+//The above copyright notice and this permission notice shall be included in all
+//copies or substantial portions of the Software.
+//
+//THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+//IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+//AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+//OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+//SOFTWARE.
+
+//! A Fenwick (binary indexed) tree over an array of managed numbers, giving the prefix sums of the array
+//! in logarithmic time. An addition to a value only writes the nodes on its path, which are the only ones
+//! trailed. It can, e.g., sample a remaining value with a probability proportional to its weight.
+
+use std::ops::Range;
+
+use num_traits::Num;
+
+use crate::*;
+
+/// A Fenwick tree whose additions are undone by restoring the state of the manager used to modify it.
+///
+/// #Example
+///
+#[cfg_attr(feature = "i64", doc = "```")]
+#[cfg_attr(not(feature = "i64"), doc = "```ignore")]
+/// use search_trail::{FenwickTree, SaveAndRestore, StateManager};
+///
+/// let mut mgr = StateManager::default();
+/// let weights = FenwickTree::new(&mut mgr, &[4i64, 1, 3]);
+/// mgr.save_state();
+/// weights.add(&mut mgr, 0, -4);
+/// assert_eq!(4, weights.prefix_sum(&mgr, 3));
+/// // The value drawn by the number 3 in 0..4
+/// assert_eq!(Some(2), weights.find_prefix(&mgr, 3));
+/// mgr.restore_state();
+/// assert_eq!(Some(0), weights.find_prefix(&mgr, 3));
+/// ```
+#[derive(Debug, Clone)]
+pub struct FenwickTree<T: ScopedValue> {
+    /// The node `i` (counted from 1) holds the sum of the values in `i - lowbit(i)..i`
+    nodes: Vec<T::Handle>,
+}
+
+impl<T: ScopedValue + Num + PartialOrd> FenwickTree<T> {
+    /// Creates a tree over the given values, trailed by the given manager
+    pub fn new<B: TrailBackend>(mgr: &mut StateManager<B>, values: &[T]) -> Self {
+        let mut sums = values.to_vec();
+        for node in 1..=sums.len() {
+            let parent = node + lowbit(node);
+            if parent <= sums.len() {
+                sums[parent - 1] = sums[parent - 1] + sums[node - 1];
+            }
+        }
+        Self {
+            nodes: sums.into_iter().map(|sum| mgr.manage(sum)).collect(),
+        }
+    }
+
+    /// Returns the number of values in the array
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Returns true if the array is empty
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Adds `delta` to the value at the given index of the array
+    pub fn add<B: TrailBackend>(&self, mgr: &mut StateManager<B>, index: usize, delta: T) {
+        assert!(index < self.len(), "The index {} is out of the array of {} values", index, self.len());
+        let mut node = index + 1;
+        while node <= self.len() {
+            let handle = self.nodes[node - 1];
+            mgr.set(handle, mgr.get(handle) + delta);
+            node += lowbit(node);
+        }
+    }
+
+    /// Returns the sum of the values at the indices `0..end`
+    pub fn prefix_sum<B: TrailBackend>(&self, mgr: &StateManager<B>, end: usize) -> T {
+        assert!(end <= self.len(), "The prefix of {} values is out of the array of {} values", end, self.len());
+        let mut sum = T::zero();
+        let mut node = end;
+        while node > 0 {
+            sum = sum + mgr.get(self.nodes[node - 1]);
+            node -= lowbit(node);
+        }
+        sum
+    }
+
+    /// Returns the sum of the values in the given range of indices
+    pub fn range_sum<B: TrailBackend>(&self, mgr: &StateManager<B>, range: Range<usize>) -> T {
+        self.prefix_sum(mgr, range.end) - self.prefix_sum(mgr, range.start)
+    }
+
+    /// Returns the value at the given index of the array
+    pub fn get<B: TrailBackend>(&self, mgr: &StateManager<B>, index: usize) -> T {
+        self.range_sum(mgr, index..index + 1)
+    }
+
+    /// Returns the smallest index whose prefix sum, including its value, is greater than `target`, or
+    /// `None` if the sum of the whole array is not. The values must not be negative.
+    pub fn find_prefix<B: TrailBackend>(&self, mgr: &StateManager<B>, mut target: T) -> Option<usize> {
+        let mut node = 0;
+        let mut step = if self.is_empty() { 0 } else { 1 << self.len().ilog2() };
+        while step > 0 {
+            if node + step <= self.len() {
+                let sum = mgr.get(self.nodes[node + step - 1]);
+                if sum <= target {
+                    node += step;
+                    target = target - sum;
+                }
+            }
+            step /= 2;
+        }
+        (node < self.len()).then_some(node)
+    }
+}
+
+/// Returns the lowest bit set in the index
+fn lowbit(index: usize) -> usize {
+    index & index.wrapping_neg()
+}
+
+#[cfg(all(test, feature = "i32"))]
+mod test_fenwick {
+    use crate::*;
+
+    #[test]
+    fn prefix_sums_match_a_scan() {
+        let mut mgr = StateManager::default();
+        let mut values = vec![5i32, 0, 2, 7, 1, 0, 3, 4, 6];
+        let tree = FenwickTree::new(&mut mgr, &values);
+        let mut saved = vec![];
+        for (index, delta) in [(0, -5), (8, 2), (3, -7), (5, 1)] {
+            mgr.save_state();
+            saved.push(values.clone());
+            values[index] += delta;
+            tree.add(&mut mgr, index, delta);
+            for end in 0..=values.len() {
+                assert_eq!(values[..end].iter().sum::<i32>(), tree.prefix_sum(&mgr, end));
+            }
+            let total = values.iter().sum::<i32>();
+            for target in 0..=total {
+                let expected = (0..values.len()).find(|&end| values[..=end].iter().sum::<i32>() > target);
+                assert_eq!(expected, tree.find_prefix(&mgr, target));
+            }
+        }
+        while let Some(previous) = saved.pop() {
+            mgr.restore_state();
+            assert_eq!(previous, (0..values.len()).map(|index| tree.get(&mgr, index)).collect::<Vec<_>>());
+        }
+        assert_eq!(None, FenwickTree::<i32>::new(&mut mgr, &[]).find_prefix(&mgr, 0));
+    }
+}
@@ -0,0 +1,285 @@
+//Copyright (c) 2023 X. Gillard, A. Dubray
+//
+//Permission is hereby granted, free of charge, to any person obtaining a copy
+//of this software and associated documentation files (the "Software"), to deal
+//in the Software without restriction, including without limitation the rights
+//to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+//copies of the Software, and to permit persons to whom the Software is
+//furnished to do so, subject to the following conditions:
+//
+//The above copyright notice and this permission notice shall be included in all
+//copies or substantial portions of the Software.
+//
+//THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+//IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+//AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+//OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+//SOFTWARE.
+
+
+//! A C API over the default manager, for solvers written in C or C++. The manager is an opaque
+//! pointer created by `st_manager_new` and freed by `st_manager_free`, and the handles are the
+//! indexes of the resources. The header `include/search_trail.h` is generated from this module with
+//! `cbindgen --config cbindgen.toml --output include/search_trail.h src/ffi.rs`.
+//!
+//! All the functions taking a manager require a valid pointer returned by `st_manager_new`, which has
+//! not been freed, and which is not used concurrently by another thread.
+
+use std::os::raw::c_int;
+
+use crate::*;
+
+/// Returned when the operation succeeded
+pub const ST_OK: c_int = 0;
+/// Returned when the handle is not the handle of a resource of the manager
+pub const ST_INVALID_HANDLE: c_int = -1;
+
+/// The manager seen from C
+pub type SearchTrailManager = StateManager;
+
+/// Reads a resource with the given accessor, and writes its value in `out`
+unsafe fn read<T>(out: *mut T, value: Result<T, TrailError>) -> c_int {
+    match value {
+        Ok(value) => {
+            *out = value;
+            ST_OK
+        }
+        Err(_) => ST_INVALID_HANDLE,
+    }
+}
+
+/// Returns the status of a write
+fn status<T>(result: Result<T, TrailError>) -> c_int {
+    match result {
+        Ok(_) => ST_OK,
+        Err(_) => ST_INVALID_HANDLE,
+    }
+}
+
+/// Creates a new manager, to be freed with `st_manager_free`
+#[no_mangle]
+pub extern "C" fn st_manager_new() -> *mut SearchTrailManager {
+    Box::into_raw(Box::default())
+}
+
+/// Frees a manager created by `st_manager_new`. Does nothing if the pointer is null.
+///
+/// # Safety
+///
+/// The pointer must be null or returned by `st_manager_new`, and not freed yet
+#[no_mangle]
+pub unsafe extern "C" fn st_manager_free(mgr: *mut SearchTrailManager) {
+    if !mgr.is_null() {
+        drop(Box::from_raw(mgr));
+    }
+}
+
+/// Saves the state of the manager
+///
+/// # Safety
+///
+/// The manager must be valid
+#[no_mangle]
+pub unsafe extern "C" fn st_save_state(mgr: *mut SearchTrailManager) {
+    (*mgr).save_state();
+}
+
+/// Restores the last saved state of the manager
+///
+/// # Safety
+///
+/// The manager must be valid, and have a saved state
+#[no_mangle]
+pub unsafe extern "C" fn st_restore_state(mgr: *mut SearchTrailManager) {
+    (*mgr).restore_state();
+}
+
+/// Returns the number of saved states
+///
+/// # Safety
+///
+/// The manager must be valid
+#[no_mangle]
+pub unsafe extern "C" fn st_depth(mgr: *const SearchTrailManager) -> usize {
+    (*mgr).depth
+}
+
+/// Creates a new managed `size_t` and returns its handle
+///
+/// # Safety
+///
+/// The manager must be valid
+#[no_mangle]
+pub unsafe extern "C" fn st_manage_usize(mgr: *mut SearchTrailManager, value: usize) -> usize {
+    (*mgr).manage_usize(value).0
+}
+
+/// Writes the value of a managed `size_t` in `out`
+///
+/// # Safety
+///
+/// The manager must be valid, and `out` must be a valid pointer
+#[no_mangle]
+pub unsafe extern "C" fn st_get_usize(mgr: *const SearchTrailManager, handle: usize, out: *mut usize) -> c_int {
+    let mgr = &*mgr;
+    read(out, mgr.try_get_usize(ReversibleUsize::new(mgr, handle)))
+}
+
+/// Sets the value of a managed `size_t`
+///
+/// # Safety
+///
+/// The manager must be valid
+#[no_mangle]
+pub unsafe extern "C" fn st_set_usize(mgr: *mut SearchTrailManager, handle: usize, value: usize) -> c_int {
+    let mgr = &mut *mgr;
+    status(mgr.try_set_usize(ReversibleUsize::new(mgr, handle), value))
+}
+
+/// Creates a new managed `int64_t` and returns its handle
+///
+/// # Safety
+///
+/// The manager must be valid
+#[no_mangle]
+pub unsafe extern "C" fn st_manage_i64(mgr: *mut SearchTrailManager, value: i64) -> usize {
+    (*mgr).manage_i64(value).0
+}
+
+/// Writes the value of a managed `int64_t` in `out`
+///
+/// # Safety
+///
+/// The manager must be valid, and `out` must be a valid pointer
+#[no_mangle]
+pub unsafe extern "C" fn st_get_i64(mgr: *const SearchTrailManager, handle: usize, out: *mut i64) -> c_int {
+    let mgr = &*mgr;
+    read(out, mgr.try_get_i64(ReversibleI64::new(mgr, handle)))
+}
+
+/// Sets the value of a managed `int64_t`
+///
+/// # Safety
+///
+/// The manager must be valid
+#[no_mangle]
+pub unsafe extern "C" fn st_set_i64(mgr: *mut SearchTrailManager, handle: usize, value: i64) -> c_int {
+    let mgr = &mut *mgr;
+    status(mgr.try_set_i64(ReversibleI64::new(mgr, handle), value))
+}
+
+/// Creates a new managed `double` and returns its handle
+///
+/// # Safety
+///
+/// The manager must be valid
+#[no_mangle]
+pub unsafe extern "C" fn st_manage_f64(mgr: *mut SearchTrailManager, value: f64) -> usize {
+    (*mgr).manage_f64(value).0
+}
+
+/// Writes the value of a managed `double` in `out`
+///
+/// # Safety
+///
+/// The manager must be valid, and `out` must be a valid pointer
+#[no_mangle]
+pub unsafe extern "C" fn st_get_f64(mgr: *const SearchTrailManager, handle: usize, out: *mut f64) -> c_int {
+    let mgr = &*mgr;
+    read(out, mgr.try_get_f64(ReversibleF64::new(mgr, handle)))
+}
+
+/// Sets the value of a managed `double`
+///
+/// # Safety
+///
+/// The manager must be valid
+#[no_mangle]
+pub unsafe extern "C" fn st_set_f64(mgr: *mut SearchTrailManager, handle: usize, value: f64) -> c_int {
+    let mgr = &mut *mgr;
+    status(mgr.try_set_f64(ReversibleF64::new(mgr, handle), value))
+}
+
+/// Creates a new managed `bool` and returns its handle
+///
+/// # Safety
+///
+/// The manager must be valid
+#[no_mangle]
+pub unsafe extern "C" fn st_manage_bool(mgr: *mut SearchTrailManager, value: bool) -> usize {
+    (*mgr).manage_bool(value).0
+}
+
+/// Writes the value of a managed `bool` in `out`
+///
+/// # Safety
+///
+/// The manager must be valid, and `out` must be a valid pointer
+#[no_mangle]
+pub unsafe extern "C" fn st_get_bool(mgr: *const SearchTrailManager, handle: usize, out: *mut bool) -> c_int {
+    let mgr = &*mgr;
+    if handle >= mgr.bools.len() {
+        return ST_INVALID_HANDLE;
+    }
+    read(out, Ok(mgr.get_bool(ReversibleBool::new(mgr, handle))))
+}
+
+/// Sets the value of a managed `bool`
+///
+/// # Safety
+///
+/// The manager must be valid
+#[no_mangle]
+pub unsafe extern "C" fn st_set_bool(mgr: *mut SearchTrailManager, handle: usize, value: bool) -> c_int {
+    let mgr = &mut *mgr;
+    if handle >= mgr.bools.len() {
+        return ST_INVALID_HANDLE;
+    }
+    mgr.set_bool(ReversibleBool::new(mgr, handle), value);
+    ST_OK
+}
+
+#[cfg(test)]
+mod test_ffi {
+    use super::*;
+
+    #[test]
+    fn manager_is_used_through_the_c_api() {
+        unsafe {
+            let mgr = st_manager_new();
+            let x = st_manage_usize(mgr, 3);
+            let y = st_manage_i64(mgr, -1);
+            let z = st_manage_f64(mgr, 0.5);
+            let b = st_manage_bool(mgr, false);
+            st_save_state(mgr);
+            assert_eq!(ST_OK, st_set_usize(mgr, x, 4));
+            assert_eq!(ST_OK, st_set_i64(mgr, y, 10));
+            assert_eq!(ST_OK, st_set_f64(mgr, z, 1.5));
+            assert_eq!(ST_OK, st_set_bool(mgr, b, true));
+            assert_eq!(1, st_depth(mgr));
+            st_restore_state(mgr);
+            let (mut vx, mut vy, mut vz, mut vb) = (0, 0, 0.0, true);
+            assert_eq!(ST_OK, st_get_usize(mgr, x, &mut vx));
+            assert_eq!(ST_OK, st_get_i64(mgr, y, &mut vy));
+            assert_eq!(ST_OK, st_get_f64(mgr, z, &mut vz));
+            assert_eq!(ST_OK, st_get_bool(mgr, b, &mut vb));
+            assert_eq!((3, -1, 0.5, false), (vx, vy, vz, vb));
+            st_manager_free(mgr);
+        }
+    }
+
+    #[test]
+    fn invalid_handles_are_reported() {
+        unsafe {
+            let mgr = st_manager_new();
+            let mut value = 0;
+            assert_eq!(ST_INVALID_HANDLE, st_get_usize(mgr, 0, &mut value));
+            assert_eq!(ST_INVALID_HANDLE, st_set_usize(mgr, 0, 1));
+            assert_eq!(ST_INVALID_HANDLE, st_set_bool(mgr, 3, true));
+            st_manager_free(mgr);
+            st_manager_free(std::ptr::null_mut());
+        }
+    }
+}
@@ -0,0 +1,315 @@
+//Copyright (c) 2023 X. Gillard, A. Dubray
+//
+//Permission is hereby granted, free of charge, to any person obtaining a copy
+//of this software and associated documentation files (the "Software"), to deal
+//in the Software without restriction, including without limitation the rights
+//to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+//copies of the Software, and to permit persons to whom the Software is
+//furnished to do so, subject to the following conditions:
+//
+//The above copyright notice and this permission notice shall be included in all
+//copies or substantial portions of the Software.
+//
+//THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+//IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+//AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+//OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+//SOFTWARE.
+
+
+//! A state manager with a fixed capacity, that never allocates. The managed values and the trail are
+//! stored in arrays whose sizes are given as const parameters, and exceeding them returns an error
+//! instead of allocating. This is suited for embedded and real-time applications.
+
+use std::marker::PhantomData;
+
+use crate::TrailError;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// A value that can be managed by a `StaticStateManager`. The values are stored as words of 64 bits,
+/// hence the types larger than 64 bits can not be managed.
+pub trait StaticValue: Copy {
+    /// Returns the word storing the value
+    fn to_word(self) -> u64;
+    /// Returns the value stored in the word
+    fn from_word(word: u64) -> Self;
+}
+
+macro_rules! static_integers {
+    ($($t:ty),*) => {
+        $(
+            impl StaticValue for $t {
+                fn to_word(self) -> u64 {
+                    self as u64
+                }
+
+                fn from_word(word: u64) -> Self {
+                    word as $t
+                }
+            }
+        )*
+    };
+}
+
+static_integers!(u8, u16, u32, u64, usize, i8, i16, i32, i64, isize);
+
+impl StaticValue for f32 {
+    fn to_word(self) -> u64 {
+        self.to_bits() as u64
+    }
+
+    fn from_word(word: u64) -> Self {
+        f32::from_bits(word as u32)
+    }
+}
+
+impl StaticValue for f64 {
+    fn to_word(self) -> u64 {
+        self.to_bits()
+    }
+
+    fn from_word(word: u64) -> Self {
+        f64::from_bits(word)
+    }
+}
+
+impl StaticValue for bool {
+    fn to_word(self) -> u64 {
+        self as u64
+    }
+
+    fn from_word(word: u64) -> Self {
+        word != 0
+    }
+}
+
+/// Index of a value of type `T` managed by a `StaticStateManager`
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(bound = ""))]
+pub struct StaticHandle<T>(usize, PhantomData<T>);
+
+impl<T> Clone for StaticHandle<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for StaticHandle<T> {}
+
+impl<T> PartialEq for StaticHandle<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+
+/// Index of the trail entries marking the start of a level
+const LEVEL_MARKER: usize = usize::MAX;
+
+/// An entry of the trail. Either the saved state of a value, or the start of a level holding the
+/// clock of the previous level.
+#[derive(Debug, Clone, Copy)]
+struct StaticEntry {
+    id: usize,
+    clock: usize,
+    word: u64,
+}
+
+/// A state manager storing at most `VARS` values and `TRAIL` trail entries, without any allocation.
+/// Each saved level uses one trail entry, and each value modified in a level uses one trail entry.
+///
+/// #Example
+///
+/// ```
+/// use search_trail::StaticStateManager;
+///
+/// let mut mgr = StaticStateManager::<16, 4>::new();
+/// let x = mgr.manage(3u32).unwrap();
+/// mgr.save_state().unwrap();
+/// mgr.set(x, 4).unwrap();
+/// mgr.restore_state();
+/// assert_eq!(3, mgr.get(x));
+/// ```
+#[derive(Debug, Clone)]
+pub struct StaticStateManager<const TRAIL: usize, const VARS: usize> {
+    words: [u64; VARS],
+    clocks: [usize; VARS],
+    len: usize,
+    trail: [StaticEntry; TRAIL],
+    trail_len: usize,
+    clock: usize,
+    max_clock: usize,
+}
+
+impl<const TRAIL: usize, const VARS: usize> Default for StaticStateManager<TRAIL, VARS> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const TRAIL: usize, const VARS: usize> StaticStateManager<TRAIL, VARS> {
+    /// Creates a new manager, without any managed value
+    pub const fn new() -> Self {
+        Self {
+            words: [0; VARS],
+            clocks: [0; VARS],
+            len: 0,
+            trail: [StaticEntry { id: 0, clock: 0, word: 0 }; TRAIL],
+            trail_len: 0,
+            clock: 0,
+            max_clock: 0,
+        }
+    }
+
+    /// Creates a new managed value. Returns an error if `VARS` values are already managed.
+    pub fn manage<T: StaticValue>(&mut self, value: T) -> Result<StaticHandle<T>, TrailError> {
+        if self.len == VARS {
+            return Err(TrailError::CapacityExceeded);
+        }
+        self.words[self.len] = value.to_word();
+        self.clocks[self.len] = self.clock;
+        self.len += 1;
+        Ok(StaticHandle(self.len - 1, PhantomData))
+    }
+
+    /// Returns the value of the given handle
+    pub fn get<T: StaticValue>(&self, handle: StaticHandle<T>) -> T {
+        T::from_word(self.words[handle.0])
+    }
+
+    /// Sets the value of the given handle and returns the new value. Returns an error, without
+    /// modifying the value, if its previous value must be saved and the trail is full.
+    pub fn set<T: StaticValue>(&mut self, handle: StaticHandle<T>, value: T) -> Result<T, TrailError> {
+        let id = handle.0;
+        let word = value.to_word();
+        if word != self.words[id] {
+            // A value created in a level that has since been restored keeps the clock of this level, which
+            // is more recent than the current one, and is saved as if it was created at the root. Nothing
+            // is saved at the root, whose clock is 0.
+            let clock = if self.clocks[id] > self.clock { 0 } else { self.clocks[id] };
+            if clock != self.clock {
+                self.push(StaticEntry {
+                    id,
+                    clock,
+                    word: self.words[id],
+                })?;
+            }
+            self.clocks[id] = self.clock;
+            self.words[id] = word;
+        }
+        Ok(value)
+    }
+
+    /// Saves the current state of the managed values. Returns an error if the trail is full.
+    pub fn save_state(&mut self) -> Result<(), TrailError> {
+        self.push(StaticEntry {
+            id: LEVEL_MARKER,
+            clock: self.clock,
+            word: 0,
+        })?;
+        self.max_clock += 1;
+        self.clock = self.max_clock;
+        Ok(())
+    }
+
+    /// Restores the values as they were at the last call to `save_state()`. Does nothing at the root.
+    pub fn restore_state(&mut self) {
+        while self.trail_len > 0 {
+            self.trail_len -= 1;
+            let entry = self.trail[self.trail_len];
+            if entry.id == LEVEL_MARKER {
+                self.clock = entry.clock;
+                return;
+            }
+            self.words[entry.id] = entry.word;
+            self.clocks[entry.id] = entry.clock;
+        }
+    }
+
+    /// Returns the number of managed values
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns true if the manager does not manage any value
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the number of used trail entries
+    pub fn trail_len(&self) -> usize {
+        self.trail_len
+    }
+
+    fn push(&mut self, entry: StaticEntry) -> Result<(), TrailError> {
+        if self.trail_len == TRAIL {
+            return Err(TrailError::CapacityExceeded);
+        }
+        self.trail[self.trail_len] = entry;
+        self.trail_len += 1;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test_static_manager {
+    use super::StaticStateManager;
+    use crate::TrailError;
+
+    #[test]
+    fn values_are_restored() {
+        let mut mgr = StaticStateManager::<8, 3>::new();
+        let a = mgr.manage(-3i8).unwrap();
+        let b = mgr.manage(0.5f64).unwrap();
+        let c = mgr.manage(false).unwrap();
+        mgr.save_state().unwrap();
+        mgr.set(a, 4).unwrap();
+        mgr.set(a, 5).unwrap();
+        mgr.set(b, 1.5).unwrap();
+        mgr.save_state().unwrap();
+        mgr.set(c, true).unwrap();
+        assert_eq!(5, mgr.trail_len());
+        mgr.restore_state();
+        assert!(!mgr.get(c));
+        assert_eq!(5, mgr.get(a));
+        mgr.restore_state();
+        assert_eq!(-3, mgr.get(a));
+        assert_eq!(0.5, mgr.get(b));
+        assert_eq!(0, mgr.trail_len());
+    }
+
+    #[test]
+    fn capacity_is_not_exceeded() {
+        let mut mgr = StaticStateManager::<2, 1>::new();
+        let a = mgr.manage(0u32).unwrap();
+        assert_eq!(Err(TrailError::CapacityExceeded), mgr.manage(0u32).map(|_| ()));
+        mgr.save_state().unwrap();
+        mgr.set(a, 1).unwrap();
+        assert_eq!(Err(TrailError::CapacityExceeded), mgr.save_state());
+        mgr.set(a, 2).unwrap();
+        mgr.restore_state();
+        assert_eq!(0, mgr.get(a));
+    }
+
+    #[test]
+    fn values_of_restored_levels_are_not_saved_at_the_root() {
+        let mut mgr = StaticStateManager::<2, 2>::new();
+        mgr.save_state().unwrap();
+        let a = mgr.manage(0u32).unwrap();
+        mgr.restore_state();
+        for i in 1..100 {
+            assert_eq!(Ok(i), mgr.set(a, i));
+        }
+        assert_eq!(0, mgr.trail_len());
+        mgr.save_state().unwrap();
+        mgr.set(a, 0).unwrap();
+        mgr.restore_state();
+        assert_eq!(99, mgr.get(a));
+        // Restoring the root does nothing
+        mgr.restore_state();
+        assert_eq!((99, 0), (mgr.get(a), mgr.trail_len()));
+    }
+}
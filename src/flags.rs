@@ -0,0 +1,174 @@
+//Copyright (c) 2023 X. Gillard, A. Dubray
+//
+//Permission is hereby granted, free of charge, to any person obtaining a copy
+//of this software and associated documentation files (the "Software"), to deal
+//in the Software without restriction, including without limitation the rights
+//to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+//copies of the Software, and to permit persons to whom the Software is
+//furnished to do so, subject to the following conditions:
+//
+//The above copyright notice and this permission notice shall be included in all
+//copies or substantial portions of the Software.
+//
+//THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+//IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+//AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+//OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+//SOFTWARE.
+
+//! Managed sets of named bits, for the flags types generated by the `bitflags` crate. The flags are
+//! stored in one managed integer of their bits type, and are modified through their names instead of
+//! raw bit indices.
+//!
+//! ```
+//! use bitflags::bitflags;
+//! use search_trail::{SaveAndRestore, StateManager};
+//!
+//! bitflags! {
+//!     #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+//!     struct Status: u8 {
+//!         const FIXED = 1;
+//!         const PROPAGATED = 1 << 1;
+//!         const WATCHED = 1 << 2;
+//!     }
+//! }
+//!
+//! let mut mgr = StateManager::default();
+//! let status = mgr.manage_flags(Status::WATCHED);
+//! mgr.save_state();
+//! mgr.insert_flags(status, Status::FIXED | Status::PROPAGATED);
+//! mgr.remove_flags(status, Status::WATCHED);
+//! assert!(mgr.contains_flags(status, Status::FIXED));
+//! mgr.restore_state();
+//! assert_eq!(Status::WATCHED, mgr.get_flags(status));
+//! ```
+
+use std::fmt::Debug;
+use std::marker::PhantomData;
+
+use bitflags::Flags;
+
+use crate::*;
+
+/// The handle of a managed set of flags of type `F`
+pub struct ReversibleFlags<F: Flags<Bits: ScopedValue>> {
+    bits: Reversible<F::Bits>,
+    flags: PhantomData<F>,
+}
+
+impl<F: Flags<Bits: ScopedValue>> ReversibleFlags<F> {
+    /// Returns the handle of the integer holding the bits of the flags
+    pub fn handle(&self) -> Reversible<F::Bits> {
+        self.bits
+    }
+}
+
+impl<F: Flags<Bits: ScopedValue>> Clone for ReversibleFlags<F> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<F: Flags<Bits: ScopedValue>> Copy for ReversibleFlags<F> {}
+
+impl<F: Flags<Bits: ScopedValue>> PartialEq for ReversibleFlags<F> {
+    fn eq(&self, other: &Self) -> bool {
+        self.bits == other.bits
+    }
+}
+
+impl<F: Flags<Bits: ScopedValue>> Debug for ReversibleFlags<F> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("ReversibleFlags").field(&self.bits).finish()
+    }
+}
+
+impl<F: Flags<Bits: ScopedValue>> From<ReversibleFlags<F>> for AnyHandle {
+    fn from(handle: ReversibleFlags<F>) -> Self {
+        handle.bits.into()
+    }
+}
+
+impl<B: TrailBackend> StateManager<B> {
+    /// Creates a new managed set of flags with the given value and returns its handle
+    pub fn manage_flags<F: Flags<Bits: ScopedValue>>(&mut self, flags: F) -> ReversibleFlags<F> {
+        ReversibleFlags {
+            bits: self.manage(flags.bits()),
+            flags: PhantomData,
+        }
+    }
+
+    /// Returns the flags of the set, including the unnamed bits that were set
+    pub fn get_flags<F: Flags<Bits: ScopedValue>>(&self, handle: ReversibleFlags<F>) -> F {
+        F::from_bits_retain(self.get(handle.bits))
+    }
+
+    /// Sets the flags of the set and returns the new value
+    pub fn set_flags<F: Flags<Bits: ScopedValue>>(&mut self, handle: ReversibleFlags<F>, flags: F) -> F {
+        F::from_bits_retain(self.set(handle.bits, flags.bits()))
+    }
+
+    /// Sets the given flags in the set and returns the new value
+    pub fn insert_flags<F: Flags<Bits: ScopedValue>>(&mut self, handle: ReversibleFlags<F>, flags: F) -> F {
+        let current = self.get_flags(handle);
+        self.set_flags(handle, current.union(flags))
+    }
+
+    /// Unsets the given flags in the set and returns the new value
+    pub fn remove_flags<F: Flags<Bits: ScopedValue>>(&mut self, handle: ReversibleFlags<F>, flags: F) -> F {
+        let current = self.get_flags(handle);
+        self.set_flags(handle, current.difference(flags))
+    }
+
+    /// Flips the given flags in the set and returns the new value
+    pub fn toggle_flags<F: Flags<Bits: ScopedValue>>(&mut self, handle: ReversibleFlags<F>, flags: F) -> F {
+        let current = self.get_flags(handle);
+        self.set_flags(handle, current.symmetric_difference(flags))
+    }
+
+    /// Returns true if all the given flags are set in the set
+    pub fn contains_flags<F: Flags<Bits: ScopedValue>>(&self, handle: ReversibleFlags<F>, flags: F) -> bool {
+        self.get_flags(handle).contains(flags)
+    }
+}
+
+#[cfg(all(test, feature = "u16"))]
+mod test_flags {
+    use bitflags::bitflags;
+
+    use crate::*;
+
+    bitflags! {
+        #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+        struct Domain: u16 {
+            const EMPTY = 1;
+            const BOUNDED = 1 << 1;
+            const SINGLETON = 1 << 2;
+        }
+    }
+
+    #[test]
+    fn toggles_are_restored() {
+        let mut mgr = StateManager::default();
+        let domain = mgr.manage_flags(Domain::BOUNDED);
+        let other = mgr.manage_flags(Domain::empty());
+        mgr.save_state();
+        assert_eq!(Domain::BOUNDED | Domain::SINGLETON, mgr.toggle_flags(domain, Domain::SINGLETON));
+        mgr.save_state();
+        assert_eq!(Domain::SINGLETON, mgr.toggle_flags(domain, Domain::BOUNDED));
+        mgr.insert_flags(other, Domain::EMPTY);
+        // The unnamed bits are kept
+        mgr.set_flags(other, Domain::from_bits_retain(1 << 8));
+        assert!(!mgr.contains_flags(other, Domain::EMPTY));
+        assert_eq!(1 << 8, mgr.get_flags(other).bits());
+        mgr.restore_state();
+        assert_eq!(Domain::BOUNDED | Domain::SINGLETON, mgr.get_flags(domain));
+        assert_eq!(Domain::empty(), mgr.get_flags(other));
+        mgr.restore_state();
+        assert_eq!(Domain::BOUNDED, mgr.get_flags(domain));
+        assert_ne!(domain, other);
+        assert_eq!(AnyHandle::U16(domain.handle()), domain.into());
+    }
+}
@@ -0,0 +1,135 @@
+//Copyright (c) 2023 X. Gillard, A. Dubray
+//
+//Permission is hereby granted, free of charge, to any person obtaining a copy
+//of this software and associated documentation files (the "Software"), to deal
+//in the Software without restriction, including without limitation the rights
+//to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+//copies of the Software, and to permit persons to whom the Software is
+//furnished to do so, subject to the following conditions:
+//
+//The above copyright notice and this permission notice shall be included in all
+//copies or substantial portions of the Software.
+//
+//THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+//IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+//AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+//OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+//SOFTWARE.
+
+//! A rooted forest given by parent pointers, whose links and cuts are restored with the state of the
+//! manager, as needed by the tree constraints and the backtracking spanning tree algorithms. Linking or
+//! cutting a node is one trailed write, and the root of a node is found by following its parents.
+
+use crate::*;
+
+/// A forest whose links and cuts are undone by restoring the state of the manager used to modify it.
+///
+/// #Example
+///
+/// ```
+/// use search_trail::{ReversibleForest, SaveAndRestore, StateManager};
+///
+/// let mut mgr = StateManager::default();
+/// let forest = ReversibleForest::new(&mut mgr, 4);
+/// forest.link(&mut mgr, 1, 0);
+/// mgr.save_state();
+/// forest.link(&mut mgr, 2, 1);
+/// assert_eq!(0, forest.root_of(&mgr, 2));
+/// mgr.restore_state();
+/// assert!(!forest.connected(&mgr, 0, 2));
+/// ```
+#[derive(Debug, Clone)]
+pub struct ReversibleForest {
+    /// The parent of each node, a root being its own parent
+    parents: Vec<ReversibleUsize>,
+}
+
+impl ReversibleForest {
+    /// Creates a forest of the given number of nodes, each one being a root, trailed by the given manager
+    pub fn new<B: TrailBackend>(mgr: &mut StateManager<B>, nodes: usize) -> Self {
+        Self {
+            parents: (0..nodes).map(|node| mgr.manage(node)).collect(),
+        }
+    }
+
+    /// Returns the number of nodes
+    pub fn len(&self) -> usize {
+        self.parents.len()
+    }
+
+    /// Returns true if the forest has no node
+    pub fn is_empty(&self) -> bool {
+        self.parents.is_empty()
+    }
+
+    /// Returns the parent of the node, or `None` if it is a root
+    pub fn parent<B: TrailBackend>(&self, mgr: &StateManager<B>, node: usize) -> Option<usize> {
+        let parent = mgr.get(self.parents[node]);
+        (parent != node).then_some(parent)
+    }
+
+    /// Returns true if the node is the root of its tree
+    pub fn is_root<B: TrailBackend>(&self, mgr: &StateManager<B>, node: usize) -> bool {
+        self.parent(mgr, node).is_none()
+    }
+
+    /// Returns the root of the tree of the node
+    pub fn root_of<B: TrailBackend>(&self, mgr: &StateManager<B>, mut node: usize) -> usize {
+        while let Some(parent) = self.parent(mgr, node) {
+            node = parent;
+        }
+        node
+    }
+
+    /// Returns true if the two nodes are in the same tree
+    pub fn connected<B: TrailBackend>(&self, mgr: &StateManager<B>, a: usize, b: usize) -> bool {
+        self.root_of(mgr, a) == self.root_of(mgr, b)
+    }
+
+    /// Makes the root `child` a child of `parent`, merging their trees. Returns false, without modifying
+    /// the forest, if `child` is not a root or if the nodes are already in the same tree.
+    pub fn link<B: TrailBackend>(&self, mgr: &mut StateManager<B>, child: usize, parent: usize) -> bool {
+        if !self.is_root(mgr, child) || self.root_of(mgr, parent) == child {
+            return false;
+        }
+        mgr.set(self.parents[child], parent);
+        true
+    }
+
+    /// Detaches the node from its parent, making it the root of its subtree. Returns false if it was
+    /// already a root.
+    pub fn cut<B: TrailBackend>(&self, mgr: &mut StateManager<B>, node: usize) -> bool {
+        if self.is_root(mgr, node) {
+            return false;
+        }
+        mgr.set(self.parents[node], node);
+        true
+    }
+}
+
+#[cfg(test)]
+mod test_forest {
+    use crate::*;
+
+    #[test]
+    fn links_and_cuts_are_restored() {
+        let mut mgr = StateManager::default();
+        let forest = ReversibleForest::new(&mut mgr, 5);
+        assert!(forest.link(&mut mgr, 1, 0));
+        assert!(forest.link(&mut mgr, 2, 1));
+        mgr.save_state();
+        // A cycle is refused
+        assert!(!forest.link(&mut mgr, 0, 2));
+        assert!(!forest.link(&mut mgr, 2, 3));
+        assert!(forest.cut(&mut mgr, 1));
+        assert!(forest.link(&mut mgr, 1, 4));
+        assert_eq!((4, 0), (forest.root_of(&mgr, 2), forest.root_of(&mgr, 0)));
+        assert!(!forest.connected(&mgr, 0, 2));
+        mgr.restore_state();
+        assert_eq!(Some(0), forest.parent(&mgr, 1));
+        assert!(forest.connected(&mgr, 0, 2) && forest.is_root(&mgr, 4));
+        assert!(!forest.cut(&mut mgr, 3));
+    }
+}
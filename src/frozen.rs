@@ -0,0 +1,324 @@
+//Copyright (c) 2023 X. Gillard, A. Dubray
+//
+//Permission is hereby granted, free of charge, to any person obtaining a copy
+//of this software and associated documentation files (the "Software"), to deal
+//in the Software without restriction, including without limitation the rights
+//to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+//copies of the Software, and to permit persons to whom the Software is
+//furnished to do so, subject to the following conditions:
+//
+//The above copyright notice and this permission notice shall be included in all
+//copies or substantial portions of the Software.
+//
+//THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+//IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+//AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+//OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+//SOFTWARE.
+
+
+//! Immutable snapshots of the managed values. A `FrozenState` is a copy of the current values of a
+//! manager that can be shared with other threads (for instance to compute a heuristic, to log the
+//! progress of the search or to display it) while the owning thread keeps modifying the manager.
+//!
+//! Two frozen states can be compared resource by resource with `diff()`, e.g. to check that two
+//! versions of a propagator reach the same fixpoint.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Arc;
+
+use paste::paste;
+
+use crate::*;
+
+/// An immutable copy of the values of a manager at the time it was frozen. The handles of the
+/// manager can be used to read the values of the frozen state. Cloning a frozen state is cheap, and
+/// the clones can be sent to, and shared with, other threads.
+///
+/// #Example
+///
+#[cfg_attr(feature = "usize", doc = "```")]
+#[cfg_attr(not(feature = "usize"), doc = "```ignore")]
+/// use search_trail::{StateManager, SaveAndRestore, UsizeManager};
+///
+/// let mut mgr = StateManager::default();
+/// let n = mgr.manage_usize(3);
+/// let frozen = mgr.freeze();
+/// let reader = std::thread::spawn(move || frozen.get_usize(n));
+/// mgr.save_state();
+/// mgr.set_usize(n, 4);
+/// assert_eq!(3, reader.join().unwrap());
+/// ```
+pub struct FrozenState<B: TrailBackend = Trailing> {
+    pub(crate) mgr: Arc<StateManager<B>>,
+    depth: usize,
+}
+
+impl<B: TrailBackend> Clone for FrozenState<B> {
+    fn clone(&self) -> Self {
+        Self {
+            mgr: self.mgr.clone(),
+            depth: self.depth,
+        }
+    }
+}
+
+/// The change of a resource between two frozen states
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypedChange {
+    /// The handle of the resource
+    pub handle: AnyHandle,
+    /// The value of the resource in the first state, or `None` if it did not exist
+    pub before: Option<Value>,
+    /// The value of the resource in the second state, or `None` if it does not exist
+    pub after: Option<Value>,
+}
+
+/// Returns the resources whose value differs between two frozen states of the same manager, grouped by
+/// type and ordered by index
+///
+#[cfg_attr(feature = "usize", doc = "```")]
+#[cfg_attr(not(feature = "usize"), doc = "```ignore")]
+/// use search_trail::{diff, StateManager, UsizeManager, Value};
+///
+/// let mut mgr = StateManager::default();
+/// let x = mgr.manage_usize(0);
+/// let y = mgr.manage_usize(0);
+/// let before = mgr.freeze();
+/// mgr.set_usize(y, 2);
+/// let changes = diff(&before, &mgr.freeze());
+/// assert_eq!(1, changes.len());
+/// assert_eq!((y.into(), Some(Value::Usize(0)), Some(Value::Usize(2))), (changes[0].handle, changes[0].before, changes[0].after));
+/// assert_ne!(before, mgr.freeze());
+/// assert_eq!(before, before.thaw().freeze());
+/// ```
+pub fn diff<B: TrailBackend>(before: &FrozenState<B>, after: &FrozenState<B>) -> Vec<TypedChange> {
+    before.changes_to(after)
+}
+
+/// Appends the changes between the values of two states, given by index
+fn push_changes(changes: &mut Vec<TypedChange>, values: BTreeMap<usize, (AnyHandle, Option<Value>, Option<Value>)>) {
+    for (_, (handle, before, after)) in values {
+        if before != after {
+            changes.push(TypedChange { handle, before, after });
+        }
+    }
+}
+
+impl<B: TrailBackend> PartialEq for FrozenState<B> {
+    /// Two frozen states are equal if their resources have the same values
+    fn eq(&self, other: &Self) -> bool {
+        self.changes_to(other).is_empty()
+    }
+}
+
+impl<B: TrailBackend> fmt::Debug for FrozenState<B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FrozenState").field("depth", &self.depth).finish_non_exhaustive()
+    }
+}
+
+impl<B: TrailBackend> StateManager<B> {
+    /// Returns an immutable copy of the current values of the managed resources. The copy does not
+    /// include the trail of the manager.
+    pub fn freeze(&self) -> FrozenState<B> {
+        FrozenState {
+            mgr: Arc::new(self.fork()),
+            depth: self.depth,
+        }
+    }
+}
+
+impl<B: TrailBackend> FrozenState<B> {
+    /// Returns the number of levels that were saved in the manager when it was frozen
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Returns the name of a managed resource, if it had one when the manager was frozen
+    pub fn name_of(&self, handle: impl Into<AnyHandle>) -> Option<&str> {
+        self.mgr.name_of(handle)
+    }
+
+    /// Returns a new manager whose root holds the frozen values
+    pub fn thaw(&self) -> StateManager<B> {
+        self.mgr.fork()
+    }
+}
+
+macro_rules! frozen_getters {
+    ($($u:ty => $feature:literal),*) => {
+        paste! {
+            impl<B: TrailBackend> FrozenState<B> {
+                $(
+                    #[cfg(feature = $feature)]
+                    #[doc="Returns the frozen value of the resource"]
+                    pub fn [<get_ $u>](&self, id: [<Reversible $u:camel>]) -> $u {
+                        self.mgr.[<get_ $u>](id)
+                    }
+
+                    #[cfg(feature = $feature)]
+                    #[doc="Returns the frozen value of the resource"]
+                    pub fn [<get_option_ $u>](&self, id: [<ReversibleOption $u:camel>]) -> Option<$u> {
+                        self.mgr.[<get_option_ $u>](id)
+                    }
+                )*
+
+                #[cfg(feature = "bool")]
+                /// Returns the frozen value of the boolean
+                pub fn get_bool(&self, id: ReversibleBool) -> bool {
+                    self.mgr.get_bool(id)
+                }
+
+                #[cfg(feature = "bool")]
+                /// Returns the frozen value of the boolean
+                pub fn get_option_bool(&self, id: ReversibleOptionBool) -> Option<bool> {
+                    self.mgr.get_option_bool(id)
+                }
+            }
+        }
+    };
+}
+
+for_each_managed_type!(frozen_getters);
+
+macro_rules! frozen_diff {
+    ($($u:ty => $feature:literal),*) => {
+        paste! {
+            impl<B: TrailBackend> FrozenState<B> {
+                /// Returns the resources whose value differs in the other state
+                fn changes_to(&self, after: &Self) -> Vec<TypedChange> {
+                    let (a, b) = (&*self.mgr, &*after.mgr);
+                    #[allow(unused_mut)]
+                    let mut changes = vec![];
+                    $(
+                        #[cfg(feature = $feature)]
+                        {
+                            let mut values = BTreeMap::new();
+                            for index in 0..a.[<numbers _ $u>].len() {
+                                let handle = [<Reversible $u:camel>]::new(a, index);
+                                values.insert(index, (handle.into(), Some(Value::[<$u:camel>](a.[<get_ $u>](handle))), None));
+                            }
+                            for index in 0..b.[<numbers _ $u>].len() {
+                                let handle = [<Reversible $u:camel>]::new(b, index);
+                                let entry = values.entry(index).or_insert((handle.into(), None, None));
+                                entry.2 = Some(Value::[<$u:camel>](b.[<get_ $u>](handle)));
+                            }
+                            push_changes(&mut changes, values);
+                            let mut values = BTreeMap::new();
+                            for index in 0..a.[<numbers_option_ $u>].len() {
+                                let handle = [<ReversibleOption $u:camel>]::new(a, index);
+                                values.insert(index, (handle.into(), Some(Value::[<Option $u:camel>](a.[<get_option_ $u>](handle))), None));
+                            }
+                            for index in 0..b.[<numbers_option_ $u>].len() {
+                                let handle = [<ReversibleOption $u:camel>]::new(b, index);
+                                let entry = values.entry(index).or_insert((handle.into(), None, None));
+                                entry.2 = Some(Value::[<Option $u:camel>](b.[<get_option_ $u>](handle)));
+                            }
+                            push_changes(&mut changes, values);
+                        }
+                    )*
+                    #[cfg(feature = "bool")]
+                    {
+                        let mut values = BTreeMap::new();
+                        for index in a.bools.ids() {
+                            let handle = ReversibleBool::new(a, index);
+                            values.insert(index, (handle.into(), Some(Value::Bool(a.get_bool(handle))), None));
+                        }
+                        for index in b.bools.ids() {
+                            let handle = ReversibleBool::new(b, index);
+                            let entry = values.entry(index).or_insert((handle.into(), None, None));
+                            entry.2 = Some(Value::Bool(b.get_bool(handle)));
+                        }
+                        push_changes(&mut changes, values);
+                        let mut values = BTreeMap::new();
+                        for index in a.option_bools.ids() {
+                            let handle = ReversibleOptionBool::new(a, index);
+                            values.insert(index, (handle.into(), Some(Value::OptionBool(a.get_option_bool(handle))), None));
+                        }
+                        for index in b.option_bools.ids() {
+                            let handle = ReversibleOptionBool::new(b, index);
+                            let entry = values.entry(index).or_insert((handle.into(), None, None));
+                            entry.2 = Some(Value::OptionBool(b.get_option_bool(handle)));
+                        }
+                        push_changes(&mut changes, values);
+                    }
+                    changes
+                }
+            }
+        }
+    };
+}
+
+for_each_managed_type!(frozen_diff);
+
+const _: () = {
+    const fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<FrozenState<Trailing>>();
+    assert_send_sync::<FrozenState<CopyOnWrite>>();
+};
+
+#[cfg(all(test, feature = "usize", feature = "bool"))]
+mod test_frozen {
+    use crate::*;
+
+    #[test]
+    fn frozen_values_are_read_by_other_threads() {
+        let mut mgr = StateManager::default();
+        let xs = (0..4).map(|i| mgr.manage_usize(i)).collect::<Vec<_>>();
+        let b = mgr.manage_option_bool(Some(true));
+        mgr.set_name(xs[0], "first");
+        mgr.save_state();
+        xs.iter().for_each(|&x| {
+            mgr.set_usize(x, 10);
+        });
+        let frozen = mgr.freeze();
+        std::thread::scope(|s| {
+            let readers = (0..2)
+                .map(|_| s.spawn(|| xs.iter().map(|&x| frozen.get_usize(x)).sum::<usize>()))
+                .collect::<Vec<_>>();
+            mgr.set_usize(xs[1], 0);
+            mgr.set_option_bool_none(b);
+            mgr.restore_state();
+            readers.into_iter().for_each(|reader| assert_eq!(40, reader.join().unwrap()));
+        });
+        assert_eq!(Some(true), frozen.get_option_bool(b));
+        assert_eq!(1, frozen.depth());
+        assert_eq!(Some("first"), frozen.name_of(xs[0]));
+        assert_eq!(0, mgr.get_usize(xs[0]));
+        let mut thawed = frozen.thaw();
+        thawed.set_usize(xs[0], 5);
+        assert_eq!(10, frozen.get_usize(xs[0]));
+    }
+
+    #[test]
+    fn states_are_compared_by_resource() {
+        let mut mgr = StateManager::default();
+        let xs = (0..4).map(|i| mgr.manage_usize(i)).collect::<Vec<_>>();
+        let b = mgr.manage_option_bool(Some(true));
+        let before = mgr.freeze();
+        mgr.save_state();
+        mgr.set_usize(xs[2], 0);
+        mgr.set_option_bool_none(b);
+        let c = mgr.manage_bool(true);
+        let after = mgr.freeze();
+        assert_eq!(
+            vec![
+                TypedChange { handle: xs[2].into(), before: Some(Value::Usize(2)), after: Some(Value::Usize(0)) },
+                TypedChange { handle: c.into(), before: None, after: Some(Value::Bool(true)) },
+                TypedChange { handle: b.into(), before: Some(Value::OptionBool(Some(true))), after: Some(Value::OptionBool(None)) },
+            ],
+            diff(&before, &after)
+        );
+        assert_eq!(None, diff(&after, &before)[1].after);
+        mgr.set_usize(xs[2], 2);
+        mgr.set_option_bool(b, true);
+        mgr.restore_state();
+        // The boolean created in the restored level is kept
+        assert_ne!(before, mgr.freeze());
+        assert!(diff(&after, &after.clone()).is_empty());
+    }
+}
@@ -0,0 +1,235 @@
+//Copyright (c) 2023 X. Gillard, A. Dubray
+//
+//Permission is hereby granted, free of charge, to any person obtaining a copy
+//of this software and associated documentation files (the "Software"), to deal
+//in the Software without restriction, including without limitation the rights
+//to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+//copies of the Software, and to permit persons to whom the Software is
+//furnished to do so, subject to the following conditions:
+//
+//The above copyright notice and this permission notice shall be included in all
+//copies or substantial portions of the Software.
+//
+//THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+//IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+//AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+//OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+//SOFTWARE.
+
+
+//! Entry points for fuzzers. A fuzzer generates a sequence of `Op` (they implement
+//! `arbitrary::Arbitrary`), and `apply_ops` applies them deterministically to a manager. For instance,
+//! a cargo-fuzz target is
+//!
+//! ```ignore
+//! fuzz_target!(|ops: Vec<Op>| {
+//!     let mut mgr = StateManager::default();
+//!     apply_ops(&mut mgr, &ops);
+//! });
+//! ```
+//!
+//! The handles implement `Arbitrary` as well, so that the operations of a solver (e.g., on its
+//! propagators) can embed them. An arbitrary handle is turned into a handle of the fuzzed manager
+//! with `adopt`:
+//!
+//! ```ignore
+//! #[derive(Arbitrary, Debug)]
+//! enum SolverOp {
+//!     Fix(ReversibleUsize, usize),
+//!     Backtrack,
+//! }
+//!
+//! fn apply(mgr: &mut StateManager, op: SolverOp) {
+//!     match op {
+//!         SolverOp::Fix(x, value) => if let Some(AnyHandle::Usize(x)) = mgr.adopt(x) {
+//!             mgr.set_usize(x, value);
+//!         },
+//!         ...
+//!     }
+//! }
+//! ```
+
+use arbitrary::Arbitrary;
+use paste::paste;
+
+use crate::*;
+
+macro_rules! adopt_handles {
+    ($($u:ty => $feature:literal),*) => {
+        paste! {
+            impl<B: TrailBackend> StateManager<B> {
+                /// Returns a handle of this manager of the same type as the given one, e.g. a handle
+                /// generated by a fuzzer. The index of the handle is taken modulo the number of resources
+                /// of its type. Returns `None` if the manager has no resource of this type.
+                pub fn adopt(&self, handle: impl Into<AnyHandle>) -> Option<AnyHandle> {
+                    match handle.into() {
+                        $(
+                            #[cfg(feature = $feature)]
+                            AnyHandle::[<$u:camel>](h) => {
+                                let len = self.[<numbers _ $u>].len();
+                                (len > 0).then(|| AnyHandle::[<$u:camel>]([<Reversible $u:camel>]::new(self, h.0 % len)))
+                            }
+                            #[cfg(feature = $feature)]
+                            AnyHandle::[<Option $u:camel>](h) => {
+                                let len = self.[<numbers_option_ $u>].len();
+                                (len > 0).then(|| AnyHandle::[<Option $u:camel>]([<ReversibleOption $u:camel>]::new(self, h.0 % len)))
+                            }
+                        )*
+                        #[cfg(feature = "bool")]
+                        AnyHandle::Bool(h) => {
+                            let len = self.bools.len();
+                            (len > 0).then(|| AnyHandle::Bool(ReversibleBool::new(self, h.0 % len)))
+                        }
+                        #[cfg(feature = "bool")]
+                        AnyHandle::OptionBool(h) => {
+                            let len = self.option_bools.len();
+                            (len > 0).then(|| AnyHandle::OptionBool(ReversibleOptionBool::new(self, h.0 % len)))
+                        }
+                    }
+                }
+            }
+        }
+    };
+}
+
+for_each_managed_type!(adopt_handles);
+
+/// An operation on a manager
+#[derive(Debug, Clone, Copy, PartialEq, Arbitrary)]
+pub enum Op {
+    /// Saves the state of the manager
+    SaveState,
+    /// Restores the last state saved by the previous operations. Ignored if there is none.
+    RestoreState,
+    /// Creates a new managed resource with the given value
+    Manage(Value),
+    /// Sets a resource of the type of the value. The resource is the first one of this type from the
+    /// `index`-th created resource (modulo their number). Ignored if there is none.
+    Set { index: u16, value: Value },
+}
+
+/// Applies the operations to the manager, and checks its invariants after each of them. Only the
+/// resources created by the operations are modified, and only the states saved by the operations are
+/// restored. The same operations on the same manager always have the same effect.
+pub fn apply_ops<B: TrailBackend>(mgr: &mut StateManager<B>, ops: &[Op]) {
+    let mut handles: Vec<AnyHandle> = vec![];
+    let mut saved = 0;
+    for op in ops.iter().copied() {
+        match op {
+            Op::SaveState => {
+                mgr.save_state();
+                saved += 1;
+            }
+            Op::RestoreState => {
+                if saved > 0 {
+                    mgr.restore_state();
+                    saved -= 1;
+                }
+            }
+            Op::Manage(value) => handles.push(value.manage(mgr)),
+            Op::Set { index, value } => {
+                if !handles.is_empty() {
+                    let start = index as usize % handles.len();
+                    let (before, after) = handles.split_at(start);
+                    let _ = after.iter().chain(before.iter()).any(|h| value.set(mgr, *h));
+                }
+            }
+        }
+        mgr.debug_validate();
+    }
+}
+
+#[cfg(all(test, feature = "usize", feature = "bool"))]
+mod test_fuzz {
+    use arbitrary::{Arbitrary, Unstructured};
+
+    use super::{apply_ops, Op};
+    use crate::{AnyHandle, BoolManager, Copying, ReversibleBool, ReversibleUsize, SaveAndRestore, StateManager, UsizeManager, Value};
+
+    #[test]
+    fn operations_are_applied() {
+        let mut mgr = StateManager::default();
+        let x = mgr.manage_usize(0);
+        let b = mgr.manage_bool(false);
+        let ops = [
+            Op::Manage(Value::Usize(3)),
+            Op::SaveState,
+            Op::Set { index: 7, value: Value::Usize(5) },
+            Op::Set { index: 0, value: Value::Bool(true) },
+            Op::SaveState,
+            Op::Manage(Value::Bool(false)),
+            Op::Set { index: 1, value: Value::Bool(true) },
+            Op::RestoreState,
+        ];
+        apply_ops(&mut mgr, &ops);
+        // The resources that were not created by the operations are left unchanged
+        assert_eq!(0, mgr.get_usize(x));
+        assert!(!mgr.get_bool(b));
+        let created = ReversibleUsize::new(&mgr, 1);
+        assert_eq!(5, mgr.get_usize(created));
+        mgr.restore_state();
+        assert_eq!(3, mgr.get_usize(created));
+    }
+
+    #[test]
+    fn unmatched_restores_are_ignored() {
+        let mut mgr = StateManager::default();
+        let x = mgr.manage_usize(1);
+        mgr.save_state();
+        mgr.set_usize(x, 2);
+        apply_ops(&mut mgr, &[Op::RestoreState, Op::RestoreState]);
+        assert_eq!(2, mgr.get_usize(x));
+        mgr.restore_state();
+        assert_eq!(1, mgr.get_usize(x));
+    }
+
+    #[test]
+    fn arbitrary_handles_are_adopted() {
+        #[derive(Debug, Arbitrary)]
+        enum SolverOp {
+            Fix(ReversibleUsize, usize),
+            Flip(ReversibleBool),
+            Save,
+        }
+
+        let bytes = (0..512u32).map(|i| (i.wrapping_mul(2246822519) >> 11) as u8).collect::<Vec<u8>>();
+        let mut data = Unstructured::new(&bytes);
+        let mut mgr = StateManager::default();
+        let xs = (0..3).map(|i| mgr.manage_usize(i)).collect::<Vec<_>>();
+        assert_eq!(None, mgr.adopt(ReversibleBool::new(&mgr, 5)));
+        mgr.manage_bool(false);
+        while !data.is_empty() {
+            match SolverOp::arbitrary(&mut data).unwrap() {
+                SolverOp::Fix(x, value) => match mgr.adopt(x) {
+                    Some(AnyHandle::Usize(x)) => {
+                        assert!(xs.contains(&x));
+                        mgr.set_usize(x, value);
+                    }
+                    other => panic!("{:?} is not a usize of the manager", other),
+                },
+                SolverOp::Flip(b) => match mgr.adopt(b) {
+                    Some(AnyHandle::Bool(b)) => {
+                        mgr.flip_bool(b);
+                    }
+                    other => panic!("{:?} is not a bool of the manager", other),
+                },
+                SolverOp::Save => mgr.save_state(),
+            }
+        }
+        mgr.debug_validate();
+    }
+
+    #[test]
+    fn arbitrary_operations_are_valid() {
+        let bytes = (0..4096u32).map(|i| (i.wrapping_mul(2654435761) >> 13) as u8).collect::<Vec<u8>>();
+        let mut data = Unstructured::new(&bytes);
+        let mut ops = vec![];
+        while !data.is_empty() {
+            ops.push(Op::arbitrary(&mut data).unwrap());
+        }
+        apply_ops(&mut StateManager::default(), &ops);
+        apply_ops(&mut StateManager::<Copying>::new(), &ops);
+    }
+}
@@ -0,0 +1,178 @@
+//Copyright (c) 2023 X. Gillard, A. Dubray
+//
+//Permission is hereby granted, free of charge, to any person obtaining a copy
+//of this software and associated documentation files (the "Software"), to deal
+//in the Software without restriction, including without limitation the rights
+//to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+//copies of the Software, and to permit persons to whom the Software is
+//furnished to do so, subject to the following conditions:
+//
+//The above copyright notice and this permission notice shall be included in all
+//copies or substantial portions of the Software.
+//
+//THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+//IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+//AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+//OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+//SOFTWARE.
+
+//! A directed graph in compressed sparse row form whose edges can be removed, the removals being
+//! restored with the state of the manager. The edges leaving each node are stored in a contiguous block,
+//! permuted so that the remaining ones come first: iterating over the remaining neighbors of a node reads
+//! a contiguous range of managed values, and removing an edge is a constant number of trailed writes.
+
+use crate::*;
+
+/// A directed graph whose edge removals are undone by restoring the state of the manager used to modify
+/// it. The edges are identified by their index in the list given at the creation of the graph.
+///
+/// #Example
+///
+/// ```
+/// use search_trail::{ReversibleGraph, SaveAndRestore, StateManager};
+///
+/// let mut mgr = StateManager::default();
+/// let graph = ReversibleGraph::new(&mut mgr, 3, &[(0, 1), (0, 2), (1, 2)]);
+/// mgr.save_state();
+/// graph.remove_edge(&mut mgr, 0);
+/// assert_eq!(vec![2], graph.neighbors(&mgr, 0).collect::<Vec<_>>());
+/// mgr.restore_state();
+/// assert_eq!(2, graph.degree(&mgr, 0));
+/// ```
+#[derive(Debug, Clone)]
+pub struct ReversibleGraph {
+    /// The first slot of the edges leaving each node, and the total number of edges at the end
+    offsets: Vec<usize>,
+    /// The source and the target of each edge
+    endpoints: Vec<(usize, usize)>,
+    /// The edge in each slot. The remaining edges leaving a node are in the first slots of its block.
+    slots: Vec<ReversibleUsize>,
+    /// The slot of each edge
+    positions: Vec<ReversibleUsize>,
+    /// Number of remaining edges leaving each node
+    degrees: Vec<ReversibleUsize>,
+}
+
+impl ReversibleGraph {
+    /// Creates a graph with the given number of nodes and edges, given by their source and target,
+    /// trailed by the given manager. Panics if an edge has an endpoint that is not a node.
+    pub fn new<B: TrailBackend>(mgr: &mut StateManager<B>, nodes: usize, edges: &[(usize, usize)]) -> Self {
+        let mut offsets = vec![0; nodes + 1];
+        for &(source, target) in edges.iter() {
+            assert!(source < nodes && target < nodes, "The edge ({}, {}) has an endpoint that is not a node", source, target);
+            offsets[source + 1] += 1;
+        }
+        for node in 0..nodes {
+            offsets[node + 1] += offsets[node];
+        }
+        let mut next = offsets.clone();
+        let mut slots = vec![0; edges.len()];
+        let mut positions = Vec::with_capacity(edges.len());
+        for (edge, &(source, _)) in edges.iter().enumerate() {
+            slots[next[source]] = edge;
+            positions.push(next[source]);
+            next[source] += 1;
+        }
+        Self {
+            slots: slots.into_iter().map(|edge| mgr.manage(edge)).collect(),
+            positions: positions.into_iter().map(|slot| mgr.manage(slot)).collect(),
+            degrees: offsets.windows(2).map(|block| mgr.manage(block[1] - block[0])).collect(),
+            endpoints: edges.to_vec(),
+            offsets,
+        }
+    }
+
+    /// Returns the number of nodes
+    pub fn node_count(&self) -> usize {
+        self.degrees.len()
+    }
+
+    /// Returns the number of edges, including the removed ones
+    pub fn edge_count(&self) -> usize {
+        self.endpoints.len()
+    }
+
+    /// Returns the source of the edge
+    pub fn source(&self, edge: usize) -> usize {
+        self.endpoints[edge].0
+    }
+
+    /// Returns the target of the edge
+    pub fn target(&self, edge: usize) -> usize {
+        self.endpoints[edge].1
+    }
+
+    /// Returns the number of remaining edges leaving the node
+    pub fn degree<B: TrailBackend>(&self, mgr: &StateManager<B>, node: usize) -> usize {
+        mgr.get(self.degrees[node])
+    }
+
+    /// Returns true if the edge has been removed
+    pub fn is_removed<B: TrailBackend>(&self, mgr: &StateManager<B>, edge: usize) -> bool {
+        let source = self.source(edge);
+        mgr.get(self.positions[edge]) >= self.offsets[source] + self.degree(mgr, source)
+    }
+
+    /// Iterates over the remaining edges leaving the node, in no particular order
+    pub fn edges<'a, B: TrailBackend>(&'a self, mgr: &'a StateManager<B>, node: usize) -> impl Iterator<Item = usize> + 'a {
+        let first = self.offsets[node];
+        self.slots[first..first + self.degree(mgr, node)].iter().map(move |&slot| mgr.get(slot))
+    }
+
+    /// Iterates over the targets of the remaining edges leaving the node, in no particular order
+    pub fn neighbors<'a, B: TrailBackend>(&'a self, mgr: &'a StateManager<B>, node: usize) -> impl Iterator<Item = usize> + 'a {
+        self.edges(mgr, node).map(move |edge| self.target(edge))
+    }
+
+    /// Removes the edge. Returns false if it was already removed.
+    pub fn remove_edge<B: TrailBackend>(&self, mgr: &mut StateManager<B>, edge: usize) -> bool {
+        if self.is_removed(mgr, edge) {
+            return false;
+        }
+        // The edge is swapped with the last remaining edge of its source, which is shrunk
+        let source = self.source(edge);
+        let degree = self.degree(mgr, source) - 1;
+        let last = self.offsets[source] + degree;
+        let position = mgr.get(self.positions[edge]);
+        let other = mgr.get(self.slots[last]);
+        mgr.set(self.slots[position], other);
+        mgr.set(self.positions[other], position);
+        mgr.set(self.slots[last], edge);
+        mgr.set(self.positions[edge], last);
+        mgr.set(self.degrees[source], degree);
+        true
+    }
+}
+
+#[cfg(test)]
+mod test_graph {
+    use crate::*;
+
+    #[test]
+    fn removals_are_restored() {
+        let mut mgr = StateManager::default();
+        let edges = [(0, 1), (1, 2), (0, 2), (2, 0), (0, 3)];
+        let graph = ReversibleGraph::new(&mut mgr, 4, &edges);
+        assert_eq!((4, 5), (graph.node_count(), graph.edge_count()));
+        assert_eq!(vec![0, 2, 4], graph.edges(&mgr, 0).collect::<Vec<_>>());
+        mgr.save_state();
+        assert!(graph.remove_edge(&mut mgr, 2));
+        assert!(!graph.remove_edge(&mut mgr, 2));
+        mgr.save_state();
+        graph.remove_edge(&mut mgr, 0);
+        graph.remove_edge(&mut mgr, 3);
+        assert_eq!(vec![3], graph.neighbors(&mgr, 0).collect::<Vec<_>>());
+        assert_eq!(0, graph.degree(&mgr, 2));
+        assert!(graph.is_removed(&mgr, 0) && !graph.is_removed(&mgr, 1));
+        mgr.restore_state();
+        let mut neighbors = graph.neighbors(&mgr, 0).collect::<Vec<_>>();
+        neighbors.sort_unstable();
+        assert_eq!(vec![1, 3], neighbors);
+        assert_eq!(vec![0], graph.neighbors(&mgr, 2).collect::<Vec<_>>());
+        mgr.restore_state();
+        assert!((0..5).all(|edge| !graph.is_removed(&mgr, edge)));
+        assert_eq!(3, graph.degree(&mgr, 0));
+    }
+}
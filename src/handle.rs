@@ -0,0 +1,134 @@
+//Copyright (c) 2023 X. Gillard, A. Dubray
+//
+//Permission is hereby granted, free of charge, to any person obtaining a copy
+//of this software and associated documentation files (the "Software"), to deal
+//in the Software without restriction, including without limitation the rights
+//to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+//copies of the Software, and to permit persons to whom the Software is
+//furnished to do so, subject to the following conditions:
+//
+//The above copyright notice and this permission notice shall be included in all
+//copies or substantial portions of the Software.
+//
+//THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+//IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+//AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+//OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+//SOFTWARE.
+
+
+//! Tags of the handles, used to detect the handles used with the wrong manager (feature
+//! `checked-handles`)
+
+use std::sync::atomic::{AtomicU32, Ordering};
+
+#[cfg(feature = "snapshot")]
+use crate::snapshot::{Snapshot, SnapshotValue};
+#[cfg(feature = "snapshot")]
+use crate::SnapshotError;
+use crate::{StateManager, TrailBackend, TrailError};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Identifier given to the next manager
+static NEXT_MANAGER: AtomicU32 = AtomicU32::new(0);
+
+/// Identifies the manager that created a handle, and the generation of the manager at that time
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "fuzz", derive(arbitrary::Arbitrary))]
+pub(crate) struct HandleTag {
+    manager: u32,
+    /// Incremented each time the manager is reset
+    generation: u32,
+}
+
+impl HandleTag {
+    /// Returns the tag of a new manager
+    pub fn new() -> Self {
+        Self {
+            manager: NEXT_MANAGER.fetch_add(1, Ordering::Relaxed),
+            generation: 0,
+        }
+    }
+
+    /// Invalidates the handles created with the current tag
+    pub fn next_generation(&mut self) {
+        self.generation = self.generation.wrapping_add(1);
+    }
+
+    /// Returns the tag of the same manager, the given number of generations ago
+    #[cfg(feature = "time-travel")]
+    pub fn rewind(self, generations: u32) -> Self {
+        Self {
+            generation: self.generation.wrapping_sub(generations),
+            ..self
+        }
+    }
+}
+
+#[cfg(feature = "snapshot")]
+impl Snapshot for HandleTag {
+    fn write_snapshot(&self, out: &mut Vec<u8>) {
+        self.manager.write(out);
+        self.generation.write(out);
+    }
+
+    fn read_snapshot(input: &mut &[u8]) -> Result<Self, SnapshotError> {
+        Ok(Self {
+            manager: u32::read(input)?,
+            generation: u32::read(input)?,
+        })
+    }
+}
+
+impl<B: TrailBackend> StateManager<B> {
+    /// Returns an error if a handle with the given tag has not been created by this manager since its
+    /// last reset. The clones of a manager share its tag.
+    pub(crate) fn check_tag(&self, tag: HandleTag) -> Result<(), TrailError> {
+        if tag.manager != self.tag.manager {
+            Err(TrailError::ForeignHandle)
+        } else if tag.generation != self.tag.generation {
+            Err(TrailError::StaleHandle)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[cfg(all(test, feature = "usize"))]
+mod test_handle {
+    use crate::{OptionUsizeManager, StateManager, TrailError, UsizeManager};
+
+    #[test]
+    fn foreign_handles_are_detected() {
+        let mut a = StateManager::default();
+        let mut b = StateManager::default();
+        let x = a.manage_usize(0);
+        let y = b.manage_option_usize(None);
+        b.manage_usize(0);
+        assert_eq!(Err(TrailError::ForeignHandle), b.try_get_usize(x));
+        assert_eq!(Err(TrailError::ForeignHandle), a.try_set_option_usize(y, None));
+        assert_eq!(Ok(0), a.clone().try_get_usize(x));
+    }
+
+    #[test]
+    #[should_panic]
+    fn foreign_handles_panic() {
+        let mut a = StateManager::default();
+        let b = StateManager::default();
+        let x = a.manage_usize(0);
+        b.get_usize(x);
+    }
+
+    #[test]
+    fn stale_handles_are_detected() {
+        let mut mgr = StateManager::default();
+        let x = mgr.manage_usize(0);
+        let mut mgr = StateManager::with_arena(mgr.into_arena());
+        mgr.manage_usize(0);
+        assert_eq!(Err(TrailError::StaleHandle), mgr.try_get_usize(x));
+    }
+}
@@ -0,0 +1,354 @@
+//Copyright (c) 2023 X. Gillard, A. Dubray
+//
+//Permission is hereby granted, free of charge, to any person obtaining a copy
+//of this software and associated documentation files (the "Software"), to deal
+//in the Software without restriction, including without limitation the rights
+//to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+//copies of the Software, and to permit persons to whom the Software is
+//furnished to do so, subject to the following conditions:
+//
+//The above copyright notice and this permission notice shall be included in all
+//copies or substantial portions of the Software.
+//
+//THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+//IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+//AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+//OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+//SOFTWARE.
+
+
+//! An incrementally updated hash of the state of a manager, in the style of Zobrist hashing. Each
+//! managed resource contributes a pseudo-random key, derived from its type, its index and its value,
+//! and the hash of the state is the xor of these keys. A write thus updates the hash in constant time,
+//! and the hash of each saved level is restored with it.
+//!
+//! Two managers with the same resources and values have the same hash, whatever the path that led to
+//! their state, which makes the hash a cheap fingerprint for transposition tables and dominance
+//! detection. The hash is a fingerprint: distinct states may (rarely) have the same hash.
+//!
+//! The resources created during the search are not removed by the restorations, and the writes done
+//! in the level in which they were created are not undone. The hashes of the levels saved before such
+//! a creation are thus stale, and the hash is computed from all the managed values when they are
+//! restored.
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::*;
+
+/// A value whose bits can be hashed
+#[cfg(numbers)]
+pub(crate) trait Fingerprint: Copy {
+    /// Identifies the type of the value
+    const KIND: u64;
+    /// Returns the bits of the value
+    fn fingerprint(self) -> u64;
+}
+
+/// Hashes a string at compile time (FNV-1a)
+const fn kind(name: &str) -> u64 {
+    let bytes = name.as_bytes();
+    let mut hash = 0xcbf2_9ce4_8422_2325u64;
+    let mut i = 0;
+    while i < bytes.len() {
+        hash = (hash ^ bytes[i] as u64).wrapping_mul(0x0100_0000_01b3);
+        i += 1;
+    }
+    hash
+}
+
+macro_rules! integer_fingerprints {
+    ($($t:ty),*) => {
+        $(
+            #[cfg(numbers)]
+            impl Fingerprint for $t {
+                const KIND: u64 = kind(stringify!($t));
+
+                fn fingerprint(self) -> u64 {
+                    let bits = self as u128;
+                    (bits ^ (bits >> 64)) as u64
+                }
+            }
+        )*
+    };
+}
+
+integer_fingerprints!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize);
+
+#[cfg(numbers)]
+impl Fingerprint for f32 {
+    const KIND: u64 = kind("f32");
+
+    fn fingerprint(self) -> u64 {
+        self.to_bits() as u64
+    }
+}
+
+#[cfg(numbers)]
+impl Fingerprint for f64 {
+    const KIND: u64 = kind("f64");
+
+    fn fingerprint(self) -> u64 {
+        self.to_bits()
+    }
+}
+
+#[cfg(numbers)]
+impl<T: Fingerprint> Fingerprint for Option<T> {
+    const KIND: u64 = !T::KIND;
+
+    fn fingerprint(self) -> u64 {
+        self.map_or(0x2545_f491_4f6c_dd1d, T::fingerprint)
+    }
+}
+
+/// The key of the booleans
+#[cfg(feature = "bool")]
+const BOOL: u64 = kind("bool");
+/// The key of the optional booleans
+#[cfg(feature = "bool")]
+const OPTION_BOOL: u64 = kind("Option<bool>");
+
+/// Mixes the bits of a value (the finalizer of SplitMix64)
+fn mix(mut x: u64) -> u64 {
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
+
+/// Returns the key of the resource of the given kind and index, with the given value
+fn key(kind: u64, id: usize, fingerprint: u64) -> u64 {
+    mix(mix(kind ^ id as u64) ^ fingerprint)
+}
+
+/// Returns the key of a packed flag with the given bits. The flags whose bits are all 0 do not
+/// contribute to the hash, so that the unused flags of the words can be ignored.
+#[cfg(feature = "bool")]
+fn flag_key(option: bool, id: usize, bits: u64) -> u64 {
+    if bits == 0 {
+        0
+    } else {
+        key(if option { OPTION_BOOL } else { BOOL }, id, bits)
+    }
+}
+
+/// The hash of the current state, and the hashes at the time each level was saved
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub(crate) struct StateHash {
+    hash: u64,
+    saved: Vec<u64>,
+    /// The saved hashes before this index are stale, because resources have been created since
+    stale: usize,
+}
+
+impl StateHash {
+    /// Adds the key of a new resource
+    fn add(&mut self, key: u64) {
+        self.hash ^= key;
+        self.stale = self.saved.len();
+    }
+}
+
+macro_rules! full_hash {
+    ($($u:ty => $feature:literal),*) => {
+        paste::paste! {
+            impl<B: TrailBackend> StateManager<B> {
+                /// Computes the hash of the current state from all the managed values
+                fn full_state_hash(&self) -> u64 {
+                    #[allow(unused_mut)]
+                    let mut hash = 0;
+                    $(
+                        #[cfg(feature = $feature)]
+                        {
+                            hash = (0..self.[<numbers _ $u>].len()).fold(hash, |hash, id| hash ^ self.value_key::<$u>(id));
+                            hash = (0..self.[<numbers_option_ $u>].len()).fold(hash, |hash, id| hash ^ self.value_key::<Option<$u>>(id));
+                        }
+                    )*
+                    #[cfg(feature = "bool")]
+                    {
+                        hash = self.bools.ids().fold(hash, |hash, id| hash ^ flag_key(false, id, self.bools.get(id)));
+                        hash = self.option_bools.ids().fold(hash, |hash, id| hash ^ flag_key(true, id, self.option_bools.get(id)));
+                    }
+                    hash
+                }
+            }
+        }
+    };
+}
+
+for_each_managed_type!(full_hash);
+
+impl<B: TrailBackend> StateManager<B> {
+    /// Starts maintaining the hash of the state, which is computed from all the managed values
+    ///
+    #[cfg_attr(feature = "usize", doc = "```")]
+    #[cfg_attr(not(feature = "usize"), doc = "```ignore")]
+    /// use search_trail::{SaveAndRestore, StateManager, UsizeManager};
+    ///
+    /// let mut mgr = StateManager::default();
+    /// let x = mgr.manage_usize(0);
+    /// let y = mgr.manage_usize(0);
+    /// mgr.track_state_hash();
+    /// let root = mgr.state_hash().unwrap();
+    /// mgr.save_state();
+    /// mgr.set_usize(x, 1);
+    /// mgr.set_usize(y, 2);
+    /// let child = mgr.state_hash().unwrap();
+    /// assert_ne!(root, child);
+    /// mgr.restore_state();
+    /// assert_eq!(Some(root), mgr.state_hash());
+    /// // The same state reached in another order has the same hash
+    /// mgr.set_usize(y, 2);
+    /// mgr.set_usize(x, 1);
+    /// assert_eq!(Some(child), mgr.state_hash());
+    /// ```
+    pub fn track_state_hash(&mut self) {
+        let hash = self.full_state_hash();
+        self.state_hash = Some(StateHash { hash, ..StateHash::default() });
+    }
+
+    /// Stops maintaining the hash of the state
+    pub fn stop_tracking_state_hash(&mut self) {
+        self.state_hash = None;
+    }
+
+    /// Returns the hash of the current state, if it is maintained
+    pub fn state_hash(&self) -> Option<u64> {
+        self.state_hash.as_ref().map(|state| state.hash)
+    }
+
+    /// Returns the key of the resource of type `T` at the given index
+    #[cfg(numbers)]
+    fn value_key<T: Managed>(&self, id: usize) -> u64 {
+        key(T::KIND, id, self.get_value::<T>(id).fingerprint())
+    }
+
+    /// Updates the hash for a resource of type `T` changed from `old` to `new`. The old value of a
+    /// new resource is `None`.
+    #[cfg(numbers)]
+    #[inline]
+    pub(crate) fn hash_value<T: Managed>(&mut self, id: usize, old: Option<T>, new: T) {
+        if let Some(state) = self.state_hash.as_mut() {
+            match old {
+                Some(old) => state.hash ^= key(T::KIND, id, old.fingerprint()) ^ key(T::KIND, id, new.fingerprint()),
+                None => state.add(key(T::KIND, id, new.fingerprint())),
+            }
+        }
+    }
+
+    /// Updates the hash for the resources of `first..first + values.len()`, before they are set to the
+    /// given values
+    #[cfg(numbers)]
+    pub(crate) fn hash_range<T: Managed>(&mut self, first: usize, values: &[T]) {
+        if self.state_hash.is_some() {
+            for (i, &value) in values.iter().enumerate() {
+                let old = self.get_value::<T>(first + i);
+                self.hash_value(first + i, Some(old), value);
+            }
+        }
+    }
+
+    /// Updates the hash for a packed flag whose bits changed from `old` to `new`. The old bits of a
+    /// new flag are `None`.
+    #[cfg(feature = "bool")]
+    pub(crate) fn hash_flag(&mut self, option: bool, id: usize, old: Option<u64>, new: u64) {
+        if let Some(state) = self.state_hash.as_mut() {
+            match old {
+                Some(old) => state.hash ^= flag_key(option, id, old) ^ flag_key(option, id, new),
+                None => state.add(flag_key(option, id, new)),
+            }
+        }
+    }
+
+    /// Updates the hash for a word of packed flags changed from `old` to `new`
+    #[cfg(feature = "bool")]
+    pub(crate) fn hash_bool_word(&mut self, option: bool, word: usize, old: u64, new: u64) {
+        if self.state_hash.is_none() {
+            return;
+        }
+        let (per_word, unpack): (usize, fn(u64, usize) -> u64) = if option {
+            (OptionBools::<B::Store<u64>>::PER_WORD, OptionBools::<B::Store<u64>>::unpack)
+        } else {
+            (Bools::<B::Store<u64>>::PER_WORD, Bools::<B::Store<u64>>::unpack)
+        };
+        for id in word * per_word..(word + 1) * per_word {
+            self.hash_flag(option, id, Some(unpack(old, id)), unpack(new, id));
+        }
+    }
+
+    /// Records a call to `save_state()` in the hash, if it is maintained
+    pub(crate) fn save_state_hash(&mut self) {
+        if let Some(state) = self.state_hash.as_mut() {
+            state.saved.push(state.hash);
+        }
+    }
+
+    /// Records a call to `restore_state()` in the hash, if it is maintained
+    pub(crate) fn restore_state_hash(&mut self) {
+        let Some(state) = self.state_hash.as_mut() else {
+            return;
+        };
+        let Some(hash) = state.saved.pop() else {
+            return;
+        };
+        if state.saved.len() < state.stale {
+            state.stale = state.saved.len();
+            let hash = self.full_state_hash();
+            self.state_hash.as_mut().unwrap().hash = hash;
+        } else {
+            state.hash = hash;
+        }
+    }
+
+    /// Panics if the maintained hash is not the hash of the current state
+    #[cfg(feature = "paranoid")]
+    pub(crate) fn check_state_hash(&self) {
+        if let Some(state) = self.state_hash.as_ref() {
+            assert_eq!(self.full_state_hash(), state.hash, "The hash of the state is not up to date");
+        }
+    }
+}
+
+#[cfg(all(test, feature = "u32", feature = "f64", feature = "bool"))]
+mod test_hash {
+    use crate::*;
+
+    #[test]
+    fn hash_is_maintained() {
+        let mut mgr = StateManager::default();
+        let xs = (0..5).map(|i| mgr.manage_u32(i)).collect::<Vec<_>>();
+        mgr.track_state_hash();
+        let f = mgr.manage_option_f64(None);
+        let b = mgr.manage_bool(true);
+        mgr.save_state();
+        let o = mgr.manage_option_bool(Some(false));
+        assert_eq!(mgr.full_state_hash(), mgr.state_hash().unwrap());
+        mgr.set_u32_slice(xs[1], &[10, 2, 30]);
+        mgr.set_option_f64(f, Some(0.5));
+        mgr.set_bool(b, false);
+        mgr.set_option_bool_none(o);
+        mgr.save_state();
+        mgr.set_option_bool(o, true);
+        assert_eq!(mgr.full_state_hash(), mgr.state_hash().unwrap());
+        mgr.restore_state();
+        assert_eq!(mgr.full_state_hash(), mgr.state_hash().unwrap());
+        mgr.restore_state();
+        // The resources created in the restored level are kept, with the last value written in it
+        assert_eq!(mgr.full_state_hash(), mgr.state_hash().unwrap());
+        let root = mgr.state_hash();
+        mgr.save_state();
+        mgr.set_u32(xs[3], 30);
+        mgr.set_u32(xs[1], 10);
+        let child = mgr.state_hash();
+        assert_ne!(root, child);
+        mgr.restore_state();
+        assert_eq!(root, mgr.state_hash());
+        mgr.set_u32_slice(xs[1], &[10, 2, 30]);
+        assert_eq!(child, mgr.state_hash());
+        mgr.stop_tracking_state_hash();
+        assert!(mgr.state_hash().is_none());
+    }
+}
@@ -0,0 +1,163 @@
+//Copyright (c) 2023 X. Gillard, A. Dubray
+//
+//Permission is hereby granted, free of charge, to any person obtaining a copy
+//of this software and associated documentation files (the "Software"), to deal
+//in the Software without restriction, including without limitation the rights
+//to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+//copies of the Software, and to permit persons to whom the Software is
+//furnished to do so, subject to the following conditions:
+//
+//The above copyright notice and this permission notice shall be included in all
+//copies or substantial portions of the Software.
+//
+//THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+//IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+//AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+//OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+//SOFTWARE.
+
+
+//! A histogram whose counters are restored with the state of the manager, as maintained by the global
+//! cardinality and load balancing propagators. Each update of a counter is a constant number of trailed
+//! writes, and the non-empty buckets are kept in a sparse set so that they can be enumerated without
+//! scanning the empty ones.
+
+use crate::{ReversibleUsize, StateManager, TrailBackend, UsizeManager};
+
+/// A histogram whose updates are undone by restoring the state of the manager used to modify it.
+///
+/// #Example
+///
+/// ```
+/// use search_trail::{Histogram, SaveAndRestore, StateManager};
+///
+/// let mut mgr = StateManager::default();
+/// let load = Histogram::new(&mut mgr, 4);
+/// load.incr_bucket(&mut mgr, 1);
+/// mgr.save_state();
+/// load.incr_bucket(&mut mgr, 3);
+/// load.incr_bucket(&mut mgr, 3);
+/// assert_eq!(2, load.count(&mgr, 3));
+/// mgr.restore_state();
+/// assert_eq!(vec![1], load.nonzero_buckets(&mgr).collect::<Vec<_>>());
+/// ```
+#[derive(Debug, Clone)]
+pub struct Histogram {
+    /// The counter of each bucket
+    counts: Vec<ReversibleUsize>,
+    /// The buckets, the non-empty ones being the first `nonzero` ones
+    dense: Vec<ReversibleUsize>,
+    /// The index of each bucket in `dense`
+    positions: Vec<ReversibleUsize>,
+    /// Number of non-empty buckets
+    nonzero: ReversibleUsize,
+    /// Sum of the counters
+    total: ReversibleUsize,
+}
+
+impl Histogram {
+    /// Creates a histogram with the given number of buckets, all empty, trailed by the given manager
+    pub fn new<B: TrailBackend>(mgr: &mut StateManager<B>, buckets: usize) -> Self {
+        Self {
+            counts: (0..buckets).map(|_| mgr.manage_usize(0)).collect(),
+            dense: (0..buckets).map(|bucket| mgr.manage_usize(bucket)).collect(),
+            positions: (0..buckets).map(|bucket| mgr.manage_usize(bucket)).collect(),
+            nonzero: mgr.manage_usize(0),
+            total: mgr.manage_usize(0),
+        }
+    }
+
+    /// Returns the number of buckets
+    pub fn len(&self) -> usize {
+        self.counts.len()
+    }
+
+    /// Returns true if the histogram has no bucket
+    pub fn is_empty(&self) -> bool {
+        self.counts.is_empty()
+    }
+
+    /// Returns the counter of the bucket
+    pub fn count<B: TrailBackend>(&self, mgr: &StateManager<B>, bucket: usize) -> usize {
+        mgr.get_usize(self.counts[bucket])
+    }
+
+    /// Returns the sum of the counters
+    pub fn total<B: TrailBackend>(&self, mgr: &StateManager<B>) -> usize {
+        mgr.get_usize(self.total)
+    }
+
+    /// Returns the number of non-empty buckets
+    pub fn nonzero_len<B: TrailBackend>(&self, mgr: &StateManager<B>) -> usize {
+        mgr.get_usize(self.nonzero)
+    }
+
+    /// Iterates over the non-empty buckets, in no particular order
+    pub fn nonzero_buckets<'a, B: TrailBackend>(&'a self, mgr: &'a StateManager<B>) -> impl Iterator<Item = usize> + 'a {
+        self.dense[..self.nonzero_len(mgr)].iter().map(move |&bucket| mgr.get_usize(bucket))
+    }
+
+    /// Increments the counter of the bucket and returns its new value
+    pub fn incr_bucket<B: TrailBackend>(&self, mgr: &mut StateManager<B>, bucket: usize) -> usize {
+        let count = mgr.increment_usize(self.counts[bucket]);
+        mgr.increment_usize(self.total);
+        if count == 1 {
+            // The bucket is moved at the end of the non-empty ones
+            let nonzero = self.nonzero_len(mgr);
+            self.swap(mgr, bucket, nonzero);
+            mgr.set_usize(self.nonzero, nonzero + 1);
+        }
+        count
+    }
+
+    /// Decrements the counter of the bucket and returns its new value. Panics if the bucket is empty.
+    pub fn decr_bucket<B: TrailBackend>(&self, mgr: &mut StateManager<B>, bucket: usize) -> usize {
+        assert!(self.count(mgr, bucket) > 0, "The bucket {} is empty", bucket);
+        let count = mgr.decrement_usize(self.counts[bucket]);
+        mgr.decrement_usize(self.total);
+        if count == 0 {
+            // The bucket is moved at the end of the non-empty ones, which are shrunk
+            let nonzero = self.nonzero_len(mgr) - 1;
+            self.swap(mgr, bucket, nonzero);
+            mgr.set_usize(self.nonzero, nonzero);
+        }
+        count
+    }
+
+    /// Moves the bucket at the given index of `dense`
+    fn swap<B: TrailBackend>(&self, mgr: &mut StateManager<B>, bucket: usize, index: usize) {
+        let position = mgr.get_usize(self.positions[bucket]);
+        let other = mgr.get_usize(self.dense[index]);
+        mgr.set_usize(self.dense[position], other);
+        mgr.set_usize(self.positions[other], position);
+        mgr.set_usize(self.dense[index], bucket);
+        mgr.set_usize(self.positions[bucket], index);
+    }
+}
+
+#[cfg(test)]
+mod test_histogram {
+    use crate::{Histogram, SaveAndRestore, StateManager};
+
+    #[test]
+    fn nonzero_buckets_follow_the_restorations() {
+        let mut mgr = StateManager::default();
+        let histogram = Histogram::new(&mut mgr, 5);
+        histogram.incr_bucket(&mut mgr, 0);
+        mgr.save_state();
+        // The bucket 0 becomes empty and the bucket 4 non-empty in the same level
+        histogram.decr_bucket(&mut mgr, 0);
+        histogram.incr_bucket(&mut mgr, 4);
+        histogram.incr_bucket(&mut mgr, 2);
+        histogram.incr_bucket(&mut mgr, 4);
+        let mut nonzero = histogram.nonzero_buckets(&mgr).collect::<Vec<_>>();
+        nonzero.sort_unstable();
+        assert_eq!(vec![2, 4], nonzero);
+        assert_eq!((3, 2), (histogram.total(&mgr), histogram.count(&mgr, 4)));
+        mgr.restore_state();
+        assert_eq!(vec![0], histogram.nonzero_buckets(&mgr).collect::<Vec<_>>());
+        assert_eq!((1, 0), (histogram.total(&mgr), histogram.count(&mgr, 4)));
+    }
+}
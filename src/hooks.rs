@@ -0,0 +1,127 @@
+//Copyright (c) 2023 X. Gillard, A. Dubray
+//
+//Permission is hereby granted, free of charge, to any person obtaining a copy
+//of this software and associated documentation files (the "Software"), to deal
+//in the Software without restriction, including without limitation the rights
+//to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+//copies of the Software, and to permit persons to whom the Software is
+//furnished to do so, subject to the following conditions:
+//
+//The above copyright notice and this permission notice shall be included in all
+//copies or substantial portions of the Software.
+//
+//THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+//IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+//AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+//OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+//SOFTWARE.
+
+
+//! Callbacks called at each save and restoration of the manager, so that the structures that are not
+//! managed (e.g., caches or statistics) can follow the levels of the search.
+
+use crate::{StateManager, TrailBackend};
+
+/// A callback receiving the depth of a level
+type Hook = Box<dyn FnMut(usize) + Send + Sync>;
+
+/// The lifecycle hooks of a manager. They are not shared with the clones of the manager.
+#[derive(Default)]
+pub(crate) struct Hooks {
+    save: Vec<Hook>,
+    restore: Vec<Hook>,
+}
+
+impl Clone for Hooks {
+    fn clone(&self) -> Self {
+        Self::default()
+    }
+}
+
+impl<B: TrailBackend> StateManager<B> {
+    /// Registers a callback called after each `save_state()`, with the depth of the new level
+    ///
+    /// ```
+    /// use std::sync::{Arc, Mutex};
+    /// use search_trail::{SaveAndRestore, StateManager};
+    ///
+    /// let mut mgr = StateManager::default();
+    /// let events = Arc::new(Mutex::new(vec![]));
+    /// let log = events.clone();
+    /// mgr.on_save(move |depth| log.lock().unwrap().push(format!("save {}", depth)));
+    /// let log = events.clone();
+    /// mgr.on_restore(move |depth| log.lock().unwrap().push(format!("restore {}", depth)));
+    /// mgr.save_state();
+    /// mgr.save_state();
+    /// mgr.restore_state();
+    /// assert_eq!(vec!["save 1", "save 2", "restore 2"], *events.lock().unwrap());
+    /// ```
+    pub fn on_save(&mut self, hook: impl FnMut(usize) + Send + Sync + 'static) {
+        self.hooks.save.push(Box::new(hook));
+    }
+
+    /// Registers a callback called after each `restore_state()`, with the depth the restored level had
+    pub fn on_restore(&mut self, hook: impl FnMut(usize) + Send + Sync + 'static) {
+        self.hooks.restore.push(Box::new(hook));
+    }
+
+    /// Removes the callbacks registered with `on_save` and `on_restore`
+    pub fn clear_hooks(&mut self) {
+        self.hooks = Hooks::default();
+    }
+
+    /// Calls the save hooks
+    #[inline]
+    pub(crate) fn run_save_hooks(&mut self) {
+        let depth = self.depth;
+        self.hooks.save.iter_mut().for_each(|hook| hook(depth));
+    }
+
+    /// Calls the restore hooks
+    #[inline]
+    pub(crate) fn run_restore_hooks(&mut self) {
+        let depth = self.depth + 1;
+        self.hooks.restore.iter_mut().for_each(|hook| hook(depth));
+    }
+}
+
+#[cfg(all(test, feature = "usize"))]
+mod test_hooks {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use crate::*;
+
+    #[test]
+    fn hooks_follow_the_levels() {
+        let mut mgr = StateManager::default();
+        let x = mgr.manage_usize(0);
+        // A cache of the value of x at each depth, kept in sync by the hooks
+        let deepest = Arc::new(AtomicUsize::new(0));
+        let saves = Arc::new(AtomicUsize::new(0));
+        let (d, s) = (deepest.clone(), saves.clone());
+        mgr.on_save(move |depth| {
+            d.fetch_max(depth, Ordering::Relaxed);
+            s.fetch_add(1, Ordering::Relaxed);
+        });
+        let restored = Arc::new(AtomicUsize::new(usize::MAX));
+        let r = restored.clone();
+        mgr.on_restore(move |depth| r.store(depth, Ordering::Relaxed));
+        for i in 1..=3 {
+            mgr.save_state();
+            mgr.set_usize(x, i);
+        }
+        mgr.restore_state();
+        assert_eq!(3, restored.load(Ordering::Relaxed));
+        mgr.restore_state();
+        assert_eq!(2, restored.load(Ordering::Relaxed));
+        assert_eq!(3, deepest.load(Ordering::Relaxed));
+        let mut clone = mgr.clone();
+        clone.save_state();
+        mgr.clear_hooks();
+        mgr.save_state();
+        assert_eq!(3, saves.load(Ordering::Relaxed));
+    }
+}
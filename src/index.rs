@@ -0,0 +1,134 @@
+//Copyright (c) 2023 X. Gillard, A. Dubray
+//
+//Permission is hereby granted, free of charge, to any person obtaining a copy
+//of this software and associated documentation files (the "Software"), to deal
+//in the Software without restriction, including without limitation the rights
+//to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+//copies of the Software, and to permit persons to whom the Software is
+//furnished to do so, subject to the following conditions:
+//
+//The above copyright notice and this permission notice shall be included in all
+//copies or substantial portions of the Software.
+//
+//THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+//IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+//AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+//OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+//SOFTWARE.
+
+
+//! Reading the managed resources with the index operator. For each handle type, `mgr[handle]` returns a
+//! reference to the current value of the resource, which makes the code reading many values (e.g. a
+//! branching heuristic) shorter than with the getters.
+//!
+#![cfg_attr(all(feature = "usize", feature = "bool"), doc = "```")]
+#![cfg_attr(not(all(feature = "usize", feature = "bool")), doc = "```ignore")]
+//! use search_trail::{StateManager, SaveAndRestore, UsizeManager, BoolManager};
+//!
+//! let mut mgr = StateManager::default();
+//! let n = mgr.manage_usize(3);
+//! let b = mgr.manage_bool(false);
+//! mgr.save_state();
+//! mgr.set_usize(n, 42);
+//! assert_eq!(42, mgr[n]);
+//! assert!(!mgr[b]);
+//! mgr.restore_state();
+//! assert_eq!(3, mgr[n]);
+//! ```
+//!
+//! There is no `IndexMut`: the resources are modified with the setters, so that their previous value can be
+//! saved.
+
+use std::ops::Index;
+
+use paste::paste;
+
+use crate::*;
+
+macro_rules! index_values {
+    ($($u:ty => $feature:literal),*) => {
+        paste! {
+            $(
+                #[cfg(feature = $feature)]
+                impl<B: TrailBackend> Index<[<Reversible $u:camel>]> for StateManager<B> {
+                    type Output = $u;
+
+                    fn index(&self, id: [<Reversible $u:camel>]) -> &$u {
+                        <$u as Managed>::store(self).get_ref(id.index(self))
+                    }
+                }
+
+                #[cfg(feature = $feature)]
+                impl<B: TrailBackend> Index<[<ReversibleOption $u:camel>]> for StateManager<B> {
+                    type Output = Option<$u>;
+
+                    fn index(&self, id: [<ReversibleOption $u:camel>]) -> &Option<$u> {
+                        <Option<$u> as Managed>::store(self).get_ref(id.index(self))
+                    }
+                }
+            )*
+        }
+    };
+}
+
+for_each_managed_type!(index_values);
+
+#[cfg(feature = "bool")]
+impl<B: TrailBackend> Index<ReversibleBool> for StateManager<B> {
+    type Output = bool;
+
+    fn index(&self, id: ReversibleBool) -> &bool {
+        // The booleans are packed in words, so the reference is to a constant
+        if self.get_bool(id) {
+            &true
+        } else {
+            &false
+        }
+    }
+}
+
+#[cfg(feature = "bool")]
+impl<B: TrailBackend> Index<ReversibleOptionBool> for StateManager<B> {
+    type Output = Option<bool>;
+
+    fn index(&self, id: ReversibleOptionBool) -> &Option<bool> {
+        match self.get_option_bool(id) {
+            Some(true) => &Some(true),
+            Some(false) => &Some(false),
+            None => &None,
+        }
+    }
+}
+
+#[cfg(all(test, feature = "usize", feature = "bool"))]
+mod test_index {
+    use crate::*;
+
+    #[test]
+    fn index_reads_the_current_values() {
+        let mut mgr = StateManager::default();
+        let n = mgr.manage_usize(1);
+        let o = mgr.manage_option_usize(None);
+        let b = mgr.manage_option_bool(None);
+        mgr.save_state();
+        mgr.set_usize(n, 2);
+        mgr.set_option_usize(o, Some(5));
+        mgr.set_option_bool(b, true);
+        assert_eq!((2, Some(5), Some(true)), (mgr[n], mgr[o], mgr[b]));
+        mgr.restore_state();
+        assert_eq!((1, None, None), (mgr[n], mgr[o], mgr[b]));
+    }
+
+    #[test]
+    fn index_works_with_another_backend() {
+        let mut mgr = CopyingStateManager::new();
+        let n = mgr.manage_usize(1);
+        mgr.save_state();
+        mgr.set_usize(n, 7);
+        assert_eq!(7, mgr[n]);
+        mgr.restore_state();
+        assert_eq!(1, mgr[n]);
+    }
+}
@@ -0,0 +1,102 @@
+//Copyright (c) 2023 X. Gillard, A. Dubray
+//
+//Permission is hereby granted, free of charge, to any person obtaining a copy
+//of this software and associated documentation files (the "Software"), to deal
+//in the Software without restriction, including without limitation the rights
+//to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+//copies of the Software, and to permit persons to whom the Software is
+//furnished to do so, subject to the following conditions:
+//
+//The above copyright notice and this permission notice shall be included in all
+//copies or substantial portions of the Software.
+//
+//THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+//IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+//AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+//OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+//SOFTWARE.
+
+
+//! Iteration over all the managed resources of a type, with their handles, so that the heuristics scanning
+//! all the variables (e.g., the smallest domain first) do not need to keep their own lists of handles.
+//!
+#![cfg_attr(feature = "usize", doc = "```")]
+#![cfg_attr(not(feature = "usize"), doc = "```ignore")]
+//! use search_trail::{StateManager, UsizeManager};
+//!
+//! let mut mgr = StateManager::default();
+//! let sizes = [4, 2, 7].map(|size| mgr.manage_usize(size));
+//! let smallest = mgr.iter_usize().min_by_key(|&(_, size)| size).map(|(handle, _)| handle);
+//! assert_eq!(Some(sizes[1]), smallest);
+//! ```
+
+use paste::paste;
+
+use crate::*;
+
+macro_rules! iter_values {
+    ($($u:ty => $feature:literal),*) => {
+        paste! {
+            impl<B: TrailBackend> StateManager<B> {
+                $(
+                    #[doc="Iterates over all the managed resources of type `" $u "`, in the order of their creation, with their current value"]
+                    #[cfg(feature = $feature)]
+                    pub fn [<iter_ $u>](&self) -> impl Iterator<Item = ([<Reversible $u:camel>], $u)> + '_ {
+                        let store = &self.[<numbers _ $u>];
+                        (0..store.len()).map(move |id| ([<Reversible $u:camel>]::new(self, id), store.get(id)))
+                    }
+
+                    #[doc="Iterates over all the managed resources of type `Option<" $u ">`, in the order of their creation, with their current value"]
+                    #[cfg(feature = $feature)]
+                    pub fn [<iter_option_ $u>](&self) -> impl Iterator<Item = ([<ReversibleOption $u:camel>], Option<$u>)> + '_ {
+                        let store = &self.[<numbers_option_ $u>];
+                        (0..store.len()).map(move |id| ([<ReversibleOption $u:camel>]::new(self, id), store.get(id)))
+                    }
+                )*
+            }
+        }
+    };
+}
+
+for_each_managed_type!(iter_values);
+
+#[cfg(feature = "bool")]
+impl<B: TrailBackend> StateManager<B> {
+    /// Iterates over all the managed booleans, in the order of their creation, with their current value
+    pub fn iter_bool(&self) -> impl Iterator<Item = (ReversibleBool, bool)> + '_ {
+        self.bools.ids().map(move |id| (ReversibleBool::new(self, id), self.bools.get(id) != 0))
+    }
+
+    /// Iterates over all the managed optional booleans, in the order of their creation, with their current
+    /// value
+    pub fn iter_option_bool(&self) -> impl Iterator<Item = (ReversibleOptionBool, Option<bool>)> + '_ {
+        self.option_bools.ids().map(move |id| (ReversibleOptionBool::new(self, id), Self::option_bool_value(self.option_bools.get(id))))
+    }
+}
+
+#[cfg(all(test, feature = "i32", feature = "bool"))]
+mod test_iter {
+    use crate::*;
+
+    #[test]
+    fn iterators_follow_the_restorations() {
+        let mut mgr = StateManager::default();
+        let x = mgr.manage_i32(-1);
+        let y = mgr.manage_option_i32(None);
+        let b = mgr.manage_bool(true);
+        mgr.save_state();
+        let c = mgr.manage_bool(false);
+        let o = mgr.manage_option_bool(Some(true));
+        mgr.set_i32(x, 5);
+        mgr.set_option_i32(y, Some(2));
+        assert_eq!(vec![(x, 5)], mgr.iter_i32().collect::<Vec<_>>());
+        assert_eq!(vec![(y, Some(2))], mgr.iter_option_i32().collect::<Vec<_>>());
+        assert_eq!(vec![(b, true), (c, false)], mgr.iter_bool().collect::<Vec<_>>());
+        assert_eq!(vec![(o, Some(true))], mgr.iter_option_bool().collect::<Vec<_>>());
+        mgr.restore_state();
+        assert_eq!(vec![(x, -1)], mgr.iter_i32().collect::<Vec<_>>());
+        assert_eq!(vec![(y, None)], mgr.iter_option_i32().collect::<Vec<_>>());
+    }
+}
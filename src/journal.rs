@@ -0,0 +1,233 @@
+//Copyright (c) 2023 X. Gillard, A. Dubray
+//
+//Permission is hereby granted, free of charge, to any person obtaining a copy
+//of this software and associated documentation files (the "Software"), to deal
+//in the Software without restriction, including without limitation the rights
+//to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+//copies of the Software, and to permit persons to whom the Software is
+//furnished to do so, subject to the following conditions:
+//
+//The above copyright notice and this permission notice shall be included in all
+//copies or substantial portions of the Software.
+//
+//THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+//IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+//AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+//OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+//SOFTWARE.
+
+
+//! A journal of the mutating calls done on a manager, that can be replayed to reconstruct the exact
+//! same manager, including its trail. A journal captured from a real run makes a bug reproducible
+//! deterministically, or can be turned into a regression test.
+//!
+//! ```
+//! use search_trail::{SaveAndRestore, StateManager, UsizeManager};
+//!
+//! let mut mgr = StateManager::default();
+//! mgr.record_journal();
+//! let x = mgr.manage_usize(1);
+//! mgr.save_state();
+//! mgr.set_usize(x, 2);
+//! let replayed = mgr.journal().unwrap().replay();
+//! assert_eq!(format!("{:?}", mgr), format!("{:?}", replayed));
+//! ```
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::{SaveAndRestore, StateManager, TrailBackend, Value};
+
+/// A mutating call recorded in a journal. The writes are recorded only when they modify the manager.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum JournalEntry {
+    /// A call to `save_state()`
+    SaveState,
+    /// A call to `restore_state()`
+    RestoreState,
+    /// A call to `clear()`
+    Clear,
+    /// A call to `set_epsilon()`
+    SetEpsilon(f64),
+    /// The creation of a resource with the given value
+    Manage(Value),
+    /// A write of the resource of the type of the value at the given index
+    Set { id: usize, value: Value },
+    /// A write of the resources from `first` to the given values, which have the same numeric type
+    SetRange { first: usize, values: Vec<Value> },
+    /// A write of a word of the packed booleans (of the packed optional booleans if `option` is true)
+    SetBoolWord { option: bool, word: usize, value: u64 },
+    /// A call to `apply_delta()` with the given delta
+    ApplyDelta(Vec<u8>),
+    /// The release of the resource of the type of the value at the given index, whose value was the
+    /// given one
+    Release { id: usize, value: Value },
+}
+
+/// The mutating calls done on a manager since its journal is recorded
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Journal {
+    /// The recorded calls, in the order they were done
+    pub entries: Vec<JournalEntry>,
+}
+
+impl Journal {
+    /// Returns a new manager on which the calls of the journal have been replayed. If the journal has
+    /// been recorded from the creation of a manager, the new manager has the same resources, levels
+    /// and trail as the recorded one. Panics if an entry can not be replayed, e.g. if the journal has
+    /// been recorded on a manager that already had resources.
+    pub fn replay(&self) -> StateManager {
+        let mut mgr = StateManager::new();
+        self.replay_on(&mut mgr);
+        mgr
+    }
+
+    /// Replays the calls of the journal on the given manager
+    pub fn replay_on(&self, mgr: &mut StateManager) {
+        for entry in self.entries.iter() {
+            entry.replay_on(mgr);
+        }
+    }
+}
+
+impl JournalEntry {
+    /// Replays the call on the given manager
+    pub(crate) fn replay_on(&self, mgr: &mut StateManager) {
+        match self {
+            JournalEntry::SaveState => mgr.save_state(),
+            JournalEntry::RestoreState => mgr.restore_state(),
+            JournalEntry::Clear => mgr.clear(),
+            JournalEntry::SetEpsilon(epsilon) => mgr.set_epsilon(*epsilon),
+            JournalEntry::Manage(value) => {
+                value.manage(mgr);
+            }
+            JournalEntry::Set { id, value } => value.set_at(mgr, *id),
+            JournalEntry::SetRange { first, values } => Value::set_range(mgr, *first, values),
+            #[cfg(feature = "bool")]
+            JournalEntry::SetBoolWord { option, word, value } => mgr.set_bool_word(*option, *word, *value),
+            #[cfg(not(feature = "bool"))]
+            JournalEntry::SetBoolWord { .. } => panic!("The journal writes booleans, which are not managed"),
+            JournalEntry::ApplyDelta(delta) => mgr.apply_delta(delta).expect("The delta of the journal can not be applied"),
+            JournalEntry::Release { id, value } => value.release_at(mgr, *id),
+        }
+    }
+}
+
+impl<B: TrailBackend> StateManager<B> {
+    /// Starts recording the mutating calls done on the manager in a journal. Does nothing if the
+    /// journal is already recorded. To be replayed, the journal must be recorded from the creation of
+    /// the manager.
+    pub fn record_journal(&mut self) {
+        if self.journal.is_none() {
+            self.journal = Some(Journal::default());
+        }
+    }
+
+    /// Stops recording the journal and returns it, if it was recorded
+    pub fn take_journal(&mut self) -> Option<Journal> {
+        self.journal.take()
+    }
+
+    /// Returns the journal recorded so far, if the recording is enabled
+    pub fn journal(&self) -> Option<&Journal> {
+        self.journal.as_ref()
+    }
+
+    /// Records an entry in the journal, if it is recorded
+    #[inline]
+    pub(crate) fn journal_push(&mut self, entry: impl FnOnce() -> JournalEntry) {
+        if let Some(journal) = self.journal.as_mut() {
+            journal.entries.push(entry());
+        }
+    }
+}
+
+#[cfg(all(test, feature = "usize", feature = "f64", feature = "bool"))]
+mod test_journal {
+    use crate::*;
+
+    /// Returns the levels and the values of the resources, without the tags of the handles
+    fn state(mgr: &StateManager) -> (Vec<LevelDump>, Vec<(&'static str, usize, String)>) {
+        let dump = mgr.dump_state();
+        (dump.levels, dump.resources.into_iter().map(|r| (r.type_name, r.index, r.value)).collect())
+    }
+
+    /// Does a small search mixing all the mutating calls, and returns the manager
+    fn search() -> StateManager {
+        let mut mgr = StateManager::default();
+        mgr.record_journal();
+        mgr.set_epsilon(0.1);
+        let xs = (0..4).map(|i| mgr.manage_usize(i)).collect::<Vec<_>>();
+        let f = mgr.manage_f64(1.0);
+        let b = mgr.manage_bool(false);
+        let o = mgr.manage_option_bool(None);
+        for depth in 0..3 {
+            mgr.save_state();
+            mgr.set_usize(xs[depth], 10 + depth);
+            mgr.set_usize_slice(xs[0], &[depth, depth, depth]);
+            mgr.set_f64(f, 1.05);
+            mgr.set_f64(f, depth as f64);
+            mgr.flip_bool(b);
+            mgr.set_option_bool(o, depth % 2 == 0);
+            if depth == 1 {
+                mgr.restore_state();
+                mgr.save_state();
+                mgr.increment_usize(xs[3]);
+            }
+        }
+        mgr
+    }
+
+    #[test]
+    fn replay_is_exact() {
+        let mut mgr = search();
+        let mut replayed = mgr.journal().unwrap().replay();
+        assert_eq!(format!("{:?}", mgr), format!("{:?}", replayed));
+        assert_eq!(mgr.epsilon(), replayed.epsilon());
+        while mgr.depth > 0 {
+            mgr.restore_state();
+            replayed.restore_state();
+            assert_eq!(state(&mgr), state(&replayed));
+        }
+    }
+
+    #[test]
+    fn deltas_and_clears_are_replayed() {
+        let mut sender = StateManager::default();
+        let x = sender.manage_usize(0);
+        sender.save_state();
+        let token = sender.level_token();
+        sender.set_usize(x, 5);
+        sender.manage_bool(true);
+        let delta = sender.delta_since(token);
+
+        let mut mgr = StateManager::default();
+        mgr.record_journal();
+        mgr.manage_usize(0);
+        mgr.save_state();
+        mgr.apply_delta(&delta).unwrap();
+        let journal = mgr.journal().unwrap();
+        assert!(matches!(journal.entries.last(), Some(JournalEntry::ApplyDelta(_))));
+        assert_eq!(format!("{:?}", mgr), format!("{:?}", journal.replay()));
+
+        mgr.clear();
+        mgr.manage_f64(2.0);
+        let journal = mgr.take_journal().unwrap();
+        assert!(mgr.journal().is_none());
+        let replayed = journal.replay();
+        assert_eq!(state(&mgr), state(&replayed));
+    }
+
+    #[test]
+    fn writes_without_effect_are_not_recorded() {
+        let mut mgr = StateManager::default();
+        mgr.record_journal();
+        let x = mgr.manage_usize(1);
+        mgr.set_usize(x, 1);
+        assert_eq!(vec![JournalEntry::Manage(Value::Usize(1))], mgr.journal().unwrap().entries);
+    }
+}
@@ -0,0 +1,131 @@
+//Copyright (c) 2023 X. Gillard, A. Dubray
+//
+//Permission is hereby granted, free of charge, to any person obtaining a copy
+//of this software and associated documentation files (the "Software"), to deal
+//in the Software without restriction, including without limitation the rights
+//to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+//copies of the Software, and to permit persons to whom the Software is
+//furnished to do so, subject to the following conditions:
+//
+//The above copyright notice and this permission notice shall be included in all
+//copies or substantial portions of the Software.
+//
+//THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+//IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+//AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+//OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+//SOFTWARE.
+
+
+//! Labels of the decisions taken in the saved levels. A level saved with `save_state_labeled()` keeps
+//! the label of its decision until it is restored, so that the path of decisions leading to the current
+//! level can be logged at any point of the search.
+
+use std::fmt;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::{SaveAndRestore, StateManager, TrailBackend};
+
+/// The label of a decision: the index of a variant of a user enum, or a text
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum DecisionLabel {
+    /// An identifier of the decision
+    Id(usize),
+    /// A description of the decision
+    Text(String),
+}
+
+impl From<usize> for DecisionLabel {
+    fn from(id: usize) -> Self {
+        DecisionLabel::Id(id)
+    }
+}
+
+impl From<&str> for DecisionLabel {
+    fn from(text: &str) -> Self {
+        DecisionLabel::Text(text.to_string())
+    }
+}
+
+impl From<String> for DecisionLabel {
+    fn from(text: String) -> Self {
+        DecisionLabel::Text(text)
+    }
+}
+
+impl fmt::Display for DecisionLabel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecisionLabel::Id(id) => write!(f, "#{}", id),
+            DecisionLabel::Text(text) => write!(f, "{}", text),
+        }
+    }
+}
+
+impl<B: TrailBackend> StateManager<B> {
+    /// Saves the state, like `save_state()`, and labels the new level with the decision taken in it.
+    /// If the search tree is recorded, its node is also labelled.
+    ///
+    /// ```
+    /// use search_trail::{DecisionLabel, SaveAndRestore, StateManager};
+    ///
+    /// let mut mgr = StateManager::default();
+    /// mgr.save_state_labeled("x = 1");
+    /// mgr.save_state();
+    /// mgr.save_state_labeled(3);
+    /// assert_eq!(vec![&DecisionLabel::from("x = 1"), &DecisionLabel::Id(3)], mgr.decision_path());
+    /// mgr.restore_state();
+    /// assert_eq!(vec![&DecisionLabel::from("x = 1")], mgr.decision_path());
+    /// ```
+    pub fn save_state_labeled(&mut self, label: impl Into<DecisionLabel>) {
+        let label = label.into();
+        self.save_state();
+        if self.tree.is_some() {
+            self.label_level(label.to_string());
+        }
+        self.labels.push((self.depth, label));
+    }
+
+    /// Returns the labels of the decisions leading to the current level, from the root. The levels
+    /// saved without label are skipped.
+    pub fn decision_path(&self) -> Vec<&DecisionLabel> {
+        self.labels.iter().map(|(_, label)| label).collect()
+    }
+
+    /// Removes the labels of the restored levels
+    pub(crate) fn restore_labels(&mut self) {
+        while self.labels.last().is_some_and(|&(depth, _)| depth > self.depth) {
+            self.labels.pop();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_labels {
+    use crate::*;
+
+    #[test]
+    fn labels_follow_the_levels() {
+        let mut mgr = StateManager::default();
+        mgr.record_search_tree();
+        mgr.save_state_labeled(String::from("root"));
+        for i in 0..3 {
+            mgr.save_state_labeled(i);
+            mgr.save_state();
+        }
+        assert_eq!("root/#0/#1/#2", mgr.decision_path().iter().map(|l| l.to_string()).collect::<Vec<_>>().join("/"));
+        for _ in 0..3 {
+            mgr.restore_state();
+        }
+        assert_eq!(vec![&DecisionLabel::from("root"), &DecisionLabel::Id(0), &DecisionLabel::Id(1)], mgr.decision_path());
+        let tree = mgr.search_tree().unwrap();
+        assert_eq!(Some("#1"), tree.nodes[tree.current()].label.as_deref());
+        mgr.reset();
+        assert!(mgr.decision_path().is_empty());
+    }
+}
@@ -0,0 +1,267 @@
+//Copyright (c) 2023 X. Gillard, A. Dubray
+//
+//Permission is hereby granted, free of charge, to any person obtaining a copy
+//of this software and associated documentation files (the "Software"), to deal
+//in the Software without restriction, including without limitation the rights
+//to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+//copies of the Software, and to permit persons to whom the Software is
+//furnished to do so, subject to the following conditions:
+//
+//The above copyright notice and this permission notice shall be included in all
+//copies or substantial portions of the Software.
+//
+//THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+//IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+//AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+//OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+//SOFTWARE.
+
+
+//! Lazy values, derived from other managed resources. A lazy value is defined by a closure reading the
+//! manager and by the resources it depends on. It is computed on the first read, and the result is cached
+//! in a managed slot. Changing one of its dependencies empties the slot, so that the value is computed
+//! again on the next read.
+//!
+//! As the slot is a managed resource, it is restored with the other resources: after a restoration, the
+//! cached value (or the absence of value) is the one of the restored level, consistent with the values of
+//! the dependencies. A lazy value can be computed from another one: it then depends on the slot of the
+//! other one, given by `Lazy::handle()`.
+//!
+//! Like the other resources, the lazy values must be created at the level in which they are used, or at a
+//! level above it.
+
+use std::collections::HashMap;
+use std::fmt::Debug;
+
+use paste::paste;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::*;
+
+/// The slots of the lazy values depending on each resource
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub(crate) struct LazyDependencies {
+    dependents: HashMap<AnyHandle, Vec<AnyHandle>>,
+}
+
+/// The closure computing a lazy value
+type Compute<T, B> = Box<dyn Fn(&mut StateManager<B>) -> T + Send + Sync>;
+
+/// A value computed from other managed resources, and cached until one of them is modified
+pub struct Lazy<T: ScopedValue, B: TrailBackend = Trailing>
+where
+    Option<T>: ScopedValue,
+{
+    slot: <Option<T> as ScopedValue>::Handle,
+    compute: Compute<T, B>,
+}
+
+impl<T: ScopedValue, B: TrailBackend> Lazy<T, B>
+where
+    Option<T>: ScopedValue,
+{
+    /// Returns the handle of the slot caching the value. It is empty if the value must be computed.
+    pub fn handle(&self) -> <Option<T> as ScopedValue>::Handle {
+        self.slot
+    }
+}
+
+impl<T: ScopedValue, B: TrailBackend> Debug for Lazy<T, B>
+where
+    Option<T>: ScopedValue,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Lazy").field("slot", &self.slot).finish_non_exhaustive()
+    }
+}
+
+macro_rules! lazy_slots {
+    ($($u:ty => $feature:literal),*) => {
+        paste! {
+            impl<B: TrailBackend> StateManager<B> {
+                /// Empties the slot of a lazy value
+                fn empty_slot(&mut self, slot: AnyHandle) {
+                    match slot {
+                        $(
+                            #[cfg(feature = $feature)]
+                            AnyHandle::[<Option $u:camel>](h) => {
+                                self.[<set_option_ $u>](h, None);
+                            }
+                        )*
+                        #[cfg(feature = "bool")]
+                        AnyHandle::OptionBool(h) => self.set_option_bool_none(h),
+                        #[allow(unreachable_patterns)]
+                        _ => unreachable!("The slot of a lazy value is an optional resource"),
+                    }
+                }
+            }
+        }
+    };
+}
+
+for_each_managed_type!(lazy_slots);
+
+impl<B: TrailBackend> StateManager<B> {
+    /// Creates a lazy value computed by `compute`, which depends on the given resources. The closure must
+    /// not modify the resources; it may read other lazy values, which are then computed if needed.
+    ///
+    #[cfg_attr(feature = "usize", doc = "```")]
+    #[cfg_attr(not(feature = "usize"), doc = "```ignore")]
+    /// use search_trail::{OptionUsizeManager, SaveAndRestore, StateManager, UsizeManager};
+    ///
+    /// let mut mgr = StateManager::default();
+    /// let capacity = mgr.manage_usize(10);
+    /// let load = mgr.manage_usize(3);
+    /// let slack = mgr.manage_lazy(&[capacity.into(), load.into()], move |mgr| mgr.get_usize(capacity) - mgr.get_usize(load));
+    /// assert_eq!(7, mgr.get_lazy(&slack));
+    /// mgr.save_state();
+    /// mgr.set_usize(load, 8);
+    /// assert_eq!(2, mgr.get_lazy(&slack));
+    /// mgr.restore_state();
+    /// assert_eq!(Some(7), mgr.get_option_usize(slack.handle()));
+    /// ```
+    pub fn manage_lazy<T, F>(&mut self, dependencies: &[AnyHandle], compute: F) -> Lazy<T, B>
+    where
+        T: ScopedValue,
+        Option<T>: ScopedValue,
+        <Option<T> as ScopedValue>::Handle: Into<AnyHandle>,
+        F: Fn(&mut StateManager<B>) -> T + Send + Sync + 'static,
+    {
+        let slot = <Option<T>>::manage(self, None);
+        let lazies = self.lazies.get_or_insert_with(LazyDependencies::default);
+        for &dependency in dependencies {
+            lazies.dependents.entry(dependency).or_default().push(slot.into());
+        }
+        Lazy { slot, compute: Box::new(compute) }
+    }
+
+    /// Returns the value of the lazy value, computing it if it is not cached
+    pub fn get_lazy<T>(&mut self, lazy: &Lazy<T, B>) -> T
+    where
+        T: ScopedValue,
+        Option<T>: ScopedValue,
+    {
+        match <Option<T>>::get(self, lazy.slot) {
+            Some(value) => value,
+            None => {
+                let value = (lazy.compute)(self);
+                <Option<T>>::set(self, lazy.slot, Some(value));
+                value
+            }
+        }
+    }
+
+    /// Empties the slots of the lazy values depending on the resource
+    fn invalidate(&mut self, handle: AnyHandle) {
+        let slots = match self.lazies.as_ref().and_then(|lazies| lazies.dependents.get(&handle)) {
+            Some(slots) => slots.clone(),
+            None => return,
+        };
+        for slot in slots {
+            self.empty_slot(slot);
+        }
+    }
+
+    /// Empties the slots of the lazy values depending on the resource at the given index, if there are
+    /// lazy values
+    #[inline]
+    #[cfg(numbers)]
+    pub(crate) fn invalidate_lazy<T: Managed>(&mut self, id: usize) {
+        if self.lazies.is_some() {
+            self.invalidate(T::handle(self, id));
+        }
+    }
+
+    /// Empties the slots of the lazy values depending on the resources of `first..first + len`, if there
+    /// are lazy values
+    #[cfg(numbers)]
+    pub(crate) fn invalidate_lazy_range<T: Managed>(&mut self, first: usize, len: usize) {
+        if self.lazies.is_some() {
+            for id in first..first + len {
+                self.invalidate(T::handle(self, id));
+            }
+        }
+    }
+
+    /// Empties the slots of the lazy values depending on the packed booleans of a word whose value changed
+    /// from `old` to `new`, if there are lazy values
+    #[cfg(feature = "bool")]
+    pub(crate) fn invalidate_lazy_bools(&mut self, option: bool, word: usize, old: u64, new: u64) {
+        if self.lazies.is_none() {
+            return;
+        }
+        if option {
+            let per_word = OptionBools::<B::Store<u64>>::PER_WORD;
+            for id in word * per_word..(word + 1) * per_word {
+                if OptionBools::<B::Store<u64>>::unpack(old, id) != OptionBools::<B::Store<u64>>::unpack(new, id) {
+                    self.invalidate(AnyHandle::OptionBool(ReversibleOptionBool::new(self, id)));
+                }
+            }
+        } else {
+            let per_word = Bools::<B::Store<u64>>::PER_WORD;
+            for id in word * per_word..(word + 1) * per_word {
+                if Bools::<B::Store<u64>>::unpack(old, id) != Bools::<B::Store<u64>>::unpack(new, id) {
+                    self.invalidate(AnyHandle::Bool(ReversibleBool::new(self, id)));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(all(test, feature = "usize", feature = "f64", feature = "bool"))]
+mod test_lazy {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use crate::*;
+
+    #[test]
+    fn lazy_values_are_computed_once_per_change() {
+        let mut mgr = StateManager::default();
+        let xs = (0..4).map(|i| mgr.manage_usize(i)).collect::<Vec<_>>();
+        let active = mgr.manage_bool(true);
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let dependencies = xs.iter().map(|&x| x.into()).chain(std::iter::once(active.into())).collect::<Vec<_>>();
+        let sum = {
+            let xs = xs.clone();
+            mgr.manage_lazy(&dependencies, move |mgr| {
+                counter.fetch_add(1, Ordering::Relaxed);
+                if mgr.get_bool(active) {
+                    xs.iter().map(|&x| mgr.get_usize(x)).sum()
+                } else {
+                    0
+                }
+            })
+        };
+        let sum = Arc::new(sum);
+        let mean = {
+            let sum = sum.clone();
+            mgr.manage_lazy(&[sum.handle().into()], move |mgr| mgr.get_lazy(&sum) as f64 / 4.0)
+        };
+        assert_eq!(6, mgr.get_lazy(&sum));
+        assert_eq!(6, mgr.get_lazy(&sum));
+        assert_eq!(1.5, mgr.get_lazy(&mean));
+        assert_eq!(1, calls.load(Ordering::Relaxed));
+
+        mgr.save_state();
+        mgr.set_usize_slice(xs[2], &[6, 7]);
+        assert_eq!(None, mgr.get_option_f64(mean.handle()));
+        assert_eq!(14, mgr.get_lazy(&sum));
+        mgr.save_state();
+        mgr.set_bool(active, false);
+        assert_eq!(0.0, mgr.get_lazy(&mean));
+        assert_eq!(3, calls.load(Ordering::Relaxed));
+
+        mgr.restore_state();
+        assert_eq!(14, mgr.get_lazy(&sum));
+        mgr.restore_state();
+        assert_eq!(6, mgr.get_lazy(&sum));
+        assert_eq!(1.5, mgr.get_lazy(&mean));
+        assert_eq!(3, calls.load(Ordering::Relaxed));
+    }
+}
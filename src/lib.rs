@@ -18,27 +18,315 @@
 //OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
 //SOFTWARE.
 
+// The per-type manager traits are deprecated, but still implemented and used by the crate
+#![allow(deprecated)]
+// Without any managed type, the per-type code expands to nothing and leaves most of the manager unused
+#![cfg_attr(not(managed), allow(dead_code, unused_variables, unused_mut, unused_imports, unused_macros, unreachable_code))]
+
 use paste::paste;
 
-macro_rules! manage_numbers {
-    ($($u:ty),*) => {
-        paste!{
-            /// This structure keeps track of the length of a given level of the trail as well as the number of
-            /// managed resources of each kind. This second information is useful in order to truncate the
-            /// vector in the state manager.
-            #[derive(Debug, Clone, Copy, Default)]
-            struct Level {
-                /// The length of the trail at the moment this level was started
-                trail_size: usize,
+#[cfg(feature = "bool")]
+use bools::{Bools, OptionBools};
+#[cfg(feature = "checked-handles")]
+use handle::HandleTag;
+use names::NameRegistry;
+use std::marker::PhantomData;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Calls the given macro with the list of managed types, each type being associated with the
+/// feature that enables it.
+macro_rules! for_each_managed_type {
+    ($m:ident) => {
+        $m! {
+            u8 => "u8",
+            u16 => "u16",
+            u32 => "u32",
+            u64 => "u64",
+            u128 => "u128",
+            usize => "usize",
+            i8 => "i8",
+            i16 => "i16",
+            i32 => "i32",
+            i64 => "i64",
+            i128 => "i128",
+            isize => "isize",
+            f32 => "f32",
+            f64 => "f64"
+        }
+    };
+}
+
+mod arena;
+#[cfg(feature = "usize")]
+mod assignments;
+mod backend;
+mod batch;
+#[cfg(feature = "bool")]
+mod bools;
+mod builder;
+#[cfg(feature = "checkpoint")]
+mod checkpoint;
+mod concurrent;
+mod copying;
+mod cow;
+mod custom;
+mod dd;
+mod delta;
+mod dirty;
+mod dump;
+mod dynamic;
+mod error;
+mod extract;
+mod fenwick;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+mod fixed;
+#[cfg(feature = "bitflags")]
+mod flags;
+#[cfg(feature = "usize")]
+mod forest;
+mod frozen;
+#[cfg(feature = "fuzz")]
+mod fuzz;
+#[cfg(feature = "usize")]
+mod graph;
+#[cfg(feature = "journal")]
+mod journal;
+#[cfg(feature = "checked-handles")]
+mod handle;
+mod hash;
+#[cfg(feature = "usize")]
+mod histogram;
+mod hooks;
+mod index;
+mod iter;
+mod labels;
+mod lazy;
+#[cfg(feature = "listeners")]
+mod listeners;
+mod lns;
+mod memory;
+#[cfg(feature = "metrics")]
+mod metrics;
+#[cfg(feature = "minicp")]
+pub mod minicp;
+#[cfg(feature = "mirror")]
+mod mirror;
+mod names;
+mod niche;
+mod number;
+mod objective;
+#[cfg(all(feature = "usize", feature = "bool"))]
+mod objects;
+#[cfg(feature = "persistent")]
+mod persistent;
+mod pool;
+pub mod prelude;
+#[cfg(feature = "python")]
+mod python;
+mod recomputing;
+mod release;
+mod restart;
+mod reversible;
+#[cfg(feature = "usize")]
+mod ring;
+mod scoped;
+mod segment;
+#[cfg(feature = "search")]
+mod search;
+#[cfg(feature = "snapshot")]
+mod snapshot;
+mod stats;
+#[cfg(feature = "time-travel")]
+mod time_travel;
+#[cfg(any(test, feature = "testing"))]
+mod testing;
+mod tree;
+#[cfg(feature = "usize")]
+mod trie;
+mod value;
+#[cfg(feature = "wasm")]
+mod wasm;
+#[cfg(feature = "usize")]
+mod watch;
+
+pub use arena::TrailArena;
+#[cfg(feature = "usize")]
+pub use assignments::{Assignment, AssignmentStack};
+pub use backend::{Store, TrailBackend};
+pub use builder::StateManagerBuilder;
+#[cfg(feature = "checkpoint")]
+pub use checkpoint::Checkpointer;
+pub use concurrent::ConcurrentStateManager;
+pub use copying::{CopyStore, Copying, CopyingStateManager};
+pub use cow::{CopyOnWrite, CowStateManager, CowStore};
+pub use custom::ReversibleNumber;
+use custom::CustomNumbers;
+pub use dd::DdState;
+pub use delta::{DeltaError, LevelToken, NodeDescriptor};
+use dirty::DirtySet;
+pub use dirty::EventKind;
+pub use dump::{LevelDump, ResourceDump, StateDump};
+pub use dynamic::{DynStateManager, TypedAssignment};
+pub use error::TrailError;
+pub use fenwick::FenwickTree;
+pub use fixed::{StaticHandle, StaticStateManager, StaticValue};
+#[cfg(feature = "bitflags")]
+pub use flags::ReversibleFlags;
+#[cfg(feature = "usize")]
+pub use forest::ReversibleForest;
+pub use frozen::{diff, FrozenState, TypedChange};
+#[cfg(feature = "fuzz")]
+pub use fuzz::{apply_ops, Op};
+#[cfg(feature = "usize")]
+pub use graph::ReversibleGraph;
+#[cfg(numbers)]
+use hash::Fingerprint;
+use hash::StateHash;
+#[cfg(feature = "usize")]
+pub use histogram::Histogram;
+use hooks::Hooks;
+#[cfg(feature = "journal")]
+pub use journal::{Journal, JournalEntry};
+pub use labels::DecisionLabel;
+use lazy::LazyDependencies;
+pub use lazy::Lazy;
+#[cfg(feature = "listeners")]
+pub use listeners::Listened;
+#[cfg(feature = "listeners")]
+use listeners::{ListenerMap, Listeners};
+use lns::FrozenSet;
+pub use memory::{MemoryReport, StoreMemory};
+#[cfg(feature = "mirror")]
+pub use mirror::{Mirror, MirrorValue, Mirrored};
+#[cfg(feature = "mirror")]
+use mirror::Mirrors;
+pub use niche::ReversibleNiche;
+pub use number::{ManagedNumber, NumberStore, Trailing};
+pub use objective::Objective;
+#[cfg(all(feature = "usize", feature = "bool"))]
+pub use objects::{ObjectArena, ObjectKey};
+#[cfg(numbers)]
+use number::{decrement, increment, Negligible, Step};
+#[cfg(feature = "persistent")]
+pub use persistent::{Persistent, PersistentStateManager, PersistentStore};
+pub use pool::{ManagerPool, PooledManager};
+pub use prelude::NumericManager;
+use release::FreeSlots;
+pub use recomputing::{Recomputing, RecomputingStateManager, ReplayStore};
+pub use restart::{Geometric, Luby, RestartPolicy};
+pub use reversible::{Reversible, ReversibleHandle};
+#[cfg(feature = "usize")]
+pub use ring::RingBuffer;
+pub use scoped::{Branded, ScopedManager, ScopedValue};
+pub use segment::SegmentTree;
+#[cfg(feature = "search")]
+pub use search::{Dfs, SearchOutcome, SearchProblem, SearchStats};
+#[cfg(feature = "snapshot")]
+pub use snapshot::SnapshotError;
+use stats::StatsRecorder;
+pub use stats::TrailStats;
+#[cfg(feature = "time-travel")]
+pub use time_travel::TimeTravel;
+#[cfg(feature = "testing")]
+pub use testing::check_against_model;
+pub use tree::{SearchNode, SearchTree};
+#[cfg(feature = "usize")]
+pub use trie::ReversibleTrie;
+pub use value::Value;
+#[cfg(feature = "usize")]
+pub use watch::WatchLists;
+
+/// This structure keeps track of a level of the state manager
+#[derive(Debug, Clone, Copy, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+struct Level {
+    /// The clock of the level. Every level has a different clock.
+    clock: usize,
+    /// Number of empty levels saved on top of this one. Saving a level only increments this counter;
+    /// the level is materialized when a resource is created or modified in it. Hence, long chains of
+    /// levels without any modification cost nothing to save and restore.
+    empties: usize,
+}
+
+/// Number of levels stored inline, before the levels need to allocate
+#[cfg(feature = "smallvec")]
+const INLINE_LEVELS: usize = 8;
+
+/// Storage of the levels
+#[cfg(not(feature = "smallvec"))]
+type LevelVec = Vec<Level>;
+/// Storage of the levels. The first levels are kept inline so that shallow searches never allocate
+#[cfg(feature = "smallvec")]
+type LevelVec = smallvec::SmallVec<[Level; INLINE_LEVELS]>;
+
+/// Minimum number of values restored by a level for it to be restored in parallel
+#[cfg(feature = "rayon")]
+const PARALLEL_RESTORE_THRESHOLD: usize = 1 << 16;
+
+pub trait SaveAndRestore {
+    /// Saves the current state of all managed resources
+    fn save_state(&mut self);
+
+    /// Restores the previous state of all managed resources
+    fn restore_state(&mut self);
+}
+
+/// Implements the conversions between a handle and the index of its resource. With the
+/// `checked-handles` feature, the handles are tagged with the manager that created them, and using a
+/// handle with another manager, or after its manager has been reset, panics.
+macro_rules! tagged_handle {
+    ($handle:ty) => {
+        #[allow(clippy::init_numbered_fields, unused_variables)]
+        impl $handle {
+            /// Creates the handle of the resource at the given index of the manager
+            fn new<B: TrailBackend>(mgr: &StateManager<B>, id: usize) -> Self {
+                Self {
+                    0: id,
+                    #[cfg(feature = "checked-handles")]
+                    1: mgr.tag,
+                }
+            }
+
+            /// Returns the index of the resource of the handle in the manager
+            fn index<B: TrailBackend>(self, mgr: &StateManager<B>) -> usize {
+                #[cfg(feature = "checked-handles")]
+                if let Err(error) = mgr.check_tag(self.1) {
+                    panic!("{}", error);
+                }
+                self.0
             }
 
-            /// An entry that is used to restore data from the trail
-            #[derive(Debug, Clone, Copy)]
-            enum TrailEntry {
+            /// Same as `index`, but returns an error if the handle can not be used with the manager
+            #[allow(dead_code)]
+            fn try_index<B: TrailBackend>(self, mgr: &StateManager<B>) -> Result<usize, TrailError> {
+                #[cfg(feature = "checked-handles")]
+                mgr.check_tag(self.1)?;
+                Ok(self.0)
+            }
+        }
+    };
+}
+
+macro_rules! manage_numbers {
+    ($($u:ty => $feature:literal),*) => {
+        paste!{
+            /// A handle to a managed resource of any type
+            #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+            #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+            #[cfg_attr(feature = "fuzz", derive(arbitrary::Arbitrary))]
+            pub enum AnyHandle {
                 $(
-                    [<$u:camel Entry>]([<State $u:camel>]),
-                    [<Option $u:camel Entry>]([<StateOption $u:camel>]),
+                    #[cfg(feature = $feature)]
+                    [<$u:camel>]([<Reversible $u:camel>]),
+                    #[cfg(feature = $feature)]
+                    [<Option $u:camel>]([<ReversibleOption $u:camel>]),
                 )*
+                #[cfg(feature = "bool")]
+                Bool(ReversibleBool),
+                #[cfg(feature = "bool")]
+                OptionBool(ReversibleOptionBool),
             }
 
             /// This structure implements a simple manager that can save a state and restore it later.
@@ -46,141 +334,886 @@ macro_rules! manage_numbers {
             /// The states are stored and restored like a stack. This means that when restoring the state of the
             /// manager, all the managed values are restored to their **most recently** saved value.
             ///
+            /// The way the states are saved and restored is given by the backend `B`. By default, the
+            /// previous values are saved on a trail (see `Trailing`), and the manager is created with
+            /// `StateManager::default()`. Managers with another backend are created with
+            /// `StateManager::<B>::new()`.
+            ///
             /// #Example
             ///
-            /// ```
-            /// use search_trail::{StateManager, SaveAndRestore, UsizeManager};
+            #[cfg_attr(feature = "usize", doc = "```")]
+            #[cfg_attr(not(feature = "usize"), doc = "```ignore")]
+            /// use search_trail::{StateManager, SaveAndRestore};
             ///
             /// fn main() {
             ///     let mut mgr = StateManager::default();
-            ///     let n = mgr.manage_usize(0);
-            ///     assert_eq!(0, mgr.get_usize(n));
+            ///     let n = mgr.manage(0usize);
+            ///     assert_eq!(0, mgr.get(n));
             ///
             ///     mgr.save_state();
             ///
-            ///     mgr.set_usize(n, 20);
-            ///     assert_eq!(20, mgr.get_usize(n));
+            ///     mgr.set(n, 20);
+            ///     assert_eq!(20, mgr.get(n));
             ///
             ///     mgr.save_state();
             ///
-            ///     mgr.set_usize(n, 42);
-            ///     assert_eq!(42, mgr.get_usize(n));
+            ///     mgr.set(n, 42);
+            ///     assert_eq!(42, mgr.get(n));
             ///
             ///     mgr.restore_state();
-            ///     assert_eq!(20, mgr.get_usize(n));
+            ///     assert_eq!(20, mgr.get(n));
             ///
             ///     mgr.restore_state();
-            ///     assert_eq!(0, mgr.get_usize(n));
+            ///     assert_eq!(0, mgr.get(n));
             /// }
             /// ```
-            #[derive(Debug, Clone)]
-            pub struct StateManager {
+            #[derive(Clone)]
+            #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+            pub struct StateManager<B: TrailBackend = Trailing> {
                 /// This clock is responsible to tell if a data need to be stored on the trail for restitution
-                /// or not. If a managed resource X is changed and X.clock < clock, then it needs to be saved
-                /// on the trail for restitution. Once the managed resource is updated, X.clock = clock.
-                ///
-                /// This clock is incremented at each call to `save_state()`
+                /// or not. It is the clock of the current level. If a managed resource X is changed and
+                /// X.clock != clock, then it needs to be saved on the trail for restitution. Once the
+                /// managed resource is updated, X.clock = clock.
                 clock: usize,
-                /// The values that are saved on the trail. These entries are used to restore the managed
-                /// resources when `restore_state()` is called
-                trail: Vec<TrailEntry>,
-                /// Levels of the trail where a level is an indicator of the number of `TrailEntry` for a given
-                /// timestamp of `clock`
-                levels: Vec<Level>,
+                /// The last clock given to a level. It is incremented at each call to `save_state()`, so that
+                /// each level has a fresh clock.
+                max_clock: usize,
+                /// The saved levels. The first level is the root level, which can not be restored.
+                levels: LevelVec,
+                /// The number of saved levels, including the empty levels that are not materialized
+                depth: usize,
+                /// The number of calls to `restart()`
+                #[cfg_attr(feature = "serde", serde(default))]
+                restarts: usize,
+                /// Changes of a floating point value smaller than this epsilon are ignored
+                epsilon: f64,
+                /// The tag of the handles created by this manager
+                #[cfg(feature = "checked-handles")]
+                tag: HandleTag,
+                /// The names given to the resources
+                names: NameRegistry,
+                /// The labels of the decisions of the saved levels, with the depth of their level
+                #[cfg_attr(feature = "serde", serde(default))]
+                labels: Vec<(usize, DecisionLabel)>,
+                /// The tree of the explored levels, if it is recorded
+                tree: Option<SearchTree>,
+                /// The statistics of the search, if they are recorded
+                #[cfg_attr(feature = "serde", serde(skip))]
+                stats: Option<StatsRecorder>,
+                /// The resources modified since the last drain, if they are tracked
+                dirty: Option<DirtySet>,
+                /// The incremental hash of the state, if it is maintained
+                state_hash: Option<StateHash>,
+                /// The resources that can not be modified, if any has been frozen
+                frozen: Option<FrozenSet>,
+                /// The lazy values depending on each resource, if any lazy value has been created
+                #[cfg_attr(feature = "serde", serde(default))]
+                lazies: Option<LazyDependencies>,
+                /// The released slots that can be reused, if any resource has been released
+                #[cfg_attr(feature = "serde", serde(default))]
+                free_slots: Option<FreeSlots>,
+                /// True between `begin_batch()` and `end_batch()`
+                #[cfg_attr(feature = "serde", serde(skip))]
+                batch: bool,
+                /// The callbacks called at each save and restoration
+                #[cfg_attr(feature = "serde", serde(skip))]
+                hooks: Hooks,
+                /// The journal of the mutating calls, if it is recorded
+                #[cfg(feature = "journal")]
+                journal: Option<Journal>,
+                /// The length of the trail when the metrics were last published
+                #[cfg(feature = "metrics")]
+                #[cfg_attr(feature = "serde", serde(skip))]
+                published_trail: usize,
+                /// The atomic mirrors of selected values
+                #[cfg(feature = "mirror")]
+                #[cfg_attr(feature = "serde", serde(skip))]
+                mirrors: Mirrors<B>,
+                /// The callbacks called when a resource is changed
+                #[cfg(feature = "listeners")]
+                #[cfg_attr(feature = "serde", serde(skip))]
+                listeners: Listeners,
+                /// The stores of the managed numbers of the types defined outside of this crate
+                #[cfg_attr(feature = "serde", serde(skip))]
+                custom: CustomNumbers,
                 $(
-                    [<numbers _ $u>]: Vec<[<State $u:camel>]>,
-                    [<numbers _ option _ $u>]: Vec<[<State Option $u:camel>]>,
+                    #[cfg(feature = $feature)]
+                    [<numbers _ $u>]: B::Store<$u>,
+                    #[cfg(feature = $feature)]
+                    [<numbers _ option _ $u>]: B::Store<Option<$u>>,
                 )*
+                // The bounds are not inferred through the packed words
+                #[cfg(feature = "bool")]
+                #[cfg_attr(feature = "serde", serde(bound(serialize = "B::Store<u64>: Serialize", deserialize = "B::Store<u64>: Deserialize<'de>")))]
+                bools: Bools<B::Store<u64>>,
+                #[cfg(feature = "bool")]
+                #[cfg_attr(feature = "serde", serde(bound(serialize = "B::Store<u64>: Serialize", deserialize = "B::Store<u64>: Deserialize<'de>")))]
+                option_bools: OptionBools<B::Store<u64>>,
+                /// The backend, which is not used by any field when no type is managed
+                #[cfg_attr(feature = "serde", serde(skip))]
+                backend: PhantomData<B>,
             }
 
-            impl Default for StateManager {
-                fn default() -> Self {
+            impl<B: TrailBackend> StateManager<B> {
+                /// Creates a new manager, without any managed resource
+                pub fn new() -> Self {
                     Self {
                         clock: 0,
-                        trail: vec![],
-                        levels: vec![Level {
-                            trail_size: 0,
-                        }],
+                        max_clock: 0,
+                        levels: std::iter::once(Level {
+                            clock: 0,
+                            empties: 0,
+                        }).collect(),
+                        depth: 0,
+                        restarts: 0,
+                        epsilon: 0.0,
+                        #[cfg(feature = "checked-handles")]
+                        tag: HandleTag::new(),
+                        names: NameRegistry::default(),
+                        labels: vec![],
+                        tree: None,
+                        stats: None,
+                        dirty: None,
+                        state_hash: None,
+                        frozen: None,
+                        lazies: None,
+                        free_slots: None,
+                        batch: false,
+                        hooks: Hooks::default(),
+                        #[cfg(feature = "journal")]
+                        journal: None,
+                        #[cfg(feature = "metrics")]
+                        published_trail: 0,
+                        #[cfg(feature = "mirror")]
+                        mirrors: Mirrors::default(),
+                        #[cfg(feature = "listeners")]
+                        listeners: Listeners::default(),
+                        custom: CustomNumbers::default(),
                         $(
-                            [<numbers _ $u>]: vec![],
-                            [<numbers_option_ $u>]: vec![],
+                            #[cfg(feature = $feature)]
+                            [<numbers _ $u>]: Default::default(),
+                            #[cfg(feature = $feature)]
+                            [<numbers_option_ $u>]: Default::default(),
                         )*
+                        #[cfg(feature = "bool")]
+                        bools: Default::default(),
+                        #[cfg(feature = "bool")]
+                        option_bools: Default::default(),
+                        backend: PhantomData,
+                    }
+                }
+
+                /// Sets the epsilon of the manager. Setting a floating point value (or an optional one) to a
+                /// value that differs from the current one by less than `epsilon` is a no-op: the value is not
+                /// modified and the setter returns the current value. Hence, solvers that keep setting nearly
+                /// identical values do not fill the trail. The epsilon is zero by default, and does not apply to
+                /// the integers.
+                pub fn set_epsilon(&mut self, epsilon: f64) {
+                    #[cfg(feature = "journal")]
+                    self.journal_push(|| JournalEntry::SetEpsilon(epsilon));
+                    self.epsilon = epsilon;
+                }
+
+                /// Returns the epsilon under which the changes of floating point values are ignored
+                pub fn epsilon(&self) -> f64 {
+                    self.epsilon
+                }
+
+                /// Removes all the managed resources and saved levels, keeping the allocated memory, so that
+                /// the manager can be reused for another problem. The handles created before are not valid
+                /// anymore.
+                pub fn reset(&mut self) {
+                    self.clear();
+                }
+
+                /// Removes all the managed resources and saved levels, keeping the allocated memory
+                fn clear(&mut self) {
+                    self.clock = 0;
+                    self.max_clock = 0;
+                    self.levels.truncate(1);
+                    self.levels[0].empties = 0;
+                    self.depth = 0;
+                    self.restarts = 0;
+                    // The handles created before are not valid anymore
+                    #[cfg(feature = "checked-handles")]
+                    self.tag.next_generation();
+                    self.names.clear();
+                    self.labels.clear();
+                    if self.tree.is_some() {
+                        self.tree = Some(SearchTree::default());
+                    }
+                    if self.dirty.is_some() {
+                        self.dirty = Some(DirtySet::default());
+                    }
+                    self.frozen = None;
+                    self.lazies = None;
+                    self.free_slots = None;
+                    if self.state_hash.is_some() {
+                        self.state_hash = Some(StateHash::default());
+                    }
+                    #[cfg(feature = "metrics")]
+                    {
+                        self.published_trail = 0;
+                    }
+                    #[cfg(feature = "journal")]
+                    self.journal_push(|| JournalEntry::Clear);
+                    // The handles of the mirrored resources are not valid anymore
+                    #[cfg(feature = "mirror")]
+                    self.clear_mirrors();
+                    #[cfg(feature = "listeners")]
+                    {
+                        self.listeners = Listeners::default();
+                    }
+                    self.custom.clear();
+                    $(
+                        #[cfg(feature = $feature)]
+                        self.[<numbers _ $u>].clear();
+                        #[cfg(feature = $feature)]
+                        self.[<numbers_option_ $u>].clear();
+                    )*
+                    #[cfg(feature = "bool")]
+                    {
+                        self.bools.clear();
+                        self.option_bools.clear();
+                    }
+                    self.reset_stats();
+                }
+
+                /// Returns an independent manager starting from the current state of this one, but without its
+                /// saved levels: the current values are the root of the returned manager, which can not be
+                /// restored below them. The handles of this manager are valid for the fork. Unlike `clone()`,
+                /// the trail is not copied, so that the workers of a portfolio search can be spawned from a
+                /// manager deep in the search tree at the cost of a copy of its values.
+                ///
+                #[cfg_attr(feature = "usize", doc = "```")]
+                #[cfg_attr(not(feature = "usize"), doc = "```ignore")]
+                /// use search_trail::{StateManager, SaveAndRestore, UsizeManager};
+                ///
+                /// let mut mgr = StateManager::default();
+                /// let n = mgr.manage_usize(0);
+                /// mgr.save_state();
+                /// mgr.set_usize(n, 3);
+                /// let mut worker = mgr.fork();
+                /// assert_eq!(0, worker.trail_len());
+                /// worker.save_state();
+                /// worker.set_usize(n, 5);
+                /// worker.restore_state();
+                /// assert_eq!(3, worker.get_usize(n));
+                /// mgr.restore_state();
+                /// assert_eq!(0, mgr.get_usize(n));
+                /// ```
+                pub fn fork(&self) -> Self {
+                    let mut fork = Self::new();
+                    fork.epsilon = self.epsilon;
+                    #[cfg(feature = "checked-handles")]
+                    {
+                        fork.tag = self.tag;
+                    }
+                    fork.names = self.names.clone();
+                    if self.tree.is_some() {
+                        fork.tree = Some(SearchTree::default());
+                    }
+                    fork.dirty = self.dirty.as_ref().map(DirtySet::fork);
+                    fork.lazies = self.lazies.clone();
+                    fork.free_slots = self.free_slots.as_ref().map(FreeSlots::fork);
+                    fork.custom = self.custom.fork();
+                    $(
+                        #[cfg(feature = $feature)]
+                        {
+                            fork.[<numbers _ $u>] = self.[<numbers _ $u>].fork();
+                            fork.[<numbers_option_ $u>] = self.[<numbers_option_ $u>].fork();
+                        }
+                    )*
+                    #[cfg(feature = "bool")]
+                    {
+                        fork.bools = self.bools.fork();
+                        fork.option_bools = self.option_bools.fork();
+                    }
+                    fork
+                }
+
+                /// Returns the number of values currently saved on the trail, for all the managed types. The
+                /// booleans are counted by words. The backends without a trail always return 0.
+                pub fn trail_len(&self) -> usize {
+                    let mut len = self.custom.trail_len();
+                    $(
+                        #[cfg(feature = $feature)]
+                        {
+                            len += self.[<numbers _ $u>].trail_len() + self.[<numbers_option_ $u>].trail_len();
+                        }
+                    )*
+                    #[cfg(feature = "bool")]
+                    {
+                        len += self.bools.words.trail_len() + self.option_bools.words.trail_len();
+                    }
+                    len
+                }
+
+                /// Returns the memory used by the manager, detailed for the levels and the store of each
+                /// managed type
+                pub fn memory_usage(&self) -> MemoryReport {
+                    let mut report = MemoryReport {
+                        levels: memory::bytes::<Level>(self.levels.capacity()),
+                        stores: vec![],
+                    };
+                    $(
+                        #[cfg(feature = $feature)]
+                        {
+                            report.stores.push((stringify!($u), self.[<numbers _ $u>].memory_usage()));
+                            report.stores.push((concat!("Option<", stringify!($u), ">"), self.[<numbers_option_ $u>].memory_usage()));
+                        }
+                    )*
+                    #[cfg(feature = "bool")]
+                    {
+                        report.stores.push(("bool", self.bools.words.memory_usage()));
+                        report.stores.push(("Option<bool>", self.option_bools.words.memory_usage()));
+                    }
+                    report
+                }
+
+                /// Returns the number of entries the given resource has pushed on the trail. The booleans
+                /// are packed in words, and the entries are counted for the word holding the boolean.
+                #[cfg(feature = "instrument")]
+                pub fn trail_count(&self, handle: impl Into<AnyHandle>) -> u64 {
+                    match handle.into() {
+                        $(
+                            #[cfg(feature = $feature)]
+                            AnyHandle::[<$u:camel>](h) => self.[<numbers _ $u>].trail_counts().get(h.0).copied().unwrap_or(0),
+                            #[cfg(feature = $feature)]
+                            AnyHandle::[<Option $u:camel>](h) => self.[<numbers_option_ $u>].trail_counts().get(h.0).copied().unwrap_or(0),
+                        )*
+                        #[cfg(feature = "bool")]
+                        AnyHandle::Bool(h) => self.bools.words.trail_counts().get(h.0 / Bools::<B::Store<u64>>::PER_WORD).copied().unwrap_or(0),
+                        #[cfg(feature = "bool")]
+                        AnyHandle::OptionBool(h) => self.option_bools.words.trail_counts().get(h.0 / OptionBools::<B::Store<u64>>::PER_WORD).copied().unwrap_or(0),
                     }
                 }
-            }
 
-            pub trait SaveAndRestore {
-                /// Saves the current state of all managed resources
-                fn save_state(&mut self);
+                /// Returns the (at most) `k` resources that have pushed the most entries on the trail, with
+                /// their number of entries, from the hottest to the coldest. Resources that have never been
+                /// trailed are not reported. A word of packed booleans is reported with the handle of its
+                /// first boolean.
+                #[cfg(feature = "instrument")]
+                pub fn hottest(&self, k: usize) -> Vec<(AnyHandle, u64)> {
+                    let mut counts: Vec<(AnyHandle, u64)> = vec![];
+                    $(
+                        #[cfg(feature = $feature)]
+                        {
+                            let trailed = self.[<numbers _ $u>].trail_counts().iter().copied().enumerate();
+                            counts.extend(trailed.filter(|(_, c)| *c > 0).map(|(i, c)| (AnyHandle::[<$u:camel>]([<Reversible $u:camel>]::new(self, i)), c)));
+                            let trailed = self.[<numbers_option_ $u>].trail_counts().iter().copied().enumerate();
+                            counts.extend(trailed.filter(|(_, c)| *c > 0).map(|(i, c)| (AnyHandle::[<Option $u:camel>]([<ReversibleOption $u:camel>]::new(self, i)), c)));
+                        }
+                    )*
+                    #[cfg(feature = "bool")]
+                    {
+                        let per_word = Bools::<B::Store<u64>>::PER_WORD;
+                        let trailed = self.bools.words.trail_counts().iter().copied().enumerate();
+                        counts.extend(trailed.filter(|(_, c)| *c > 0).map(|(i, c)| (AnyHandle::Bool(ReversibleBool::new(self, i * per_word)), c)));
+                        let per_word = OptionBools::<B::Store<u64>>::PER_WORD;
+                        let trailed = self.option_bools.words.trail_counts().iter().copied().enumerate();
+                        counts.extend(trailed.filter(|(_, c)| *c > 0).map(|(i, c)| (AnyHandle::OptionBool(ReversibleOptionBool::new(self, i * per_word)), c)));
+                    }
+                    counts.sort_by(|a, b| b.1.cmp(&a.1));
+                    counts.truncate(k);
+                    counts
+                }
+
+                /// Checks the internal invariants of the manager and of its stores, and panics with a
+                /// description of the first one that is violated. With the `paranoid` feature, this is done
+                /// after every modification of the manager.
+                pub fn debug_validate(&self) {
+                    assert!(!self.levels.is_empty(), "The root level has been removed");
+                    for pair in self.levels.windows(2) {
+                        assert!(pair[0].clock < pair[1].clock, "The clocks of the levels are not increasing: {:?}", pair);
+                    }
+                    assert_eq!(self.clock, self.levels.last().unwrap().clock, "The clock is not the clock of the last level");
+                    assert!(self.clock <= self.max_clock, "The clock {} is greater than the last given clock {}", self.clock, self.max_clock);
+                    let depth = self.levels.len() - 1 + self.levels.iter().map(|level| level.empties).sum::<usize>();
+                    assert_eq!(depth, self.depth, "The depth does not match the saved levels");
+                    self.custom.debug_validate(self.clock);
+                    $(
+                        #[cfg(feature = $feature)]
+                        {
+                            self.[<numbers _ $u>].debug_validate(self.clock);
+                            self.[<numbers_option_ $u>].debug_validate(self.clock);
+                        }
+                    )*
+                    #[cfg(feature = "bool")]
+                    {
+                        self.bools.words.debug_validate(self.clock);
+                        self.option_bools.words.debug_validate(self.clock);
+                    }
+                }
+
+                /// Validates the manager with the `paranoid` feature, does nothing otherwise
+                #[inline]
+                fn paranoid_check(&self) {
+                    #[cfg(feature = "paranoid")]
+                    {
+                        self.debug_validate();
+                        self.check_state_hash();
+                    }
+                }
+
+                /// Returns the number of values restored by the restoration of the level identified by `clock`
+                #[cfg(feature = "rayon")]
+                fn restore_size(&self, clock: usize) -> usize {
+                    let mut size = 0;
+                    $(
+                        #[cfg(feature = $feature)]
+                        {
+                            size += self.[<numbers _ $u>].restore_size(clock);
+                            size += self.[<numbers_option_ $u>].restore_size(clock);
+                        }
+                    )*
+                    #[cfg(feature = "bool")]
+                    {
+                        size += self.bools.words.restore_size(clock);
+                        size += self.option_bools.words.restore_size(clock);
+                    }
+                    size
+                }
+            }
 
-                /// Restores the previous state of all managed resources
-                fn restore_state(&mut self);
+            impl Default for StateManager<Trailing> {
+                fn default() -> Self {
+                    Self::new()
+                }
             }
 
-            impl SaveAndRestore for StateManager {
+            impl<B: TrailBackend> SaveAndRestore for StateManager<B> {
                 fn save_state(&mut self) {
-                    // Increment the clock of the state manager. After this, every managed resource will become
+                    debug_assert!(!self.batch, "Can not save the state during a batch of writes");
+                    #[cfg(feature = "tracing")]
+                    let _span = tracing::trace_span!("save_state", depth = self.depth + 1).entered();
+                    self.count_stats(true);
+                    // The level is only materialized when a resource is modified in it
+                    self.depth += 1;
+                    self.levels.last_mut().unwrap().empties += 1;
+                    self.record_save();
+                    self.save_dirty();
+                    self.save_frozen();
+                    self.save_free_slots();
+                    self.save_state_hash();
+                    #[cfg(feature = "journal")]
+                    self.journal_push(|| JournalEntry::SaveState);
+                    #[cfg(feature = "metrics")]
+                    self.publish_save();
+                    #[cfg(feature = "mirror")]
+                    self.refresh_mirrors();
+                    self.run_save_hooks();
+                    self.paranoid_check();
+                }
+
+                fn restore_state(&mut self) {
+                    debug_assert!(!self.batch, "Can not restore the state during a batch of writes");
+                    #[cfg(feature = "tracing")]
+                    let _span = tracing::trace_span!("restore_state", depth = self.depth).entered();
+                    #[cfg(feature = "metrics")]
+                    self.publish_trailed();
+                    self.count_stats(false);
+                    self.restore_level();
+                    self.restore_labels();
+                    self.record_restore();
+                    self.restore_dirty();
+                    self.restore_frozen();
+                    self.restore_free_slots();
+                    self.restore_state_hash();
+                    #[cfg(feature = "journal")]
+                    self.journal_push(|| JournalEntry::RestoreState);
+                    #[cfg(feature = "metrics")]
+                    self.publish_restore();
+                    #[cfg(feature = "mirror")]
+                    self.refresh_mirrors();
+                    self.run_restore_hooks();
+                    self.paranoid_check();
+                }
+            }
+
+            impl<B: TrailBackend> StateManager<B> {
+                /// Restores the most recent level
+                fn restore_level(&mut self) {
+                    debug_assert!(self.depth > 0);
+                    self.depth = self.depth.saturating_sub(1);
+                    let top = self.levels.last_mut().unwrap();
+                    if top.empties > 0 {
+                        top.empties -= 1;
+                        return;
+                    }
+                    let level = self
+                        .levels
+                        .pop()
+                        .expect("Can not pop the root level of the state manager");
+                    self.clock = self.levels.last().map_or(0, |level| level.clock);
+                    self.custom.restore(level.clock);
+
+                    #[cfg(feature = "rayon")]
+                    if self.restore_size(level.clock) >= PARALLEL_RESTORE_THRESHOLD {
+                        // Each store restores its own values, so they can be processed in parallel
+                        rayon::scope(|s| {
+                            $(
+                                #[cfg(feature = $feature)]
+                                {
+                                    let store = &mut self.[<numbers _ $u>];
+                                    s.spawn(move |_| store.restore(level.clock));
+                                    let store = &mut self.[<numbers_option_ $u>];
+                                    s.spawn(move |_| store.restore(level.clock));
+                                }
+                            )*
+                            #[cfg(feature = "bool")]
+                            {
+                                let store = &mut self.bools.words;
+                                s.spawn(move |_| store.restore(level.clock));
+                                let store = &mut self.option_bools.words;
+                                s.spawn(move |_| store.restore(level.clock));
+                            }
+                        });
+                        return;
+                    }
+                    $(
+                        #[cfg(feature = $feature)]
+                        self.[<numbers _ $u>].restore(level.clock);
+                        #[cfg(feature = $feature)]
+                        self.[<numbers_option_ $u>].restore(level.clock);
+                    )*
+                    #[cfg(feature = "bool")]
+                    {
+                        self.bools.words.restore(level.clock);
+                        self.option_bools.words.restore(level.clock);
+                    }
+                }
+            }
+
+            /// A type whose values are stored in the state manager. This links the type to the field of the
+            /// manager holding its store.
+            #[cfg(numbers)]
+            trait Managed: Negligible + Fingerprint + PartialEq + std::fmt::Debug + Send + Sync {
+                fn store<B: TrailBackend>(mgr: &StateManager<B>) -> &B::Store<Self>;
+                fn store_mut<B: TrailBackend>(mgr: &mut StateManager<B>) -> &mut B::Store<Self>;
+                /// Wraps the value in a `Value`, to record it in a journal or in the dirty set
+                fn into_value(self) -> Value;
+                /// Returns the callbacks listening to the resources of this type
+                #[cfg(feature = "listeners")]
+                fn listeners<B: TrailBackend>(mgr: &mut StateManager<B>) -> &mut ListenerMap<Self>;
+                /// Returns the handle of the resource at the given index
+                fn handle<B: TrailBackend>(mgr: &StateManager<B>, id: usize) -> AnyHandle;
+            }
+
+            impl<B: TrailBackend> StateManager<B> {
+                /// Materializes the most recent level if it has been saved without being materialized yet,
+                /// so that the modifications done from now on are recorded in it
+                #[inline]
+                fn materialize(&mut self) {
+                    if self.levels.last().unwrap().empties > 0 {
+                        self.materialize_level();
+                    }
+                }
+
+                #[cold]
+                fn materialize_level(&mut self) {
+                    self.levels.last_mut().unwrap().empties -= 1;
+                    // Give a fresh clock to the new level. After this, every managed resource will become
                     // "invalid" and will need to be stored on the trail if changed
-                    self.clock += 1;
+                    self.max_clock += 1;
+                    self.clock = self.max_clock;
                     self.levels.push(Level {
-                        trail_size: self.trail.len(),
+                        clock: self.clock,
+                        empties: 0,
                     });
+                    let depth = self.depth - 1;
+                    self.custom.save(self.clock, depth);
+                    $(
+                        #[cfg(feature = $feature)]
+                        self.[<numbers _ $u>].save(self.clock, depth);
+                        #[cfg(feature = $feature)]
+                        self.[<numbers_option_ $u>].save(self.clock, depth);
+                    )*
+                    #[cfg(feature = "bool")]
+                    {
+                        self.bools.words.save(self.clock, depth);
+                        self.option_bools.words.save(self.clock, depth);
+                    }
                 }
+            }
 
-                    fn restore_state(&mut self) {
-                        debug_assert!(self.levels.len() > 1);
-                        let level = self
-                            .levels
-                            .pop()
-                            .expect("Can not pop the root level of the state manager");
-
-                        // Before the creation of the current level, the trail was `trail_size` long, so we skip
-                        // these first elements.
-                        for e in self.trail.iter().skip(level.trail_size).rev().copied() {
-                            match e {
-                                $(
-                                    TrailEntry::[<$u:camel Entry>](state) => self.[<numbers _ $u>][state.id.0] = state,
-                                    TrailEntry::[<Option $u:camel Entry>](state) => self.[<numbers_option_ $u>][state.id.0] = state,
-                                )*
-                            }
+            #[cfg(numbers)]
+            impl<B: TrailBackend> StateManager<B> {
+                /// Creates a new managed resource and returns its index in the corresponding vector
+                fn manage_value<T: Managed>(&mut self, value: T) -> usize {
+                    #[cfg(feature = "journal")]
+                    self.journal_push(|| JournalEntry::Manage(value.into_value()));
+                    self.materialize();
+                    let clock = self.clock;
+                    let id = match self.reuse_slot::<T>() {
+                        Some(id) => {
+                            let old = self.get_value::<T>(id);
+                            T::store_mut(self).set(clock, id, value);
+                            self.hash_value(id, Some(old), value);
+                            id
+                        }
+                        None => {
+                            let id = T::store_mut(self).manage(clock, value);
+                            self.hash_value(id, None, value);
+                            id
+                        }
+                    };
+                    self.paranoid_check();
+                    id
+                }
+
+                /// Returns the value of the resource at the given index
+                fn get_value<T: Managed>(&self, id: usize) -> T {
+                    self.check_released::<T>(id);
+                    T::store(self).get(id)
+                }
+
+                /// Sets the resource at the given index to the given value and returns the new value
+                fn set_value<T: Managed>(&mut self, id: usize, value: T) -> T {
+                    self.replace_value(id, value).1
+                }
+
+                /// Sets the resource at the given index to the given value and returns its previous value and
+                /// its new value
+                fn replace_value<T: Managed>(&mut self, id: usize, value: T) -> (T, T) {
+                    let current = self.get_value::<T>(id);
+                    self.write_value(id, current, value)
+                }
+
+                /// Same as `replace_value`, with the current value of the resource already read
+                fn write_value<T: Managed>(&mut self, id: usize, current: T, value: T) -> (T, T) {
+                    if current == value || T::negligible(current, value, self.epsilon) || self.is_frozen_value::<T>(id) {
+                        return (current, current);
+                    }
+                    self.materialize();
+                    #[cfg(feature = "journal")]
+                    self.journal_push(|| JournalEntry::Set { id, value: value.into_value() });
+                    self.trace_write(std::any::type_name::<T>(), id, &current, &value);
+                    let clock = self.clock;
+                    T::store_mut(self).set(clock, id, value);
+                    self.hash_value(id, Some(current), value);
+                    self.mark_dirty(id, current);
+                    self.invalidate_lazy::<T>(id);
+                    #[cfg(feature = "listeners")]
+                    self.notify(id, current, value);
+                    self.after_write();
+                    (current, value)
+                }
+
+                /// Sets the resources from the given index to the given values. Nothing is modified if all
+                /// the values are equal to (or within the epsilon of) the current ones.
+                fn set_values<T: Managed>(&mut self, first: usize, values: &[T]) {
+                    let store = T::store(self);
+                    let unchanged = values.iter().copied().enumerate().all(|(i, value)| {
+                        let current = store.get(first + i);
+                        current == value || T::negligible(current, value, self.epsilon)
+                    });
+                    if unchanged {
+                        return;
+                    }
+                    if self.frozen.is_some() && (first..first + values.len()).any(|id| self.is_frozen_value::<T>(id)) {
+                        // The frozen resources of the range are skipped
+                        for (i, &value) in values.iter().enumerate() {
+                            self.set_value(first + i, value);
                         }
-                        self.trail.truncate(level.trail_size);
+                        return;
+                    }
+                    self.materialize();
+                    #[cfg(feature = "journal")]
+                    self.journal_push(|| JournalEntry::SetRange { first, values: values.iter().map(|&value| value.into_value()).collect() });
+                    #[cfg(feature = "tracing")]
+                    tracing::trace!(resource = std::any::type_name::<T>(), first, len = values.len(), depth = self.depth, "write range");
+                    self.hash_range(first, values);
+                    self.mark_dirty_range(first, values);
+                    #[cfg(feature = "listeners")]
+                    let listened = self.listened_values::<T>(first, values.len());
+                    let clock = self.clock;
+                    T::store_mut(self).set_range(clock, first, values);
+                    self.invalidate_lazy_range::<T>(first, values.len());
+                    #[cfg(feature = "listeners")]
+                    self.notify_changes(listened);
+                    self.after_write();
+                }
+
+                /// Emits a tracing event for a write of a resource
+                #[inline]
+                #[allow(unused_variables)]
+                fn trace_write(&self, resource: &str, id: usize, old: &dyn std::fmt::Debug, new: &dyn std::fmt::Debug) {
+                    #[cfg(feature = "tracing")]
+                    tracing::trace!(resource, id, old = ?old, new = ?new, depth = self.depth, "write");
+                }
+
+                /// Returns an error if there is no resource of type `T` at the given index
+                fn check_handle<T: Managed>(&self, id: usize) -> Result<(), TrailError> {
+                    let len = T::store(self).len();
+                    if id < len {
+                        Ok(())
+                    } else {
+                        Err(TrailError::InvalidHandle { id, len })
+                    }
+                }
+
+                /// Same as `get_value`, but returns an error if the index is not valid
+                fn try_get_value<T: Managed>(&self, id: usize) -> Result<T, TrailError> {
+                    self.check_handle::<T>(id)?;
+                    Ok(self.get_value(id))
+                }
+
+                /// Same as `set_value`, but returns an error if the index is not valid
+                fn try_set_value<T: Managed>(&mut self, id: usize, value: T) -> Result<T, TrailError> {
+                    self.check_handle::<T>(id)?;
+                    if self.is_frozen_value::<T>(id) {
+                        return Err(TrailError::Frozen);
+                    }
+                    Ok(self.set_value(id, value))
+                }
+
+                /// Increments the value of the resource at the given index and returns the new value
+                fn increment_value<T: Managed + Step>(&mut self, id: usize) -> T {
+                    self.set_value(id, increment(self.get_value::<T>(id)))
+                }
+
+                /// Decrements the value of the resource at the given index and returns the new value
+                fn decrement_value<T: Managed + Step>(&mut self, id: usize) -> T {
+                    self.set_value(id, decrement(self.get_value::<T>(id)))
+                }
+
+                /// Increments the value of the optional resource at the given index and returns the new
+                /// value. Panic if the option is none.
+                fn increment_option_value<T: Step>(&mut self, id: usize) -> T
+                where
+                    Option<T>: Managed,
+                {
+                    let value = increment(self.get_value::<Option<T>>(id).unwrap());
+                    self.set_value(id, Some(value)).unwrap()
+                }
+
+                /// Decrements the value of the optional resource at the given index and returns the new
+                /// value. Panic if the option is none.
+                fn decrement_option_value<T: Step>(&mut self, id: usize) -> T
+                where
+                    Option<T>: Managed,
+                {
+                    let value = decrement(self.get_value::<Option<T>>(id).unwrap());
+                    self.set_value(id, Some(value)).unwrap()
+                }
+
+                /// Same as `get_value`, without bounds checking
+                ///
+                /// # Safety
+                ///
+                /// `id` must be the index of a managed resource
+                #[cfg(feature = "unchecked")]
+                unsafe fn get_value_unchecked<T: Managed>(&self, id: usize) -> T {
+                    T::store(self).get_unchecked(id)
+                }
+
+                /// Same as `set_value`, without bounds checking
+                ///
+                /// # Safety
+                ///
+                /// `id` must be the index of a managed resource
+                #[cfg(feature = "unchecked")]
+                unsafe fn set_value_unchecked<T: Managed>(&mut self, id: usize, value: T) -> T {
+                    let current = self.get_value_unchecked::<T>(id);
+                    if current == value || T::negligible(current, value, self.epsilon) || self.is_frozen_value::<T>(id) {
+                        return current;
                     }
+                    self.materialize();
+                    #[cfg(feature = "journal")]
+                    self.journal_push(|| JournalEntry::Set { id, value: value.into_value() });
+                    self.trace_write(std::any::type_name::<T>(), id, &current, &value);
+                    let clock = self.clock;
+                    T::store_mut(self).set_unchecked(clock, id, value);
+                    self.hash_value(id, Some(current), value);
+                    self.mark_dirty(id, current);
+                    self.invalidate_lazy::<T>(id);
+                    #[cfg(feature = "listeners")]
+                    self.notify(id, current, value);
+                    self.after_write();
+                    value
+                }
             }
 
         $(
+            #[cfg(feature = $feature)]
+            impl Managed for $u {
+                fn store<B: TrailBackend>(mgr: &StateManager<B>) -> &B::Store<Self> {
+                    &mgr.[<numbers _ $u>]
+                }
+                fn store_mut<B: TrailBackend>(mgr: &mut StateManager<B>) -> &mut B::Store<Self> {
+                    &mut mgr.[<numbers _ $u>]
+                }
+                fn into_value(self) -> Value {
+                    Value::[<$u:camel>](self)
+                }
+                #[cfg(feature = "listeners")]
+                fn listeners<B: TrailBackend>(mgr: &mut StateManager<B>) -> &mut ListenerMap<Self> {
+                    &mut mgr.listeners.[<numbers _ $u>]
+                }
+                fn handle<B: TrailBackend>(mgr: &StateManager<B>, id: usize) -> AnyHandle {
+                    AnyHandle::[<$u:camel>]([<Reversible $u:camel>]::new(mgr, id))
+                }
+            }
+
+            #[cfg(feature = $feature)]
+            impl Managed for Option<$u> {
+                fn store<B: TrailBackend>(mgr: &StateManager<B>) -> &B::Store<Self> {
+                    &mgr.[<numbers_option_ $u>]
+                }
+                fn store_mut<B: TrailBackend>(mgr: &mut StateManager<B>) -> &mut B::Store<Self> {
+                    &mut mgr.[<numbers_option_ $u>]
+                }
+                fn into_value(self) -> Value {
+                    Value::[<Option $u:camel>](self)
+                }
+                #[cfg(feature = "listeners")]
+                fn listeners<B: TrailBackend>(mgr: &mut StateManager<B>) -> &mut ListenerMap<Self> {
+                    &mut mgr.listeners.[<numbers_option_ $u>]
+                }
+                fn handle<B: TrailBackend>(mgr: &StateManager<B>, id: usize) -> AnyHandle {
+                    AnyHandle::[<Option $u:camel>]([<ReversibleOption $u:camel>]::new(mgr, id))
+                }
+            }
+
             // Can not use format!() in this doc
+            #[cfg(feature = $feature)]
             #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+            #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+            #[cfg_attr(feature = "fuzz", derive(arbitrary::Arbitrary))]
             #[doc="An index of the managed resource type"]
-            pub struct [<Reversible $u:camel>](usize);
-
-            #[doc="A state for the managed resource type"]
-            #[derive(Debug, Clone, Copy)]
-            struct [<State $u:camel>] {
-                #[doc="Index of the resource in the asociated vector in the trail"]
-                id: [<Reversible $u:camel>],
-                #[doc="Clock of the resource. If less than the clock of the manager, the data needs to be saved on the trail if modified"]
-                clock: usize,
-                #[doc="The value of the managed resource"]
-                value: $u,
-            }
+            pub struct [<Reversible $u:camel>](usize, #[cfg(feature = "checked-handles")] HandleTag);
+
+            #[cfg(feature = $feature)]
+            tagged_handle!([<Reversible $u:camel>]);
 
+            #[cfg(feature = $feature)]
             #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+            #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+            #[cfg_attr(feature = "fuzz", derive(arbitrary::Arbitrary))]
             #[doc="An index of the managed resource type"]
-            pub struct [<Reversible Option $u:camel>](usize);
-
-            #[doc="A state for the managed resource type"]
-            #[derive(Debug, Clone, Copy)]
-            struct [<StateOption $u:camel>] {
-                #[doc="Index of the resource in the asociated vector in the trail"]
-                id: [<ReversibleOption $u:camel>],
-                #[doc="Clock of the resource. If less than the clock of the manager, the data needs to be saved on the trail if modified"]
-                clock: usize,
-                #[doc="The value of the managed resource"]
-                value: Option<$u>,
+            pub struct [<Reversible Option $u:camel>](usize, #[cfg(feature = "checked-handles")] HandleTag);
+
+            #[cfg(feature = $feature)]
+            tagged_handle!([<Reversible Option $u:camel>]);
+
+            #[cfg(feature = $feature)]
+            impl From<[<Reversible $u:camel>]> for AnyHandle {
+                fn from(handle: [<Reversible $u:camel>]) -> Self {
+                    AnyHandle::[<$u:camel>](handle)
+                }
+            }
+
+            #[cfg(feature = $feature)]
+            impl From<[<ReversibleOption $u:camel>]> for AnyHandle {
+                fn from(handle: [<ReversibleOption $u:camel>]) -> Self {
+                    AnyHandle::[<Option $u:camel>](handle)
+                }
             }
 
+            #[cfg(feature = $feature)]
             #[doc="Trait that define what operation can be done on the managed resource type"]
+            #[deprecated(note = "use the generic `manage`, `get` and `set` of `StateManager` with a `Reversible<T>` handle")]
             pub trait [<$u:camel Manager>] {
                 #[doc="Creates a new managed ressource.Returns the index of the resource in the corresponding vector"]
                 fn [<manage _ $u>](&mut self, value: $u) -> [<Reversible $u:camel>];
@@ -188,13 +1221,35 @@ macro_rules! manage_numbers {
                 fn [<get _ $u>](&self, id: [<Reversible $u:camel>]) -> $u;
                 #[doc="Sets the resource at the given index to the given value and returns the new value"]
                 fn [<set _ $u>](&mut self, id: [<Reversible $u:camel>], value: $u) -> $u;
-                #[doc="Increments the value of the resource at the given index and returns the new value"]
+                #[doc="Sets the resources created consecutively from the given one to the given values. Their previous values are saved at once."]
+                fn [<set _ $u _slice>](&mut self, first: [<Reversible $u:camel>], values: &[$u]);
+                #[doc="Returns the value of the resource at the given index, or an error if the handle is not valid"]
+                fn [<try_get _ $u>](&self, id: [<Reversible $u:camel>]) -> Result<$u, TrailError>;
+                #[doc="Sets the resource at the given index to the given value and returns the new value, or an error if the handle is not valid"]
+                fn [<try_set _ $u>](&mut self, id: [<Reversible $u:camel>], value: $u) -> Result<$u, TrailError>;
+                #[doc="Increments the value of the resource at the given index and returns the new value. On overflow, panics in debug builds or with the `strict-arithmetic` feature, and wraps around otherwise."]
                 fn [<increment _ $u>](&mut self, id: [<Reversible $u:camel>]) -> $u;
-                #[doc="Decrements the value of the resource at the given index and returns the new value"]
+                #[doc="Decrements the value of the resource at the given index and returns the new value. On overflow, panics in debug builds or with the `strict-arithmetic` feature, and wraps around otherwise."]
                 fn [<decrement _ $u>](&mut self, id: [<Reversible $u:camel>]) -> $u;
+                #[cfg(feature = "unchecked")]
+                #[doc="Returns the value of the resource at the given index, without bounds checking"]
+                #[doc=""]
+                #[doc="# Safety"]
+                #[doc=""]
+                #[doc="The handle must have been returned by this manager"]
+                unsafe fn [<get _ $u _unchecked>](&self, id: [<Reversible $u:camel>]) -> $u;
+                #[cfg(feature = "unchecked")]
+                #[doc="Sets the resource at the given index to the given value and returns the new value, without bounds checking"]
+                #[doc=""]
+                #[doc="# Safety"]
+                #[doc=""]
+                #[doc="The handle must have been returned by this manager"]
+                unsafe fn [<set _ $u _unchecked>](&mut self, id: [<Reversible $u:camel>], value: $u) -> $u;
             }
 
+            #[cfg(feature = $feature)]
             #[doc="Trait that define what operation can be done on the managed resource type"]
+            #[deprecated(note = "use the generic `manage`, `get` and `set` of `StateManager` with a `Reversible<T>` handle")]
             pub trait [<Option $u:camel Manager>] {
                 #[doc="Creates a new managed {}.Returns the index of the resource in the corresponding vector"]
                 fn [<manage _ option _ $u>](&mut self, value: Option<$u>) -> [<Reversible Option $u:camel>];
@@ -210,107 +1265,120 @@ macro_rules! manage_numbers {
                 }
                 #[doc="Sets the resource at the given index to the given value and returns the new value"]
                 fn [<set _ option _ $u>](&mut self, id: [<Reversible Option $u:camel>], value: Option<$u>) -> Option<$u>;
+                #[doc="Returns the value of the resource at the given index, or an error if the handle is not valid"]
+                fn [<try_get _ option _ $u>](&self, id: [<Reversible Option $u:camel>]) -> Result<Option<$u>, TrailError>;
+                #[doc="Sets the resource at the given index to the given value and returns the new value, or an error if the handle is not valid"]
+                fn [<try_set _ option _ $u>](&mut self, id: [<Reversible Option $u:camel>], value: Option<$u>) -> Result<Option<$u>, TrailError>;
                 #[doc="Increments the value of the resource at the given index and returns the new value. Panic if the option is none."]
                 fn [<increment _ option _ $u>](&mut self, id: [<Reversible Option $u:camel>]) -> $u;
                 #[doc="Decrements the value of the resource at the given index and returns the new value. Panic if the option is none."]
                 fn [<decrement _ option _ $u>](&mut self, id: [<Reversible Option $u:camel>]) -> $u;
             }
 
-            impl [<$u:camel Manager>] for StateManager {
+            #[cfg(feature = $feature)]
+            impl<B: TrailBackend> [<$u:camel Manager>] for StateManager<B> {
                 fn [<manage _ $u>](&mut self, value: $u) -> [<Reversible $u:camel>] {
-                    let id = [<Reversible $u:camel>](self.[<numbers _ $u>].len());
-                    self.[<numbers _ $u>].push([<State $u:camel>]{
-                        id,
-                        clock: self.clock,
-                        value,
-                    });
-                    id
+                    let id = self.manage_value(value);
+                    [<Reversible $u:camel>]::new(self, id)
                 }
+
                 fn [<get _ $u>](&self, id: [<Reversible $u:camel>]) -> $u {
-                    self.[<numbers _ $u>][id.0].value
+                    self.get_value(id.index(self))
                 }
+
                 fn [<set _ $u>](&mut self, id: [<Reversible $u:camel>], value: $u) -> $u {
-                    let curr = self.[<numbers _ $u>][id.0];
-                    if value != curr.value {
-                        if curr.clock < self.clock {
-                            self.trail.push(TrailEntry::[<$u:camel Entry>](curr));
-                            self.[<numbers _ $u>][id.0] = [<State $u:camel>] {
-                                id,
-                                clock: self.clock,
-                                value,
-                            };
-                        } else {
-                            self.[<numbers _ $u>][id.0].value = value;
-                        }
-                    }
-                    value
+                    self.set_value(id.index(self), value)
+                }
+
+                fn [<set _ $u _slice>](&mut self, first: [<Reversible $u:camel>], values: &[$u]) {
+                    self.set_values(first.index(self), values)
+                }
+
+                fn [<try_get _ $u>](&self, id: [<Reversible $u:camel>]) -> Result<$u, TrailError> {
+                    self.try_get_value(id.try_index(self)?)
+                }
+
+                fn [<try_set _ $u>](&mut self, id: [<Reversible $u:camel>], value: $u) -> Result<$u, TrailError> {
+                    self.try_set_value(id.try_index(self)?, value)
                 }
 
                 fn [<increment _ $u>](&mut self, id: [<Reversible $u:camel>]) -> $u {
-                    self.[<set _ $u>](id, self.[<get _ $u>](id) + 1 as $u)
+                    self.increment_value::<$u>(id.index(self))
                 }
 
                 fn [<decrement _ $u>](&mut self, id: [<Reversible $u:camel>]) -> $u {
-                    self.[<set _ $u>](id, self.[<get _ $u>](id) - 1 as $u)
+                    self.decrement_value::<$u>(id.index(self))
+                }
+
+                #[cfg(feature = "unchecked")]
+                unsafe fn [<get _ $u _unchecked>](&self, id: [<Reversible $u:camel>]) -> $u {
+                    self.get_value_unchecked(id.0)
+                }
+
+                #[cfg(feature = "unchecked")]
+                unsafe fn [<set _ $u _unchecked>](&mut self, id: [<Reversible $u:camel>], value: $u) -> $u {
+                    self.set_value_unchecked(id.0, value)
                 }
             }
 
-            impl [<Option $u:camel Manager>] for StateManager {
+            #[cfg(feature = $feature)]
+            impl<B: TrailBackend> [<Option $u:camel Manager>] for StateManager<B> {
                 fn [<manage_option_ $u>](&mut self, value: Option<$u>) -> [<ReversibleOption $u:camel>] {
-                    let id = [<ReversibleOption $u:camel>](self.[<numbers_option_ $u>].len());
-                    self.[<numbers_option_ $u>].push([<StateOption $u:camel>]{
-                        id,
-                        clock: self.clock,
-                        value,
-                    });
-                    id
+                    let id = self.manage_value(value);
+                    [<ReversibleOption $u:camel>]::new(self, id)
                 }
 
                 fn [<get_option_ $u>](&self, id: [<ReversibleOption $u:camel>]) -> Option<$u> {
-                    self.[<numbers_option_ $u>][id.0].value
+                    self.get_value(id.index(self))
                 }
 
                 fn [<set_option_ $u>](&mut self, id: [<ReversibleOption $u:camel>], value: Option<$u>) -> Option<$u> {
-                    let curr = self.[<numbers_option_ $u>][id.0];
-                    if value != curr.value {
-                        if curr.clock < self.clock {
-                            self.trail.push(TrailEntry::[<Option $u:camel Entry>](curr));
-                            self.[<numbers_option_ $u>][id.0] = [<StateOption $u:camel>] {
-                                id,
-                                clock: self.clock,
-                                value,
-                            };
-                        } else {
-                            self.[<numbers_option_ $u>][id.0].value = value;
-                        }
-                    }
-                    value
+                    self.set_value(id.index(self), value)
+                }
+
+                fn [<try_get_option_ $u>](&self, id: [<ReversibleOption $u:camel>]) -> Result<Option<$u>, TrailError> {
+                    self.try_get_value(id.try_index(self)?)
+                }
+
+                fn [<try_set_option_ $u>](&mut self, id: [<ReversibleOption $u:camel>], value: Option<$u>) -> Result<Option<$u>, TrailError> {
+                    self.try_set_value(id.try_index(self)?, value)
                 }
 
                 fn [<increment_option_ $u>](&mut self, id: [<ReversibleOption $u:camel>]) -> $u {
-                    let value = self.[<numbers_option_ $u>][id.0].value.unwrap();
-                    self.[<set_option_ $u>](id, Some(value + 1 as $u));
-                    value + 1 as $u
+                    self.increment_option_value::<$u>(id.index(self))
                 }
 
                 fn [<decrement_option_ $u>](&mut self, id: [<ReversibleOption $u:camel>]) -> $u {
-                    let value = self.[<numbers_option_ $u>][id.0].value.unwrap();
-                    self.[<set_option_ $u>](id, Some(value -1 as $u));
-                    value - 1 as $u
+                    self.decrement_option_value::<$u>(id.index(self))
                 }
             }
 
+            #[cfg(feature = $feature)]
             #[cfg(test)]
             mod [<test _ $u>] {
 
-                use crate::{StateManager, SaveAndRestore,[<$u:camel Manager>], [<Reversible $u:camel>]};
+                use crate::{StateManager, SaveAndRestore, TrailError, [<$u:camel Manager>], [<Option $u:camel Manager>], [<Reversible $u:camel>], [<ReversibleOption $u:camel>]};
+
+                #[test]
+                fn invalid_handles_are_reported() {
+                    let mut mgr = StateManager::default();
+                    let n = mgr.[<manage _ $u>](3 as $u);
+                    assert_eq!(Ok(3 as $u), mgr.[<try_get _ $u>](n));
+                    assert_eq!(Ok(4 as $u), mgr.[<try_set _ $u>](n, 4 as $u));
+                    let invalid = Err(TrailError::InvalidHandle { id: 1, len: 1 });
+                    assert_eq!(invalid, mgr.[<try_get _ $u>]([<Reversible $u:camel>]::new(&mgr, 1)));
+                    assert_eq!(invalid, mgr.[<try_set _ $u>]([<Reversible $u:camel>]::new(&mgr, 1), 0 as $u));
+                    let invalid = Err(TrailError::InvalidHandle { id: 0, len: 0 });
+                    assert_eq!(invalid, mgr.[<try_get_option _ $u>]([<ReversibleOption $u:camel>]::new(&mgr, 0)));
+                    assert_eq!(invalid, mgr.[<try_set_option _ $u>]([<ReversibleOption $u:camel>]::new(&mgr, 0), None));
+                }
 
                 #[test]
                 fn manager_return_values() {
                     let mut mgr = StateManager::default();
                     let values: Vec<[<Reversible $u:camel>]> = (0..10).map(|i| mgr.[<manage _ $u>](i as $u)).collect();
                     for i in 0..10 {
-                        assert_eq!([<Reversible $u:camel>](i), values[i]);
+                        assert_eq!(i, values[i].0);
                         let x = mgr.[<set _ $u>](values[i], i as $u + 1 as $u);
                         assert_eq!(i as $u + 1 as $u, x);
                         assert_eq!(x + 1 as $u, mgr.[<increment _ $u>](values[i]));
@@ -394,38 +1462,106 @@ macro_rules! manage_numbers {
                     mgr.restore_state();
                     assert_eq!(30 as $u, mgr.[<get _ $u>](n));
                 }
+
+                #[test]
+                #[cfg(feature = "unchecked")]
+                fn unchecked_set_and_restore() {
+                    let mut mgr = StateManager::default();
+                    let n = mgr.[<manage _ $u>](10 as $u);
+                    unsafe {
+                        assert_eq!(10 as $u, mgr.[<get _ $u _unchecked>](n));
+                        mgr.save_state();
+                        assert_eq!(20 as $u, mgr.[<set _ $u _unchecked>](n, 20 as $u));
+                        assert_eq!(20 as $u, mgr.[<get _ $u _unchecked>](n));
+                        assert_eq!(23 as $u, mgr.[<set _ $u _unchecked>](n, 23 as $u));
+                        mgr.restore_state();
+                        assert_eq!(10 as $u, mgr.[<get _ $u _unchecked>](n));
+                    }
+                }
             }
         )*
     }
     }
 }
 
-manage_numbers! {
-    u8,
-    u16,
-    u32,
-    u64,
-    u128,
-    usize,
-    i8,
-    i16,
-    i32,
-    i64,
-    i128,
-    isize,
-    f32,
-    f64
-}
+for_each_managed_type!(manage_numbers);
 
-/// Index for a managed bool. Note that this only redirect towards a managed usize
+#[cfg(feature = "bool")]
+/// Index for a managed bool. The booleans are packed in managed words of 64 bits
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-pub struct ReversibleBool(ReversibleUsize);
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "fuzz", derive(arbitrary::Arbitrary))]
+pub struct ReversibleBool(usize, #[cfg(feature = "checked-handles")] HandleTag);
+
+#[cfg(feature = "bool")]
+tagged_handle!(ReversibleBool);
 
-/// Index for a managed optional bool. Note that this only redirect towards a managed usize
+#[cfg(feature = "bool")]
+/// Index for a managed optional bool. The optional booleans are packed in managed words of 64 bits
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-pub struct ReversibleOptionBool(ReversibleOptionUsize);
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "fuzz", derive(arbitrary::Arbitrary))]
+pub struct ReversibleOptionBool(usize, #[cfg(feature = "checked-handles")] HandleTag);
 
+#[cfg(feature = "bool")]
+tagged_handle!(ReversibleOptionBool);
+
+#[cfg(feature = "bool")]
+impl From<ReversibleBool> for AnyHandle {
+    fn from(handle: ReversibleBool) -> Self {
+        AnyHandle::Bool(handle)
+    }
+}
+
+#[cfg(feature = "bool")]
+impl From<ReversibleOptionBool> for AnyHandle {
+    fn from(handle: ReversibleOptionBool) -> Self {
+        AnyHandle::OptionBool(handle)
+    }
+}
+
+#[cfg(feature = "bool")]
+impl<B: TrailBackend> StateManager<B> {
+    /// Sets the given word of the packed booleans (or of the packed optional booleans if `option` is
+    /// true) to the given value
+    fn set_bool_word(&mut self, option: bool, word: usize, value: u64) {
+        self.materialize();
+        #[cfg(feature = "journal")]
+        self.journal_push(|| JournalEntry::SetBoolWord { option, word, value });
+        #[cfg(feature = "tracing")]
+        {
+            let (resource, old) = if option { ("Option<bool> word", self.option_bools.words.get(word)) } else { ("bool word", self.bools.words.get(word)) };
+            self.trace_write(resource, word, &old, &value);
+        }
+        let old = if option { self.option_bools.words.get(word) } else { self.bools.words.get(word) };
+        let clock = self.clock;
+        if option {
+            self.option_bools.words.set(clock, word, value);
+        } else {
+            self.bools.words.set(clock, word, value);
+        }
+        self.hash_bool_word(option, word, old, value);
+        self.mark_dirty_bools(option, word, old, value);
+        self.invalidate_lazy_bools(option, word, old, value);
+        #[cfg(feature = "listeners")]
+        self.notify_bool_word(option, word, old, value);
+        self.after_write();
+    }
+
+    /// Encodes an optional boolean in the bits of a packed optional boolean
+    fn option_bool_bits(value: Option<bool>) -> u64 {
+        value.map_or(0, |b| 1 | (b as u64) << 1)
+    }
+
+    /// Decodes the bits of a packed optional boolean
+    fn option_bool_value(bits: u64) -> Option<bool> {
+        (bits & 1 != 0).then_some(bits & 2 != 0)
+    }
+}
+
+#[cfg(feature = "bool")]
 /// Trait that define the operation that can be done on a managed boolean.
+#[deprecated(note = "use the generic `manage`, `get` and `set` of `StateManager` with a `Reversible<T>` handle")]
 pub trait BoolManager {
     /// Creates a new managed boolean
     fn manage_bool(&mut self, value: bool) -> ReversibleBool;
@@ -439,27 +1575,16 @@ pub trait BoolManager {
     }
 }
 
-impl BoolManager for StateManager {
-    fn manage_bool(&mut self, value: bool) -> ReversibleBool {
-        ReversibleBool(self.manage_usize(value as usize))
-    }
-
-    fn get_bool(&self, id: ReversibleBool) -> bool {
-        self.get_usize(id.0) != 0
-    }
-
-    fn set_bool(&mut self, id: ReversibleBool, value: bool) -> bool {
-        self.set_usize(id.0, value as usize) != 0
-    }
-}
-
+#[cfg(feature = "bool")]
 /// Trait that define the operation that can be done on a managed boolean.
+#[deprecated(note = "use the generic `manage`, `get` and `set` of `StateManager` with a `Reversible<T>` handle")]
 pub trait OptionBoolManager {
     /// Creates a new managed boolean
     fn manage_option_bool(&mut self, value: Option<bool>) -> ReversibleOptionBool;
     /// Returns the value of a managed boolean
     fn get_option_bool(&self, id: ReversibleOptionBool) -> Option<bool>;
-    /// Sets the value of a managed boolean to the given value and returns the new value
+    /// Sets the value of a managed boolean to the given value and returns the new value. A frozen boolean
+    /// is not modified, and its stored value (false if it is None) is returned.
     fn set_option_bool(&mut self, id: ReversibleOptionBool, value: bool) -> bool;
     /// Sets the value of a managed boolean to None
     fn set_option_bool_none(&mut self, id: ReversibleOptionBool);
@@ -479,34 +1604,70 @@ pub trait OptionBoolManager {
     }
 }
 
-impl OptionBoolManager for StateManager {
-    fn manage_option_bool(&mut self, value: Option<bool>) -> ReversibleOptionBool {
-        if let Some(b) = value {
-            ReversibleOptionBool(self.manage_option_usize(Some(b as usize)))
-        } else {
-            ReversibleOptionBool(self.manage_option_usize(None))
+#[cfg(feature = "bool")]
+impl<B: TrailBackend> BoolManager for StateManager<B> {
+    fn manage_bool(&mut self, value: bool) -> ReversibleBool {
+        #[cfg(feature = "journal")]
+        self.journal_push(|| JournalEntry::Manage(Value::Bool(value)));
+        self.materialize();
+        let id = self.bools.manage(self.clock, value as u64);
+        self.hash_flag(false, id, None, value as u64);
+        self.paranoid_check();
+        ReversibleBool::new(self, id)
+    }
+
+    fn get_bool(&self, id: ReversibleBool) -> bool {
+        self.bools.get(id.index(self)) != 0
+    }
+
+    fn set_bool(&mut self, id: ReversibleBool, value: bool) -> bool {
+        if self.frozen.is_some() && self.is_frozen(id) {
+            return self.get_bool(id);
         }
+        if let Some((word, new_value)) = self.bools.replace(id.index(self), value as u64) {
+            self.set_bool_word(false, word, new_value);
+        }
+        value
+    }
+}
+
+#[cfg(feature = "bool")]
+impl<B: TrailBackend> OptionBoolManager for StateManager<B> {
+    fn manage_option_bool(&mut self, value: Option<bool>) -> ReversibleOptionBool {
+        #[cfg(feature = "journal")]
+        self.journal_push(|| JournalEntry::Manage(Value::OptionBool(value)));
+        self.materialize();
+        let id = self.option_bools.manage(self.clock, Self::option_bool_bits(value));
+        self.hash_flag(true, id, None, Self::option_bool_bits(value));
+        self.paranoid_check();
+        ReversibleOptionBool::new(self, id)
     }
 
     fn get_option_bool(&self, id: ReversibleOptionBool) -> Option<bool> {
-        if let Some(v) = self.get_option_usize(id.0) {
-            Some(v != 0)
-        } else {
-            None
-        }
+        Self::option_bool_value(self.option_bools.get(id.index(self)))
     }
 
     fn set_option_bool(&mut self, id: ReversibleOptionBool, value: bool) -> bool {
-        self.set_option_usize(id.0, Some(value as usize));
+        if self.frozen.is_some() && self.is_frozen(id) {
+            return self.get_option_bool(id).unwrap_or(false);
+        }
+        if let Some((word, new_value)) = self.option_bools.replace(id.index(self), Self::option_bool_bits(Some(value))) {
+            self.set_bool_word(true, word, new_value);
+        }
         value
     }
 
     fn set_option_bool_none(&mut self, id: ReversibleOptionBool) {
-        self.set_option_usize(id.0, None);
+        if self.frozen.is_some() && self.is_frozen(id) {
+            return;
+        }
+        if let Some((word, new_value)) = self.option_bools.replace(id.index(self), Self::option_bool_bits(None)) {
+            self.set_bool_word(true, word, new_value);
+        }
     }
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "bool"))]
 mod test_manager {
     use crate::{BoolManager, SaveAndRestore, StateManager};
 
@@ -524,7 +1685,7 @@ mod test_manager {
     }
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "bool"))]
 mod test_manager_bool {
 
     use crate::{BoolManager, SaveAndRestore, StateManager};
@@ -557,3 +1718,497 @@ mod test_manager_bool {
         assert!(mgr.get_bool(a));
     }
 }
+
+#[cfg(all(test, feature = "usize", feature = "bool"))]
+mod test_fork {
+    use crate::*;
+
+    fn forks_from_current_values<B: TrailBackend>() {
+        let mut mgr = StateManager::<B>::new();
+        let n = mgr.manage_usize(0);
+        let o = mgr.manage_option_usize(None);
+        let b = mgr.manage_bool(false);
+        mgr.save_state();
+        mgr.set_usize(n, 1);
+        mgr.set_option_usize(o, Some(2));
+        mgr.set_bool(b, true);
+        mgr.save_state();
+        let c = mgr.manage_bool(true);
+
+        let mut fork = mgr.fork();
+        assert_eq!(0, fork.trail_len());
+        assert_eq!(1, fork.get_usize(n));
+        assert_eq!(Some(2), fork.get_option_usize(o));
+        assert!(fork.get_bool(b) && fork.get_bool(c));
+        fork.save_state();
+        fork.set_usize(n, 3);
+        fork.set_bool(c, false);
+        fork.restore_state();
+        assert_eq!(1, fork.get_usize(n));
+        assert!(fork.get_bool(c));
+        assert_eq!(0, fork.depth);
+
+        mgr.restore_state();
+        mgr.restore_state();
+        assert_eq!(0, mgr.get_usize(n));
+        assert_eq!(None, mgr.get_option_usize(o));
+        assert!(!mgr.get_bool(b));
+        assert_eq!(1, fork.get_usize(n));
+    }
+
+    #[test]
+    fn forks_all_backends() {
+        forks_from_current_values::<Trailing>();
+        forks_from_current_values::<Copying>();
+        forks_from_current_values::<Recomputing<2>>();
+        forks_from_current_values::<CopyOnWrite>();
+        #[cfg(feature = "persistent")]
+        forks_from_current_values::<Persistent>();
+    }
+}
+
+// Validating the manager after each of the large number of modifications would take too long
+#[cfg(all(test, feature = "rayon", feature = "usize", feature = "f64", not(feature = "paranoid")))]
+mod test_parallel_restore {
+    use crate::{F64Manager, OptionUsizeManager, SaveAndRestore, StateManager, UsizeManager, PARALLEL_RESTORE_THRESHOLD};
+
+    #[test]
+    fn large_level_is_restored() {
+        let mut mgr = StateManager::default();
+        let n = PARALLEL_RESTORE_THRESHOLD;
+        let a = (0..n).map(|i| mgr.manage_usize(i)).collect::<Vec<_>>();
+        let b = (0..n).map(|i| mgr.manage_f64(i as f64)).collect::<Vec<_>>();
+        let c = mgr.manage_option_usize(None);
+        mgr.save_state();
+        for i in (0..n).rev() {
+            mgr.set_usize(a[i], 0);
+            if i % 3 == 0 {
+                mgr.set_f64(b[i], -1.0);
+            }
+        }
+        mgr.set_option_usize(c, Some(3));
+        mgr.restore_state();
+        for i in 0..n {
+            assert_eq!(i, mgr.get_usize(a[i]));
+            assert_eq!(i as f64, mgr.get_f64(b[i]));
+        }
+        assert_eq!(None, mgr.get_option_usize(c));
+    }
+}
+
+#[cfg(all(test, feature = "usize"))]
+mod test_memory_usage {
+    use crate::{CopyingStateManager, SaveAndRestore, StateManager, UsizeManager};
+
+    #[test]
+    fn trail_grows_with_modifications() {
+        let mut mgr = StateManager::default();
+        let handles = (0..100).map(|i| mgr.manage_usize(i)).collect::<Vec<_>>();
+        let before = mgr.memory_usage();
+        assert!(before.store("usize").unwrap().values > 0);
+        assert_eq!(0, before.store("Option<usize>").unwrap().values);
+        mgr.save_state();
+        for h in handles.iter().copied() {
+            mgr.set_usize(h, 0);
+        }
+        let after = mgr.memory_usage();
+        assert!(after.trail() > before.trail());
+        assert!(after.total() > before.total());
+    }
+
+    #[test]
+    fn copies_are_reported() {
+        let mut mgr = CopyingStateManager::new();
+        (0..100).for_each(|i| {
+            mgr.manage_usize(i);
+        });
+        let before = mgr.memory_usage();
+        mgr.save_state();
+        mgr.set_usize(crate::ReversibleUsize::new(&mgr, 0), 42);
+        assert!(mgr.memory_usage().store("usize").unwrap().saved > before.store("usize").unwrap().saved);
+    }
+}
+
+#[cfg(all(test, feature = "instrument", feature = "usize", feature = "bool"))]
+mod test_instrument {
+    use crate::{AnyHandle, BoolManager, SaveAndRestore, StateManager, UsizeManager};
+
+    #[test]
+    fn hottest_resources() {
+        let mut mgr = StateManager::default();
+        let cold = mgr.manage_usize(0);
+        let hot = mgr.manage_usize(0);
+        let warm = mgr.manage_bool(false);
+        let never = mgr.manage_usize(0);
+        for i in 0..10 {
+            mgr.save_state();
+            mgr.set_usize(hot, i + 1);
+            mgr.set_usize(hot, i + 2);
+            if i % 2 == 0 {
+                mgr.flip_bool(warm);
+            }
+            if i == 0 {
+                mgr.set_usize(cold, 1);
+            }
+        }
+        assert_eq!(10, mgr.trail_count(hot));
+        assert_eq!(1, mgr.trail_count(cold));
+        assert_eq!(0, mgr.trail_count(never));
+        let hottest = mgr.hottest(2);
+        assert_eq!(vec![(AnyHandle::from(hot), 10), (AnyHandle::from(warm), 5)], hottest);
+        assert_eq!(3, mgr.hottest(10).len());
+    }
+}
+
+#[cfg(all(test, feature = "usize"))]
+mod test_empty_levels {
+    use crate::{CopyingStateManager, RecomputingStateManager, SaveAndRestore, StateManager, TrailBackend, UsizeManager};
+
+    #[test]
+    fn empty_levels_are_not_materialized() {
+        let mut mgr = StateManager::default();
+        let n = mgr.manage_usize(0);
+        for _ in 0..1000 {
+            mgr.save_state();
+        }
+        assert_eq!(1, mgr.levels.len());
+        mgr.set_usize(n, 0);
+        assert_eq!(1, mgr.levels.len());
+        mgr.set_usize(n, 1);
+        assert_eq!(2, mgr.levels.len());
+        for _ in 0..10 {
+            mgr.save_state();
+        }
+        mgr.set_usize(n, 2);
+        assert_eq!(3, mgr.levels.len());
+        for _ in 0..10 {
+            mgr.restore_state();
+        }
+        assert_eq!(1, mgr.get_usize(n));
+        mgr.restore_state();
+        assert_eq!(0, mgr.get_usize(n));
+        for _ in 0..999 {
+            mgr.restore_state();
+        }
+        assert_eq!(0, mgr.get_usize(n));
+        assert_eq!(0, mgr.depth);
+    }
+
+    #[test]
+    fn backends_save_lazily() {
+        let mut copying = CopyingStateManager::new();
+        let mut recomputing = RecomputingStateManager::<3>::new();
+        let a = copying.manage_usize(0);
+        let b = recomputing.manage_usize(0);
+        for i in 0..10 {
+            copying.save_state();
+            recomputing.save_state();
+            copying.save_state();
+            recomputing.save_state();
+            copying.set_usize(a, i + 1);
+            recomputing.set_usize(b, i + 1);
+        }
+        for i in (0..10).rev() {
+            copying.restore_state();
+            recomputing.restore_state();
+            assert_eq!(i, copying.get_usize(a));
+            assert_eq!(i, recomputing.get_usize(b));
+            copying.restore_state();
+            recomputing.restore_state();
+        }
+    }
+
+    fn restored_creations_are_saved_as_root_resources<B: TrailBackend>(mut mgr: StateManager<B>) {
+        let a = mgr.manage_usize(0);
+        mgr.save_state();
+        mgr.set_usize(a, 1);
+        // Created in a level which is restored before the resource is modified in its parent level
+        mgr.save_state();
+        let b = mgr.manage_usize(7);
+        mgr.restore_state();
+        mgr.set_usize(b, 8);
+        mgr.debug_validate();
+        mgr.restore_state();
+        assert_eq!((0, 7), (mgr.get_usize(a), mgr.get_usize(b)));
+        // Nothing is saved for a modification at the root
+        mgr.set_usize(b, 9);
+        assert_eq!(0, mgr.trail_len());
+        mgr.debug_validate();
+    }
+
+    #[test]
+    fn resources_created_in_restored_levels() {
+        restored_creations_are_saved_as_root_resources(StateManager::default());
+        #[cfg(feature = "persistent")]
+        restored_creations_are_saved_as_root_resources(crate::PersistentStateManager::new());
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic]
+    fn can_not_pop_root_level_after_empty_levels() {
+        let mut mgr = StateManager::default();
+        mgr.save_state();
+        mgr.restore_state();
+        mgr.restore_state();
+    }
+}
+
+#[cfg(all(test, feature = "f64"))]
+mod test_epsilon {
+    use crate::{F64Manager, OptionF64Manager, SaveAndRestore, StateManager};
+
+    #[test]
+    fn small_changes_are_not_trailed() {
+        let mut mgr = StateManager::default();
+        mgr.set_epsilon(1e-6);
+        let x = mgr.manage_f64(1.0);
+        let y = mgr.manage_option_f64(Some(1.0));
+        mgr.save_state();
+        assert_eq!(1.0, mgr.set_f64(x, 1.0 + 1e-9));
+        assert_eq!(1.0, mgr.get_f64(x));
+        mgr.set_option_f64(y, Some(1.0 - 1e-9));
+        assert_eq!(1, mgr.levels.len());
+        assert_eq!(1.5, mgr.set_f64(x, 1.5));
+        assert_eq!(2, mgr.levels.len());
+        mgr.restore_state();
+        assert_eq!(1.0, mgr.get_f64(x));
+        assert_eq!(Some(1.0), mgr.get_option_f64(y));
+    }
+}
+
+#[cfg(all(test, feature = "u32"))]
+mod test_slices {
+    use crate::{CopyingStateManager, SaveAndRestore, StateManager, TrailBackend, U32Manager};
+
+    fn slices_are_restored<B: TrailBackend>(mut mgr: StateManager<B>) {
+        let handles = (0..100).map(|i| mgr.manage_u32(i)).collect::<Vec<_>>();
+        mgr.save_state();
+        mgr.set_u32(handles[50], 0);
+        mgr.set_u32_slice(handles[10], &[1; 60]);
+        mgr.save_state();
+        mgr.set_u32_slice(handles[0], &[2; 100]);
+        assert!(handles.iter().all(|h| mgr.get_u32(*h) == 2));
+        mgr.restore_state();
+        assert_eq!(9, mgr.get_u32(handles[9]));
+        assert!(handles[10..70].iter().all(|h| mgr.get_u32(*h) == 1));
+        mgr.restore_state();
+        for (i, h) in handles.iter().copied().enumerate() {
+            assert_eq!(i as u32, mgr.get_u32(h));
+        }
+    }
+
+    #[test]
+    fn set_slice_and_restore() {
+        slices_are_restored(StateManager::default());
+        slices_are_restored(CopyingStateManager::new());
+    }
+
+    #[test]
+    fn unchanged_slice_does_not_materialize() {
+        let mut mgr = StateManager::default();
+        let first = mgr.manage_u32(1);
+        mgr.manage_u32(2);
+        mgr.save_state();
+        mgr.set_u32_slice(first, &[1, 2]);
+        assert_eq!(1, mgr.levels.len());
+    }
+}
+
+#[cfg(all(test, feature = "usize", feature = "i8"))]
+mod test_overflow {
+    use crate::StateManager;
+
+    #[test]
+    #[cfg(any(debug_assertions, feature = "strict-arithmetic"))]
+    #[should_panic(expected = "Overflow when decrementing")]
+    fn decrement_below_zero_panics() {
+        use crate::UsizeManager;
+        let mut mgr = StateManager::default();
+        let n = mgr.manage_usize(0);
+        mgr.decrement_usize(n);
+    }
+
+    #[test]
+    #[cfg(any(debug_assertions, feature = "strict-arithmetic"))]
+    #[should_panic(expected = "Overflow when incrementing")]
+    fn increment_above_max_panics() {
+        use crate::OptionUsizeManager;
+        let mut mgr = StateManager::default();
+        let n = mgr.manage_option_usize(Some(usize::MAX));
+        mgr.increment_option_usize(n);
+    }
+
+    #[test]
+    #[cfg(not(any(debug_assertions, feature = "strict-arithmetic")))]
+    fn overflow_wraps_around() {
+        use crate::I8Manager;
+        let mut mgr = StateManager::default();
+        let n = mgr.manage_i8(i8::MAX);
+        assert_eq!(i8::MIN, mgr.increment_i8(n));
+        assert_eq!(i8::MAX, mgr.decrement_i8(n));
+    }
+}
+
+#[cfg(all(test, feature = "usize", feature = "bool"))]
+mod test_validate {
+    use crate::{BoolManager, SaveAndRestore, StateManager, UsizeManager};
+
+    #[test]
+    fn valid_after_search() {
+        let mut mgr = StateManager::default();
+        let handles = (0..10).map(|i| mgr.manage_usize(i)).collect::<Vec<_>>();
+        let flag = mgr.manage_bool(false);
+        for depth in 0..5 {
+            mgr.save_state();
+            mgr.save_state();
+            for h in handles.iter().copied().skip(depth) {
+                mgr.increment_usize(h);
+            }
+            mgr.set_usize_slice(handles[0], &[depth; 4]);
+            mgr.flip_bool(flag);
+            mgr.debug_validate();
+        }
+        for _ in 0..7 {
+            mgr.restore_state();
+            mgr.debug_validate();
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "The depth does not match")]
+    fn corrupted_depth_is_detected() {
+        let mut mgr = StateManager::default();
+        mgr.manage_usize(0);
+        mgr.save_state();
+        mgr.depth = 3;
+        mgr.debug_validate();
+    }
+}
+
+#[cfg(all(test, feature = "serde", feature = "usize", feature = "f64", feature = "bool"))]
+mod test_serde {
+    use crate::{AnyHandle, BoolManager, CopyingStateManager, ReversibleBool, ReversibleUsize, F64Manager, OptionUsizeManager, SaveAndRestore, StateManager, UsizeManager};
+
+    #[test]
+    fn search_is_resumed() {
+        let mut mgr = StateManager::default();
+        let x = mgr.manage_usize(1);
+        let y = mgr.manage_f64(0.5);
+        let z = mgr.manage_option_usize(None);
+        let b = mgr.manage_bool(false);
+        mgr.save_state();
+        mgr.set_usize(x, 2);
+        mgr.set_option_usize(z, Some(4));
+        mgr.save_state();
+        mgr.save_state();
+        mgr.set_f64(y, 1.5);
+        mgr.set_bool(b, true);
+
+        let json = serde_json::to_string(&mgr).unwrap();
+        let mut resumed: StateManager = serde_json::from_str(&json).unwrap();
+        assert_eq!(2, resumed.get_usize(x));
+        assert_eq!(1.5, resumed.get_f64(y));
+        assert!(resumed.get_bool(b));
+        resumed.set_usize(x, 3);
+        resumed.restore_state();
+        assert_eq!(2, resumed.get_usize(x));
+        assert_eq!(0.5, resumed.get_f64(y));
+        assert!(!resumed.get_bool(b));
+        resumed.restore_state();
+        resumed.restore_state();
+        assert_eq!(1, resumed.get_usize(x));
+        assert_eq!(None, resumed.get_option_usize(z));
+        resumed.debug_validate();
+    }
+
+    #[test]
+    fn handles_are_serialized() {
+        #[derive(serde::Serialize, serde::Deserialize)]
+        struct Model {
+            x: ReversibleUsize,
+            flags: Vec<ReversibleBool>,
+            any: AnyHandle,
+        }
+
+        let mut mgr = StateManager::default();
+        let x = mgr.manage_usize(0);
+        let flags = vec![mgr.manage_bool(false), mgr.manage_bool(true)];
+        let model = Model { x, flags, any: x.into() };
+        let json = serde_json::to_string(&model).unwrap();
+        let model: Model = serde_json::from_str(&json).unwrap();
+        assert_eq!(x, model.x);
+        assert_eq!(AnyHandle::Usize(x), model.any);
+        mgr.set_usize(model.x, 3);
+        assert_eq!(3, mgr.get_usize(x));
+        assert!(mgr.get_bool(model.flags[1]));
+    }
+
+    #[test]
+    fn other_backends_are_serialized() {
+        let mut mgr = CopyingStateManager::new();
+        let x = mgr.manage_usize(1);
+        mgr.save_state();
+        mgr.set_usize(x, 2);
+        let json = serde_json::to_string(&mgr).unwrap();
+        let mut resumed: CopyingStateManager = serde_json::from_str(&json).unwrap();
+        assert_eq!(2, resumed.get_usize(x));
+        resumed.restore_state();
+        assert_eq!(1, resumed.get_usize(x));
+    }
+}
+
+#[cfg(all(test, feature = "tracing", feature = "usize"))]
+mod test_tracing {
+    use std::sync::{Arc, Mutex};
+
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::{Event, Metadata, Subscriber};
+
+    use crate::{SaveAndRestore, StateManager, UsizeManager};
+
+    /// Records the names of the spans and the number of events
+    #[derive(Default, Clone)]
+    struct Recorder(Arc<Mutex<(Vec<&'static str>, usize)>>);
+
+    impl Subscriber for Recorder {
+        fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, span: &Attributes<'_>) -> Id {
+            self.0.lock().unwrap().0.push(span.metadata().name());
+            Id::from_u64(1)
+        }
+
+        fn record(&self, _span: &Id, _values: &Record<'_>) {}
+
+        fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+        fn event(&self, _event: &Event<'_>) {
+            self.0.lock().unwrap().1 += 1;
+        }
+
+        fn enter(&self, _span: &Id) {}
+
+        fn exit(&self, _span: &Id) {}
+    }
+
+    #[test]
+    fn saves_restorations_and_writes_are_traced() {
+        let recorder = Recorder::default();
+        tracing::subscriber::with_default(recorder.clone(), || {
+            let mut mgr = StateManager::default();
+            let x = mgr.manage_usize(0);
+            mgr.save_state();
+            mgr.set_usize(x, 1);
+            // Writing the same value does nothing
+            mgr.set_usize(x, 1);
+            mgr.set_usize(x, 2);
+            mgr.restore_state();
+        });
+        let (spans, events) = recorder.0.lock().unwrap().clone();
+        assert_eq!(vec!["save_state", "restore_state"], spans);
+        assert_eq!(2, events);
+    }
+}
@@ -0,0 +1,263 @@
+//Copyright (c) 2023 X. Gillard, A. Dubray
+//
+//Permission is hereby granted, free of charge, to any person obtaining a copy
+//of this software and associated documentation files (the "Software"), to deal
+//in the Software without restriction, including without limitation the rights
+//to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+//copies of the Software, and to permit persons to whom the Software is
+//furnished to do so, subject to the following conditions:
+//
+//The above copyright notice and this permission notice shall be included in all
+//copies or substantial portions of the Software.
+//
+//THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+//IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+//AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+//OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+//SOFTWARE.
+
+
+//! Listeners of the changes of individual resources. A callback registered on a handle is called with
+//! the old and the new value each time a setter actually changes the value of the resource, so that a
+//! propagation engine can react to the modifications instead of polling its variables.
+//!
+//! The callbacks are not called when the values are restored by `restore_state()`, nor when the value
+//! given to a setter is equal to the current one.
+
+use std::collections::BTreeMap;
+
+use paste::paste;
+
+use crate::*;
+
+/// A callback receiving the old and the new value of a resource
+type Callback<T> = Box<dyn FnMut(T, T) + Send + Sync>;
+
+/// The callbacks registered on the resources of a type, by index of resource
+pub(crate) type ListenerMap<T> = BTreeMap<usize, Vec<Callback<T>>>;
+
+/// A handle on which change listeners can be registered
+pub trait Listened: Copy {
+    /// The type of the value of the resource
+    type Value;
+    /// Registers a callback on the resource of the handle
+    fn register<B: TrailBackend>(self, mgr: &mut StateManager<B>, callback: Box<dyn FnMut(Self::Value, Self::Value) + Send + Sync>);
+    /// Removes the callbacks registered on the resource of the handle
+    fn unregister<B: TrailBackend>(self, mgr: &mut StateManager<B>);
+}
+
+macro_rules! change_listeners {
+    ($($u:ty => $feature:literal),*) => {
+        paste! {
+            /// The listeners of a manager. They are not shared with the clones of the manager.
+            #[derive(Default)]
+            pub(crate) struct Listeners {
+                $(
+                    #[cfg(feature = $feature)]
+                    pub [<numbers _ $u>]: ListenerMap<$u>,
+                    #[cfg(feature = $feature)]
+                    pub [<numbers_option_ $u>]: ListenerMap<Option<$u>>,
+                )*
+                #[cfg(feature = "bool")]
+                bools: ListenerMap<bool>,
+                #[cfg(feature = "bool")]
+                option_bools: ListenerMap<Option<bool>>,
+            }
+
+            $(
+                #[cfg(feature = $feature)]
+                impl Listened for [<Reversible $u:camel>] {
+                    type Value = $u;
+
+                    fn register<B: TrailBackend>(self, mgr: &mut StateManager<B>, callback: Callback<$u>) {
+                        let id = self.index(mgr);
+                        mgr.listeners.[<numbers _ $u>].entry(id).or_default().push(callback);
+                    }
+
+                    fn unregister<B: TrailBackend>(self, mgr: &mut StateManager<B>) {
+                        let id = self.index(mgr);
+                        mgr.listeners.[<numbers _ $u>].remove(&id);
+                    }
+                }
+
+                #[cfg(feature = $feature)]
+                impl Listened for [<ReversibleOption $u:camel>] {
+                    type Value = Option<$u>;
+
+                    fn register<B: TrailBackend>(self, mgr: &mut StateManager<B>, callback: Callback<Option<$u>>) {
+                        let id = self.index(mgr);
+                        mgr.listeners.[<numbers_option_ $u>].entry(id).or_default().push(callback);
+                    }
+
+                    fn unregister<B: TrailBackend>(self, mgr: &mut StateManager<B>) {
+                        let id = self.index(mgr);
+                        mgr.listeners.[<numbers_option_ $u>].remove(&id);
+                    }
+                }
+            )*
+        }
+    };
+}
+
+for_each_managed_type!(change_listeners);
+
+impl Clone for Listeners {
+    fn clone(&self) -> Self {
+        Self::default()
+    }
+}
+
+#[cfg(feature = "bool")]
+impl Listened for ReversibleBool {
+    type Value = bool;
+
+    fn register<B: TrailBackend>(self, mgr: &mut StateManager<B>, callback: Callback<bool>) {
+        let id = self.index(mgr);
+        mgr.listeners.bools.entry(id).or_default().push(callback);
+    }
+
+    fn unregister<B: TrailBackend>(self, mgr: &mut StateManager<B>) {
+        let id = self.index(mgr);
+        mgr.listeners.bools.remove(&id);
+    }
+}
+
+#[cfg(feature = "bool")]
+impl Listened for ReversibleOptionBool {
+    type Value = Option<bool>;
+
+    fn register<B: TrailBackend>(self, mgr: &mut StateManager<B>, callback: Callback<Option<bool>>) {
+        let id = self.index(mgr);
+        mgr.listeners.option_bools.entry(id).or_default().push(callback);
+    }
+
+    fn unregister<B: TrailBackend>(self, mgr: &mut StateManager<B>) {
+        let id = self.index(mgr);
+        mgr.listeners.option_bools.remove(&id);
+    }
+}
+
+impl<B: TrailBackend> StateManager<B> {
+    /// Registers a callback called with the old and the new value of the resource, each time a setter
+    /// changes its value. The callbacks can, for instance, push the resource in a propagation queue or
+    /// send the change through a channel.
+    ///
+    /// ```
+    /// use std::sync::mpsc;
+    /// use search_trail::{StateManager, UsizeManager};
+    ///
+    /// let mut mgr = StateManager::default();
+    /// let x = mgr.manage_usize(1);
+    /// let (sender, changes) = mpsc::channel();
+    /// mgr.on_change(x, move |old, new| sender.send((old, new)).unwrap());
+    /// mgr.set_usize(x, 1);
+    /// mgr.set_usize(x, 2);
+    /// assert_eq!(vec![(1, 2)], changes.try_iter().collect::<Vec<_>>());
+    /// ```
+    pub fn on_change<H: Listened>(&mut self, handle: H, callback: impl FnMut(H::Value, H::Value) + Send + Sync + 'static) {
+        handle.register(self, Box::new(callback));
+    }
+
+    /// Removes the callbacks registered on the resource
+    pub fn remove_listeners(&mut self, handle: impl Listened) {
+        handle.unregister(self);
+    }
+
+    /// Calls the callbacks registered on the resource at the given index
+    #[inline]
+    pub(crate) fn notify<T: Managed>(&mut self, id: usize, old: T, new: T) {
+        if let Some(callbacks) = T::listeners(self).get_mut(&id) {
+            callbacks.iter_mut().for_each(|callback| callback(old, new));
+        }
+    }
+
+    /// Returns the current values of the resources with a listener in `first..first + len`
+    #[inline]
+    pub(crate) fn listened_values<T: Managed>(&mut self, first: usize, len: usize) -> Vec<(usize, T)> {
+        let ids = T::listeners(self).range(first..first + len).map(|(&id, _)| id).collect::<Vec<_>>();
+        ids.into_iter().map(|id| (id, self.get_value(id))).collect()
+    }
+
+    /// Calls the listeners of the resources whose value is not the given old one anymore
+    pub(crate) fn notify_changes<T: Managed>(&mut self, old: Vec<(usize, T)>) {
+        for (id, old) in old {
+            let new = self.get_value::<T>(id);
+            if new != old {
+                self.notify(id, old, new);
+            }
+        }
+    }
+
+    /// Calls the listeners of the packed booleans of a word whose value changed from `old` to `new`
+    #[cfg(feature = "bool")]
+    pub(crate) fn notify_bool_word(&mut self, option: bool, word: usize, old: u64, new: u64) {
+        if option {
+            let per_word = OptionBools::<B::Store<u64>>::PER_WORD;
+            let range = word * per_word..(word + 1) * per_word;
+            for (&id, callbacks) in self.listeners.option_bools.range_mut(range) {
+                let (old, new) = (OptionBools::<B::Store<u64>>::unpack(old, id), OptionBools::<B::Store<u64>>::unpack(new, id));
+                if old != new {
+                    let (old, new) = (Self::option_bool_value(old), Self::option_bool_value(new));
+                    callbacks.iter_mut().for_each(|callback| callback(old, new));
+                }
+            }
+        } else {
+            let per_word = Bools::<B::Store<u64>>::PER_WORD;
+            let range = word * per_word..(word + 1) * per_word;
+            for (&id, callbacks) in self.listeners.bools.range_mut(range) {
+                let (old, new) = (Bools::<B::Store<u64>>::unpack(old, id), Bools::<B::Store<u64>>::unpack(new, id));
+                if old != new {
+                    callbacks.iter_mut().for_each(|callback| callback(old != 0, new != 0));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(all(test, feature = "usize", feature = "u32", feature = "bool"))]
+mod test_listeners {
+    use std::sync::{Arc, Mutex};
+
+    use crate::*;
+
+    #[test]
+    fn listeners_see_the_changes() {
+        let mut mgr = StateManager::default();
+        let xs = (0..10).map(|i| mgr.manage_u32(i)).collect::<Vec<_>>();
+        let n = mgr.manage_option_usize(None);
+        let bs = (0..70).map(|_| mgr.manage_bool(false)).collect::<Vec<_>>();
+        let o = mgr.manage_option_bool(None);
+        let changes = Arc::new(Mutex::new(vec![]));
+        let listen = |mgr: &mut StateManager, handle: ReversibleU32| {
+            let changes = changes.clone();
+            mgr.on_change(handle, move |old, new| changes.lock().unwrap().push(format!("x{}: {} -> {}", handle.0, old, new)));
+        };
+        listen(&mut mgr, xs[2]);
+        listen(&mut mgr, xs[5]);
+        let log = changes.clone();
+        mgr.on_change(n, move |old, new| log.lock().unwrap().push(format!("n: {:?} -> {:?}", old, new)));
+        let log = changes.clone();
+        mgr.on_change(bs[65], move |old, new| log.lock().unwrap().push(format!("b: {} -> {}", old, new)));
+        let log = changes.clone();
+        mgr.on_change(o, move |old, new| log.lock().unwrap().push(format!("o: {:?} -> {:?}", old, new)));
+
+        mgr.save_state();
+        mgr.set_u32(xs[2], 2);
+        mgr.set_u32(xs[2], 20);
+        mgr.set_u32(xs[3], 30);
+        mgr.set_u32_slice(xs[4], &[7; 3]);
+        mgr.set_option_usize(n, Some(3));
+        mgr.set_bool(bs[64], true);
+        mgr.set_bool(bs[65], true);
+        mgr.set_option_bool(o, false);
+        mgr.restore_state();
+        mgr.remove_listeners(xs[2]);
+        mgr.set_u32(xs[2], 0);
+        assert_eq!(
+            vec!["x2: 2 -> 20", "x5: 5 -> 7", "n: None -> Some(3)", "b: false -> true", "o: None -> Some(false)"],
+            *changes.lock().unwrap()
+        );
+    }
+}
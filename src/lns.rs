@@ -0,0 +1,190 @@
+//Copyright (c) 2023 X. Gillard, A. Dubray
+//
+//Permission is hereby granted, free of charge, to any person obtaining a copy
+//of this software and associated documentation files (the "Software"), to deal
+//in the Software without restriction, including without limitation the rights
+//to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+//copies of the Software, and to permit persons to whom the Software is
+//furnished to do so, subject to the following conditions:
+//
+//The above copyright notice and this permission notice shall be included in all
+//copies or substantial portions of the Software.
+//
+//THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+//IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+//AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+//OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+//SOFTWARE.
+
+
+//! Freezing of resources, for the Large Neighborhood Search. The resources of the fixed part of the
+//! solution are frozen while a neighborhood is explored: the setters do not modify them, and the
+//! fallible setters return `TrailError::Frozen`.
+//!
+//! The frozen resources follow the saved levels: restoring a level unfreezes the resources frozen
+//! since it was saved (and freezes again the ones unfrozen since then). Freezing the fixed part just
+//! after saving the state thus unfreezes it when the neighborhood is left.
+
+use std::collections::HashSet;
+
+use crate::*;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// The frozen resources
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub(crate) struct FrozenSet {
+    handles: HashSet<AnyHandle>,
+    /// The frozen resources at the time each level was saved
+    saved: Vec<HashSet<AnyHandle>>,
+}
+
+impl<B: TrailBackend> StateManager<B> {
+    /// Freezes the resource: it is not modified by the setters until it is unfrozen, or until the
+    /// level in which it was frozen is restored
+    ///
+    #[cfg_attr(feature = "usize", doc = "```")]
+    #[cfg_attr(not(feature = "usize"), doc = "```ignore")]
+    /// use search_trail::{SaveAndRestore, StateManager, TrailError, UsizeManager};
+    ///
+    /// let mut mgr = StateManager::default();
+    /// let x = mgr.manage_usize(3);
+    /// let y = mgr.manage_usize(5);
+    /// // Explores a neighborhood of the solution in which x is fixed
+    /// mgr.save_state();
+    /// mgr.freeze_resource(x);
+    /// mgr.set_usize(x, 0);
+    /// mgr.set_usize(y, 0);
+    /// assert_eq!(3, mgr.get_usize(x));
+    /// assert_eq!(Err(TrailError::Frozen), mgr.try_set_usize(x, 0));
+    /// mgr.restore_state();
+    /// assert!(!mgr.is_frozen(x));
+    /// assert_eq!(0, mgr.set_usize(x, 0));
+    /// ```
+    pub fn freeze_resource(&mut self, handle: impl Into<AnyHandle>) {
+        self.frozen.get_or_insert_with(FrozenSet::default).handles.insert(handle.into());
+    }
+
+    /// Unfreezes the resource
+    pub fn unfreeze_resource(&mut self, handle: impl Into<AnyHandle>) {
+        if let Some(frozen) = self.frozen.as_mut() {
+            frozen.handles.remove(&handle.into());
+        }
+    }
+
+    /// Unfreezes all the frozen resources
+    pub fn unfreeze_all(&mut self) {
+        if let Some(frozen) = self.frozen.as_mut() {
+            frozen.handles.clear();
+        }
+    }
+
+    /// Returns true if the resource is frozen
+    pub fn is_frozen(&self, handle: impl Into<AnyHandle>) -> bool {
+        self.frozen.as_ref().is_some_and(|frozen| frozen.handles.contains(&handle.into()))
+    }
+
+    /// Same as `set_bool`, but returns `TrailError::Frozen`, without modifying the boolean, if it is frozen
+    #[cfg(feature = "bool")]
+    pub fn try_set_bool(&mut self, handle: ReversibleBool, value: bool) -> Result<bool, TrailError> {
+        if self.is_frozen(handle) {
+            return Err(TrailError::Frozen);
+        }
+        Ok(self.set_bool(handle, value))
+    }
+
+    /// Same as `set_option_bool`, but sets the optional boolean to any value, and returns
+    /// `TrailError::Frozen`, without modifying it, if it is frozen
+    #[cfg(feature = "bool")]
+    pub fn try_set_option_bool(&mut self, handle: ReversibleOptionBool, value: Option<bool>) -> Result<Option<bool>, TrailError> {
+        if self.is_frozen(handle) {
+            return Err(TrailError::Frozen);
+        }
+        Ok(Option::<bool>::set(self, handle, value))
+    }
+
+    /// Returns true if the resource of type `T` at the given index is frozen
+    #[inline]
+    #[cfg(numbers)]
+    pub(crate) fn is_frozen_value<T: Managed>(&self, id: usize) -> bool {
+        self.frozen.is_some() && self.is_frozen(T::handle(self, id))
+    }
+
+    /// Records a call to `save_state()` in the frozen resources, if any has been frozen
+    pub(crate) fn save_frozen(&mut self) {
+        if let Some(frozen) = self.frozen.as_mut() {
+            frozen.saved.push(frozen.handles.clone());
+        }
+    }
+
+    /// Records a call to `restore_state()` in the frozen resources, if any has been frozen
+    pub(crate) fn restore_frozen(&mut self) {
+        if let Some(frozen) = self.frozen.as_mut() {
+            frozen.handles = frozen.saved.pop().unwrap_or_default();
+        }
+    }
+}
+
+#[cfg(all(test, feature = "u32", feature = "bool"))]
+mod test_lns {
+    use crate::*;
+
+    #[test]
+    fn frozen_resources_follow_the_levels() {
+        let mut mgr = StateManager::default();
+        let xs = (0..4).map(|i| mgr.manage_u32(i)).collect::<Vec<_>>();
+        let b = mgr.manage_bool(false);
+        let o = mgr.manage_option_bool(None);
+        mgr.freeze_resource(xs[0]);
+        mgr.save_state();
+        mgr.freeze_resource(xs[2]);
+        mgr.freeze_resource(b);
+        mgr.freeze_resource(o);
+        mgr.unfreeze_resource(xs[0]);
+        mgr.set_u32_slice(xs[0], &[10, 11, 12, 13]);
+        assert!(!mgr.set_bool(b, true));
+        mgr.set_option_bool(o, true);
+        mgr.set_option_bool_none(o);
+        assert_eq!(vec![10, 11, 2, 13], xs.iter().map(|&x| mgr.get_u32(x)).collect::<Vec<_>>());
+        assert_eq!(None, mgr.get_option_bool(o));
+        assert_eq!(Err(TrailError::Frozen), mgr.try_set_u32(xs[2], 0));
+        mgr.restore_state();
+        // xs[0] is frozen again, and the others are not frozen anymore
+        assert!(mgr.is_frozen(xs[0]) && !mgr.is_frozen(xs[2]) && !mgr.is_frozen(b));
+        mgr.set_u32_slice(xs[0], &[10, 11, 12, 13]);
+        assert!(mgr.set_bool(b, true));
+        assert_eq!(vec![0, 11, 12, 13], xs.iter().map(|&x| mgr.get_u32(x)).collect::<Vec<_>>());
+        mgr.unfreeze_all();
+        assert_eq!(Ok(10), mgr.try_set_u32(xs[0], 10));
+    }
+
+    #[test]
+    fn frozen_setters_return_the_current_value() {
+        let mut mgr = StateManager::default();
+        let x = mgr.manage_u32(3);
+        let b = mgr.manage_bool(false);
+        let o = mgr.manage_option_bool(None);
+        let p = mgr.manage_option_bool(Some(false));
+        let n = mgr.manage_option_u32(Some(7));
+        for handle in [AnyHandle::from(x), b.into(), o.into(), p.into(), n.into()] {
+            mgr.freeze_resource(handle);
+        }
+        assert_eq!(3, mgr.set_u32(x, 4));
+        assert_eq!((7, 7), (mgr.increment_option_u32(n), mgr.decrement_option_u32(n)));
+        assert!(!mgr.set_bool(b, true));
+        assert!(!mgr.set_option_bool(o, true));
+        assert_eq!(None, mgr.set(o, Some(true)));
+        assert!(!mgr.set_option_bool(p, true));
+        assert_eq!(Some(false), mgr.set(p, None));
+        assert_eq!(Err(TrailError::Frozen), mgr.try_set_bool(b, true));
+        assert_eq!(Err(TrailError::Frozen), mgr.try_set_option_bool(o, Some(true)));
+        assert_eq!(Err(TrailError::Frozen), mgr.try_set_option_bool(p, None));
+        mgr.unfreeze_all();
+        assert_eq!(Ok(Some(true)), mgr.try_set_option_bool(o, Some(true)));
+        assert_eq!(Ok(true), mgr.try_set_bool(b, true));
+        assert_eq!(None, mgr.set(p, None));
+    }
+}
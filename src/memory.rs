@@ -0,0 +1,69 @@
+//Copyright (c) 2023 X. Gillard, A. Dubray
+//
+//Permission is hereby granted, free of charge, to any person obtaining a copy
+//of this software and associated documentation files (the "Software"), to deal
+//in the Software without restriction, including without limitation the rights
+//to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+//copies of the Software, and to permit persons to whom the Software is
+//furnished to do so, subject to the following conditions:
+//
+//The above copyright notice and this permission notice shall be included in all
+//copies or substantial portions of the Software.
+//
+//THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+//IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+//AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+//OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+//SOFTWARE.
+
+
+//! Reports of the memory used by a state manager. All the sizes are given in bytes and count the
+//! memory allocated by the vectors (i.e., their capacity), not only the part in use.
+
+/// Memory used by the store of a managed type
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StoreMemory {
+    /// Bytes used by the current values of the managed resources
+    pub values: usize,
+    /// Bytes used to save the previous values (the trail, the copies or the recorded writes, depending
+    /// on the backend)
+    pub saved: usize,
+}
+
+/// Memory used by a state manager
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MemoryReport {
+    /// Bytes used by the levels of the manager
+    pub levels: usize,
+    /// Memory used by the store of each managed type, with the name of the type
+    pub stores: Vec<(&'static str, StoreMemory)>,
+}
+
+impl MemoryReport {
+    /// Bytes used to save the previous values, for all the managed types
+    pub fn trail(&self) -> usize {
+        self.stores.iter().map(|(_, store)| store.saved).sum()
+    }
+
+    /// Bytes used by the current values of the managed resources, for all the managed types
+    pub fn values(&self) -> usize {
+        self.stores.iter().map(|(_, store)| store.values).sum()
+    }
+
+    /// Total number of bytes used by the manager
+    pub fn total(&self) -> usize {
+        self.levels + self.trail() + self.values()
+    }
+
+    /// Returns the memory used by the store of the given type (e.g., `"usize"` or `"Option<f64>"`)
+    pub fn store(&self, name: &str) -> Option<StoreMemory> {
+        self.stores.iter().find(|(n, _)| *n == name).map(|(_, store)| *store)
+    }
+}
+
+/// Bytes allocated by a vector-like collection holding `capacity` elements of type `T`
+pub(crate) fn bytes<T>(capacity: usize) -> usize {
+    capacity * std::mem::size_of::<T>()
+}
@@ -0,0 +1,148 @@
+//Copyright (c) 2023 X. Gillard, A. Dubray
+//
+//Permission is hereby granted, free of charge, to any person obtaining a copy
+//of this software and associated documentation files (the "Software"), to deal
+//in the Software without restriction, including without limitation the rights
+//to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+//copies of the Software, and to permit persons to whom the Software is
+//furnished to do so, subject to the following conditions:
+//
+//The above copyright notice and this permission notice shall be included in all
+//copies or substantial portions of the Software.
+//
+//THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+//IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+//AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+//OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+//SOFTWARE.
+
+
+//! Publication of the activity of the managers through the `metrics` facade, so that a solver can be
+//! monitored with the exporter of its choice. The following metrics are published at each save and
+//! restoration:
+//!
+//! - `search_trail_saves_total` (counter): the number of calls to `save_state()`;
+//! - `search_trail_restores_total` (counter): the number of calls to `restore_state()`;
+//! - `search_trail_trailed_total` (counter): the number of values pushed on the trail;
+//! - `search_trail_trail_length` (gauge): the number of values currently on the trail;
+//! - `search_trail_depth` (gauge): the number of saved levels.
+//!
+//! The rates (e.g., restorations per second) are left to the monitoring system. The metrics of all
+//! the managers of a process are aggregated.
+
+use ::metrics::{counter, gauge};
+
+use crate::{StateManager, TrailBackend};
+
+const SAVES: &str = "search_trail_saves_total";
+const RESTORES: &str = "search_trail_restores_total";
+const TRAILED: &str = "search_trail_trailed_total";
+const TRAIL_LENGTH: &str = "search_trail_trail_length";
+const DEPTH: &str = "search_trail_depth";
+
+impl<B: TrailBackend> StateManager<B> {
+    /// Publishes the values pushed on the trail since the last publication. This must be called
+    /// before the trail is shortened by a restoration.
+    pub(crate) fn publish_trailed(&mut self) {
+        let len = self.trail_len();
+        if len > self.published_trail {
+            counter!(TRAILED).increment((len - self.published_trail) as u64);
+        }
+        self.published_trail = len;
+    }
+
+    /// Publishes a call to `save_state()`
+    pub(crate) fn publish_save(&mut self) {
+        counter!(SAVES).increment(1);
+        self.publish_trailed();
+        self.publish_gauges();
+    }
+
+    /// Publishes a call to `restore_state()`, after the restoration
+    pub(crate) fn publish_restore(&mut self) {
+        counter!(RESTORES).increment(1);
+        self.published_trail = self.trail_len();
+        self.publish_gauges();
+    }
+
+    fn publish_gauges(&self) {
+        gauge!(TRAIL_LENGTH).set(self.published_trail as f64);
+        gauge!(DEPTH).set(self.depth as f64);
+    }
+}
+
+#[cfg(all(test, feature = "usize"))]
+mod test_metrics {
+    use std::collections::HashMap;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    use ::metrics::{Counter, Gauge, Histogram, Key, KeyName, Metadata, Recorder, SharedString, Unit};
+
+    use super::*;
+    use crate::{SaveAndRestore, UsizeManager};
+
+    /// Records the last value of each counter and gauge
+    #[derive(Default)]
+    struct Values(Mutex<HashMap<String, Arc<AtomicU64>>>);
+
+    impl Values {
+        fn atomic(&self, key: &Key) -> Arc<AtomicU64> {
+            self.0.lock().unwrap().entry(key.name().to_string()).or_default().clone()
+        }
+
+        fn counter(&self, name: &str) -> u64 {
+            self.0.lock().unwrap()[name].load(Ordering::Relaxed)
+        }
+
+        fn gauge(&self, name: &str) -> f64 {
+            f64::from_bits(self.counter(name))
+        }
+    }
+
+    impl Recorder for Values {
+        fn describe_counter(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+        fn describe_gauge(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+        fn describe_histogram(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+        fn register_counter(&self, key: &Key, _: &Metadata<'_>) -> Counter {
+            Counter::from_arc(self.atomic(key))
+        }
+
+        fn register_gauge(&self, key: &Key, _: &Metadata<'_>) -> Gauge {
+            Gauge::from_arc(self.atomic(key))
+        }
+
+        fn register_histogram(&self, _: &Key, _: &Metadata<'_>) -> Histogram {
+            Histogram::noop()
+        }
+    }
+
+    #[test]
+    fn activity_is_published() {
+        let values = Values::default();
+        ::metrics::with_local_recorder(&values, || {
+            let mut mgr = StateManager::default();
+            let x = mgr.manage_usize(0);
+            let y = mgr.manage_usize(0);
+            mgr.save_state();
+            mgr.set_usize(x, 1);
+            mgr.save_state();
+            mgr.set_usize(x, 2);
+            mgr.set_usize(y, 2);
+            assert_eq!(2.0, values.gauge(DEPTH));
+            assert_eq!(1.0, values.gauge(TRAIL_LENGTH));
+            mgr.restore_state();
+            assert_eq!(1.0, values.gauge(DEPTH));
+            assert_eq!(1.0, values.gauge(TRAIL_LENGTH));
+            mgr.set_usize(y, 3);
+            mgr.restore_state();
+            assert_eq!(0.0, values.gauge(TRAIL_LENGTH));
+        });
+        assert_eq!(2, values.counter(SAVES));
+        assert_eq!(2, values.counter(RESTORES));
+        assert_eq!(4, values.counter(TRAILED));
+    }
+}
@@ -0,0 +1,199 @@
+//Copyright (c) 2023 X. Gillard, A. Dubray
+//
+//Permission is hereby granted, free of charge, to any person obtaining a copy
+//of this software and associated documentation files (the "Software"), to deal
+//in the Software without restriction, including without limitation the rights
+//to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+//copies of the Software, and to permit persons to whom the Software is
+//furnished to do so, subject to the following conditions:
+//
+//The above copyright notice and this permission notice shall be included in all
+//copies or substantial portions of the Software.
+//
+//THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+//IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+//AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+//OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+//SOFTWARE.
+
+
+//! A state layer with the shape of the one of MiniCP, so that the solvers inspired by MiniCP can
+//! use this crate with few changes. As in MiniCP, the level is -1 before the first save, and the
+//! states are created by the manager. Since the values are stored in the manager, the methods of the
+//! states take it as parameter.
+//!
+//! ```
+//! use search_trail::minicp::{StateInt, StateManager};
+//!
+//! let mut sm = search_trail::StateManager::default();
+//! let x = sm.make_state_int(3);
+//! sm.with_new_state(|sm| {
+//!     x.increment(sm);
+//!     assert_eq!(4, x.value(sm));
+//! });
+//! assert_eq!(3, x.value(&sm));
+//! ```
+
+use crate::{BoolManager, I32Manager, ReversibleBool, ReversibleI32, SaveAndRestore, TrailBackend};
+
+/// The manager of the states
+pub trait StateManager: SaveAndRestore {
+    /// The reversible integers created by the manager
+    type StateInt: StateInt<Self>;
+    /// The reversible booleans created by the manager
+    type StateBool: StateBool<Self>;
+
+    /// Returns the current level, which is -1 before the first save
+    fn get_level(&self) -> isize;
+    /// Creates a reversible integer with the given initial value
+    fn make_state_int(&mut self, init: i32) -> Self::StateInt;
+    /// Creates a reversible boolean with the given initial value
+    fn make_state_bool(&mut self, init: bool) -> Self::StateBool;
+
+    /// Restores the states until the given level is reached
+    fn restore_state_until(&mut self, level: isize) {
+        while self.get_level() > level {
+            self.restore_state();
+        }
+    }
+
+    /// Runs the body in a new state, which is restored afterwards
+    fn with_new_state<R>(&mut self, body: impl FnOnce(&mut Self) -> R) -> R {
+        self.save_state();
+        let result = body(self);
+        self.restore_state();
+        result
+    }
+}
+
+/// A reversible integer
+pub trait StateInt<M: ?Sized>: Copy {
+    /// Returns the value of the integer
+    fn value(&self, sm: &M) -> i32;
+    /// Sets the value of the integer and returns the new value
+    fn set_value(&self, sm: &mut M, v: i32) -> i32;
+
+    /// Increments the value of the integer and returns the new value
+    fn increment(&self, sm: &mut M) -> i32 {
+        self.set_value(sm, self.value(sm) + 1)
+    }
+
+    /// Decrements the value of the integer and returns the new value
+    fn decrement(&self, sm: &mut M) -> i32 {
+        self.set_value(sm, self.value(sm) - 1)
+    }
+}
+
+/// A reversible boolean
+pub trait StateBool<M: ?Sized>: Copy {
+    /// Returns the value of the boolean
+    fn value(&self, sm: &M) -> bool;
+    /// Sets the value of the boolean
+    fn set_value(&self, sm: &mut M, v: bool);
+}
+
+impl<B: TrailBackend> StateManager for crate::StateManager<B> {
+    type StateInt = ReversibleI32;
+    type StateBool = ReversibleBool;
+
+    fn get_level(&self) -> isize {
+        self.depth as isize - 1
+    }
+
+    fn make_state_int(&mut self, init: i32) -> ReversibleI32 {
+        self.manage_i32(init)
+    }
+
+    fn make_state_bool(&mut self, init: bool) -> ReversibleBool {
+        self.manage_bool(init)
+    }
+}
+
+impl<B: TrailBackend> StateInt<crate::StateManager<B>> for ReversibleI32 {
+    fn value(&self, sm: &crate::StateManager<B>) -> i32 {
+        sm.get_i32(*self)
+    }
+
+    fn set_value(&self, sm: &mut crate::StateManager<B>, v: i32) -> i32 {
+        sm.set_i32(*self, v)
+    }
+
+    fn increment(&self, sm: &mut crate::StateManager<B>) -> i32 {
+        sm.increment_i32(*self)
+    }
+
+    fn decrement(&self, sm: &mut crate::StateManager<B>) -> i32 {
+        sm.decrement_i32(*self)
+    }
+}
+
+impl<B: TrailBackend> StateBool<crate::StateManager<B>> for ReversibleBool {
+    fn value(&self, sm: &crate::StateManager<B>) -> bool {
+        sm.get_bool(*self)
+    }
+
+    fn set_value(&self, sm: &mut crate::StateManager<B>, v: bool) {
+        sm.set_bool(*self, v);
+    }
+}
+
+#[cfg(test)]
+mod test_minicp {
+    use super::*;
+
+    /// Counts the solutions of the n-queens problem with the MiniCP interface only
+    fn queens<M: StateManager>(sm: &mut M, n: usize) -> usize {
+        let placed: Vec<M::StateBool> = (0..5 * n).map(|_| sm.make_state_bool(false)).collect();
+        let row = sm.make_state_int(0);
+        fn search<M: StateManager>(sm: &mut M, n: usize, row: M::StateInt, placed: &[M::StateBool]) -> usize {
+            let r = row.value(sm) as usize;
+            if r == n {
+                return 1;
+            }
+            let mut count = 0;
+            for c in 0..n {
+                let used = [c, n + r + c, 3 * n + r + n - 1 - c];
+                if used.iter().any(|&i| placed[i].value(sm)) {
+                    continue;
+                }
+                count += sm.with_new_state(|sm| {
+                    used.iter().for_each(|&i| placed[i].set_value(sm, true));
+                    row.increment(sm);
+                    search(sm, n, row, placed)
+                });
+            }
+            count
+        }
+        search(sm, n, row, &placed)
+    }
+
+    #[test]
+    fn levels_are_restored() {
+        let mut sm = crate::StateManager::default();
+        let x = sm.make_state_int(1);
+        let b = sm.make_state_bool(false);
+        assert_eq!(-1, sm.get_level());
+        sm.save_state();
+        x.set_value(&mut sm, 5);
+        sm.save_state();
+        b.set_value(&mut sm, true);
+        x.decrement(&mut sm);
+        sm.save_state();
+        assert_eq!(2, sm.get_level());
+        assert_eq!((4, true), (x.value(&sm), b.value(&sm)));
+        sm.restore_state_until(0);
+        assert_eq!(0, sm.get_level());
+        assert_eq!((5, false), (x.value(&sm), b.value(&sm)));
+        sm.restore_state_until(-1);
+        assert_eq!((1, false), (x.value(&sm), b.value(&sm)));
+    }
+
+    #[test]
+    fn queens_are_counted() {
+        let mut sm = crate::StateManager::default();
+        assert_eq!(92, queens(&mut sm, 8));
+        assert_eq!(-1, sm.get_level());
+    }
+}
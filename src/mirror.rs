@@ -0,0 +1,273 @@
+//Copyright (c) 2023 X. Gillard, A. Dubray
+//
+//Permission is hereby granted, free of charge, to any person obtaining a copy
+//of this software and associated documentation files (the "Software"), to deal
+//in the Software without restriction, including without limitation the rights
+//to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+//copies of the Software, and to permit persons to whom the Software is
+//furnished to do so, subject to the following conditions:
+//
+//The above copyright notice and this permission notice shall be included in all
+//copies or substantial portions of the Software.
+//
+//THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+//IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+//AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+//OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+//SOFTWARE.
+
+
+//! Atomic mirrors of managed values. A mirror holds a copy of the current value of a resource (or of
+//! the depth of the manager) in an atomic, which the manager updates after each modification. Other
+//! threads can thus read the mirrored values (e.g., to report the best bound found so far) without
+//! locking or freezing the manager.
+//!
+//! The mirrors are independent from each other: a thread reading several of them may see some values
+//! that are more recent than others. A consistent view of the state is given by `freeze()`.
+
+use std::fmt;
+use std::marker::PhantomData;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use crate::*;
+
+/// A value that can be stored in the 64 bits of an atomic mirror
+pub trait MirrorValue: Copy {
+    /// Returns the bits of the value
+    fn to_bits(self) -> u64;
+    /// Returns the value encoded in the bits returned by `to_bits`
+    fn from_bits(bits: u64) -> Self;
+}
+
+macro_rules! mirror_integers {
+    ($($u:ty => $feature:literal),*) => {
+        $(
+            #[cfg(feature = $feature)]
+            impl MirrorValue for $u {
+                fn to_bits(self) -> u64 {
+                    self as u64
+                }
+
+                fn from_bits(bits: u64) -> Self {
+                    bits as $u
+                }
+            }
+        )*
+    };
+}
+
+mirror_integers!(u8 => "u8", u16 => "u16", u32 => "u32", u64 => "u64", usize => "usize", i8 => "i8", i16 => "i16", i32 => "i32", i64 => "i64", isize => "isize");
+
+#[cfg(feature = "f32")]
+impl MirrorValue for f32 {
+    fn to_bits(self) -> u64 {
+        f32::to_bits(self) as u64
+    }
+
+    fn from_bits(bits: u64) -> Self {
+        f32::from_bits(bits as u32)
+    }
+}
+
+#[cfg(feature = "f64")]
+impl MirrorValue for f64 {
+    fn to_bits(self) -> u64 {
+        f64::to_bits(self)
+    }
+
+    fn from_bits(bits: u64) -> Self {
+        f64::from_bits(bits)
+    }
+}
+
+impl MirrorValue for bool {
+    fn to_bits(self) -> u64 {
+        self as u64
+    }
+
+    fn from_bits(bits: u64) -> Self {
+        bits != 0
+    }
+}
+
+/// A handle whose value can be mirrored
+pub trait Mirrored: Copy + Send + Sync + 'static {
+    /// The type of the value of the resource
+    type Value: MirrorValue;
+    /// Returns the current value of the resource in the manager
+    fn read<B: TrailBackend>(self, mgr: &StateManager<B>) -> Self::Value;
+}
+
+macro_rules! mirrored_handles {
+    ($($u:ty => $feature:literal),*) => {
+        paste::paste! {
+            $(
+                #[cfg(feature = $feature)]
+                impl Mirrored for [<Reversible $u:camel>] {
+                    type Value = $u;
+
+                    fn read<B: TrailBackend>(self, mgr: &StateManager<B>) -> $u {
+                        mgr.[<get_ $u>](self)
+                    }
+                }
+            )*
+        }
+    };
+}
+
+mirrored_handles!(u8 => "u8", u16 => "u16", u32 => "u32", u64 => "u64", usize => "usize", i8 => "i8", i16 => "i16", i32 => "i32", i64 => "i64", isize => "isize", f32 => "f32", f64 => "f64");
+
+#[cfg(feature = "bool")]
+impl Mirrored for ReversibleBool {
+    type Value = bool;
+
+    fn read<B: TrailBackend>(self, mgr: &StateManager<B>) -> bool {
+        mgr.get_bool(self)
+    }
+}
+
+/// The current value of a resource, readable from any thread without locking the manager
+pub struct Mirror<T> {
+    cell: Arc<AtomicU64>,
+    value: PhantomData<fn() -> T>,
+}
+
+impl<T> Clone for Mirror<T> {
+    fn clone(&self) -> Self {
+        Self {
+            cell: self.cell.clone(),
+            value: PhantomData,
+        }
+    }
+}
+
+impl<T: MirrorValue + fmt::Debug> fmt::Debug for Mirror<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Mirror").field(&self.get()).finish()
+    }
+}
+
+impl<T: MirrorValue> Mirror<T> {
+    /// Returns the most recent value of the resource
+    pub fn get(&self) -> T {
+        T::from_bits(self.cell.load(Ordering::Relaxed))
+    }
+}
+
+/// Reads the value of a mirrored resource in a manager
+type Reader<B> = Box<dyn Fn(&StateManager<B>) -> u64 + Send + Sync>;
+
+/// The mirrors of a manager. The mirrors are not shared with the clones of the manager.
+pub(crate) struct Mirrors<B: TrailBackend> {
+    mirrors: Vec<(Reader<B>, Arc<AtomicU64>)>,
+}
+
+impl<B: TrailBackend> Default for Mirrors<B> {
+    fn default() -> Self {
+        Self { mirrors: vec![] }
+    }
+}
+
+impl<B: TrailBackend> Clone for Mirrors<B> {
+    fn clone(&self) -> Self {
+        Self::default()
+    }
+}
+
+impl<B: TrailBackend> StateManager<B> {
+    /// Mirrors the value of the resource in an atomic, updated after each modification of the manager
+    ///
+    /// ```
+    /// use search_trail::{StateManager, SaveAndRestore, UsizeManager};
+    ///
+    /// let mut mgr = StateManager::default();
+    /// let bound = mgr.manage_usize(100);
+    /// let mirror = mgr.mirror(bound);
+    /// let monitor = std::thread::spawn(move || mirror.get());
+    /// mgr.save_state();
+    /// mgr.set_usize(bound, 42);
+    /// assert!([100, 42].contains(&monitor.join().unwrap()));
+    /// ```
+    pub fn mirror<H: Mirrored>(&mut self, handle: H) -> Mirror<H::Value> {
+        self.add_mirror(Box::new(move |mgr| handle.read(mgr).to_bits()))
+    }
+
+    /// Mirrors the depth of the manager in an atomic, updated at each save and restoration
+    pub fn mirror_depth(&mut self) -> Mirror<usize> {
+        self.add_mirror(Box::new(|mgr| mgr.depth as u64))
+    }
+
+    fn add_mirror<T>(&mut self, reader: Reader<B>) -> Mirror<T> {
+        let cell = Arc::new(AtomicU64::new(reader(self)));
+        self.mirrors.mirrors.push((reader, cell.clone()));
+        Mirror { cell, value: PhantomData }
+    }
+
+    /// Stops mirroring the values. The mirrors keep their last value.
+    pub fn clear_mirrors(&mut self) {
+        self.mirrors.mirrors.clear();
+    }
+
+    /// Updates the mirrors with the current values of their resources
+    #[inline]
+    pub(crate) fn refresh_mirrors(&self) {
+        for (reader, cell) in self.mirrors.mirrors.iter() {
+            cell.store(reader(self), Ordering::Relaxed);
+        }
+    }
+}
+
+#[cfg(all(test, feature = "usize", feature = "f64", feature = "bool", feature = "i8"))]
+mod test_mirror {
+    use crate::*;
+
+    #[test]
+    fn mirrors_follow_the_manager() {
+        let mut mgr = StateManager::default();
+        let n = mgr.manage_usize(1);
+        let f = mgr.manage_f64(0.5);
+        let b = mgr.manage_bool(false);
+        let i = mgr.manage_i8(-3);
+        let (mn, mf, mb, mi, depth) = (mgr.mirror(n), mgr.mirror(f), mgr.mirror(b), mgr.mirror(i), mgr.mirror_depth());
+        mgr.save_state();
+        mgr.set_usize(n, 2);
+        mgr.set_f64(f, -1.5);
+        mgr.set_bool(b, true);
+        mgr.set_i8(i, -100);
+        assert_eq!((2, -1.5, true, -100, 1), (mn.get(), mf.get(), mb.get(), mi.get(), depth.get()));
+        mgr.restore_state();
+        assert_eq!((1, 0.5, false, -3, 0), (mn.get(), mf.get(), mb.get(), mi.get(), depth.get()));
+
+        let mut clone = mgr.clone();
+        clone.set_usize(n, 7);
+        assert_eq!(1, mn.get());
+        mgr.clear_mirrors();
+        mgr.set_usize(n, 8);
+        assert_eq!(1, mn.get());
+    }
+
+    #[test]
+    fn mirrors_are_read_while_searching() {
+        let mut mgr = StateManager::default();
+        let bound = mgr.manage_usize(1000);
+        let mirror = mgr.mirror(bound);
+        std::thread::scope(|s| {
+            let monitor = s.spawn(|| {
+                let mut last = usize::MAX;
+                while last > 0 {
+                    let bound = mirror.get();
+                    assert!(bound <= last);
+                    last = bound;
+                }
+            });
+            for i in (0..1000).rev() {
+                mgr.save_state();
+                mgr.set_usize(bound, i);
+            }
+            monitor.join().unwrap();
+        });
+    }
+}
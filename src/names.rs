@@ -0,0 +1,185 @@
+//Copyright (c) 2023 X. Gillard, A. Dubray
+//
+//Permission is hereby granted, free of charge, to any person obtaining a copy
+//of this software and associated documentation files (the "Software"), to deal
+//in the Software without restriction, including without limitation the rights
+//to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+//copies of the Software, and to permit persons to whom the Software is
+//furnished to do so, subject to the following conditions:
+//
+//The above copyright notice and this permission notice shall be included in all
+//copies or substantial portions of the Software.
+//
+//THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+//IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+//AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+//OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+//SOFTWARE.
+
+
+//! Optional names of the managed resources, used by the diagnostics (e.g., `dump_state`). The names are
+//! not part of the state of the search: they are not restored, and they are not written in the
+//! binary snapshots.
+
+use std::collections::HashMap;
+
+use paste::paste;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::*;
+
+/// The names given to the managed resources
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(from = "Vec<(AnyHandle, String)>", into = "Vec<(AnyHandle, String)>"))]
+pub(crate) struct NameRegistry {
+    names: HashMap<AnyHandle, String>,
+    handles: HashMap<String, AnyHandle>,
+}
+
+impl NameRegistry {
+    pub fn insert(&mut self, handle: AnyHandle, name: &str) {
+        if let Some(previous) = self.names.insert(handle, name.to_string()) {
+            if self.handles.get(&previous) == Some(&handle) {
+                self.handles.remove(&previous);
+            }
+        }
+        self.handles.insert(name.to_string(), handle);
+    }
+
+    /// Removes the name of the resource, if it has one
+    #[cfg(numbers)]
+    pub fn remove(&mut self, handle: AnyHandle) {
+        if let Some(name) = self.names.remove(&handle) {
+            if self.handles.get(&name) == Some(&handle) {
+                self.handles.remove(&name);
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.names.clear();
+        self.handles.clear();
+    }
+}
+
+impl From<Vec<(AnyHandle, String)>> for NameRegistry {
+    fn from(names: Vec<(AnyHandle, String)>) -> Self {
+        let mut registry = Self::default();
+        for (handle, name) in names.iter() {
+            registry.insert(*handle, name);
+        }
+        registry
+    }
+}
+
+impl From<NameRegistry> for Vec<(AnyHandle, String)> {
+    fn from(registry: NameRegistry) -> Self {
+        registry.names.into_iter().collect()
+    }
+}
+
+impl<B: TrailBackend> StateManager<B> {
+    /// Gives a name to a managed resource, replacing its previous name. If several resources have the
+    /// same name, `lookup` returns the last one that was named.
+    pub fn set_name(&mut self, handle: impl Into<AnyHandle>, name: &str) {
+        self.names.insert(handle.into(), name);
+    }
+
+    /// Returns the name of a managed resource, if it has one
+    pub fn name_of(&self, handle: impl Into<AnyHandle>) -> Option<&str> {
+        self.names.names.get(&handle.into()).map(|name| name.as_str())
+    }
+
+    /// Returns the resource with the given name
+    pub fn lookup(&self, name: &str) -> Option<AnyHandle> {
+        self.names.handles.get(name).copied()
+    }
+}
+
+macro_rules! named_resources {
+    ($($u:ty => $feature:literal),*) => {
+        paste! {
+            impl<B: TrailBackend> StateManager<B> {
+                $(
+                    #[cfg(feature = $feature)]
+                    #[doc="Creates a new managed resource with the given name"]
+                    pub fn [<manage_ $u _named>](&mut self, value: $u, name: &str) -> [<Reversible $u:camel>] {
+                        let handle = self.[<manage_ $u>](value);
+                        self.set_name(handle, name);
+                        handle
+                    }
+
+                    #[cfg(feature = $feature)]
+                    #[doc="Creates a new managed resource with the given name"]
+                    pub fn [<manage_option_ $u _named>](&mut self, value: Option<$u>, name: &str) -> [<ReversibleOption $u:camel>] {
+                        let handle = self.[<manage_option_ $u>](value);
+                        self.set_name(handle, name);
+                        handle
+                    }
+                )*
+
+                #[cfg(feature = "bool")]
+                /// Creates a new managed boolean with the given name
+                pub fn manage_bool_named(&mut self, value: bool, name: &str) -> ReversibleBool {
+                    let handle = self.manage_bool(value);
+                    self.set_name(handle, name);
+                    handle
+                }
+
+                #[cfg(feature = "bool")]
+                /// Creates a new managed optional boolean with the given name
+                pub fn manage_option_bool_named(&mut self, value: Option<bool>, name: &str) -> ReversibleOptionBool {
+                    let handle = self.manage_option_bool(value);
+                    self.set_name(handle, name);
+                    handle
+                }
+            }
+        }
+    };
+}
+
+for_each_managed_type!(named_resources);
+
+#[cfg(all(test, feature = "usize", feature = "bool"))]
+mod test_names {
+    use crate::{AnyHandle, StateManager, UsizeManager};
+
+    #[test]
+    fn resources_are_found_by_name() {
+        let mut mgr = StateManager::default();
+        let x = mgr.manage_usize_named(0, "x[3].count");
+        let y = mgr.manage_usize(1);
+        let b = mgr.manage_bool_named(true, "done");
+        let o = mgr.manage_option_usize_named(None, "best");
+        assert_eq!(Some("x[3].count"), mgr.name_of(x));
+        assert_eq!(None, mgr.name_of(y));
+        assert_eq!(Some(AnyHandle::Usize(x)), mgr.lookup("x[3].count"));
+        assert_eq!(Some(AnyHandle::Bool(b)), mgr.lookup("done"));
+        assert_eq!(Some(AnyHandle::OptionUsize(o)), mgr.lookup("best"));
+        assert_eq!(None, mgr.lookup("y"));
+    }
+
+    #[test]
+    fn resources_are_renamed() {
+        let mut mgr = StateManager::default();
+        let x = mgr.manage_usize_named(0, "x");
+        mgr.set_name(x, "z");
+        assert_eq!(Some("z"), mgr.name_of(x));
+        assert_eq!(None, mgr.lookup("x"));
+        let y = mgr.manage_usize_named(1, "z");
+        assert_eq!(Some(AnyHandle::Usize(y)), mgr.lookup("z"));
+        assert_eq!(Some("z"), mgr.name_of(x));
+    }
+
+    #[test]
+    fn names_are_dumped() {
+        let mut mgr = StateManager::default();
+        mgr.manage_usize_named(3, "x");
+        mgr.manage_usize(4);
+        assert_eq!("depth 0\nlevel 0 (+0 empty)\nx (usize #0) = 3\nusize #1 = 4\n", mgr.dump_state().to_string());
+    }
+}
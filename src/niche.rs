@@ -0,0 +1,142 @@
+//Copyright (c) 2023 X. Gillard, A. Dubray
+//
+//Permission is hereby granted, free of charge, to any person obtaining a copy
+//of this software and associated documentation files (the "Software"), to deal
+//in the Software without restriction, including without limitation the rights
+//to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+//copies of the Software, and to permit persons to whom the Software is
+//furnished to do so, subject to the following conditions:
+//
+//The above copyright notice and this permission notice shall be included in all
+//copies or substantial portions of the Software.
+//
+//THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+//IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+//AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+//OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+//SOFTWARE.
+
+//! Optional integers stored without the tag of an `Option`. The absence of value is encoded by a
+//! sentinel of the integer type (its maximum by default), so that the resource is stored and trailed
+//! as a plain integer: a `Reversible<Option<u64>>` uses 16 bytes per value, and a `ReversibleNiche<u64>`
+//! only 8.
+//!
+#![cfg_attr(all(feature = "u32", feature = "u64"), doc = "```")]
+#![cfg_attr(not(all(feature = "u32", feature = "u64")), doc = "```ignore")]
+//! use search_trail::{SaveAndRestore, StateManager};
+//!
+//! let mut mgr = StateManager::default();
+//! let support = mgr.manage_niche(Some(3u32));
+//! let watched = mgr.manage_niche_with(None, 0u64);
+//! mgr.save_state();
+//! mgr.set_niche(support, None);
+//! mgr.set_niche(watched, Some(42));
+//! assert_eq!((None, Some(42)), (mgr.get_niche(support), mgr.get_niche(watched)));
+//! mgr.restore_state();
+//! assert_eq!((Some(3), None), (mgr.get_niche(support), mgr.get_niche(watched)));
+//! ```
+
+use num_traits::PrimInt;
+
+use crate::*;
+
+/// The handle of an optional integer of type `T`, whose absence of value is encoded by a sentinel
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ReversibleNiche<T: ScopedValue> {
+    value: T::Handle,
+    /// The value encoding `None`
+    none: T,
+}
+
+impl<T: ScopedValue> ReversibleNiche<T> {
+    /// Returns the handle of the integer holding the encoded value
+    pub fn handle(&self) -> Reversible<T> {
+        self.value
+    }
+
+    /// Returns the value encoding `None`
+    pub fn sentinel(&self) -> T {
+        self.none
+    }
+}
+
+impl<T: ScopedValue> From<ReversibleNiche<T>> for AnyHandle {
+    fn from(handle: ReversibleNiche<T>) -> Self {
+        handle.value.into()
+    }
+}
+
+impl<B: TrailBackend> StateManager<B> {
+    /// Creates a new optional integer with the given value, encoding `None` by the maximum of the type,
+    /// and returns its handle. Panics if the value is this maximum.
+    pub fn manage_niche<T: ScopedValue + PrimInt>(&mut self, value: Option<T>) -> ReversibleNiche<T> {
+        self.manage_niche_with(value, T::max_value())
+    }
+
+    /// Creates a new optional integer with the given value, encoding `None` by the given sentinel, and
+    /// returns its handle. Panics if the value is the sentinel.
+    pub fn manage_niche_with<T: ScopedValue + PrimInt>(&mut self, value: Option<T>, none: T) -> ReversibleNiche<T> {
+        ReversibleNiche {
+            value: self.manage(Self::encode_niche(value, none)),
+            none,
+        }
+    }
+
+    /// Returns the value of the optional integer
+    pub fn get_niche<T: ScopedValue + PrimInt>(&self, handle: ReversibleNiche<T>) -> Option<T> {
+        let value = self.get(handle.value);
+        (value != handle.none).then_some(value)
+    }
+
+    /// Sets the value of the optional integer and returns the new value. Panics if the value is the
+    /// sentinel of the handle.
+    pub fn set_niche<T: ScopedValue + PrimInt>(&mut self, handle: ReversibleNiche<T>, value: Option<T>) -> Option<T> {
+        let value = self.set(handle.value, Self::encode_niche(value, handle.none));
+        (value != handle.none).then_some(value)
+    }
+
+    /// Returns the integer encoding the given value
+    fn encode_niche<T: PrimInt>(value: Option<T>, none: T) -> T {
+        match value {
+            Some(value) => {
+                assert!(value != none, "The value of an optional integer can not be its sentinel");
+                value
+            }
+            None => none,
+        }
+    }
+}
+
+#[cfg(all(test, feature = "u64", feature = "i8"))]
+mod test_niche {
+    use crate::*;
+
+    #[test]
+    fn same_values_as_an_option() {
+        let mut mgr = StateManager::default();
+        let option = mgr.manage(None::<u64>);
+        let niche = mgr.manage_niche(None::<u64>);
+        for depth in 0..10 {
+            mgr.save_state();
+            let value = (depth % 3 != 0).then_some(depth);
+            mgr.set(option, value);
+            assert_eq!(value, mgr.set_niche(niche, value));
+        }
+        for _ in 0..10 {
+            assert_eq!(mgr.get(option), mgr.get_niche(niche));
+            mgr.restore_state();
+        }
+        assert_eq!(u64::MAX, mgr.get(niche.handle()));
+    }
+
+    #[test]
+    #[should_panic(expected = "sentinel")]
+    fn the_sentinel_can_not_be_set() {
+        let mut mgr = StateManager::default();
+        let niche = mgr.manage_niche_with(Some(1i8), -1);
+        assert_eq!(-1, niche.sentinel());
+        mgr.set_niche(niche, Some(-1));
+    }
+}
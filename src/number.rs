@@ -0,0 +1,829 @@
+//Copyright (c) 2023 X. Gillard, A. Dubray
+//
+//Permission is hereby granted, free of charge, to any person obtaining a copy
+//of this software and associated documentation files (the "Software"), to deal
+//in the Software without restriction, including without limitation the rights
+//to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+//copies of the Software, and to permit persons to whom the Software is
+//furnished to do so, subject to the following conditions:
+//
+//The above copyright notice and this permission notice shall be included in all
+//copies or substantial portions of the Software.
+//
+//THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+//IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+//AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+//OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+//SOFTWARE.
+
+
+//! Generic core shared by all the managed types, and the default trailing backend. The per-type
+//! managers generated in the crate root are thin shims around the stores of the backend.
+
+use std::fmt::Debug;
+
+use crate::memory::bytes;
+#[cfg(feature = "snapshot")]
+use crate::snapshot::{read_column, read_usize, write_usize, Snapshot, SnapshotValue};
+#[cfg(feature = "snapshot")]
+use crate::SnapshotError;
+use crate::{Store, StoreMemory, TrailBackend};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// A numeric type that can be managed by the state manager, with `manage_number()`. Any copyable type
+/// implementing the basic numeric operations of `num-traits` is a managed number, including the types
+/// defined outside of this crate.
+pub trait ManagedNumber: num_traits::Num + Copy + PartialOrd + Debug + Send + Sync + 'static {}
+
+impl<T: num_traits::Num + Copy + PartialOrd + Debug + Send + Sync + 'static> ManagedNumber for T {}
+
+/// A managed value whose small changes can be ignored. The changes of floating point values smaller
+/// than the epsilon of the manager are negligible, while any change of an integer is recorded.
+#[cfg(numbers)]
+pub(crate) trait Negligible: Copy {
+    fn negligible(old: Self, new: Self, epsilon: f64) -> bool;
+}
+
+/// A managed number that can be incremented and decremented, with an explicit behaviour on overflow
+#[cfg(numbers)]
+pub(crate) trait Step: Copy + Debug {
+    /// Returns the value plus one, or `None` if it overflows
+    fn checked_increment(self) -> Option<Self>;
+    /// Returns the value minus one, or `None` if it overflows
+    fn checked_decrement(self) -> Option<Self>;
+    /// Returns the value plus one, wrapping around on overflow
+    fn wrapping_increment(self) -> Self;
+    /// Returns the value minus one, wrapping around on overflow
+    fn wrapping_decrement(self) -> Self;
+}
+
+macro_rules! integer_values {
+    ($($t:ty),*) => {
+        $(
+            #[cfg(numbers)]
+            impl Negligible for $t {
+                fn negligible(_old: Self, _new: Self, _epsilon: f64) -> bool {
+                    false
+                }
+            }
+
+            #[cfg(numbers)]
+            impl Step for $t {
+                fn checked_increment(self) -> Option<Self> {
+                    self.checked_add(1)
+                }
+
+                fn checked_decrement(self) -> Option<Self> {
+                    self.checked_sub(1)
+                }
+
+                fn wrapping_increment(self) -> Self {
+                    self.wrapping_add(1)
+                }
+
+                fn wrapping_decrement(self) -> Self {
+                    self.wrapping_sub(1)
+                }
+            }
+        )*
+    };
+}
+
+macro_rules! float_values {
+    ($($t:ty),*) => {
+        $(
+            #[cfg(numbers)]
+            impl Negligible for $t {
+                fn negligible(old: Self, new: Self, epsilon: f64) -> bool {
+                    ((new - old).abs() as f64) < epsilon
+                }
+            }
+
+            // The floating point numbers do not overflow, they saturate to the infinities
+            #[cfg(numbers)]
+            impl Step for $t {
+                fn checked_increment(self) -> Option<Self> {
+                    Some(self + 1.0)
+                }
+
+                fn checked_decrement(self) -> Option<Self> {
+                    Some(self - 1.0)
+                }
+
+                fn wrapping_increment(self) -> Self {
+                    self + 1.0
+                }
+
+                fn wrapping_decrement(self) -> Self {
+                    self - 1.0
+                }
+            }
+        )*
+    };
+}
+
+integer_values!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize);
+float_values!(f32, f64);
+
+/// Returns the value plus one. On overflow, panics in debug builds or with the `strict-arithmetic`
+/// feature, and wraps around otherwise.
+#[cfg(numbers)]
+pub(crate) fn increment<T: Step>(value: T) -> T {
+    match value.checked_increment() {
+        Some(next) => next,
+        None if cfg!(any(debug_assertions, feature = "strict-arithmetic")) => {
+            panic!("Overflow when incrementing the managed value {:?}", value)
+        }
+        None => value.wrapping_increment(),
+    }
+}
+
+/// Returns the value minus one. On overflow, panics in debug builds or with the `strict-arithmetic`
+/// feature, and wraps around otherwise.
+#[cfg(numbers)]
+pub(crate) fn decrement<T: Step>(value: T) -> T {
+    match value.checked_decrement() {
+        Some(next) => next,
+        None if cfg!(any(debug_assertions, feature = "strict-arithmetic")) => {
+            panic!("Overflow when decrementing the managed value {:?}", value)
+        }
+        None => value.wrapping_decrement(),
+    }
+}
+
+#[cfg(numbers)]
+impl<T: Negligible> Negligible for Option<T> {
+    fn negligible(old: Self, new: Self, epsilon: f64) -> bool {
+        match (old, new) {
+            (Some(old), Some(new)) => T::negligible(old, new, epsilon),
+            _ => false,
+        }
+    }
+}
+
+/// The default backend. The previous state of a resource is saved on a trail the first time it is
+/// modified in a level, and restoring a level puts back the saved states.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Trailing;
+
+impl TrailBackend for Trailing {
+    type Store<T: Copy + PartialEq + Debug + Send + Sync> = NumberStore<T>;
+}
+
+/// A state for a managed resource
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub(crate) struct NumberState<T> {
+    /// Index of the resource in the asociated vector in the trail
+    pub id: usize,
+    /// Clock of the resource. If different from the clock of the manager, the data needs to be saved on the trail if modified
+    pub clock: usize,
+    /// The value of the managed resource
+    pub value: T,
+}
+
+/// Number of saved states kept inline in the trail of a store
+#[cfg(feature = "smallvec")]
+const INLINE_STORE_TRAIL: usize = 4;
+
+/// Storage of the saved states of a store
+#[cfg(not(feature = "smallvec"))]
+type StoreTrail<T> = Vec<NumberState<T>>;
+/// Storage of the saved states of a store. The first states are kept inline so that shallow
+/// searches never allocate
+#[cfg(feature = "smallvec")]
+type StoreTrail<T> = smallvec::SmallVec<[NumberState<T>; INLINE_STORE_TRAIL]>;
+
+/// Number of saved states in each chunk of the trail of a store
+const TRAIL_CHUNK: usize = 1 << 12;
+
+/// The trail of a store, split in chunks of `TRAIL_CHUNK` states. Pushing a state never moves the
+/// states already saved, so that the trail does not need to be copied when a deep search reaches
+/// its high-water mark. The chunks are kept when the trail shrinks, to be reused by the next levels.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+struct ChunkedTrail<T> {
+    chunks: Vec<StoreTrail<T>>,
+    len: usize,
+}
+
+impl<T> ChunkedTrail<T> {
+    fn new() -> Self {
+        Self {
+            chunks: vec![],
+            len: 0,
+        }
+    }
+}
+
+impl<T: Copy> ChunkedTrail<T> {
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn get(&self, index: usize) -> NumberState<T> {
+        self.chunks[index / TRAIL_CHUNK][index % TRAIL_CHUNK]
+    }
+
+    fn push(&mut self, state: NumberState<T>) {
+        let chunk = self.len / TRAIL_CHUNK;
+        if chunk == self.chunks.len() {
+            // The first chunk grows as needed, so that shallow searches only use a small trail
+            let trail = if chunk == 0 {
+                StoreTrail::new()
+            } else {
+                StoreTrail::with_capacity(TRAIL_CHUNK)
+            };
+            self.chunks.push(trail);
+        }
+        self.chunks[chunk].push(state);
+        self.len += 1;
+    }
+
+    /// Iterates over the states saved from the given index until the top of the trail
+    fn iter_from(&self, start: usize) -> impl Iterator<Item = &NumberState<T>> {
+        self.chunks[start / TRAIL_CHUNK..].iter().flat_map(|chunk| chunk.iter()).skip(start % TRAIL_CHUNK)
+    }
+
+    fn truncate(&mut self, len: usize) {
+        for (i, chunk) in self.chunks.iter_mut().enumerate().skip(len / TRAIL_CHUNK) {
+            if chunk.is_empty() {
+                break;
+            }
+            chunk.truncate(len.saturating_sub(i * TRAIL_CHUNK));
+        }
+        self.len = len;
+    }
+
+    fn clear(&mut self) {
+        self.truncate(0);
+    }
+
+    fn capacity(&self) -> usize {
+        self.chunks.iter().map(|chunk| chunk.capacity()).sum()
+    }
+}
+
+/// This structure keeps track of the first state saved on the trail of a store for a given level
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub(crate) struct StoreLevel {
+    /// Clock of the level
+    pub clock: usize,
+    /// The length of the trail before the first state of the level was saved
+    pub trail_size: usize,
+    /// The number of saved ranges before the first range of the level was saved
+    pub ranges: usize,
+}
+
+/// The previous values and clocks of a range of resources, saved at once by `set_range`
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+struct SavedRange {
+    /// Index of the first resource of the range
+    first: usize,
+    /// Number of resources in the range
+    len: usize,
+}
+
+/// The states of all the managed resources of a given type, as well as the states that have been saved
+/// on the trail for these resources. A level is only recorded in a store when a state is saved in it,
+/// so that levels without any modification cost nothing.
+///
+/// The values and the clocks of the resources are kept in two separate arrays, so that reading the
+/// values (e.g., in a heuristic scanning many resources) does not load the clocks in the cache.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct NumberStore<T> {
+    values: Vec<T>,
+    clocks: Vec<usize>,
+    trail: ChunkedTrail<T>,
+    /// The saved ranges, whose values and clocks are stacked in `range_values` and `range_clocks`
+    ranges: Vec<SavedRange>,
+    range_values: Vec<T>,
+    range_clocks: Vec<usize>,
+    levels: Vec<StoreLevel>,
+    /// Number of entries pushed on the trail by each resource
+    #[cfg(feature = "instrument")]
+    trail_counts: Vec<u64>,
+}
+
+impl<T> Default for NumberStore<T> {
+    fn default() -> Self {
+        Self {
+            values: vec![],
+            clocks: vec![],
+            trail: ChunkedTrail::new(),
+            ranges: vec![],
+            range_values: vec![],
+            range_clocks: vec![],
+            levels: vec![],
+            #[cfg(feature = "instrument")]
+            trail_counts: vec![],
+        }
+    }
+}
+
+impl<T: Copy + PartialEq> NumberStore<T> {
+    /// Records the level identified by `clock` in the store, if it is not already recorded
+    fn record_level(&mut self, clock: usize) {
+        if self.levels.last().map(|level| level.clock) != Some(clock) {
+            self.levels.push(StoreLevel {
+                clock,
+                trail_size: self.trail.len(),
+                ranges: self.ranges.len(),
+            });
+        }
+    }
+
+    /// Returns the clock with which the state of a resource, whose clock is `curr_clock`, is saved in the
+    /// level identified by `clock`. A resource created in a level that has since been restored keeps the
+    /// clock of this level, which is more recent than the current one. No level has this clock anymore,
+    /// and the resource is saved as if it was created at the root.
+    fn saved_clock(curr_clock: usize, clock: usize) -> usize {
+        if curr_clock > clock {
+            0
+        } else {
+            curr_clock
+        }
+    }
+
+    /// Saves the current state of a resource on the trail, in the level identified by `clock`
+    fn save_on_trail(&mut self, clock: usize, state: NumberState<T>) {
+        self.record_level(clock);
+        self.trail.push(state);
+        #[cfg(feature = "instrument")]
+        {
+            if self.trail_counts.len() <= state.id {
+                self.trail_counts.resize(self.values.len(), 0);
+            }
+            self.trail_counts[state.id] += 1;
+        }
+    }
+}
+
+impl<T: Copy> NumberStore<T> {
+    /// Iterates over the resources created or modified in the level identified by `clock` or in a more
+    /// recent level, with their current value
+    pub(crate) fn modified_since(&self, clock: usize) -> impl Iterator<Item = (usize, T)> + '_ {
+        self.clocks
+            .iter()
+            .zip(self.values.iter())
+            .enumerate()
+            .filter(move |(_, (c, _))| **c >= clock)
+            .map(|(id, (_, value))| (id, *value))
+    }
+}
+
+/// The arrays of the store are written column by column. The trail counts of the `instrument` feature
+/// are not part of the snapshot.
+#[cfg(feature = "snapshot")]
+impl<T: Copy + SnapshotValue> Snapshot for NumberStore<T> {
+    fn write_snapshot(&self, out: &mut Vec<u8>) {
+        write_usize(self.values.len(), out);
+        self.values.iter().for_each(|value| value.write(out));
+        self.clocks.iter().for_each(|clock| write_usize(*clock, out));
+        write_usize(self.trail.len(), out);
+        self.trail.iter_from(0).for_each(|state| write_usize(state.id, out));
+        self.trail.iter_from(0).for_each(|state| write_usize(state.clock, out));
+        self.trail.iter_from(0).for_each(|state| state.value.write(out));
+        write_usize(self.ranges.len(), out);
+        self.ranges.iter().for_each(|range| write_usize(range.first, out));
+        self.ranges.iter().for_each(|range| write_usize(range.len, out));
+        self.range_values.iter().for_each(|value| value.write(out));
+        self.range_clocks.iter().for_each(|clock| write_usize(*clock, out));
+        write_usize(self.levels.len(), out);
+        self.levels.iter().for_each(|level| write_usize(level.clock, out));
+        self.levels.iter().for_each(|level| write_usize(level.trail_size, out));
+        self.levels.iter().for_each(|level| write_usize(level.ranges, out));
+    }
+
+    fn read_snapshot(input: &mut &[u8]) -> Result<Self, SnapshotError> {
+        let mut store = Self::default();
+        let len = read_usize(input)?;
+        store.values = read_column(input, len, T::read)?;
+        store.clocks = read_column(input, len, read_usize)?;
+        let trail = read_usize(input)?;
+        let ids = read_column(input, trail, read_usize)?;
+        let clocks = read_column(input, trail, read_usize)?;
+        let values = read_column(input, trail, T::read)?;
+        for ((id, clock), value) in ids.into_iter().zip(clocks).zip(values) {
+            if id >= len {
+                return Err(SnapshotError::Corrupted);
+            }
+            store.trail.push(NumberState { id, clock, value });
+        }
+        let ranges = read_usize(input)?;
+        let firsts = read_column(input, ranges, read_usize)?;
+        let lens = read_column(input, ranges, read_usize)?;
+        store.ranges = firsts.into_iter().zip(lens).map(|(first, len)| SavedRange { first, len }).collect();
+        if store.ranges.iter().any(|range| range.first.checked_add(range.len).is_none_or(|end| end > len)) {
+            return Err(SnapshotError::Corrupted);
+        }
+        let saved = store.ranges.iter().map(|range| range.len).sum();
+        store.range_values = read_column(input, saved, T::read)?;
+        store.range_clocks = read_column(input, saved, read_usize)?;
+        let levels = read_usize(input)?;
+        let clocks = read_column(input, levels, read_usize)?;
+        let trail_sizes = read_column(input, levels, read_usize)?;
+        let ranges = read_column(input, levels, read_usize)?;
+        store.levels = clocks
+            .into_iter()
+            .zip(trail_sizes)
+            .zip(ranges)
+            .map(|((clock, trail_size), ranges)| StoreLevel { clock, trail_size, ranges })
+            .collect();
+        let levels_are_valid = store.levels.windows(2).all(|pair| pair[0].trail_size <= pair[1].trail_size && pair[0].ranges <= pair[1].ranges)
+            && store.levels.last().is_none_or(|level| level.trail_size <= trail && level.ranges <= store.ranges.len());
+        if !levels_are_valid {
+            return Err(SnapshotError::Corrupted);
+        }
+        #[cfg(feature = "instrument")]
+        {
+            store.trail_counts = vec![0; len];
+        }
+        Ok(store)
+    }
+}
+
+impl<T: Copy + PartialEq + Debug + Send + Sync> Store<T> for NumberStore<T> {
+    fn manage(&mut self, clock: usize, value: T) -> usize {
+        let id = self.values.len();
+        self.values.push(value);
+        self.clocks.push(clock);
+        id
+    }
+
+    fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    fn reserve(&mut self, additional: usize) {
+        self.values.reserve(additional);
+        self.clocks.reserve(additional);
+    }
+
+    fn get(&self, id: usize) -> T {
+        self.values[id]
+    }
+
+    fn get_ref(&self, id: usize) -> &T {
+        &self.values[id]
+    }
+
+    /// The levels are scanned from the oldest one. In a level, a resource saved on the trail was saved
+    /// before any range including it.
+    fn root_value(&self, id: usize) -> T {
+        let mut saved = 0;
+        for (i, level) in self.levels.iter().enumerate() {
+            let (trail_end, ranges_end) = self.levels.get(i + 1).map_or((self.trail.len(), self.ranges.len()), |next| (next.trail_size, next.ranges));
+            if let Some(state) = self.trail.iter_from(level.trail_size).take(trail_end - level.trail_size).find(|state| state.id == id) {
+                return state.value;
+            }
+            for range in self.ranges[level.ranges..ranges_end].iter() {
+                if (range.first..range.first + range.len).contains(&id) {
+                    return self.range_values[saved + id - range.first];
+                }
+                saved += range.len;
+            }
+        }
+        self.values[id]
+    }
+
+    fn set(&mut self, clock: usize, id: usize, value: T) {
+        let curr = self.values[id];
+        if value != curr {
+            if self.clocks[id] != clock {
+                let saved = Self::saved_clock(self.clocks[id], clock);
+                if saved != clock {
+                    self.save_on_trail(clock, NumberState { id, clock: saved, value: curr });
+                }
+                self.clocks[id] = clock;
+            }
+            self.values[id] = value;
+        }
+    }
+
+    /// If a resource of the range has not been saved in the level yet, the values and clocks of the whole
+    /// range are saved as a single entry, instead of one entry per resource.
+    fn set_range(&mut self, clock: usize, first: usize, values: &[T]) {
+        let range = first..first + values.len();
+        if self.clocks[range.clone()].iter().any(|c| Self::saved_clock(*c, clock) != clock) {
+            self.record_level(clock);
+            self.ranges.push(SavedRange { first, len: values.len() });
+            self.range_values.extend_from_slice(&self.values[range.clone()]);
+            self.range_clocks.extend(self.clocks[range.clone()].iter().map(|c| Self::saved_clock(*c, clock)));
+            self.clocks[range.clone()].fill(clock);
+            #[cfg(feature = "instrument")]
+            {
+                self.trail_counts.resize(self.values.len(), 0);
+                self.trail_counts[range.clone()].iter_mut().for_each(|count| *count += 1);
+            }
+        }
+        self.values[range].copy_from_slice(values);
+    }
+
+    fn save(&mut self, _clock: usize, _depth: usize) {}
+
+    /// The ranges of a level are restored first, from the most recent one. Then, the other saved states
+    /// are restored. They all come from the same level, in which a resource is saved at most once, and
+    /// before any range including it. Hence, the order in which they are restored does not matter. When
+    /// they cover a contiguous range of resources (e.g., after updating a whole array of variables),
+    /// they are copied as one block in each array.
+    fn restore(&mut self, clock: usize) {
+        let (start, ranges) = match self.levels.last() {
+            Some(level) if level.clock == clock => (level.trail_size, level.ranges),
+            _ => return,
+        };
+        self.levels.pop();
+        while self.ranges.len() > ranges {
+            let range = self.ranges.pop().unwrap();
+            let saved = self.range_values.len() - range.len;
+            let ids = range.first..range.first + range.len;
+            self.values[ids.clone()].copy_from_slice(&self.range_values[saved..]);
+            self.clocks[ids].copy_from_slice(&self.range_clocks[saved..]);
+            self.range_values.truncate(saved);
+            self.range_clocks.truncate(saved);
+        }
+        let saved = self.trail.len() - start;
+        if saved == 0 {
+            return;
+        }
+        let first = self.trail.get(start).id;
+        if self.trail.iter_from(start).enumerate().all(|(i, state)| state.id == first + i) {
+            let range = first..first + saved;
+            for (value, state) in self.values[range.clone()].iter_mut().zip(self.trail.iter_from(start)) {
+                *value = state.value;
+            }
+            for (clock, state) in self.clocks[range].iter_mut().zip(self.trail.iter_from(start)) {
+                *clock = state.clock;
+            }
+        } else {
+            for state in self.trail.iter_from(start) {
+                self.values[state.id] = state.value;
+                self.clocks[state.id] = state.clock;
+            }
+        }
+        self.trail.truncate(start);
+    }
+
+    fn restore_size(&self, clock: usize) -> usize {
+        match self.levels.last() {
+            Some(level) if level.clock == clock => {
+                let ranges = self.ranges[level.ranges..].iter().map(|range| range.len).sum::<usize>();
+                self.trail.len() - level.trail_size + ranges
+            }
+            _ => 0,
+        }
+    }
+
+    fn trail_len(&self) -> usize {
+        self.trail.len() + self.range_values.len()
+    }
+
+    fn clear(&mut self) {
+        self.values.clear();
+        self.clocks.clear();
+        self.trail.clear();
+        self.ranges.clear();
+        self.range_values.clear();
+        self.range_clocks.clear();
+        self.levels.clear();
+        #[cfg(feature = "instrument")]
+        self.trail_counts.clear();
+    }
+
+    fn memory_usage(&self) -> StoreMemory {
+        StoreMemory {
+            values: bytes::<T>(self.values.capacity()) + bytes::<usize>(self.clocks.capacity()),
+            saved: bytes::<NumberState<T>>(self.trail.capacity())
+                + bytes::<SavedRange>(self.ranges.capacity())
+                + bytes::<T>(self.range_values.capacity())
+                + bytes::<usize>(self.range_clocks.capacity())
+                + bytes::<StoreLevel>(self.levels.capacity()),
+        }
+    }
+
+    fn debug_validate(&self, clock: usize) {
+        let len = self.values.len();
+        assert_eq!(len, self.clocks.len(), "The values and the clocks have different lengths");
+        for pair in self.levels.windows(2) {
+            assert!(pair[0].clock < pair[1].clock, "The clocks of the store levels are not increasing: {:?}", pair);
+            assert!(pair[0].trail_size <= pair[1].trail_size, "The trail sizes are not increasing: {:?}", pair);
+            assert!(pair[0].ranges <= pair[1].ranges, "The saved ranges are not increasing: {:?}", pair);
+        }
+        match self.levels.first() {
+            Some(level) => assert_eq!((0, 0), (level.trail_size, level.ranges), "States saved outside of a level"),
+            None => assert_eq!((0, 0), (self.trail.len(), self.ranges.len()), "States saved outside of a level"),
+        }
+        if let Some(level) = self.levels.last() {
+            assert!(level.clock <= clock, "The store has a level {} more recent than the current one {}", level.clock, clock);
+        }
+        for (i, level) in self.levels.iter().enumerate() {
+            let end = self.levels.get(i + 1).map_or(self.trail.len(), |next| next.trail_size);
+            let mut saved = std::collections::HashSet::new();
+            for state in self.trail.iter_from(level.trail_size).take(end - level.trail_size) {
+                assert!(state.id < len, "The trail refers to the resource {} of a store with {} resources", state.id, len);
+                assert!(saved.insert(state.id), "The resource {} is saved twice in the level {}", state.id, level.clock);
+                assert!(state.clock < level.clock, "The resource {} is saved with a clock from the future", state.id);
+            }
+        }
+        for range in self.ranges.iter() {
+            assert!(range.first + range.len <= len, "The saved range {:?} exceeds the {} resources", range, len);
+        }
+        let saved = self.ranges.iter().map(|range| range.len).sum::<usize>();
+        assert_eq!(saved, self.range_values.len(), "The saved ranges and their values have different lengths");
+        assert_eq!(saved, self.range_clocks.len(), "The saved ranges and their clocks have different lengths");
+    }
+
+    fn trailed(&self, clock: usize) -> Vec<(usize, T)> {
+        let Ok(i) = self.levels.binary_search_by_key(&clock, |level| level.clock) else {
+            return vec![];
+        };
+        let level = self.levels[i];
+        let (trail_end, ranges_end) = self.levels.get(i + 1).map_or((self.trail.len(), self.ranges.len()), |next| (next.trail_size, next.ranges));
+        let mut trailed = self.trail.iter_from(level.trail_size).take(trail_end - level.trail_size).map(|state| (state.id, state.value)).collect::<Vec<_>>();
+        let mut saved = self.ranges[..level.ranges].iter().map(|range| range.len).sum::<usize>();
+        for range in self.ranges[level.ranges..ranges_end].iter() {
+            trailed.extend((range.first..range.first + range.len).zip(self.range_values[saved..saved + range.len].iter().copied()));
+            saved += range.len;
+        }
+        trailed
+    }
+
+    #[cfg(feature = "instrument")]
+    fn trail_counts(&self) -> &[u64] {
+        &self.trail_counts
+    }
+
+    #[cfg(feature = "unchecked")]
+    unsafe fn get_unchecked(&self, id: usize) -> T {
+        *self.values.get_unchecked(id)
+    }
+
+    #[cfg(feature = "unchecked")]
+    unsafe fn set_unchecked(&mut self, clock: usize, id: usize, value: T) {
+        let curr = *self.values.get_unchecked(id);
+        if value != curr {
+            let curr_clock = *self.clocks.get_unchecked(id);
+            if curr_clock != clock {
+                let saved = Self::saved_clock(curr_clock, clock);
+                if saved != clock {
+                    self.save_on_trail(clock, NumberState { id, clock: saved, value: curr });
+                }
+                *self.clocks.get_unchecked_mut(id) = clock;
+            }
+            *self.values.get_unchecked_mut(id) = value;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_number_store {
+    #[cfg(numbers)]
+    use super::Negligible;
+    use super::{NumberStore, TRAIL_CHUNK};
+    use crate::Store;
+
+    #[test]
+    fn state_saved_once_per_level() {
+        let mut store = NumberStore::default();
+        let id = store.manage(0, 3u32);
+        store.set(0, id, 4);
+        assert_eq!(0, store.restore_size(0));
+        store.set(1, id, 5);
+        store.set(1, id, 6);
+        store.set(1, id, 6);
+        assert_eq!(1, store.restore_size(1));
+        store.restore(1);
+        assert_eq!(4, store.get(id));
+    }
+
+    #[test]
+    fn resources_of_restored_levels_are_saved_as_root_resources() {
+        let mut store = NumberStore::default();
+        let old = store.manage(0, 0u32);
+        store.set(1, old, 1);
+        // Created in the level 2, which is restored before the resource is modified in the level 1
+        let id = store.manage(2, 7u32);
+        store.restore(2);
+        store.set(1, id, 8);
+        store.set_range(1, old, &[2, 9]);
+        store.debug_validate(1);
+        store.restore(1);
+        assert_eq!((0, 7), (store.get(old), store.get(id)));
+        // At the root, nothing is saved
+        store.set(0, id, 10);
+        assert_eq!(0, store.trail_len());
+    }
+
+    #[test]
+    fn restore_contiguous_and_scattered_blocks() {
+        let mut store = NumberStore::default();
+        let ids = (0..6).map(|i| store.manage(0, i as u32)).collect::<Vec<usize>>();
+        for id in ids.iter().copied().skip(1).take(4) {
+            store.set(1, id, 42);
+        }
+        store.restore(1);
+        for id in ids.iter().copied() {
+            assert_eq!(id as u32, store.get(id));
+        }
+        for id in [4, 0, 5] {
+            store.set(2, id, 42);
+        }
+        store.restore(2);
+        for id in ids.iter().copied() {
+            assert_eq!(id as u32, store.get(id));
+        }
+    }
+
+    #[test]
+    fn levels_without_modification_are_skipped() {
+        let mut store = NumberStore::default();
+        let id = store.manage(0, 0u8);
+        store.set(1, id, 1);
+        store.restore(3);
+        store.restore(2);
+        assert_eq!(1, store.get(id));
+        store.restore(1);
+        assert_eq!(0, store.get(id));
+    }
+
+    #[test]
+    #[cfg(numbers)]
+    fn only_small_float_changes_are_negligible() {
+        assert!(f64::negligible(1.0, 1.0 + 1e-9, 1e-6));
+        assert!(!f64::negligible(1.0, 1.1, 1e-6));
+        assert!(!f32::negligible(1.0, 1.0 + 1e-3, 0.0));
+        assert!(!f64::negligible(1.0, f64::NAN, 1e-6));
+        assert!(Option::<f32>::negligible(Some(2.0), Some(2.0), 1e-6));
+        assert!(!Option::<f64>::negligible(None, Some(2.0), 1e-6));
+        assert!(!u32::negligible(1, 2, 10.0));
+    }
+
+    #[test]
+    fn ranges_are_saved_once() {
+        let mut store = NumberStore::default();
+        let ids = (0..10).map(|i| store.manage(0, i as u32)).collect::<Vec<usize>>();
+        store.set(1, 2, 42);
+        store.set_range(1, 1, &[7; 5]);
+        store.set_range(1, 4, &[8; 6]);
+        store.set_range(1, 2, &[5; 3]);
+        assert_eq!(1, store.trail.len());
+        assert_eq!(2, store.ranges.len());
+        assert_eq!(12, store.restore_size(1));
+        store.set(2, 5, 9);
+        store.set_range(2, 0, &[1, 2]);
+        store.restore(2);
+        assert_eq!(0, store.get(0));
+        assert_eq!(8, store.get(5));
+        assert_eq!(2, store.ranges.len());
+        store.restore(1);
+        for id in ids.iter().copied() {
+            assert_eq!(id as u32, store.get(id));
+        }
+        assert_eq!(0, store.range_values.len());
+    }
+
+    #[test]
+    fn restore_across_trail_chunks() {
+        let mut store = NumberStore::default();
+        let ids = (0..3 * TRAIL_CHUNK).map(|i| store.manage(0, i)).collect::<Vec<usize>>();
+        for id in ids.iter().copied().take(TRAIL_CHUNK + 2) {
+            store.set(1, id, 0);
+        }
+        for id in ids.iter().copied().rev() {
+            store.set(2, id, 1);
+        }
+        assert_eq!(3 * TRAIL_CHUNK, store.restore_size(2));
+        store.restore(2);
+        store.restore(1);
+        for id in ids.iter().copied() {
+            assert_eq!(id, store.get(id));
+        }
+        assert_eq!(0, store.trail.len());
+        for id in ids.iter().copied() {
+            store.set(3, id, 2);
+        }
+        store.restore(3);
+        assert_eq!(5, store.get(5));
+    }
+
+    #[test]
+    #[cfg(feature = "smallvec")]
+    fn shallow_search_stays_inline() {
+        let mut store = NumberStore::default();
+        let id = store.manage(0, 0usize);
+        for clock in 1..5 {
+            store.set(clock, id, clock);
+        }
+        assert!(!store.trail.chunks[0].spilled());
+        for clock in (1..5).rev() {
+            store.restore(clock);
+        }
+        assert_eq!(0, store.get(id));
+    }
+}
@@ -0,0 +1,147 @@
+//Copyright (c) 2023 X. Gillard, A. Dubray
+//
+//Permission is hereby granted, free of charge, to any person obtaining a copy
+//of this software and associated documentation files (the "Software"), to deal
+//in the Software without restriction, including without limitation the rights
+//to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+//copies of the Software, and to permit persons to whom the Software is
+//furnished to do so, subject to the following conditions:
+//
+//The above copyright notice and this permission notice shall be included in all
+//copies or substantial portions of the Software.
+//
+//THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+//IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+//AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+//OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+//SOFTWARE.
+
+
+//! The objective of an optimization problem. The objective combines a managed value, e.g. a bound
+//! on the cost of the solutions below the current node, which is restored with the levels, and the
+//! value of the best solution found so far (the incumbent), which is not: finding a solution deep in
+//! the search must still constrain the nodes explored after backtracking.
+
+use crate::*;
+
+/// A managed objective value, with the best value found so far that survives the restorations
+///
+/// #Example
+///
+#[cfg_attr(feature = "i32", doc = "```")]
+#[cfg_attr(not(feature = "i32"), doc = "```ignore")]
+/// use search_trail::{Objective, SaveAndRestore, StateManager};
+///
+/// let mut mgr = StateManager::default();
+/// let mut cost = Objective::minimize(&mut mgr, 0i32);
+/// mgr.save_state();
+/// cost.set(&mut mgr, 10);
+/// // A solution of cost 10 is found
+/// assert!(cost.tighten(cost.value(&mgr)));
+/// mgr.restore_state();
+/// assert_eq!(0, cost.value(&mgr));
+/// assert_eq!(Some(10), cost.incumbent());
+/// assert!(cost.is_improving(9) && !cost.is_improving(10));
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct Objective<T: ScopedValue + PartialOrd> {
+    /// The managed value of the objective
+    handle: T::Handle,
+    /// The value of the best solution found so far
+    incumbent: Option<T>,
+    /// True if the objective is minimized, false if it is maximized
+    minimize: bool,
+}
+
+impl<T: ScopedValue + PartialOrd> Objective<T> {
+    /// Creates an objective to minimize, whose managed value is initialized to the given one
+    pub fn minimize<B: TrailBackend>(mgr: &mut StateManager<B>, value: T) -> Self {
+        Self {
+            handle: T::manage(mgr, value),
+            incumbent: None,
+            minimize: true,
+        }
+    }
+
+    /// Creates an objective to maximize, whose managed value is initialized to the given one
+    pub fn maximize<B: TrailBackend>(mgr: &mut StateManager<B>, value: T) -> Self {
+        Self {
+            minimize: false,
+            ..Self::minimize(mgr, value)
+        }
+    }
+
+    /// Returns the handle of the managed value
+    pub fn handle(&self) -> T::Handle {
+        self.handle
+    }
+
+    /// Returns the managed value
+    pub fn value<B: TrailBackend>(&self, mgr: &StateManager<B>) -> T {
+        T::get(mgr, self.handle)
+    }
+
+    /// Sets the managed value and returns the new value
+    pub fn set<B: TrailBackend>(&self, mgr: &mut StateManager<B>, value: T) -> T {
+        T::set(mgr, self.handle, value)
+    }
+
+    /// Returns the value of the best solution found so far, if any
+    pub fn incumbent(&self) -> Option<T> {
+        self.incumbent
+    }
+
+    /// Returns true if the value is strictly better than the incumbent, or if there is no incumbent
+    pub fn is_improving(&self, value: T) -> bool {
+        match self.incumbent {
+            None => true,
+            Some(incumbent) if self.minimize => value < incumbent,
+            Some(incumbent) => value > incumbent,
+        }
+    }
+
+    /// Returns true if the managed value is improving. When the managed value is a bound on the
+    /// solutions below the current node, the node can be pruned if it is not.
+    pub fn can_improve<B: TrailBackend>(&self, mgr: &StateManager<B>) -> bool {
+        self.is_improving(self.value(mgr))
+    }
+
+    /// Records a solution with the given value. Returns true, and makes it the incumbent, if it is
+    /// improving. The incumbent is not modified by the restorations.
+    pub fn tighten(&mut self, value: T) -> bool {
+        let improving = self.is_improving(value);
+        if improving {
+            self.incumbent = Some(value);
+        }
+        improving
+    }
+}
+
+#[cfg(all(test, feature = "f64", feature = "u32"))]
+mod test_objective {
+    use crate::*;
+
+    #[test]
+    fn incumbent_survives_the_restorations() {
+        let mut mgr = StateManager::default();
+        let mut profit = Objective::maximize(&mut mgr, 0.0f64);
+        let mut makespan = Objective::minimize(&mut mgr, 0u32);
+        assert!(profit.can_improve(&mgr) && profit.incumbent().is_none());
+        for (p, m) in [(5.0, 10), (3.0, 8), (7.5, 12)] {
+            mgr.save_state();
+            profit.set(&mut mgr, p);
+            makespan.set(&mut mgr, m);
+            profit.tighten(p);
+            makespan.tighten(m);
+            mgr.restore_state();
+        }
+        assert_eq!(Some(7.5), profit.incumbent());
+        assert_eq!(Some(8), makespan.incumbent());
+        assert!(!profit.tighten(7.5) && !makespan.tighten(9));
+        assert_eq!(0.0, profit.value(&mgr));
+        assert!(!profit.can_improve(&mgr) && makespan.can_improve(&mgr));
+        assert_eq!(0, mgr.get_u32(makespan.handle()));
+    }
+}
@@ -0,0 +1,185 @@
+//Copyright (c) 2023 X. Gillard, A. Dubray
+//
+//Permission is hereby granted, free of charge, to any person obtaining a copy
+//of this software and associated documentation files (the "Software"), to deal
+//in the Software without restriction, including without limitation the rights
+//to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+//copies of the Software, and to permit persons to whom the Software is
+//furnished to do so, subject to the following conditions:
+//
+//The above copyright notice and this permission notice shall be included in all
+//copies or substantial portions of the Software.
+//
+//THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+//IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+//AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+//OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+//SOFTWARE.
+
+
+//! An arena of arbitrary objects whose insertions and removals are trailed by a state manager. The
+//! objects inserted in a level are removed when the level is restored, and the objects removed in a
+//! level reappear. This is useful to allocate objects during the search (e.g., nodes of explanations or
+//! decision diagrams) that must disappear on backtrack.
+
+use crate::{BoolManager, ReversibleBool, ReversibleUsize, StateManager, TrailBackend, UsizeManager};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// A key to an object of an `ObjectArena`. The key of an object removed by a restoration is not valid
+/// anymore, even if another object is inserted in its slot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "fuzz", derive(arbitrary::Arbitrary))]
+pub struct ObjectKey {
+    index: usize,
+    generation: u32,
+}
+
+/// An arena of objects of type `T`, whose insertions and removals are undone by restoring the state
+/// of the manager used to modify it. The objects themselves are not trailed: they can not be modified
+/// once inserted.
+///
+/// Removed objects are kept in memory until the level in which they were inserted is restored, so
+/// that they can reappear.
+///
+/// #Example
+///
+/// ```
+/// use search_trail::{ObjectArena, SaveAndRestore, StateManager};
+///
+/// let mut mgr = StateManager::default();
+/// let mut arena = ObjectArena::new(&mut mgr);
+/// let a = arena.insert(&mut mgr, "a".to_string());
+/// mgr.save_state();
+/// let b = arena.insert(&mut mgr, "b".to_string());
+/// arena.remove(&mut mgr, a);
+/// assert_eq!(None, arena.get(&mgr, a));
+/// mgr.restore_state();
+/// assert_eq!(Some(&"a".to_string()), arena.get(&mgr, a));
+/// assert_eq!(None, arena.get(&mgr, b));
+/// ```
+#[derive(Debug, Clone)]
+pub struct ObjectArena<T> {
+    /// The objects, including the objects beyond `len` that have been removed by a restoration
+    objects: Vec<T>,
+    /// The generation of each slot, incremented each time a new object is inserted in it
+    generations: Vec<u32>,
+    /// For each slot, true if its object has not been removed
+    alive: Vec<ReversibleBool>,
+    /// Number of slots of the arena in the current state
+    len: ReversibleUsize,
+    /// Number of objects that have not been removed
+    count: ReversibleUsize,
+}
+
+impl<T> ObjectArena<T> {
+    /// Creates an empty arena, trailed by the given manager
+    pub fn new<B: TrailBackend>(mgr: &mut StateManager<B>) -> Self {
+        Self {
+            objects: vec![],
+            generations: vec![],
+            alive: vec![],
+            len: mgr.manage_usize(0),
+            count: mgr.manage_usize(0),
+        }
+    }
+
+    /// Inserts an object in the arena and returns its key
+    pub fn insert<B: TrailBackend>(&mut self, mgr: &mut StateManager<B>, object: T) -> ObjectKey {
+        let index = mgr.get_usize(self.len);
+        // The objects beyond the current length have been inserted in restored levels
+        self.objects.truncate(index);
+        self.objects.push(object);
+        if index < self.alive.len() {
+            self.generations[index] += 1;
+            mgr.set_bool(self.alive[index], true);
+        } else {
+            self.generations.push(0);
+            self.alive.push(mgr.manage_bool(true));
+        }
+        mgr.increment_usize(self.len);
+        mgr.increment_usize(self.count);
+        ObjectKey {
+            index,
+            generation: self.generations[index],
+        }
+    }
+
+    /// Returns the object of the given key, if it is in the arena
+    pub fn get<B: TrailBackend>(&self, mgr: &StateManager<B>, key: ObjectKey) -> Option<&T> {
+        self.contains(mgr, key).then(|| &self.objects[key.index])
+    }
+
+    /// Returns true if the object of the given key is in the arena
+    pub fn contains<B: TrailBackend>(&self, mgr: &StateManager<B>, key: ObjectKey) -> bool {
+        key.index < mgr.get_usize(self.len)
+            && self.generations[key.index] == key.generation
+            && mgr.get_bool(self.alive[key.index])
+    }
+
+    /// Removes the object of the given key from the arena. Returns false if it was not in the arena.
+    pub fn remove<B: TrailBackend>(&mut self, mgr: &mut StateManager<B>, key: ObjectKey) -> bool {
+        if !self.contains(mgr, key) {
+            return false;
+        }
+        mgr.set_bool(self.alive[key.index], false);
+        mgr.decrement_usize(self.count);
+        true
+    }
+
+    /// Returns the number of objects in the arena
+    pub fn len<B: TrailBackend>(&self, mgr: &StateManager<B>) -> usize {
+        mgr.get_usize(self.count)
+    }
+
+    /// Returns true if there is no object in the arena
+    pub fn is_empty<B: TrailBackend>(&self, mgr: &StateManager<B>) -> bool {
+        self.len(mgr) == 0
+    }
+
+    /// Iterates over the objects of the arena, with their keys
+    pub fn iter<'a, B: TrailBackend>(
+        &'a self,
+        mgr: &'a StateManager<B>,
+    ) -> impl Iterator<Item = (ObjectKey, &'a T)> + 'a {
+        (0..mgr.get_usize(self.len))
+            .filter(move |i| mgr.get_bool(self.alive[*i]))
+            .map(move |index| {
+                let key = ObjectKey {
+                    index,
+                    generation: self.generations[index],
+                };
+                (key, &self.objects[index])
+            })
+    }
+}
+
+#[cfg(test)]
+mod test_object_arena {
+    use super::ObjectArena;
+    use crate::{SaveAndRestore, StateManager};
+
+    #[test]
+    fn insertions_and_removals_are_restored() {
+        let mut mgr = StateManager::default();
+        let mut arena = ObjectArena::new(&mut mgr);
+        let a = arena.insert(&mut mgr, vec![1]);
+        let b = arena.insert(&mut mgr, vec![2]);
+        mgr.save_state();
+        assert!(arena.remove(&mut mgr, a));
+        assert!(!arena.remove(&mut mgr, a));
+        let c = arena.insert(&mut mgr, vec![3]);
+        assert_eq!(vec![(b, &vec![2]), (c, &vec![3])], arena.iter(&mgr).collect::<Vec<_>>());
+        mgr.restore_state();
+        assert_eq!(2, arena.len(&mgr));
+        assert!(arena.contains(&mgr, a));
+        assert!(!arena.contains(&mgr, c));
+        let d = arena.insert(&mut mgr, vec![4]);
+        assert_ne!(c, d);
+        assert!(!arena.contains(&mgr, c));
+        assert_eq!(Some(&vec![4]), arena.get(&mgr, d));
+    }
+}
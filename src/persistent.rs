@@ -0,0 +1,187 @@
+//Copyright (c) 2023 X. Gillard, A. Dubray
+//
+//Permission is hereby granted, free of charge, to any person obtaining a copy
+//of this software and associated documentation files (the "Software"), to deal
+//in the Software without restriction, including without limitation the rights
+//to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+//copies of the Software, and to permit persons to whom the Software is
+//furnished to do so, subject to the following conditions:
+//
+//The above copyright notice and this permission notice shall be included in all
+//copies or substantial portions of the Software.
+//
+//THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+//IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+//AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+//OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+//SOFTWARE.
+
+
+//! A trailing backend whose storage is made of persistent vectors. Cloning a manager using this backend
+//! shares the managed values and the trail with the original manager, so that a manager with millions
+//! of entries can be duplicated (e.g., for the workers of a portfolio) in near-constant time. The
+//! price to pay is that each access costs a logarithmic number of steps instead of a single one.
+
+use std::fmt::Debug;
+
+use im::Vector;
+
+use crate::memory::bytes;
+use crate::number::{NumberState, StoreLevel};
+use crate::{StateManager, Store, StoreMemory, TrailBackend};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// A trailing backend using persistent vectors, which can be cloned in near-constant time
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Persistent;
+
+impl TrailBackend for Persistent {
+    type Store<T: Copy + PartialEq + Debug + Send + Sync> = PersistentStore<T>;
+}
+
+/// A state manager that can be duplicated in near-constant time with `cheap_clone()`
+pub type PersistentStateManager = StateManager<Persistent>;
+
+impl StateManager<Persistent> {
+    /// Returns a copy of the manager sharing its storage with this one. The two managers are
+    /// independent: the modifications done in one of them are not visible in the other.
+    pub fn cheap_clone(&self) -> Self {
+        self.clone()
+    }
+}
+
+/// Same as `NumberStore`, but stored in persistent vectors
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct PersistentStore<T: Clone> {
+    states: Vector<NumberState<T>>,
+    trail: Vector<NumberState<T>>,
+    levels: Vector<StoreLevel>,
+}
+
+impl<T: Clone> Default for PersistentStore<T> {
+    fn default() -> Self {
+        Self {
+            states: Vector::new(),
+            trail: Vector::new(),
+            levels: Vector::new(),
+        }
+    }
+}
+
+impl<T: Copy + PartialEq + Debug + Send + Sync> Store<T> for PersistentStore<T> {
+    fn manage(&mut self, clock: usize, value: T) -> usize {
+        let id = self.states.len();
+        self.states.push_back(NumberState { id, clock, value });
+        id
+    }
+
+    fn len(&self) -> usize {
+        self.states.len()
+    }
+
+    fn get(&self, id: usize) -> T {
+        self.states[id].value
+    }
+
+    fn get_ref(&self, id: usize) -> &T {
+        &self.states[id].value
+    }
+
+    fn root_value(&self, id: usize) -> T {
+        // A resource is saved at most once per level, so its first saved state is the oldest one
+        self.trail.iter().find(|state| state.id == id).map_or(self.states[id].value, |state| state.value)
+    }
+
+    fn set(&mut self, clock: usize, id: usize, value: T) {
+        let mut curr = self.states[id];
+        if value != curr.value {
+            // As in `NumberStore`, a resource created in a restored level is saved as created at the root
+            if curr.clock > clock {
+                curr.clock = 0;
+            }
+            if curr.clock != clock {
+                if self.levels.last().map(|level| level.clock) != Some(clock) {
+                    self.levels.push_back(StoreLevel {
+                        clock,
+                        trail_size: self.trail.len(),
+                        ranges: 0,
+                    });
+                }
+                self.trail.push_back(curr);
+                self.states.set(id, NumberState { id, clock, value });
+            } else {
+                self.states[id].value = value;
+            }
+        }
+    }
+
+    fn save(&mut self, _clock: usize, _depth: usize) {}
+
+    fn restore(&mut self, clock: usize) {
+        let start = match self.levels.last() {
+            Some(level) if level.clock == clock => level.trail_size,
+            _ => return,
+        };
+        self.levels.pop_back();
+        for state in self.trail.slice(start..).into_iter() {
+            self.states.set(state.id, state);
+        }
+    }
+
+    fn restore_size(&self, clock: usize) -> usize {
+        match self.levels.last() {
+            Some(level) if level.clock == clock => self.trail.len() - level.trail_size,
+            _ => 0,
+        }
+    }
+
+    fn trail_len(&self) -> usize {
+        self.trail.len()
+    }
+
+    fn clear(&mut self) {
+        self.states.clear();
+        self.trail.clear();
+        self.levels.clear();
+    }
+
+    fn memory_usage(&self) -> StoreMemory {
+        // The nodes may be shared with other managers, the sizes are the ones of the contents
+        StoreMemory {
+            values: bytes::<NumberState<T>>(self.states.len()),
+            saved: bytes::<NumberState<T>>(self.trail.len()) + bytes::<StoreLevel>(self.levels.len()),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "usize"))]
+mod test_persistent {
+    use crate::{PersistentStateManager, SaveAndRestore, UsizeManager};
+
+    #[test]
+    fn clones_are_independent() {
+        let mut mgr = PersistentStateManager::new();
+        let handles = (0..1000).map(|i| mgr.manage_usize(i)).collect::<Vec<_>>();
+        mgr.save_state();
+        for h in handles.iter().copied() {
+            mgr.increment_usize(h);
+        }
+        let mut worker = mgr.cheap_clone();
+        for h in handles.iter().copied() {
+            worker.set_usize(h, 0);
+        }
+        mgr.restore_state();
+        for (i, h) in handles.iter().copied().enumerate() {
+            assert_eq!(i, mgr.get_usize(h));
+            assert_eq!(0, worker.get_usize(h));
+        }
+        worker.restore_state();
+        for (i, h) in handles.iter().copied().enumerate() {
+            assert_eq!(i, worker.get_usize(h));
+        }
+    }
+}
@@ -0,0 +1,181 @@
+//Copyright (c) 2023 X. Gillard, A. Dubray
+//
+//Permission is hereby granted, free of charge, to any person obtaining a copy
+//of this software and associated documentation files (the "Software"), to deal
+//in the Software without restriction, including without limitation the rights
+//to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+//copies of the Software, and to permit persons to whom the Software is
+//furnished to do so, subject to the following conditions:
+//
+//The above copyright notice and this permission notice shall be included in all
+//copies or substantial portions of the Software.
+//
+//THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+//IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+//AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+//OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+//SOFTWARE.
+
+
+//! Pools of managers. Solvers that solve many independent subproblems (one manager per task) can take
+//! their managers from a pool: a manager returned to the pool is reset but keeps its memory, so that
+//! the next task does not pay again for the allocations of the trail and of the managed resources.
+
+use std::cell::RefCell;
+use std::ops::{Deref, DerefMut};
+
+use crate::{StateManager, TrailBackend, Trailing};
+
+/// A pool of managers, meant to be owned by a single thread (e.g. in a `thread_local!`). The managers
+/// are taken from the pool with `take()`, and are reset and returned to the pool when the returned
+/// guard is dropped.
+///
+/// #Example
+///
+#[cfg_attr(feature = "usize", doc = "```")]
+#[cfg_attr(not(feature = "usize"), doc = "```ignore")]
+/// use search_trail::{ManagerPool, SaveAndRestore, UsizeManager};
+///
+/// let pool = ManagerPool::default();
+/// for task in 0..4 {
+///     let mut mgr = pool.take();
+///     let n = mgr.manage_usize(task);
+///     mgr.save_state();
+///     mgr.set_usize(n, 0);
+/// }
+/// assert_eq!(1, pool.len());
+/// ```
+#[derive(Debug)]
+pub struct ManagerPool<B: TrailBackend = Trailing> {
+    free: RefCell<Vec<StateManager<B>>>,
+}
+
+impl Default for ManagerPool<Trailing> {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+impl<B: TrailBackend> ManagerPool<B> {
+    /// Creates a pool holding `size` empty managers
+    pub fn new(size: usize) -> Self {
+        Self {
+            free: RefCell::new((0..size).map(|_| StateManager::new()).collect()),
+        }
+    }
+
+    /// Returns a manager of the pool, or a new one if the pool is empty. The manager is returned to
+    /// the pool when the guard is dropped.
+    pub fn take(&self) -> PooledManager<'_, B> {
+        let mgr = self.free.borrow_mut().pop().unwrap_or_else(StateManager::new);
+        PooledManager { pool: self, mgr: Some(mgr) }
+    }
+
+    /// Resets a manager and adds it to the pool
+    pub fn recycle(&self, mut mgr: StateManager<B>) {
+        mgr.reset();
+        self.free.borrow_mut().push(mgr);
+    }
+
+    /// Returns the number of managers waiting in the pool
+    pub fn len(&self) -> usize {
+        self.free.borrow().len()
+    }
+
+    /// Returns true if the pool does not hold any manager
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl ManagerPool {
+    /// Runs `f` with a manager taken from the pool of the current thread, and returns the manager to
+    /// this pool afterwards
+    pub fn with_local<R>(f: impl FnOnce(&mut StateManager) -> R) -> R {
+        thread_local! {
+            static POOL: ManagerPool = ManagerPool::default();
+        }
+        POOL.with(|pool| f(&mut pool.take()))
+    }
+}
+
+/// A manager taken from a `ManagerPool`, returned to the pool when dropped
+#[derive(Debug)]
+pub struct PooledManager<'a, B: TrailBackend = Trailing> {
+    pool: &'a ManagerPool<B>,
+    mgr: Option<StateManager<B>>,
+}
+
+impl<B: TrailBackend> PooledManager<'_, B> {
+    /// Keeps the manager instead of returning it to the pool
+    pub fn detach(mut self) -> StateManager<B> {
+        self.mgr.take().unwrap()
+    }
+}
+
+impl<B: TrailBackend> Deref for PooledManager<'_, B> {
+    type Target = StateManager<B>;
+
+    fn deref(&self) -> &Self::Target {
+        self.mgr.as_ref().unwrap()
+    }
+}
+
+impl<B: TrailBackend> DerefMut for PooledManager<'_, B> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.mgr.as_mut().unwrap()
+    }
+}
+
+impl<B: TrailBackend> Drop for PooledManager<'_, B> {
+    fn drop(&mut self) {
+        if let Some(mgr) = self.mgr.take() {
+            self.pool.recycle(mgr);
+        }
+    }
+}
+
+#[cfg(all(test, feature = "usize"))]
+mod test_pool {
+    use crate::*;
+
+    #[test]
+    fn managers_are_recycled() {
+        let pool = ManagerPool::<Copying>::new(2);
+        let capacity = {
+            let mut mgr = pool.take();
+            assert_eq!(1, pool.len());
+            for i in 0..100 {
+                mgr.manage_usize(i);
+                mgr.save_state();
+            }
+            mgr.levels.capacity()
+        };
+        assert_eq!(2, pool.len());
+        let mut mgr = pool.take();
+        assert_eq!(capacity, mgr.levels.capacity());
+        assert_eq!(0, mgr.depth);
+        let n = mgr.manage_usize(3);
+        assert_eq!(3, mgr.get_usize(n));
+        let mgr = mgr.detach();
+        assert_eq!(1, pool.len());
+        pool.recycle(mgr);
+        assert_eq!(2, pool.len());
+    }
+
+    #[test]
+    fn local_pool_is_reused() {
+        ManagerPool::with_local(|mgr| {
+            let n = mgr.manage_usize(1);
+            mgr.save_state();
+            mgr.set_usize(n, 2);
+        });
+        ManagerPool::with_local(|mgr| {
+            assert_eq!(0, mgr.depth);
+            assert_eq!(0, mgr.trail_len());
+            assert_eq!(0, mgr.manage_usize(3).0);
+        });
+    }
+}
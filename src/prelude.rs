@@ -0,0 +1,142 @@
+//Copyright (c) 2023 X. Gillard, A. Dubray
+//
+//Permission is hereby granted, free of charge, to any person obtaining a copy
+//of this software and associated documentation files (the "Software"), to deal
+//in the Software without restriction, including without limitation the rights
+//to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+//copies of the Software, and to permit persons to whom the Software is
+//furnished to do so, subject to the following conditions:
+//
+//The above copyright notice and this permission notice shall be included in all
+//copies or substantial portions of the Software.
+//
+//THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+//IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+//AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+//OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+//SOFTWARE.
+
+
+//! The traits needed to use a manager, to be glob-imported with `use search_trail::prelude::*`.
+//!
+//! It also contains `NumericManager`, which combines all the per-type manager traits. Generic code can take a
+//! `&mut impl NumericManager` instead of listing a bound for each managed type:
+//!
+#![cfg_attr(all(feature = "usize", feature = "bool"), doc = "```")]
+#![cfg_attr(not(all(feature = "usize", feature = "bool")), doc = "```ignore")]
+//! use search_trail::prelude::*;
+//!
+//! fn decrease<M: NumericManager>(mgr: &mut M, n: ReversibleUsize, fixed: ReversibleBool) {
+//!     mgr.save_state();
+//!     mgr.decrement_usize(n);
+//!     mgr.set_bool(fixed, true);
+//! }
+//!
+//! let mut mgr = StateManager::default();
+//! let n = mgr.manage_usize(3);
+//! let fixed = mgr.manage_bool(false);
+//! decrease(&mut mgr, n, fixed);
+//! assert_eq!(2, mgr.get_usize(n));
+//! mgr.restore_state();
+//! assert!(!mgr.get_bool(fixed));
+//! ```
+
+use paste::paste;
+
+pub use crate::{Reversible, ReversibleHandle, SaveAndRestore, StateManager};
+#[cfg(feature = "bool")]
+pub use crate::{BoolManager, OptionBoolManager, ReversibleBool, ReversibleOptionBool};
+
+/// The bounds of `NumericManager`. Each type has a trait that is the manager traits of the type when its
+/// feature is enabled, and that is implemented by every type otherwise.
+mod bounds {
+    use paste::paste;
+
+    use crate::*;
+
+    macro_rules! type_bounds {
+        ($($u:ty => $feature:literal),*) => {
+            paste! {
+                $(
+                    #[cfg(feature = $feature)]
+                    pub trait [<Manages $u:camel>]: [<$u:camel Manager>] + [<Option $u:camel Manager>] {}
+                    #[cfg(feature = $feature)]
+                    impl<M: [<$u:camel Manager>] + [<Option $u:camel Manager>] + ?Sized> [<Manages $u:camel>] for M {}
+                    #[cfg(not(feature = $feature))]
+                    pub trait [<Manages $u:camel>] {}
+                    #[cfg(not(feature = $feature))]
+                    impl<M: ?Sized> [<Manages $u:camel>] for M {}
+                )*
+
+                /// Combines the bounds of all the managed types
+                pub trait ManagesAll: ManagesBool $(+ [<Manages $u:camel>])* {}
+                impl<M: ManagesBool $(+ [<Manages $u:camel>])* + ?Sized> ManagesAll for M {}
+            }
+        };
+    }
+
+    for_each_managed_type!(type_bounds);
+
+    #[cfg(feature = "bool")]
+    pub trait ManagesBool: BoolManager + OptionBoolManager {}
+    #[cfg(feature = "bool")]
+    impl<M: BoolManager + OptionBoolManager + ?Sized> ManagesBool for M {}
+    #[cfg(not(feature = "bool"))]
+    pub trait ManagesBool {}
+    #[cfg(not(feature = "bool"))]
+    impl<M: ?Sized> ManagesBool for M {}
+}
+
+/// A manager of every managed type, i.e. implementing `SaveAndRestore` and the manager traits of all the types
+/// enabled by the features. It is implemented by `StateManager` with any backend.
+pub trait NumericManager: SaveAndRestore + bounds::ManagesAll {}
+
+impl<M: SaveAndRestore + bounds::ManagesAll + ?Sized> NumericManager for M {}
+
+macro_rules! prelude_traits {
+    ($($u:ty => $feature:literal),*) => {
+        paste! {
+            $(
+                #[cfg(feature = $feature)]
+                pub use crate::{
+                    [<$u:camel Manager>], [<Option $u:camel Manager>], [<Reversible $u:camel>], [<ReversibleOption $u:camel>],
+                };
+            )*
+        }
+    };
+}
+
+for_each_managed_type!(prelude_traits);
+
+#[cfg(all(test, feature = "usize", feature = "f64"))]
+mod test_prelude {
+    use crate::prelude::*;
+
+    fn halve<M: NumericManager>(mgr: &mut M, x: ReversibleF64, steps: ReversibleUsize) {
+        mgr.save_state();
+        let value = mgr.get_f64(x);
+        mgr.set_f64(x, value / 2.0);
+        mgr.increment_usize(steps);
+    }
+
+    #[test]
+    fn generic_code_works_with_every_backend() {
+        let mut mgr = StateManager::default();
+        let x = mgr.manage_f64(8.0);
+        let steps = mgr.manage_usize(0);
+        halve(&mut mgr, x, steps);
+        halve(&mut mgr, x, steps);
+        assert_eq!((2.0, 2), (mgr.get_f64(x), mgr.get_usize(steps)));
+        mgr.restore_state();
+        assert_eq!((4.0, 1), (mgr.get_f64(x), mgr.get_usize(steps)));
+
+        let mut mgr = crate::CopyingStateManager::new();
+        let x = mgr.manage_f64(8.0);
+        let steps = mgr.manage_usize(0);
+        halve(&mut mgr, x, steps);
+        mgr.restore_state();
+        assert_eq!((8.0, 0), (mgr.get_f64(x), mgr.get_usize(steps)));
+    }
+}
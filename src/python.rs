@@ -0,0 +1,298 @@
+//Copyright (c) 2023 X. Gillard, A. Dubray
+//
+//Permission is hereby granted, free of charge, to any person obtaining a copy
+//of this software and associated documentation files (the "Software"), to deal
+//in the Software without restriction, including without limitation the rights
+//to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+//copies of the Software, and to permit persons to whom the Software is
+//furnished to do so, subject to the following conditions:
+//
+//The above copyright notice and this permission notice shall be included in all
+//copies or substantial portions of the Software.
+//
+//THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+//IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+//AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+//OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+//SOFTWARE.
+
+
+//! Python bindings of the default manager, so that branching heuristics can be prototyped in Python
+//! on top of the trail. The extension module is built with `maturin build --release`, which reads the
+//! features to enable from `pyproject.toml`.
+//!
+//! ```python
+//! from search_trail import StateManager
+//!
+//! mgr = StateManager()
+//! x = mgr.manage_i64(3)
+//! mgr.save_state()
+//! mgr.set_i64(x, 10)
+//! mgr.restore_state()
+//! assert mgr.get_i64(x) == 3
+//! ```
+
+// The code generated by pyo3 for the methods returning a `PyResult` converts their errors into `PyErr`
+#![allow(clippy::useless_conversion)]
+
+use pyo3::exceptions::{PyRuntimeError, PyValueError};
+use pyo3::prelude::*;
+
+use crate::*;
+
+impl From<TrailError> for PyErr {
+    fn from(error: TrailError) -> Self {
+        PyValueError::new_err(error.to_string())
+    }
+}
+
+/// A handle to a managed `usize`
+#[pyclass(frozen, name = "UsizeHandle")]
+#[derive(Clone, Copy)]
+pub struct PyUsizeHandle(ReversibleUsize);
+
+/// A handle to a managed `i64`
+#[pyclass(frozen, name = "I64Handle")]
+#[derive(Clone, Copy)]
+pub struct PyI64Handle(ReversibleI64);
+
+/// A handle to a managed `f64`
+#[pyclass(frozen, name = "F64Handle")]
+#[derive(Clone, Copy)]
+pub struct PyF64Handle(ReversibleF64);
+
+/// A handle to a managed `bool`
+#[pyclass(frozen, name = "BoolHandle")]
+#[derive(Clone, Copy)]
+pub struct PyBoolHandle(ReversibleBool);
+
+#[pymethods]
+impl PyUsizeHandle {
+    fn __repr__(&self) -> String {
+        format!("UsizeHandle({})", self.0 .0)
+    }
+}
+
+#[pymethods]
+impl PyI64Handle {
+    fn __repr__(&self) -> String {
+        format!("I64Handle({})", self.0 .0)
+    }
+}
+
+#[pymethods]
+impl PyF64Handle {
+    fn __repr__(&self) -> String {
+        format!("F64Handle({})", self.0 .0)
+    }
+}
+
+#[pymethods]
+impl PyBoolHandle {
+    fn __repr__(&self) -> String {
+        format!("BoolHandle({})", self.0 .0)
+    }
+}
+
+/// The default manager, seen from Python
+#[pyclass(name = "StateManager")]
+#[derive(Default)]
+pub struct PyStateManager {
+    mgr: StateManager,
+}
+
+impl PyStateManager {
+    /// Returns an error if the bool handle is not in the manager, for which `get_bool` would panic
+    fn check_bool(&self, handle: ReversibleBool) -> PyResult<()> {
+        if handle.0 < self.mgr.bools.len() {
+            Ok(())
+        } else {
+            Err(TrailError::InvalidHandle { id: handle.0, len: self.mgr.bools.len() }.into())
+        }
+    }
+}
+
+#[pymethods]
+impl PyStateManager {
+    #[new]
+    fn new() -> Self {
+        Self::default()
+    }
+
+    /// The number of saved states
+    #[getter]
+    fn depth(&self) -> usize {
+        self.mgr.depth
+    }
+
+    fn save_state(&mut self) {
+        self.mgr.save_state();
+    }
+
+    /// Restores the last saved state, or raises a `RuntimeError` if there is none
+    fn restore_state(&mut self) -> PyResult<()> {
+        if self.mgr.depth == 0 {
+            return Err(PyRuntimeError::new_err("no saved state to restore"));
+        }
+        self.mgr.restore_state();
+        Ok(())
+    }
+
+    fn manage_usize(&mut self, value: usize) -> PyUsizeHandle {
+        PyUsizeHandle(self.mgr.manage_usize(value))
+    }
+
+    fn get_usize(&self, handle: &PyUsizeHandle) -> PyResult<usize> {
+        Ok(self.mgr.try_get_usize(handle.0)?)
+    }
+
+    fn set_usize(&mut self, handle: &PyUsizeHandle, value: usize) -> PyResult<usize> {
+        Ok(self.mgr.try_set_usize(handle.0, value)?)
+    }
+
+    fn increment_usize(&mut self, handle: &PyUsizeHandle) -> PyResult<usize> {
+        let value = self.mgr.try_get_usize(handle.0)?;
+        let value = value.checked_add(1).ok_or_else(|| PyValueError::new_err("overflow"))?;
+        Ok(self.mgr.set_usize(handle.0, value))
+    }
+
+    fn decrement_usize(&mut self, handle: &PyUsizeHandle) -> PyResult<usize> {
+        let value = self.mgr.try_get_usize(handle.0)?;
+        let value = value.checked_sub(1).ok_or_else(|| PyValueError::new_err("overflow"))?;
+        Ok(self.mgr.set_usize(handle.0, value))
+    }
+
+    fn manage_i64(&mut self, value: i64) -> PyI64Handle {
+        PyI64Handle(self.mgr.manage_i64(value))
+    }
+
+    fn get_i64(&self, handle: &PyI64Handle) -> PyResult<i64> {
+        Ok(self.mgr.try_get_i64(handle.0)?)
+    }
+
+    fn set_i64(&mut self, handle: &PyI64Handle, value: i64) -> PyResult<i64> {
+        Ok(self.mgr.try_set_i64(handle.0, value)?)
+    }
+
+    fn increment_i64(&mut self, handle: &PyI64Handle) -> PyResult<i64> {
+        let value = self.mgr.try_get_i64(handle.0)?;
+        let value = value.checked_add(1).ok_or_else(|| PyValueError::new_err("overflow"))?;
+        Ok(self.mgr.set_i64(handle.0, value))
+    }
+
+    fn decrement_i64(&mut self, handle: &PyI64Handle) -> PyResult<i64> {
+        let value = self.mgr.try_get_i64(handle.0)?;
+        let value = value.checked_sub(1).ok_or_else(|| PyValueError::new_err("overflow"))?;
+        Ok(self.mgr.set_i64(handle.0, value))
+    }
+
+    fn manage_f64(&mut self, value: f64) -> PyF64Handle {
+        PyF64Handle(self.mgr.manage_f64(value))
+    }
+
+    fn get_f64(&self, handle: &PyF64Handle) -> PyResult<f64> {
+        Ok(self.mgr.try_get_f64(handle.0)?)
+    }
+
+    fn set_f64(&mut self, handle: &PyF64Handle, value: f64) -> PyResult<f64> {
+        Ok(self.mgr.try_set_f64(handle.0, value)?)
+    }
+
+    fn manage_bool(&mut self, value: bool) -> PyBoolHandle {
+        PyBoolHandle(self.mgr.manage_bool(value))
+    }
+
+    fn get_bool(&self, handle: &PyBoolHandle) -> PyResult<bool> {
+        self.check_bool(handle.0)?;
+        Ok(self.mgr.get_bool(handle.0))
+    }
+
+    fn set_bool(&mut self, handle: &PyBoolHandle, value: bool) -> PyResult<bool> {
+        self.check_bool(handle.0)?;
+        Ok(self.mgr.set_bool(handle.0, value))
+    }
+
+    fn flip_bool(&mut self, handle: &PyBoolHandle) -> PyResult<bool> {
+        self.check_bool(handle.0)?;
+        Ok(self.mgr.flip_bool(handle.0))
+    }
+
+    fn __repr__(&self) -> String {
+        self.mgr.to_string()
+    }
+}
+
+/// The `search_trail` extension module
+#[pymodule]
+#[pyo3(name = "search_trail")]
+fn python_module(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_class::<PyStateManager>()?;
+    module.add_class::<PyUsizeHandle>()?;
+    module.add_class::<PyI64Handle>()?;
+    module.add_class::<PyF64Handle>()?;
+    module.add_class::<PyBoolHandle>()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test_python {
+    use pyo3::types::PyDict;
+
+    use super::*;
+
+    fn run(code: &str) -> PyResult<()> {
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let module = PyModule::new_bound(py, "search_trail")?;
+            python_module(&module)?;
+            let globals = PyDict::new_bound(py);
+            globals.set_item("search_trail", module)?;
+            py.run_bound(code, Some(&globals), None)
+        })
+    }
+
+    #[test]
+    fn manager_is_used_from_python() {
+        run(r#"
+mgr = search_trail.StateManager()
+x = mgr.manage_usize(3)
+y = mgr.manage_i64(-1)
+z = mgr.manage_f64(0.5)
+b = mgr.manage_bool(False)
+mgr.save_state()
+mgr.increment_usize(x)
+mgr.set_i64(y, 10)
+mgr.set_f64(z, 1.5)
+mgr.flip_bool(b)
+assert (mgr.get_usize(x), mgr.get_i64(y), mgr.get_f64(z), mgr.get_bool(b)) == (4, 10, 1.5, True)
+assert mgr.depth == 1
+mgr.restore_state()
+assert (mgr.get_usize(x), mgr.get_i64(y), mgr.get_f64(z), mgr.get_bool(b)) == (3, -1, 0.5, False)
+"#)
+        .unwrap();
+    }
+
+    #[test]
+    fn errors_are_raised() {
+        run(r#"
+mgr = search_trail.StateManager()
+other = search_trail.StateManager()
+x = other.manage_usize(0)
+b = other.manage_bool(True)
+for call in (lambda: mgr.get_usize(x), lambda: mgr.set_bool(b, False), lambda: other.decrement_usize(x)):
+    try:
+        call()
+        assert False
+    except ValueError:
+        pass
+try:
+    mgr.restore_state()
+    assert False
+except RuntimeError:
+    pass
+"#)
+        .unwrap();
+    }
+}
@@ -0,0 +1,248 @@
+//Copyright (c) 2023 X. Gillard, A. Dubray
+//
+//Permission is hereby granted, free of charge, to any person obtaining a copy
+//of this software and associated documentation files (the "Software"), to deal
+//in the Software without restriction, including without limitation the rights
+//to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+//copies of the Software, and to permit persons to whom the Software is
+//furnished to do so, subject to the following conditions:
+//
+//The above copyright notice and this permission notice shall be included in all
+//copies or substantial portions of the Software.
+//
+//THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+//IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+//AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+//OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+//SOFTWARE.
+
+
+//! A backend that restores the state of the manager by recomputation. Instead of trailing the previous
+//! values, it records the sequence of writes done in each level and keeps a full copy of the values only
+//! every few levels. Restoring a level replays the writes done since the closest copy. The interval
+//! between two copies is a knob between trailing everything and copying everything.
+
+use std::fmt::Debug;
+
+use crate::memory::bytes;
+use crate::{StateManager, Store, StoreMemory, TrailBackend};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// A backend restoring the state by replaying the writes done since a full copy of the values. A copy
+/// is made every `INTERVAL` levels (levels in which nothing is modified are not counted): a small
+/// interval uses more memory and a large one more CPU time when restoring.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Recomputing<const INTERVAL: usize>;
+
+impl<const INTERVAL: usize> TrailBackend for Recomputing<INTERVAL> {
+    type Store<T: Copy + PartialEq + Debug + Send + Sync> = ReplayStore<T, INTERVAL>;
+}
+
+/// A state manager implementing the same traits as `StateManager`, but restoring its state by
+/// replaying the writes done since a full copy of the values, made every `INTERVAL` levels.
+///
+/// #Example
+///
+#[cfg_attr(feature = "usize", doc = "```")]
+#[cfg_attr(not(feature = "usize"), doc = "```ignore")]
+/// use search_trail::{RecomputingStateManager, SaveAndRestore, UsizeManager};
+///
+/// let mut mgr = RecomputingStateManager::<4>::new();
+/// let n = mgr.manage_usize(0);
+/// mgr.save_state();
+/// mgr.set_usize(n, 42);
+/// assert_eq!(42, mgr.get_usize(n));
+/// mgr.restore_state();
+/// assert_eq!(0, mgr.get_usize(n));
+/// ```
+pub type RecomputingStateManager<const INTERVAL: usize> = StateManager<Recomputing<INTERVAL>>;
+
+/// A full copy of the values of a store
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+struct Snapshot<T> {
+    /// Number of saved levels when the copy was made
+    depth: usize,
+    /// Number of recorded writes when the copy was made
+    writes: usize,
+    values: Vec<T>,
+}
+
+/// The values of all the managed resources of a given type, with the writes done on them since the
+/// root and the copies made every `INTERVAL` levels
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ReplayStore<T, const INTERVAL: usize> {
+    values: Vec<T>,
+    /// The sequence of writes (index of the resource, new value), including the creation of resources
+    writes: Vec<(usize, T)>,
+    /// For each saved level, the number of writes when it was saved
+    levels: Vec<usize>,
+    snapshots: Vec<Snapshot<T>>,
+}
+
+impl<T, const INTERVAL: usize> Default for ReplayStore<T, INTERVAL> {
+    fn default() -> Self {
+        assert!(INTERVAL > 0, "The snapshot interval must be positive");
+        Self {
+            values: vec![],
+            writes: vec![],
+            levels: vec![],
+            snapshots: vec![],
+        }
+    }
+}
+
+impl<T: Copy + PartialEq + Debug + Send + Sync, const INTERVAL: usize> Store<T> for ReplayStore<T, INTERVAL> {
+    fn manage(&mut self, _clock: usize, value: T) -> usize {
+        let id = self.values.len();
+        self.values.push(value);
+        self.writes.push((id, value));
+        id
+    }
+
+    fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    fn reserve(&mut self, additional: usize) {
+        self.values.reserve(additional);
+    }
+
+    fn get(&self, id: usize) -> T {
+        self.values[id]
+    }
+
+    fn get_ref(&self, id: usize) -> &T {
+        &self.values[id]
+    }
+
+    fn root_value(&self, id: usize) -> T {
+        // The first copy is the one made when saving the root level
+        match self.snapshots.first() {
+            Some(snapshot) if id < snapshot.values.len() => snapshot.values[id],
+            _ => self.values[id],
+        }
+    }
+
+    fn set(&mut self, _clock: usize, id: usize, value: T) {
+        if self.values[id] != value {
+            self.values[id] = value;
+            self.writes.push((id, value));
+        }
+    }
+
+    fn save(&mut self, _clock: usize, _depth: usize) {
+        // The levels without modification are not saved, so the depth of the manager can skip the
+        // multiples of the interval. The copies are made based on the levels seen by the store.
+        let depth = self.levels.len();
+        if depth.is_multiple_of(INTERVAL) {
+            self.snapshots.push(Snapshot {
+                depth,
+                writes: self.writes.len(),
+                values: self.values.clone(),
+            });
+        }
+        self.levels.push(self.writes.len());
+    }
+
+    fn restore(&mut self, _clock: usize) {
+        let writes = self.levels.pop().expect("Can not pop the root level of the state manager");
+        let depth = self.levels.len();
+        // A copy is made when saving the root level, so there is always a copy to start from
+        let snapshot = self.snapshots.last().unwrap();
+        // Resources created after the copy are set again by their creation in the replayed writes
+        self.values[..snapshot.values.len()].copy_from_slice(&snapshot.values);
+        for &(id, value) in self.writes[snapshot.writes..writes].iter() {
+            self.values[id] = value;
+        }
+        self.writes.truncate(writes);
+        // The copy is made again when saving the current level
+        if snapshot.depth == depth {
+            self.snapshots.pop();
+        }
+    }
+
+    fn restore_size(&self, _clock: usize) -> usize {
+        match (self.levels.last(), self.snapshots.last()) {
+            (Some(writes), Some(snapshot)) => snapshot.values.len() + writes - snapshot.writes,
+            _ => 0,
+        }
+    }
+
+    fn clear(&mut self) {
+        self.values.clear();
+        self.writes.clear();
+        self.levels.clear();
+        self.snapshots.clear();
+    }
+
+    fn memory_usage(&self) -> StoreMemory {
+        let snapshots = self.snapshots.iter().map(|s| bytes::<T>(s.values.capacity())).sum::<usize>();
+        StoreMemory {
+            values: bytes::<T>(self.values.capacity()),
+            saved: bytes::<(usize, T)>(self.writes.capacity())
+                + bytes::<usize>(self.levels.capacity())
+                + bytes::<Snapshot<T>>(self.snapshots.capacity())
+                + snapshots,
+        }
+    }
+}
+
+#[cfg(all(test, feature = "usize"))]
+mod test_recomputing_manager {
+    use crate::{OptionUsizeManager, RecomputingStateManager, SaveAndRestore, StateManager, TrailBackend, UsizeManager};
+
+    fn same_values_as_trailing<B: TrailBackend>() {
+        let mut trailing = StateManager::default();
+        let mut recomputing = StateManager::<B>::new();
+        let mut handles = vec![];
+        for depth in 0..10 {
+            handles.push((trailing.manage_usize(depth), recomputing.manage_usize(depth)));
+            trailing.save_state();
+            recomputing.save_state();
+            for (i, (a, b)) in handles.iter().copied().enumerate() {
+                trailing.set_usize(a, depth * 10 + i);
+                recomputing.set_usize(b, depth * 10 + i);
+                trailing.increment_usize(a);
+                recomputing.increment_usize(b);
+            }
+        }
+        for _ in 0..10 {
+            trailing.restore_state();
+            recomputing.restore_state();
+            for (a, b) in handles.iter().copied() {
+                assert_eq!(trailing.get_usize(a), recomputing.get_usize(b));
+            }
+        }
+    }
+
+    #[test]
+    fn same_values_as_trailing_at_every_depth() {
+        same_values_as_trailing::<crate::Recomputing<1>>();
+        same_values_as_trailing::<crate::Recomputing<2>>();
+        same_values_as_trailing::<crate::Recomputing<3>>();
+        same_values_as_trailing::<crate::Recomputing<8>>();
+    }
+
+    #[test]
+    fn save_again_after_restore() {
+        let mut mgr = RecomputingStateManager::<2>::new();
+        let n = mgr.manage_option_usize(None);
+        mgr.save_state();
+        mgr.set_option_usize(n, Some(1));
+        mgr.restore_state();
+        mgr.set_option_usize(n, Some(2));
+        mgr.save_state();
+        mgr.set_option_usize(n, Some(3));
+        mgr.save_state();
+        mgr.set_option_usize(n, Some(4));
+        mgr.restore_state();
+        assert_eq!(Some(3), mgr.get_option_usize(n));
+        mgr.restore_state();
+        assert_eq!(Some(2), mgr.get_option_usize(n));
+    }
+}
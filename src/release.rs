@@ -0,0 +1,333 @@
+//Copyright (c) 2023 X. Gillard, A. Dubray
+//
+//Permission is hereby granted, free of charge, to any person obtaining a copy
+//of this software and associated documentation files (the "Software"), to deal
+//in the Software without restriction, including without limitation the rights
+//to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+//copies of the Software, and to permit persons to whom the Software is
+//furnished to do so, subject to the following conditions:
+//
+//The above copyright notice and this permission notice shall be included in all
+//copies or substantial portions of the Software.
+//
+//THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+//IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+//AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+//OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+//SOFTWARE.
+
+
+//! Release of the managed resources. A released resource is not used anymore by the model, and its slot is
+//! reused by the next creation of a resource of the same type, so that the models creating many temporary
+//! resources do not grow the stores without bound.
+//!
+//! The releases follow the levels: restoring the level in which a resource was released makes it live
+//! again, with the value it had before the level (the value given to the slot when it was reused is
+//! restored like any other write). In debug builds, reading or writing a released resource panics, until
+//! its slot is reused: the handles do not identify the successive resources of a slot, and a handle of the
+//! released resource is then a handle of the new one.
+//!
+#![cfg_attr(feature = "usize", doc = "```")]
+#![cfg_attr(not(feature = "usize"), doc = "```ignore")]
+//! use search_trail::{SaveAndRestore, StateManager, UsizeManager};
+//!
+//! let mut mgr = StateManager::default();
+//! let x = mgr.manage_usize(1);
+//! mgr.save_state();
+//! mgr.release_usize(x);
+//! let y = mgr.manage_usize(2);
+//! assert_eq!(x, y);
+//! mgr.restore_state();
+//! assert_eq!(1, mgr.get_usize(x));
+//! assert_eq!(1, mgr.iter_usize().count());
+//! ```
+//!
+//! Releasing a resource also removes its name, its listeners, its subscribers and its frozen mark, and removes
+//! it from the set of the modified resources, so that the resource reusing its slot starts clean. Like the
+//! names, the listeners and the subscribers are not restored with the level of the release; the frozen mark
+//! and the modified resources are.
+//!
+//! The booleans, which are packed in words of 64 bits, can not be released.
+
+use std::collections::{HashMap, HashSet};
+
+use paste::paste;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::*;
+
+/// The released slots, not reused yet, of each type
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub(crate) struct FreeSlots {
+    /// The released slots of each type, identified by its kind, in the order of their release
+    free: HashMap<u64, Vec<usize>>,
+    /// The slots in `free`, to detect the uses of a released resource
+    released: HashSet<(u64, usize)>,
+    /// The releases (true) and the reuses (false) of slots done since the first saved level, to undo them
+    /// when their level is restored. Nothing is recorded at the root, which is never restored.
+    log: Vec<(u64, usize, bool)>,
+    /// The length of the log when each level was saved
+    saved: Vec<usize>,
+}
+
+impl FreeSlots {
+    /// Returns empty released slots, created at the given depth
+    #[cfg(numbers)]
+    fn new(depth: usize) -> Self {
+        Self {
+            saved: vec![0; depth],
+            ..Self::default()
+        }
+    }
+
+    #[cfg(numbers)]
+    fn release(&mut self, kind: u64, id: usize) {
+        assert!(self.released.insert((kind, id)), "The resource {} has already been released", id);
+        self.free.entry(kind).or_default().push(id);
+        if !self.saved.is_empty() {
+            self.log.push((kind, id, true));
+        }
+    }
+
+    #[cfg(numbers)]
+    fn reuse(&mut self, kind: u64) -> Option<usize> {
+        let id = self.free.get_mut(&kind)?.pop()?;
+        self.released.remove(&(kind, id));
+        if !self.saved.is_empty() {
+            self.log.push((kind, id, false));
+        }
+        Some(id)
+    }
+
+    fn restore(&mut self) {
+        let len = self.saved.pop().unwrap_or(0);
+        // The releases and reuses are undone in the reverse order, so that each released slot is at the end
+        // of its list when its release is undone
+        for (kind, id, released) in self.log.drain(len..).rev() {
+            let free = self.free.entry(kind).or_default();
+            if released {
+                free.pop();
+                self.released.remove(&(kind, id));
+            } else {
+                free.push(id);
+                self.released.insert((kind, id));
+            }
+        }
+    }
+
+    /// Returns the released slots, without the saved levels
+    pub fn fork(&self) -> Self {
+        Self {
+            free: self.free.clone(),
+            released: self.released.clone(),
+            ..Self::default()
+        }
+    }
+}
+
+impl<B: TrailBackend> StateManager<B> {
+    /// Releases the resource at the given index
+    #[cfg(numbers)]
+    fn release_value<T: Managed>(&mut self, id: usize) {
+        #[cfg(feature = "journal")]
+        {
+            let value = self.get_value::<T>(id).into_value();
+            self.journal_push(|| JournalEntry::Release { id, value });
+        }
+        let handle = T::handle(self, id);
+        self.names.remove(handle);
+        self.unfreeze_resource(handle);
+        if let Some(dirty) = self.dirty.as_mut() {
+            dirty.remove(handle);
+        }
+        #[cfg(feature = "listeners")]
+        T::listeners(self).remove(&id);
+        let depth = self.depth;
+        self.free_slots.get_or_insert_with(|| FreeSlots::new(depth)).release(T::KIND, id);
+    }
+
+    /// Returns a released slot of type `T`, which is not released anymore, if there is one
+    #[cfg(numbers)]
+    pub(crate) fn reuse_slot<T: Managed>(&mut self) -> Option<usize> {
+        self.free_slots.as_mut()?.reuse(T::KIND)
+    }
+
+    /// Panics if the resource at the given index has been released. This is only checked in debug builds.
+    #[inline]
+    #[allow(unused_variables)]
+    #[cfg(numbers)]
+    pub(crate) fn check_released<T: Managed>(&self, id: usize) {
+        #[cfg(debug_assertions)]
+        if let Some(slots) = self.free_slots.as_ref() {
+            assert!(!slots.released.contains(&(T::KIND, id)), "The resource {} of type {} has been released", id, std::any::type_name::<T>());
+        }
+    }
+
+    /// Records a call to `save_state()` in the released slots, if any resource has been released
+    pub(crate) fn save_free_slots(&mut self) {
+        if let Some(slots) = self.free_slots.as_mut() {
+            slots.saved.push(slots.log.len());
+        }
+    }
+
+    /// Records a call to `restore_state()` in the released slots, if any resource has been released
+    pub(crate) fn restore_free_slots(&mut self) {
+        if let Some(slots) = self.free_slots.as_mut() {
+            slots.restore();
+        }
+    }
+}
+
+macro_rules! release_values {
+    ($($u:ty => $feature:literal),*) => {
+        paste! {
+            impl<B: TrailBackend> StateManager<B> {
+                $(
+                    #[doc="Releases the resource, whose slot is reused by the next resource of type `" $u "` created"]
+                    #[cfg(feature = $feature)]
+                    pub fn [<release_ $u>](&mut self, id: [<Reversible $u:camel>]) {
+                        self.release_value::<$u>(id.index(self));
+                    }
+
+                    #[doc="Releases the resource, whose slot is reused by the next resource of type `Option<" $u ">` created"]
+                    #[cfg(feature = $feature)]
+                    pub fn [<release_option_ $u>](&mut self, id: [<ReversibleOption $u:camel>]) {
+                        self.release_value::<Option<$u>>(id.index(self));
+                    }
+                )*
+            }
+
+            impl Value {
+                /// Releases the resource of the type of this value at the given index
+                #[cfg(feature = "journal")]
+                pub(crate) fn release_at<B: TrailBackend>(self, mgr: &mut StateManager<B>, id: usize) {
+                    match self {
+                        $(
+                            #[cfg(feature = $feature)]
+                            Value::[<$u:camel>](_) => mgr.release_value::<$u>(id),
+                            #[cfg(feature = $feature)]
+                            Value::[<Option $u:camel>](_) => mgr.release_value::<Option<$u>>(id),
+                        )*
+                        #[allow(unreachable_patterns)]
+                        _ => panic!("The booleans are not released"),
+                    }
+                }
+            }
+        }
+    };
+}
+
+for_each_managed_type!(release_values);
+
+#[cfg(all(test, feature = "u16", feature = "i8"))]
+mod test_release {
+    use crate::*;
+
+    #[test]
+    fn released_slots_are_reused_and_restored() {
+        let mut mgr = StateManager::default();
+        let xs = (0..3).map(|i| mgr.manage_u16(i)).collect::<Vec<_>>();
+        let o = mgr.manage_option_i8(Some(1));
+        mgr.release_u16(xs[0]);
+        mgr.save_state();
+        mgr.release_u16(xs[2]);
+        mgr.release_option_i8(o);
+        // The slots are reused from the most recently released one
+        let y = mgr.manage_u16(10);
+        let z = mgr.manage_u16(11);
+        assert_eq!((xs[2], xs[0]), (y, z));
+        assert_eq!(o, mgr.manage_option_i8(Some(-1)));
+        assert_eq!(Some(-1), mgr.get_option_i8(o));
+        assert_eq!(3, mgr.manage_u16(12).0);
+        mgr.restore_state();
+        assert_eq!((2, Some(1)), (mgr.get_u16(xs[2]), mgr.get_option_i8(o)));
+        assert_eq!(xs[0], mgr.manage_u16(5));
+        assert_eq!(5, mgr.get_u16(xs[0]));
+    }
+
+    #[test]
+    fn releases_at_the_root_are_not_recorded() {
+        let mut mgr = StateManager::default();
+        let mut x = mgr.manage_u16(0);
+        for i in 0..100 {
+            mgr.release_u16(x);
+            x = mgr.manage_u16(i);
+        }
+        assert_eq!(0, mgr.free_slots.as_ref().unwrap().log.len());
+        mgr.save_state();
+        mgr.release_u16(x);
+        mgr.manage_u16(0);
+        assert_eq!(2, mgr.free_slots.as_ref().unwrap().log.len());
+        mgr.restore_state();
+        assert_eq!((0, 99), (mgr.free_slots.as_ref().unwrap().log.len(), mgr.get_u16(x)));
+    }
+
+    #[test]
+    fn releases_in_the_first_level_are_restored() {
+        let mut mgr = StateManager::default();
+        let x = mgr.manage_u16(1);
+        mgr.save_state();
+        mgr.release_u16(x);
+        mgr.restore_state();
+        assert_eq!(1, mgr.get_u16(x));
+        assert_ne!(x, mgr.manage_u16(2));
+    }
+
+    #[test]
+    fn reused_slots_start_clean() {
+        let mut mgr = StateManager::default();
+        let x = mgr.manage_u16_named(1, "x");
+        let y = mgr.manage_u16(2);
+        mgr.freeze_resource(x);
+        mgr.subscribe(x, 0, 0);
+        mgr.subscribe(y, 1, 0);
+        mgr.save_state();
+        mgr.unfreeze_resource(x);
+        mgr.set_u16(x, 3);
+        mgr.set_u16(y, 3);
+        mgr.release_u16(x);
+        let z = mgr.manage_u16(4);
+        assert_eq!(x, z);
+        assert_eq!((None, None, false), (mgr.name_of(z), mgr.lookup("x"), mgr.is_frozen(z)));
+        // The released resource is not modified anymore, and its subscribers are not woken by the new one
+        assert_eq!(vec![AnyHandle::U16(y)], mgr.drain_dirty());
+        mgr.set_u16(z, 5);
+        assert!(mgr.drain_subscribers().is_empty());
+        mgr.restore_state();
+        assert!(mgr.is_frozen(x));
+        assert_eq!(1, mgr.get_u16(x));
+    }
+
+    #[test]
+    #[cfg(feature = "listeners")]
+    fn listeners_of_released_resources_are_removed() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        let mut mgr = StateManager::default();
+        let x = mgr.manage_u16(0);
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        mgr.on_change(x, move |_, _| {
+            counter.fetch_add(1, Ordering::Relaxed);
+        });
+        mgr.release_u16(x);
+        let y = mgr.manage_u16(0);
+        mgr.set_u16(y, 1);
+        assert_eq!(0, calls.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "has been released")]
+    fn released_resources_can_not_be_used() {
+        let mut mgr = StateManager::default();
+        let x = mgr.manage_u16(0);
+        mgr.release_u16(x);
+        mgr.get_u16(x);
+    }
+}
@@ -0,0 +1,357 @@
+//Copyright (c) 2023 X. Gillard, A. Dubray
+//
+//Permission is hereby granted, free of charge, to any person obtaining a copy
+//of this software and associated documentation files (the "Software"), to deal
+//in the Software without restriction, including without limitation the rights
+//to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+//copies of the Software, and to permit persons to whom the Software is
+//furnished to do so, subject to the following conditions:
+//
+//The above copyright notice and this permission notice shall be included in all
+//copies or substantial portions of the Software.
+//
+//THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+//IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+//AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+//OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+//SOFTWARE.
+
+
+//! Restarts of the search. A restart restores all the saved levels to go back to the root, and is
+//! counted by the manager so that the budget of the next run can be given by a restart policy (e.g.,
+//! the Luby sequence or a geometric one).
+//!
+//! The structures that are not managed (activity scores, learned clauses, ...) are not modified by a
+//! restart. The managed resources that must survive it (e.g., a learned bound) can be kept with
+//! `restart_keeping()`: their values are set at the root level after the restoration. Conversely, a
+//! soft restart can reset some resources to their value at the root with `reset_to_initial()`, without
+//! restoring the saved levels.
+
+use paste::paste;
+
+use crate::*;
+
+macro_rules! any_values {
+    ($($u:ty => $feature:literal),*) => {
+        paste! {
+            impl<B: TrailBackend> StateManager<B> {
+                /// Returns the value of the resource of any type
+                pub(crate) fn any_value(&self, handle: AnyHandle) -> Value {
+                    match handle {
+                        $(
+                            #[cfg(feature = $feature)]
+                            AnyHandle::[<$u:camel>](h) => Value::[<$u:camel>](self.[<get_ $u>](h)),
+                            #[cfg(feature = $feature)]
+                            AnyHandle::[<Option $u:camel>](h) => Value::[<Option $u:camel>](self.[<get_option_ $u>](h)),
+                        )*
+                        #[cfg(feature = "bool")]
+                        AnyHandle::Bool(h) => Value::Bool(self.get_bool(h)),
+                        #[cfg(feature = "bool")]
+                        AnyHandle::OptionBool(h) => Value::OptionBool(self.get_option_bool(h)),
+                    }
+                }
+
+                /// Returns an error if the handle of any type can not be used with the manager
+                pub(crate) fn check_any_handle(&self, handle: AnyHandle) -> Result<(), TrailError> {
+                    match handle {
+                        $(
+                            #[cfg(feature = $feature)]
+                            AnyHandle::[<$u:camel>](h) => self.check_handle::<$u>(h.try_index(self)?),
+                            #[cfg(feature = $feature)]
+                            AnyHandle::[<Option $u:camel>](h) => self.check_handle::<Option<$u>>(h.try_index(self)?),
+                        )*
+                        #[cfg(feature = "bool")]
+                        AnyHandle::Bool(h) => Self::check_flag(h.try_index(self)?, self.bools.len()),
+                        #[cfg(feature = "bool")]
+                        AnyHandle::OptionBool(h) => Self::check_flag(h.try_index(self)?, self.option_bools.len()),
+                    }
+                }
+
+                /// Sets the resource of any type to a value returned by `any_value()` for this resource
+                pub(crate) fn set_any_value(&mut self, handle: AnyHandle, value: Value) {
+                    match (handle, value) {
+                        $(
+                            #[cfg(feature = $feature)]
+                            (AnyHandle::[<$u:camel>](h), Value::[<$u:camel>](v)) => {
+                                self.[<set_ $u>](h, v);
+                            }
+                            #[cfg(feature = $feature)]
+                            (AnyHandle::[<Option $u:camel>](h), Value::[<Option $u:camel>](v)) => {
+                                self.[<set_option_ $u>](h, v);
+                            }
+                        )*
+                        #[cfg(feature = "bool")]
+                        (AnyHandle::Bool(h), Value::Bool(v)) => {
+                            self.set_bool(h, v);
+                        }
+                        #[cfg(feature = "bool")]
+                        (AnyHandle::OptionBool(h), Value::OptionBool(Some(v))) => {
+                            self.set_option_bool(h, v);
+                        }
+                        #[cfg(feature = "bool")]
+                        (AnyHandle::OptionBool(h), Value::OptionBool(None)) => self.set_option_bool_none(h),
+                        #[allow(unreachable_patterns)]
+                        _ => unreachable!("The value is not of the type of the resource"),
+                    }
+                }
+            }
+        }
+    };
+}
+
+for_each_managed_type!(any_values);
+
+impl<B: TrailBackend> StateManager<B> {
+    /// Returns an error if the index is not the one of a flag among the `len` packed booleans
+    #[cfg(feature = "bool")]
+    fn check_flag(id: usize, len: usize) -> Result<(), TrailError> {
+        if id < len {
+            Ok(())
+        } else {
+            Err(TrailError::InvalidHandle { id, len })
+        }
+    }
+}
+
+impl<B: TrailBackend> StateManager<B> {
+    /// Restores all the saved levels, going back to the root, and counts the restart
+    ///
+    #[cfg_attr(feature = "usize", doc = "```")]
+    #[cfg_attr(not(feature = "usize"), doc = "```ignore")]
+    /// use search_trail::{SaveAndRestore, StateManager, UsizeManager};
+    ///
+    /// let mut mgr = StateManager::default();
+    /// let n = mgr.manage_usize(0);
+    /// for i in 1..10 {
+    ///     mgr.save_state();
+    ///     mgr.set_usize(n, i);
+    /// }
+    /// mgr.restart();
+    /// assert_eq!(0, mgr.get_usize(n));
+    /// assert_eq!(1, mgr.restarts());
+    /// ```
+    pub fn restart(&mut self) {
+        while self.depth > 0 {
+            self.restore_state();
+        }
+        self.restarts += 1;
+    }
+
+    /// Same as `restart()`, but the given resources keep their current value: it is set at the root
+    /// level after the restoration, and is thus never restored.
+    ///
+    #[cfg_attr(feature = "usize", doc = "```")]
+    #[cfg_attr(not(feature = "usize"), doc = "```ignore")]
+    /// use search_trail::{SaveAndRestore, StateManager, UsizeManager};
+    ///
+    /// let mut mgr = StateManager::default();
+    /// let n = mgr.manage_usize(0);
+    /// let best = mgr.manage_usize(100);
+    /// mgr.save_state();
+    /// mgr.set_usize(n, 3);
+    /// mgr.set_usize(best, 42);
+    /// mgr.restart_keeping(&[best.into()]);
+    /// assert_eq!(0, mgr.get_usize(n));
+    /// assert_eq!(42, mgr.get_usize(best));
+    /// ```
+    pub fn restart_keeping(&mut self, kept: &[AnyHandle]) {
+        let values = kept.iter().map(|&handle| self.any_value(handle)).collect::<Vec<_>>();
+        self.restart();
+        for (&handle, value) in kept.iter().zip(values) {
+            self.set_any_value(handle, value);
+        }
+    }
+
+    /// Returns the number of restarts since the manager was created or reset
+    pub fn restarts(&self) -> usize {
+        self.restarts
+    }
+
+    /// Returns the value the resource had at the root, i.e., the value it will have once all the saved
+    /// levels are restored. A resource created after the root has one of the values it had since its
+    /// creation.
+    pub fn initial<H: ReversibleHandle>(&self, handle: H) -> H::Value {
+        H::Value::initial(self, handle)
+    }
+
+    /// Sets the resource to the value it had at the root and returns this value. Unlike a restart, the
+    /// saved levels are kept, and the change is saved like any other write.
+    ///
+    #[cfg_attr(feature = "usize", doc = "```")]
+    #[cfg_attr(not(feature = "usize"), doc = "```ignore")]
+    /// use search_trail::{SaveAndRestore, StateManager};
+    ///
+    /// let mut mgr = StateManager::default();
+    /// let n = mgr.manage(0usize);
+    /// for i in 1..10 {
+    ///     mgr.save_state();
+    ///     mgr.set(n, i);
+    /// }
+    /// assert_eq!(0, mgr.reset_to_initial(n));
+    /// mgr.restore_state();
+    /// assert_eq!(8, mgr.get(n));
+    /// ```
+    pub fn reset_to_initial<H: ReversibleHandle>(&mut self, handle: H) -> H::Value {
+        let value = self.initial(handle);
+        self.set(handle, value)
+    }
+
+    /// Returns the value the resource at the given index had at the root
+    #[cfg(numbers)]
+    pub(crate) fn initial_value<T: Managed>(&self, id: usize) -> T {
+        self.check_released::<T>(id);
+        T::store(self).root_value(id)
+    }
+}
+
+/// A policy giving the budget (e.g., the number of failures) of each run of a restarted search
+pub trait RestartPolicy {
+    /// Returns the budget of the run following the given number of restarts
+    fn budget(&self, restarts: usize) -> u64;
+}
+
+/// The Luby sequence (1, 1, 2, 1, 1, 2, 4, 1, 1, 2, ...) multiplied by a unit
+///
+/// ```
+/// use search_trail::{Luby, RestartPolicy};
+///
+/// let luby = Luby { unit: 100 };
+/// let budgets = (0..8).map(|restarts| luby.budget(restarts)).collect::<Vec<_>>();
+/// assert_eq!(vec![100, 100, 200, 100, 100, 200, 400, 100], budgets);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Luby {
+    /// The budget of the first run
+    pub unit: u64,
+}
+
+impl RestartPolicy for Luby {
+    fn budget(&self, restarts: usize) -> u64 {
+        // Finds the smallest complete subsequence of length 2^k - 1 containing the index, then goes down
+        // in its first half (which is the whole sequence of length 2^(k-1) - 1) until the index is its
+        // last element
+        let mut index = restarts as u128;
+        let (mut size, mut exponent) = (1u128, 0);
+        while size < index + 1 {
+            exponent += 1;
+            size = 2 * size + 1;
+        }
+        while size - 1 != index {
+            size = (size - 1) / 2;
+            exponent -= 1;
+            index %= size;
+        }
+        // The budgets too large for a u64 are saturated
+        1u64.checked_shl(exponent).and_then(|factor| self.unit.checked_mul(factor)).unwrap_or(u64::MAX)
+    }
+}
+
+/// A geometric sequence of budgets: the first one, multiplied by the factor at each restart
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Geometric {
+    /// The budget of the first run
+    pub first: u64,
+    /// The growth of the budget at each restart
+    pub factor: f64,
+}
+
+impl RestartPolicy for Geometric {
+    fn budget(&self, restarts: usize) -> u64 {
+        (self.first as f64 * self.factor.powi(restarts.min(i32::MAX as usize) as i32)) as u64
+    }
+}
+
+#[cfg(all(test, feature = "usize", feature = "bool"))]
+mod test_restart {
+    use crate::*;
+
+    #[test]
+    fn restart_keeps_the_designated_resources() {
+        let mut mgr = StateManager::default();
+        let n = mgr.manage_usize(0);
+        let bound = mgr.manage_option_usize(None);
+        let b = mgr.manage_option_bool(None);
+        mgr.set_usize(n, 1);
+        for depth in 1..5 {
+            mgr.save_state();
+            mgr.set_usize(n, depth + 1);
+            mgr.set_option_usize(bound, Some(depth));
+        }
+        mgr.set_option_bool(b, true);
+        mgr.restart_keeping(&[bound.into(), b.into()]);
+        assert_eq!(1, mgr.get_usize(n));
+        assert_eq!(Some(4), mgr.get_option_usize(bound));
+        assert_eq!(Some(true), mgr.get_option_bool(b));
+        assert_eq!(0, mgr.trail_len());
+        // The kept values are at the root level, and survive the next restarts
+        mgr.save_state();
+        mgr.set_option_usize(bound, None);
+        mgr.restart();
+        assert_eq!(Some(4), mgr.get_option_usize(bound));
+        assert_eq!(2, mgr.restarts());
+        mgr.reset();
+        assert_eq!(0, mgr.restarts());
+    }
+
+    #[test]
+    #[cfg(target_pointer_width = "64")]
+    fn luby_budgets_are_saturated() {
+        // The last element of the subsequence of length 2^63 - 1
+        let last = (1usize << 63) - 2;
+        assert_eq!(1 << 62, Luby { unit: 1 }.budget(last));
+        assert_eq!(u64::MAX, Luby { unit: 4 }.budget(last));
+        assert_eq!(1 << 63, Luby { unit: 1 }.budget(usize::MAX - 1));
+        assert_eq!(u64::MAX, Luby { unit: 3 }.budget(usize::MAX - 1));
+        assert_eq!(1, Luby { unit: 1 }.budget(usize::MAX));
+    }
+
+    fn reset_to_initial_with<B: TrailBackend>() {
+        let mut mgr = StateManager::<B>::new();
+        let xs = (0..4usize).map(|i| mgr.manage(i)).collect::<Vec<_>>();
+        let b = mgr.manage(false);
+        let o = mgr.manage(Some(true));
+        mgr.save_state();
+        mgr.set(xs[1], 10);
+        mgr.save_state();
+        mgr.set_usize_slice(xs[0], &[20, 21, 22, 23]);
+        mgr.set(b, true);
+        mgr.set(o, None);
+        mgr.save_state();
+        for &x in xs.iter() {
+            mgr.reset_to_initial(x);
+        }
+        assert!(!mgr.reset_to_initial(b));
+        assert_eq!(Some(true), mgr.reset_to_initial(o));
+        assert_eq!(vec![0, 1, 2, 3], xs.iter().map(|&x| mgr.get(x)).collect::<Vec<_>>());
+        // The resets are undone like any other write
+        mgr.restore_state();
+        assert_eq!(vec![20, 21, 22, 23], xs.iter().map(|&x| mgr.get(x)).collect::<Vec<_>>());
+        assert_eq!((true, None), (mgr.get(b), mgr.get(o)));
+        mgr.restore_state();
+        assert_eq!(1, mgr.initial(xs[1]));
+        mgr.restore_state();
+        assert_eq!(1, mgr.reset_to_initial(xs[1]));
+    }
+
+    #[test]
+    fn reset_to_initial_with_every_backend() {
+        reset_to_initial_with::<Trailing>();
+        reset_to_initial_with::<CopyOnWrite>();
+        reset_to_initial_with::<Copying>();
+        reset_to_initial_with::<Recomputing<2>>();
+        #[cfg(feature = "persistent")]
+        reset_to_initial_with::<Persistent>();
+    }
+
+    #[test]
+    fn policies() {
+        let luby = Luby { unit: 1 };
+        let budgets = (0..15).map(|restarts| luby.budget(restarts)).collect::<Vec<_>>();
+        assert_eq!(vec![1, 1, 2, 1, 1, 2, 4, 1, 1, 2, 1, 1, 2, 4, 8], budgets);
+        let geometric = Geometric { first: 100, factor: 1.5 };
+        let budgets = (0..4).map(|restarts| geometric.budget(restarts)).collect::<Vec<_>>();
+        assert_eq!(vec![100, 150, 225, 337], budgets);
+    }
+}
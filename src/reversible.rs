@@ -0,0 +1,280 @@
+//Copyright (c) 2023 X. Gillard, A. Dubray
+//
+//Permission is hereby granted, free of charge, to any person obtaining a copy
+//of this software and associated documentation files (the "Software"), to deal
+//in the Software without restriction, including without limitation the rights
+//to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+//copies of the Software, and to permit persons to whom the Software is
+//furnished to do so, subject to the following conditions:
+//
+//The above copyright notice and this permission notice shall be included in all
+//copies or substantial portions of the Software.
+//
+//THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+//IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+//AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+//OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+//SOFTWARE.
+
+
+//! The generic API of the manager. A resource of any managed type `T` has a handle of type `Reversible<T>`,
+//! created by `manage()`, read by `get()` and written by `set()`:
+//!
+#![cfg_attr(all(feature = "usize", feature = "bool"), doc = "```")]
+#![cfg_attr(not(all(feature = "usize", feature = "bool")), doc = "```ignore")]
+//! use search_trail::{Reversible, SaveAndRestore, StateManager};
+//!
+//! let mut mgr = StateManager::default();
+//! let n: Reversible<usize> = mgr.manage(3usize);
+//! let b = mgr.manage(Some(true));
+//! mgr.save_state();
+//! mgr.set(n, 42);
+//! mgr.set(b, None);
+//! assert_eq!((42, None), (mgr.get(n), mgr.get(b)));
+//! mgr.restore_state();
+//! assert_eq!((3, Some(true)), (mgr.get(n), mgr.get(b)));
+//! ```
+//!
+//! Code generic over the managed types uses the `ScopedValue` bound of the values:
+//!
+//! ```
+//! use search_trail::{Reversible, ScopedValue, StateManager, TrailBackend};
+//!
+//! fn values<T: ScopedValue, B: TrailBackend>(mgr: &StateManager<B>, handles: &[Reversible<T>]) -> Vec<T> {
+//!     handles.iter().map(|&handle| mgr.get(handle)).collect()
+//! }
+//! ```
+//!
+//! The per-type traits (`UsizeManager`, `BoolManager`, ...) are deprecated in favor of this API.
+
+use std::fmt::Debug;
+
+use paste::paste;
+
+use crate::*;
+
+/// The handle of a resource of type `T`
+pub type Reversible<T> = <T as ScopedValue>::Handle;
+
+/// A handle of a managed resource, linked to the type of the resource
+pub trait ReversibleHandle: Copy + Debug + Into<AnyHandle> {
+    /// The type of the resource
+    type Value: ScopedValue<Handle = Self>;
+}
+
+macro_rules! reversible_handles {
+    ($($u:ty => $feature:literal),*) => {
+        paste! {
+            $(
+                #[cfg(feature = $feature)]
+                impl ReversibleHandle for [<Reversible $u:camel>] {
+                    type Value = $u;
+                }
+
+                #[cfg(feature = $feature)]
+                impl ReversibleHandle for [<ReversibleOption $u:camel>] {
+                    type Value = Option<$u>;
+                }
+            )*
+        }
+    };
+}
+
+for_each_managed_type!(reversible_handles);
+
+macro_rules! replace_values {
+    ($($u:ty => $feature:literal),*) => {
+        paste! {
+            impl<B: TrailBackend> StateManager<B> {
+                $(
+                    #[doc="Sets the resource of type `" $u "` to the given value and returns its previous value"]
+                    #[cfg(feature = $feature)]
+                    pub fn [<replace_ $u>](&mut self, handle: [<Reversible $u:camel>], value: $u) -> $u {
+                        self.replace_value(handle.index(self), value).0
+                    }
+
+                    #[doc="Sets the resource of type `Option<" $u ">` to the given value and returns its previous value"]
+                    #[cfg(feature = $feature)]
+                    pub fn [<replace_option_ $u>](&mut self, handle: [<ReversibleOption $u:camel>], value: Option<$u>) -> Option<$u> {
+                        self.replace_value(handle.index(self), value).0
+                    }
+
+                    #[doc="Sets the resource of type `" $u "` to the given value if the predicate holds for its current value and the new one. Returns true if the resource is set."]
+                    #[cfg(feature = $feature)]
+                    pub fn [<set_if_ $u>](&mut self, handle: [<Reversible $u:camel>], value: $u, predicate: impl FnOnce($u, $u) -> bool) -> bool {
+                        self.set_value_if(handle.index(self), value, predicate)
+                    }
+
+                    #[doc="Sets the resource of type `Option<" $u ">` to the given value if the predicate holds for its current value and the new one. Returns true if the resource is set."]
+                    #[cfg(feature = $feature)]
+                    pub fn [<set_if_option_ $u>](&mut self, handle: [<ReversibleOption $u:camel>], value: Option<$u>, predicate: impl FnOnce(Option<$u>, Option<$u>) -> bool) -> bool {
+                        self.set_value_if(handle.index(self), value, predicate)
+                    }
+                )*
+            }
+        }
+    };
+}
+
+for_each_managed_type!(replace_values);
+
+#[cfg(feature = "bool")]
+impl<B: TrailBackend> StateManager<B> {
+    /// Sets the managed boolean to the given value and returns its previous value
+    pub fn replace_bool(&mut self, handle: ReversibleBool, value: bool) -> bool {
+        let previous = self.get_bool(handle);
+        self.set_bool(handle, value);
+        previous
+    }
+
+    /// Sets the managed optional boolean to the given value and returns its previous value
+    pub fn replace_option_bool(&mut self, handle: ReversibleOptionBool, value: Option<bool>) -> Option<bool> {
+        let previous = self.get_option_bool(handle);
+        Option::<bool>::set(self, handle, value);
+        previous
+    }
+
+    /// Sets the managed boolean to the given value if the predicate holds for its current value and the
+    /// new one. Returns true if the boolean is set.
+    pub fn set_if_bool(&mut self, handle: ReversibleBool, value: bool, predicate: impl FnOnce(bool, bool) -> bool) -> bool {
+        self.set_if(handle, value, predicate)
+    }
+
+    /// Sets the managed optional boolean to the given value if the predicate holds for its current value
+    /// and the new one. Returns true if the boolean is set.
+    pub fn set_if_option_bool(&mut self, handle: ReversibleOptionBool, value: Option<bool>, predicate: impl FnOnce(Option<bool>, Option<bool>) -> bool) -> bool {
+        self.set_if(handle, value, predicate)
+    }
+}
+
+#[cfg(numbers)]
+impl<B: TrailBackend> StateManager<B> {
+    /// Sets the resource at the given index to the given value if the predicate holds for its current
+    /// value and the new one, and returns true if it is set
+    fn set_value_if<T: Managed>(&mut self, id: usize, value: T, predicate: impl FnOnce(T, T) -> bool) -> bool {
+        let current = self.get_value::<T>(id);
+        if !predicate(current, value) || self.is_frozen_value::<T>(id) {
+            return false;
+        }
+        self.write_value(id, current, value);
+        true
+    }
+}
+
+#[cfg(feature = "bool")]
+impl ReversibleHandle for ReversibleBool {
+    type Value = bool;
+}
+
+#[cfg(feature = "bool")]
+impl ReversibleHandle for ReversibleOptionBool {
+    type Value = Option<bool>;
+}
+
+impl<B: TrailBackend> StateManager<B> {
+    /// Creates a new managed resource with the given value and returns its handle
+    pub fn manage<T: ScopedValue>(&mut self, value: T) -> Reversible<T> {
+        T::manage(self, value)
+    }
+
+    /// Returns the value of the resource
+    pub fn get<H: ReversibleHandle>(&self, handle: H) -> H::Value {
+        H::Value::get(self, handle)
+    }
+
+    /// Sets the value of the resource and returns the new value
+    pub fn set<H: ReversibleHandle>(&mut self, handle: H, value: H::Value) -> H::Value {
+        H::Value::set(self, handle, value)
+    }
+
+    /// Sets the value of the resource and returns its previous value
+    pub fn replace<H: ReversibleHandle>(&mut self, handle: H, value: H::Value) -> H::Value {
+        H::Value::replace(self, handle, value)
+    }
+
+    /// Sets the value of the resource if the predicate holds for its current value and the new one, and
+    /// returns true if the resource is set. Frozen resources are never set.
+    ///
+    #[cfg_attr(feature = "i32", doc = "```")]
+    #[cfg_attr(not(feature = "i32"), doc = "```ignore")]
+    /// use search_trail::StateManager;
+    ///
+    /// let mut mgr = StateManager::default();
+    /// let lb = mgr.manage(3i32);
+    /// // The lower bound can only increase
+    /// assert!(!mgr.set_if(lb, 2, |current, new| new > current));
+    /// assert!(mgr.set_if(lb, 5, |current, new| new > current));
+    /// assert_eq!(5, mgr.get(lb));
+    /// ```
+    pub fn set_if<H: ReversibleHandle>(&mut self, handle: H, value: H::Value, predicate: impl FnOnce(H::Value, H::Value) -> bool) -> bool {
+        if !predicate(self.get(handle), value) || self.is_frozen(handle) {
+            return false;
+        }
+        self.set(handle, value);
+        true
+    }
+}
+
+#[cfg(all(test, feature = "u8", feature = "f32", feature = "bool"))]
+mod test_reversible {
+    use crate::*;
+
+    #[test]
+    fn mixed_handles_can_be_stored_together() {
+        let mut mgr = StateManager::default();
+        let b = mgr.manage(Some(false));
+        let handles: Vec<AnyHandle> = vec![mgr.manage(1u8).into(), mgr.manage(0.5f32).into(), b.into()];
+        mgr.save_state();
+        for &handle in handles.iter() {
+            match handle {
+                AnyHandle::U8(h) => assert_eq!(2, mgr.set(h, 2)),
+                AnyHandle::F32(h) => assert_eq!(1.5, mgr.set(h, mgr.get(h) + 1.0)),
+                AnyHandle::OptionBool(h) => assert_eq!(None, mgr.set(h, None)),
+                _ => unreachable!(),
+            }
+        }
+        mgr.restore_state();
+        assert_eq!(vec![Value::U8(1), Value::F32(0.5), Value::OptionBool(Some(false))], mgr.extract(&handles));
+        assert_eq!(Some(false), mgr.get_option_bool(b));
+    }
+
+    #[test]
+    fn replace_returns_the_previous_value() {
+        let mut mgr = StateManager::default();
+        let n = mgr.manage(3u8);
+        let x = mgr.manage(Some(0.5f32));
+        let b = mgr.manage(Some(true));
+        mgr.save_state();
+        assert_eq!(3, mgr.replace_u8(n, 4));
+        assert_eq!(4, mgr.replace(n, 4));
+        assert_eq!(Some(0.5), mgr.replace_option_f32(x, None));
+        assert_eq!(Some(true), mgr.replace_option_bool(b, Some(false)));
+        assert_eq!(Some(false), mgr.replace(b, None));
+        assert_eq!((4, None, None), (mgr.get(n), mgr.get(x), mgr.get(b)));
+        mgr.restore_state();
+        assert_eq!((3, Some(0.5), Some(true)), (mgr.get(n), mgr.get(x), mgr.get(b)));
+        let flag = mgr.manage(false);
+        mgr.freeze_resource(flag);
+        assert!(!mgr.replace_bool(flag, true));
+        assert!(!mgr.get(flag));
+    }
+
+    #[test]
+    fn set_if_checks_the_predicate() {
+        let mut mgr = StateManager::default();
+        let n = mgr.manage(Some(3u8));
+        let b = mgr.manage(false);
+        mgr.save_state();
+        assert!(!mgr.set_if_option_u8(n, Some(1), |current, new| new > current));
+        assert!(mgr.set_if_option_u8(n, Some(4), |current, new| new > current));
+        assert!(mgr.set_if_bool(b, true, |current, _| !current));
+        assert!(!mgr.set_if_bool(b, false, |current, _| !current));
+        assert_eq!((Some(4), true), (mgr.get(n), mgr.get(b)));
+        mgr.freeze_resource(n);
+        assert!(!mgr.set_if(n, None, |_, _| true));
+        mgr.restore_state();
+        assert_eq!((Some(3), false), (mgr.get(n), mgr.get(b)));
+    }
+}
@@ -0,0 +1,161 @@
+//Copyright (c) 2023 X. Gillard, A. Dubray
+//
+//Permission is hereby granted, free of charge, to any person obtaining a copy
+//of this software and associated documentation files (the "Software"), to deal
+//in the Software without restriction, including without limitation the rights
+//to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+//copies of the Software, and to permit persons to whom the Software is
+//furnished to do so, subject to the following conditions:
+//
+//The above copyright notice and this permission notice shall be included in all
+//copies or substantial portions of the Software.
+//
+//THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+//IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+//AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+//OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+//SOFTWARE.
+
+
+//! A ring buffer of fixed capacity whose contents are restored with the state of the manager. Pushing in
+//! a full buffer evicts its oldest value, and restoring a level puts back the values evicted in this
+//! level. It can hold the tabu list or the window of recent conflicts of a hybrid search.
+
+use crate::{ReversibleUsize, ScopedValue, StateManager, TrailBackend, UsizeManager};
+
+/// A ring buffer of managed values whose pushes are undone by restoring the state of the manager used to
+/// modify it.
+///
+/// #Example
+///
+/// ```
+/// use search_trail::{RingBuffer, SaveAndRestore, StateManager};
+///
+/// let mut mgr = StateManager::default();
+/// let mut tabu = RingBuffer::<usize>::new(&mut mgr, 2);
+/// tabu.push(&mut mgr, 3);
+/// tabu.push(&mut mgr, 5);
+/// mgr.save_state();
+/// assert_eq!(Some(3), tabu.push(&mut mgr, 8));
+/// assert_eq!(vec![5, 8], tabu.iter(&mgr).collect::<Vec<_>>());
+/// mgr.restore_state();
+/// assert!(tabu.contains(&mgr, 3));
+/// assert!(!tabu.contains(&mgr, 8));
+/// ```
+#[derive(Debug, Clone)]
+pub struct RingBuffer<T: ScopedValue> {
+    /// The managed slots of the buffer
+    slots: Vec<T::Handle>,
+    /// The slot of the oldest value
+    start: ReversibleUsize,
+    /// Number of values in the buffer
+    len: ReversibleUsize,
+}
+
+impl<T: ScopedValue + Default> RingBuffer<T> {
+    /// Creates an empty buffer of the given capacity, trailed by the given manager
+    pub fn new<B: TrailBackend>(mgr: &mut StateManager<B>, capacity: usize) -> Self {
+        assert!(capacity > 0, "The capacity of a ring buffer must be positive");
+        Self {
+            slots: (0..capacity).map(|_| T::manage(mgr, T::default())).collect(),
+            start: mgr.manage_usize(0),
+            len: mgr.manage_usize(0),
+        }
+    }
+}
+
+impl<T: ScopedValue> RingBuffer<T> {
+    /// Returns the maximum number of values in the buffer
+    pub fn capacity(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// Returns the number of values in the buffer
+    pub fn len<B: TrailBackend>(&self, mgr: &StateManager<B>) -> usize {
+        mgr.get_usize(self.len)
+    }
+
+    /// Returns true if the buffer is empty
+    pub fn is_empty<B: TrailBackend>(&self, mgr: &StateManager<B>) -> bool {
+        self.len(mgr) == 0
+    }
+
+    /// Returns true if the next push evicts the oldest value
+    pub fn is_full<B: TrailBackend>(&self, mgr: &StateManager<B>) -> bool {
+        self.len(mgr) == self.capacity()
+    }
+
+    /// Returns the slot of the `i`-th oldest value
+    fn slot<B: TrailBackend>(&self, mgr: &StateManager<B>, i: usize) -> T::Handle {
+        self.slots[(mgr.get_usize(self.start) + i) % self.capacity()]
+    }
+
+    /// Pushes a value, and returns the oldest one if it is evicted because the buffer is full
+    pub fn push<B: TrailBackend>(&self, mgr: &mut StateManager<B>, value: T) -> Option<T> {
+        let len = self.len(mgr);
+        if len < self.capacity() {
+            T::set(mgr, self.slot(mgr, len), value);
+            mgr.set_usize(self.len, len + 1);
+            None
+        } else {
+            let slot = self.slot(mgr, 0);
+            let evicted = T::get(mgr, slot);
+            T::set(mgr, slot, value);
+            let start = mgr.get_usize(self.start);
+            mgr.set_usize(self.start, (start + 1) % self.capacity());
+            Some(evicted)
+        }
+    }
+
+    /// Returns the `i`-th oldest value, if there are more than `i` values in the buffer
+    pub fn get<B: TrailBackend>(&self, mgr: &StateManager<B>, i: usize) -> Option<T> {
+        (i < self.len(mgr)).then(|| T::get(mgr, self.slot(mgr, i)))
+    }
+
+    /// Iterates over the values of the buffer, from the oldest to the most recent
+    pub fn iter<'a, B: TrailBackend>(&'a self, mgr: &'a StateManager<B>) -> impl Iterator<Item = T> + 'a {
+        (0..self.len(mgr)).map(move |i| T::get(mgr, self.slot(mgr, i)))
+    }
+
+    /// Returns true if the buffer contains the value
+    pub fn contains<B: TrailBackend>(&self, mgr: &StateManager<B>, value: T) -> bool
+    where
+        T: PartialEq,
+    {
+        self.iter(mgr).any(|v| v == value)
+    }
+
+    /// Removes all the values of the buffer
+    pub fn clear<B: TrailBackend>(&self, mgr: &mut StateManager<B>) {
+        mgr.set_usize(self.len, 0);
+    }
+}
+
+#[cfg(all(test, feature = "i64"))]
+mod test_ring {
+    use crate::{RingBuffer, SaveAndRestore, StateManager};
+
+    #[test]
+    fn evictions_are_undone_on_restore() {
+        let mut mgr = StateManager::default();
+        let window = RingBuffer::<i64>::new(&mut mgr, 3);
+        for i in 0..3 {
+            assert_eq!(None, window.push(&mut mgr, i));
+        }
+        assert!(window.is_full(&mgr));
+        mgr.save_state();
+        assert_eq!(Some(0), window.push(&mut mgr, 3));
+        assert_eq!(Some(1), window.push(&mut mgr, 4));
+        assert_eq!(vec![2, 3, 4], window.iter(&mgr).collect::<Vec<_>>());
+        mgr.save_state();
+        window.clear(&mut mgr);
+        window.push(&mut mgr, 5);
+        assert_eq!((Some(5), None), (window.get(&mgr, 0), window.get(&mgr, 1)));
+        mgr.restore_state();
+        assert_eq!(Some(4), window.get(&mgr, 2));
+        mgr.restore_state();
+        assert_eq!(vec![0, 1, 2], window.iter(&mgr).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,303 @@
+//Copyright (c) 2023 X. Gillard, A. Dubray
+//
+//Permission is hereby granted, free of charge, to any person obtaining a copy
+//of this software and associated documentation files (the "Software"), to deal
+//in the Software without restriction, including without limitation the rights
+//to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+//copies of the Software, and to permit persons to whom the Software is
+//furnished to do so, subject to the following conditions:
+//
+//The above copyright notice and this permission notice shall be included in all
+//copies or substantial portions of the Software.
+//
+//THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+//IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+//AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+//OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+//SOFTWARE.
+
+
+//! A scoped API whose handles are branded with an invariant lifetime, unique to each scope. A handle
+//! can only be used with the manager of the scope that created it, and using it with another manager
+//! is a compile error:
+//!
+//! ```compile_fail
+//! use search_trail::StateManager;
+//!
+//! StateManager::scoped(|a| {
+//!     let x = a.manage(0usize);
+//!     StateManager::scoped(|b| {
+//!         b.get(x);
+//!     });
+//! });
+//! ```
+
+use std::fmt::Debug;
+use std::marker::PhantomData;
+
+use paste::paste;
+
+use crate::*;
+
+/// An invariant lifetime, used to brand the handles of a scope
+type Brand<'brand> = PhantomData<fn(&'brand ()) -> &'brand ()>;
+
+/// A type of value that can be managed in a scope
+pub trait ScopedValue: Copy {
+    /// The handle of a managed value of this type
+    type Handle: ReversibleHandle<Value = Self> + PartialEq;
+
+    /// Creates a new managed value and returns its handle
+    fn manage<B: TrailBackend>(mgr: &mut StateManager<B>, value: Self) -> Self::Handle;
+    /// Returns the value of the given handle
+    fn get<B: TrailBackend>(mgr: &StateManager<B>, handle: Self::Handle) -> Self;
+    /// Sets the value of the given handle and returns the new value
+    fn set<B: TrailBackend>(mgr: &mut StateManager<B>, handle: Self::Handle, value: Self) -> Self;
+    /// Sets the value of the given handle and returns its previous value
+    fn replace<B: TrailBackend>(mgr: &mut StateManager<B>, handle: Self::Handle, value: Self) -> Self;
+    /// Returns the value the given handle had at the root
+    fn initial<B: TrailBackend>(mgr: &StateManager<B>, handle: Self::Handle) -> Self;
+}
+
+macro_rules! scoped_values {
+    ($($u:ty => $feature:literal),*) => {
+        paste! {
+            $(
+                #[cfg(feature = $feature)]
+                impl ScopedValue for $u {
+                    type Handle = [<Reversible $u:camel>];
+
+                    fn manage<B: TrailBackend>(mgr: &mut StateManager<B>, value: Self) -> Self::Handle {
+                        mgr.[<manage _ $u>](value)
+                    }
+
+                    fn get<B: TrailBackend>(mgr: &StateManager<B>, handle: Self::Handle) -> Self {
+                        mgr.[<get _ $u>](handle)
+                    }
+
+                    fn set<B: TrailBackend>(mgr: &mut StateManager<B>, handle: Self::Handle, value: Self) -> Self {
+                        mgr.[<set _ $u>](handle, value)
+                    }
+
+                    fn replace<B: TrailBackend>(mgr: &mut StateManager<B>, handle: Self::Handle, value: Self) -> Self {
+                        mgr.[<replace_ $u>](handle, value)
+                    }
+
+                    fn initial<B: TrailBackend>(mgr: &StateManager<B>, handle: Self::Handle) -> Self {
+                        mgr.initial_value(handle.index(mgr))
+                    }
+                }
+
+                #[cfg(feature = $feature)]
+                impl ScopedValue for Option<$u> {
+                    type Handle = [<ReversibleOption $u:camel>];
+
+                    fn manage<B: TrailBackend>(mgr: &mut StateManager<B>, value: Self) -> Self::Handle {
+                        mgr.[<manage_option_ $u>](value)
+                    }
+
+                    fn get<B: TrailBackend>(mgr: &StateManager<B>, handle: Self::Handle) -> Self {
+                        mgr.[<get_option_ $u>](handle)
+                    }
+
+                    fn set<B: TrailBackend>(mgr: &mut StateManager<B>, handle: Self::Handle, value: Self) -> Self {
+                        mgr.[<set_option_ $u>](handle, value)
+                    }
+
+                    fn replace<B: TrailBackend>(mgr: &mut StateManager<B>, handle: Self::Handle, value: Self) -> Self {
+                        mgr.[<replace_option_ $u>](handle, value)
+                    }
+
+                    fn initial<B: TrailBackend>(mgr: &StateManager<B>, handle: Self::Handle) -> Self {
+                        mgr.initial_value(handle.index(mgr))
+                    }
+                }
+            )*
+        }
+    };
+}
+
+for_each_managed_type!(scoped_values);
+
+#[cfg(feature = "bool")]
+impl ScopedValue for bool {
+    type Handle = ReversibleBool;
+
+    fn manage<B: TrailBackend>(mgr: &mut StateManager<B>, value: Self) -> Self::Handle {
+        mgr.manage_bool(value)
+    }
+
+    fn get<B: TrailBackend>(mgr: &StateManager<B>, handle: Self::Handle) -> Self {
+        mgr.get_bool(handle)
+    }
+
+    fn set<B: TrailBackend>(mgr: &mut StateManager<B>, handle: Self::Handle, value: Self) -> Self {
+        mgr.set_bool(handle, value)
+    }
+
+    fn replace<B: TrailBackend>(mgr: &mut StateManager<B>, handle: Self::Handle, value: Self) -> Self {
+        mgr.replace_bool(handle, value)
+    }
+
+    fn initial<B: TrailBackend>(mgr: &StateManager<B>, handle: Self::Handle) -> Self {
+        mgr.bools.root(handle.index(mgr)) != 0
+    }
+}
+
+#[cfg(feature = "bool")]
+impl ScopedValue for Option<bool> {
+    type Handle = ReversibleOptionBool;
+
+    fn manage<B: TrailBackend>(mgr: &mut StateManager<B>, value: Self) -> Self::Handle {
+        mgr.manage_option_bool(value)
+    }
+
+    fn get<B: TrailBackend>(mgr: &StateManager<B>, handle: Self::Handle) -> Self {
+        mgr.get_option_bool(handle)
+    }
+
+    fn set<B: TrailBackend>(mgr: &mut StateManager<B>, handle: Self::Handle, value: Self) -> Self {
+        match value {
+            Some(b) => {
+                mgr.set_option_bool(handle, b);
+            }
+            None => mgr.set_option_bool_none(handle),
+        }
+        mgr.get_option_bool(handle)
+    }
+
+    fn replace<B: TrailBackend>(mgr: &mut StateManager<B>, handle: Self::Handle, value: Self) -> Self {
+        mgr.replace_option_bool(handle, value)
+    }
+
+    fn initial<B: TrailBackend>(mgr: &StateManager<B>, handle: Self::Handle) -> Self {
+        StateManager::<B>::option_bool_value(mgr.option_bools.root(handle.index(mgr)))
+    }
+}
+
+/// A handle to a value of type `T`, that can only be used in the scope identified by `'brand`
+pub struct Branded<'brand, T: ScopedValue> {
+    handle: T::Handle,
+    brand: Brand<'brand>,
+}
+
+impl<T: ScopedValue> Clone for Branded<'_, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T: ScopedValue> Copy for Branded<'_, T> {}
+
+impl<T: ScopedValue> Debug for Branded<'_, T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("Branded").field(&self.handle).finish()
+    }
+}
+
+impl<T: ScopedValue> PartialEq for Branded<'_, T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.handle == other.handle
+    }
+}
+
+/// A manager borrowed for the duration of a scope. The handles it creates are branded with the
+/// lifetime `'brand` of the scope.
+pub struct ScopedManager<'brand, B: TrailBackend = Trailing> {
+    mgr: &'brand mut StateManager<B>,
+    brand: Brand<'brand>,
+}
+
+impl<'brand, B: TrailBackend> ScopedManager<'brand, B> {
+    /// Creates a new managed value and returns its handle
+    pub fn manage<T: ScopedValue>(&mut self, value: T) -> Branded<'brand, T> {
+        Branded {
+            handle: T::manage(self.mgr, value),
+            brand: self.brand,
+        }
+    }
+
+    /// Returns the value of the given handle
+    pub fn get<T: ScopedValue>(&self, handle: Branded<'brand, T>) -> T {
+        T::get(self.mgr, handle.handle)
+    }
+
+    /// Sets the value of the given handle and returns the new value
+    pub fn set<T: ScopedValue>(&mut self, handle: Branded<'brand, T>, value: T) -> T {
+        T::set(self.mgr, handle.handle, value)
+    }
+
+    /// Returns the underlying manager
+    pub fn manager(&self) -> &StateManager<B> {
+        self.mgr
+    }
+}
+
+impl<B: TrailBackend> SaveAndRestore for ScopedManager<'_, B> {
+    fn save_state(&mut self) {
+        self.mgr.save_state();
+    }
+
+    fn restore_state(&mut self) {
+        self.mgr.restore_state();
+    }
+}
+
+impl<B: TrailBackend> StateManager<B> {
+    /// Runs the given function with a scope borrowing this manager. The handles created in the scope
+    /// can not be used outside of it, nor with the manager of another scope.
+    pub fn scope<R>(&mut self, f: impl for<'brand> FnOnce(&mut ScopedManager<'brand, B>) -> R) -> R {
+        f(&mut ScopedManager {
+            mgr: self,
+            brand: PhantomData,
+        })
+    }
+}
+
+impl StateManager<Trailing> {
+    /// Runs the given function with a scope on a new manager
+    ///
+    /// #Example
+    ///
+    #[cfg_attr(feature = "usize", doc = "```")]
+    #[cfg_attr(not(feature = "usize"), doc = "```ignore")]
+    /// use search_trail::{SaveAndRestore, StateManager};
+    ///
+    /// let sum = StateManager::scoped(|mgr| {
+    ///     let x = mgr.manage(1usize);
+    ///     let y = mgr.manage(2usize);
+    ///     mgr.save_state();
+    ///     mgr.set(x, 10);
+    ///     mgr.restore_state();
+    ///     mgr.get(x) + mgr.get(y)
+    /// });
+    /// assert_eq!(3, sum);
+    /// ```
+    pub fn scoped<R>(f: impl for<'brand> FnOnce(&mut ScopedManager<'brand>) -> R) -> R {
+        Self::default().scope(f)
+    }
+}
+
+#[cfg(all(test, feature = "usize", feature = "bool"))]
+mod test_scoped {
+    use crate::{SaveAndRestore, StateManager};
+
+    #[test]
+    fn scope_on_existing_manager() {
+        let mut mgr = StateManager::default();
+        let flags = mgr.scope(|scope| {
+            let a = scope.manage(Some(true));
+            let b = scope.manage(3usize);
+            scope.save_state();
+            scope.set(a, None);
+            scope.set(b, 4);
+            assert_eq!(None, scope.get(a));
+            scope.restore_state();
+            (scope.get(a), scope.get(b))
+        });
+        assert_eq!((Some(true), 3), flags);
+        assert_eq!(0, mgr.depth);
+    }
+}
@@ -0,0 +1,330 @@
+//Copyright (c) 2023 X. Gillard, A. Dubray
+//
+//Permission is hereby granted, free of charge, to any person obtaining a copy
+//of this software and associated documentation files (the "Software"), to deal
+//in the Software without restriction, including without limitation the rights
+//to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+//copies of the Software, and to permit persons to whom the Software is
+//furnished to do so, subject to the following conditions:
+//
+//The above copyright notice and this permission notice shall be included in all
+//copies or substantial portions of the Software.
+//
+//THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+//IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+//AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+//OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+//SOFTWARE.
+
+
+//! A depth-first search engine driven by the manager. The problem gives the branches of each node,
+//! propagates the decisions and bounds the nodes, and the engine saves and restores the state of the
+//! manager around each branch, counts the explored nodes and keeps the best solution found.
+//!
+//! The engine minimizes the cost of the solutions: a node is pruned when its bound is not lower than
+//! the cost of the best solution found so far. A satisfaction problem uses `()` as cost, so that the
+//! first solution is kept, and a maximization problem can use `std::cmp::Reverse` costs.
+//!
+//! ```
+//! use search_trail::{Dfs, ReversibleUsize, SearchProblem, StateManager, UsizeManager};
+//!
+//! /// A knapsack maximizing the total value, with the items taken in order
+//! struct Knapsack {
+//!     items: Vec<(usize, usize)>,
+//!     next: ReversibleUsize,
+//!     capacity: ReversibleUsize,
+//!     value: ReversibleUsize,
+//! }
+//!
+//! impl SearchProblem for Knapsack {
+//!     type Decision = bool;
+//!     type Solution = usize;
+//!     type Cost = std::cmp::Reverse<usize>;
+//!
+//!     fn branches(&mut self, mgr: &StateManager) -> Vec<bool> {
+//!         let next = mgr.get_usize(self.next);
+//!         if next == self.items.len() {
+//!             vec![]
+//!         } else if self.items[next].0 <= mgr.get_usize(self.capacity) {
+//!             vec![true, false]
+//!         } else {
+//!             vec![false]
+//!         }
+//!     }
+//!
+//!     fn decide(&mut self, mgr: &mut StateManager, take: &bool) {
+//!         let (weight, value) = self.items[mgr.get_usize(self.next)];
+//!         if *take {
+//!             let capacity = mgr.get_usize(self.capacity);
+//!             mgr.set_usize(self.capacity, capacity - weight);
+//!             let total = mgr.get_usize(self.value);
+//!             mgr.set_usize(self.value, total + value);
+//!         }
+//!         mgr.increment_usize(self.next);
+//!     }
+//!
+//!     fn bound(&mut self, mgr: &StateManager) -> Option<Self::Cost> {
+//!         let remaining = self.items[mgr.get_usize(self.next)..].iter().map(|item| item.1).sum::<usize>();
+//!         Some(std::cmp::Reverse(mgr.get_usize(self.value) + remaining))
+//!     }
+//!
+//!     fn solution(&mut self, mgr: &StateManager) -> Option<(usize, Self::Cost)> {
+//!         let value = mgr.get_usize(self.value);
+//!         Some((value, std::cmp::Reverse(value)))
+//!     }
+//! }
+//!
+//! let mut mgr = StateManager::default();
+//! let mut knapsack = Knapsack {
+//!     items: vec![(5, 10), (4, 40), (6, 30), (3, 50)],
+//!     next: mgr.manage_usize(0),
+//!     capacity: mgr.manage_usize(10),
+//!     value: mgr.manage_usize(0),
+//! };
+//! let outcome = Dfs::default().run(&mut mgr, &mut knapsack);
+//! assert_eq!(Some(90), outcome.best.map(|(value, _)| value));
+//! assert!(outcome.complete);
+//! // The manager is back to the state it was in before the search
+//! assert_eq!(10, mgr.get_usize(knapsack.capacity));
+//! ```
+
+use crate::{SaveAndRestore, StateManager, TrailBackend, Trailing};
+
+/// A problem explored by the `Dfs` engine
+pub trait SearchProblem<B: TrailBackend = Trailing> {
+    /// A decision taken to go from a node to one of its children
+    type Decision;
+    /// A solution of the problem
+    type Solution;
+    /// The cost of a solution, which is minimized
+    type Cost: PartialOrd;
+
+    /// Propagates the decisions taken to reach the current node. Returns false if the node can not
+    /// lead to a solution. By default, nothing is propagated.
+    fn propagate(&mut self, _mgr: &mut StateManager<B>) -> bool {
+        true
+    }
+
+    /// Returns the decisions leading to the children of the current node, in the order in which they
+    /// are explored. The node is a leaf if there is none.
+    fn branches(&mut self, mgr: &StateManager<B>) -> Vec<Self::Decision>;
+
+    /// Takes the given decision. It is called just after the state of the manager has been saved.
+    fn decide(&mut self, mgr: &mut StateManager<B>, decision: &Self::Decision);
+
+    /// Returns a lower bound on the cost of the solutions below the current node, if any. By default,
+    /// the nodes are not bounded.
+    fn bound(&mut self, _mgr: &StateManager<B>) -> Option<Self::Cost> {
+        None
+    }
+
+    /// Returns the solution of the current leaf and its cost, or none if the leaf is not a solution
+    fn solution(&mut self, mgr: &StateManager<B>) -> Option<(Self::Solution, Self::Cost)>;
+}
+
+/// The statistics of a search
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SearchStats {
+    /// The number of visited nodes, including the root
+    pub nodes: usize,
+    /// The number of nodes that failed: their propagation failed, or they are leaves that are not
+    /// solutions
+    pub failures: usize,
+    /// The number of solutions found, including the ones that do not improve the best one
+    pub solutions: usize,
+    /// The number of nodes pruned by their bound
+    pub pruned: usize,
+    /// The depth of the deepest visited node, the root being at depth 0
+    pub max_depth: usize,
+}
+
+/// The result of a search
+#[derive(Debug, Clone)]
+pub struct SearchOutcome<S, C> {
+    /// The best solution found, and its cost
+    pub best: Option<(S, C)>,
+    /// The statistics of the search
+    pub stats: SearchStats,
+    /// True if the whole tree has been explored (i.e., the search was not stopped by a limit), in
+    /// which case the best solution is optimal
+    pub complete: bool,
+}
+
+/// A depth-first search engine. The search starts at the current state of the manager, and the
+/// manager is back to this state when it returns.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Dfs {
+    /// Stops the search after visiting this number of nodes
+    pub node_limit: Option<usize>,
+    /// Stops the search at the first solution
+    pub first_solution: bool,
+}
+
+impl Dfs {
+    /// Explores the tree of the problem from the current state of the manager, and returns the best
+    /// solution found
+    pub fn run<B: TrailBackend, P: SearchProblem<B>>(&self, mgr: &mut StateManager<B>, problem: &mut P) -> SearchOutcome<P::Solution, P::Cost> {
+        let mut outcome = SearchOutcome {
+            best: None,
+            stats: SearchStats::default(),
+            complete: true,
+        };
+        // The decisions left to explore at each open node. Each open node has saved a level.
+        let mut open = vec![];
+        mgr.save_state();
+        match self.visit(mgr, problem, &mut outcome, 0) {
+            Some(branches) => open.push(branches.into_iter()),
+            None => mgr.restore_state(),
+        }
+        while let Some(branches) = open.last_mut() {
+            if self.must_stop(&outcome.stats) {
+                outcome.complete = false;
+                for _ in 0..open.len() {
+                    mgr.restore_state();
+                }
+                break;
+            }
+            match branches.next() {
+                Some(decision) => {
+                    mgr.save_state();
+                    problem.decide(mgr, &decision);
+                    match self.visit(mgr, problem, &mut outcome, open.len()) {
+                        Some(branches) => open.push(branches.into_iter()),
+                        None => mgr.restore_state(),
+                    }
+                }
+                None => {
+                    open.pop();
+                    mgr.restore_state();
+                }
+            }
+        }
+        outcome
+    }
+
+    /// Returns true if a limit of the search is reached
+    fn must_stop(&self, stats: &SearchStats) -> bool {
+        (self.first_solution && stats.solutions > 0) || self.node_limit.is_some_and(|limit| stats.nodes >= limit)
+    }
+
+    /// Propagates and bounds the current node, and returns its branches if it must be explored
+    fn visit<B: TrailBackend, P: SearchProblem<B>>(&self, mgr: &mut StateManager<B>, problem: &mut P, outcome: &mut SearchOutcome<P::Solution, P::Cost>, depth: usize) -> Option<Vec<P::Decision>> {
+        let stats = &mut outcome.stats;
+        stats.nodes += 1;
+        stats.max_depth = stats.max_depth.max(depth);
+        if !problem.propagate(mgr) {
+            stats.failures += 1;
+            return None;
+        }
+        if let (Some(bound), Some((_, best))) = (problem.bound(mgr), outcome.best.as_ref()) {
+            if bound.partial_cmp(best) != Some(std::cmp::Ordering::Less) {
+                stats.pruned += 1;
+                return None;
+            }
+        }
+        let branches = problem.branches(mgr);
+        if !branches.is_empty() {
+            return Some(branches);
+        }
+        match problem.solution(mgr) {
+            Some((solution, cost)) => {
+                stats.solutions += 1;
+                if outcome.best.as_ref().is_none_or(|(_, best)| cost < *best) {
+                    outcome.best = Some((solution, cost));
+                }
+            }
+            None => stats.failures += 1,
+        }
+        None
+    }
+}
+
+#[cfg(all(test, feature = "usize"))]
+mod test_search {
+    use crate::*;
+
+    /// The n-queens problem, one queen per row
+    struct Queens {
+        n: usize,
+        queens: Vec<ReversibleUsize>,
+        placed: ReversibleUsize,
+    }
+
+    impl SearchProblem for Queens {
+        type Decision = usize;
+        type Solution = Vec<usize>;
+        type Cost = ();
+
+        fn propagate(&mut self, mgr: &mut StateManager) -> bool {
+            let placed = mgr.get_usize(self.placed);
+            let columns = self.queens[..placed].iter().map(|&q| mgr.get_usize(q)).collect::<Vec<_>>();
+            columns.iter().enumerate().all(|(i, &a)| {
+                columns[i + 1..].iter().enumerate().all(|(j, &b)| a != b && a.abs_diff(b) != j + 1)
+            })
+        }
+
+        fn branches(&mut self, mgr: &StateManager) -> Vec<usize> {
+            if mgr.get_usize(self.placed) == self.n {
+                vec![]
+            } else {
+                (0..self.n).collect()
+            }
+        }
+
+        fn decide(&mut self, mgr: &mut StateManager, column: &usize) {
+            let row = mgr.get_usize(self.placed);
+            mgr.set_usize(self.queens[row], *column);
+            mgr.increment_usize(self.placed);
+        }
+
+        fn solution(&mut self, mgr: &StateManager) -> Option<(Vec<usize>, ())> {
+            Some((self.queens.iter().map(|&q| mgr.get_usize(q)).collect(), ()))
+        }
+    }
+
+    fn queens(mgr: &mut StateManager, n: usize) -> Queens {
+        Queens {
+            n,
+            queens: (0..n).map(|_| mgr.manage_usize(0)).collect(),
+            placed: mgr.manage_usize(0),
+        }
+    }
+
+    #[test]
+    fn counts_all_the_solutions() {
+        let mut mgr = StateManager::default();
+        let mut problem = queens(&mut mgr, 6);
+        let outcome = Dfs::default().run(&mut mgr, &mut problem);
+        assert_eq!(4, outcome.stats.solutions);
+        assert_eq!(6, outcome.stats.max_depth);
+        assert_eq!(Some(vec![1, 3, 5, 0, 2, 4]), outcome.best.map(|(queens, _)| queens));
+        assert!(outcome.complete);
+        assert_eq!(0, mgr.get_usize(problem.placed));
+        assert_eq!(0, mgr.trail_len());
+    }
+
+    #[test]
+    fn limits_stop_the_search() {
+        let mut mgr = StateManager::default();
+        let mut problem = queens(&mut mgr, 6);
+        let dfs = Dfs {
+            first_solution: true,
+            ..Dfs::default()
+        };
+        let outcome = dfs.run(&mut mgr, &mut problem);
+        assert_eq!(1, outcome.stats.solutions);
+        assert!(!outcome.complete);
+        assert_eq!(0, mgr.get_usize(problem.placed));
+
+        let dfs = Dfs {
+            node_limit: Some(10),
+            ..Dfs::default()
+        };
+        let outcome = dfs.run(&mut mgr, &mut problem);
+        assert_eq!(10, outcome.stats.nodes);
+        assert!(outcome.best.is_none() && !outcome.complete);
+        assert_eq!(0, mgr.get_usize(problem.placed));
+    }
+}
@@ -0,0 +1,199 @@
+//Copyright (c) 2023 X. Gillard, A. Dubray
+//
+//Permission is hereby granted, free of charge, to any person obtaining a copy
+//of this software and associated documentation files (the "Software"), to deal
+//in the Software without restriction, including without limitation the rights
+//to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+//copies of the Software, and to permit persons to whom the Software is
+//furnished to do so, subject to the following conditions:
+//
+//The above copyright notice and this permission notice shall be included in all
+//copies or substantial portions of the Software.
+//
+//THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+//IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+//AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+//OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+//SOFTWARE.
+
+//! A segment tree over an array of managed values, giving the aggregate (sum, minimum, maximum, ...) of
+//! any range of the array in logarithmic time, as needed by the cumulative resource reasoning. The
+//! nodes of the tree are managed values: an update writes the nodes above the updated value, and stops as
+//! soon as a node keeps its aggregate, so that only the nodes that change are trailed.
+
+use std::ops::Range;
+
+use num_traits::{Bounded, Num};
+
+use crate::*;
+
+/// A segment tree whose updates are undone by restoring the state of the manager used to modify it.
+///
+/// #Example
+///
+#[cfg_attr(feature = "i64", doc = "```")]
+#[cfg_attr(not(feature = "i64"), doc = "```ignore")]
+/// use search_trail::{SaveAndRestore, SegmentTree, StateManager};
+///
+/// let mut mgr = StateManager::default();
+/// let usage = SegmentTree::max(&mut mgr, &[2i64, 0, 5, 1]);
+/// assert_eq!(2, usage.query(&mgr, 0..2));
+/// mgr.save_state();
+/// usage.set(&mut mgr, 1, 7);
+/// assert_eq!(7, usage.query(&mgr, 0..2));
+/// mgr.restore_state();
+/// assert_eq!(5, usage.query(&mgr, 0..4));
+/// ```
+#[derive(Debug, Clone)]
+pub struct SegmentTree<T: ScopedValue> {
+    /// The nodes of the tree, whose root is the first one. The node `i` (counted from 1) has the children
+    /// `2i` and `2i + 1`, and the values of the array are the last `len` nodes.
+    nodes: Vec<T::Handle>,
+    /// The aggregate of an empty range
+    identity: T,
+    /// Aggregates two consecutive ranges
+    combine: fn(T, T) -> T,
+}
+
+impl<T: ScopedValue + PartialEq> SegmentTree<T> {
+    /// Creates a tree over the given values, trailed by the given manager. The aggregate of a range is
+    /// computed by `combine`, which must be associative and for which `identity` is neutral.
+    pub fn new<B: TrailBackend>(mgr: &mut StateManager<B>, values: &[T], identity: T, combine: fn(T, T) -> T) -> Self {
+        let len = values.len();
+        let mut aggregates = vec![identity; 2 * len];
+        aggregates[len..].copy_from_slice(values);
+        for node in (1..len).rev() {
+            aggregates[node] = combine(aggregates[2 * node], aggregates[2 * node + 1]);
+        }
+        Self {
+            nodes: aggregates.into_iter().skip(1).map(|aggregate| mgr.manage(aggregate)).collect(),
+            identity,
+            combine,
+        }
+    }
+
+    /// Returns the number of values in the array
+    pub fn len(&self) -> usize {
+        self.nodes.len().div_ceil(2)
+    }
+
+    /// Returns true if the array is empty
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Returns the value at the given index of the array
+    pub fn get<B: TrailBackend>(&self, mgr: &StateManager<B>, index: usize) -> T {
+        assert!(index < self.len(), "The index {} is out of the array of {} values", index, self.len());
+        self.node(mgr, self.len() + index)
+    }
+
+    /// Sets the value at the given index of the array, and updates the aggregates of the ranges including it
+    pub fn set<B: TrailBackend>(&self, mgr: &mut StateManager<B>, index: usize, value: T) {
+        assert!(index < self.len(), "The index {} is out of the array of {} values", index, self.len());
+        let mut node = self.len() + index;
+        mgr.set(self.nodes[node - 1], value);
+        while node > 1 {
+            node /= 2;
+            let aggregate = (self.combine)(self.node(mgr, 2 * node), self.node(mgr, 2 * node + 1));
+            if mgr.replace(self.nodes[node - 1], aggregate) == aggregate {
+                break;
+            }
+        }
+    }
+
+    /// Returns the aggregate of the values in the given range of indices
+    pub fn query<B: TrailBackend>(&self, mgr: &StateManager<B>, range: Range<usize>) -> T {
+        assert!(range.end <= self.len(), "The range {:?} is out of the array of {} values", range, self.len());
+        let (mut left, mut right) = (self.identity, self.identity);
+        let (mut start, mut end) = (range.start + self.len(), range.end + self.len());
+        while start < end {
+            if start % 2 == 1 {
+                left = (self.combine)(left, self.node(mgr, start));
+                start += 1;
+            }
+            if end % 2 == 1 {
+                end -= 1;
+                right = (self.combine)(self.node(mgr, end), right);
+            }
+            start /= 2;
+            end /= 2;
+        }
+        (self.combine)(left, right)
+    }
+
+    /// Returns the aggregate of the whole array
+    pub fn total<B: TrailBackend>(&self, mgr: &StateManager<B>) -> T {
+        self.query(mgr, 0..self.len())
+    }
+
+    /// Returns the aggregate of the node, counted from 1
+    fn node<B: TrailBackend>(&self, mgr: &StateManager<B>, node: usize) -> T {
+        mgr.get(self.nodes[node - 1])
+    }
+}
+
+impl<T: ScopedValue + Num> SegmentTree<T> {
+    /// Creates a tree giving the sum of the ranges of the given values
+    pub fn sum<B: TrailBackend>(mgr: &mut StateManager<B>, values: &[T]) -> Self {
+        Self::new(mgr, values, T::zero(), |a, b| a + b)
+    }
+}
+
+impl<T: ScopedValue + PartialOrd + Bounded> SegmentTree<T> {
+    /// Creates a tree giving the minimum of the ranges of the given values
+    pub fn min<B: TrailBackend>(mgr: &mut StateManager<B>, values: &[T]) -> Self {
+        Self::new(mgr, values, T::max_value(), |a, b| if b < a { b } else { a })
+    }
+
+    /// Creates a tree giving the maximum of the ranges of the given values
+    pub fn max<B: TrailBackend>(mgr: &mut StateManager<B>, values: &[T]) -> Self {
+        Self::new(mgr, values, T::min_value(), |a, b| if b > a { b } else { a })
+    }
+}
+
+#[cfg(all(test, feature = "u32", feature = "i16"))]
+mod test_segment {
+    use crate::*;
+
+    #[test]
+    fn queries_match_a_scan() {
+        let mut mgr = StateManager::default();
+        let mut values = vec![3u32, 1, 4, 1, 5, 9, 2, 6, 5, 3, 5];
+        let sums = SegmentTree::sum(&mut mgr, &values);
+        let mins = SegmentTree::min(&mut mgr, &values);
+        let mut saved = vec![];
+        for (index, value) in [(4, 0), (10, 8), (0, 7), (6, 6)] {
+            mgr.save_state();
+            saved.push(values.clone());
+            values[index] = value;
+            sums.set(&mut mgr, index, value);
+            mins.set(&mut mgr, index, value);
+            for start in 0..values.len() {
+                for end in start..=values.len() {
+                    assert_eq!(values[start..end].iter().sum::<u32>(), sums.query(&mgr, start..end));
+                    assert_eq!(values[start..end].iter().copied().min().unwrap_or(u32::MAX), mins.query(&mgr, start..end));
+                }
+            }
+        }
+        while let Some(previous) = saved.pop() {
+            mgr.restore_state();
+            assert_eq!(previous.iter().sum::<u32>(), sums.total(&mgr));
+            assert_eq!(previous, (0..values.len()).map(|index| sums.get(&mgr, index)).collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn empty_and_single_arrays() {
+        let mut mgr = StateManager::default();
+        let empty = SegmentTree::<i16>::max(&mut mgr, &[]);
+        assert!(empty.is_empty());
+        assert_eq!(i16::MIN, empty.total(&mgr));
+        let single = SegmentTree::max(&mut mgr, &[-4i16]);
+        assert_eq!(1, single.len());
+        single.set(&mut mgr, 0, 2);
+        assert_eq!(2, single.total(&mgr));
+    }
+}
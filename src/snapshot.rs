@@ -0,0 +1,375 @@
+//Copyright (c) 2023 X. Gillard, A. Dubray
+//
+//Permission is hereby granted, free of charge, to any person obtaining a copy
+//of this software and associated documentation files (the "Software"), to deal
+//in the Software without restriction, including without limitation the rights
+//to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+//copies of the Software, and to permit persons to whom the Software is
+//furnished to do so, subject to the following conditions:
+//
+//The above copyright notice and this permission notice shall be included in all
+//copies or substantial portions of the Software.
+//
+//THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+//IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+//AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+//OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+//SOFTWARE.
+
+
+//! Compact binary snapshots of a manager, including its saved levels. The arrays of the stores are
+//! written column by column, and the integers (values, indexes, clocks) are written as variable-length
+//! integers, so that a snapshot of a large manager is much smaller than its in-memory representation.
+
+use paste::paste;
+
+use crate::*;
+
+/// Identifies the snapshots of a manager
+const MAGIC: &[u8; 4] = b"STRL";
+/// Version of the layout of the snapshots
+const VERSION: u8 = 1;
+
+/// The errors that can occur when reading a snapshot
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SnapshotError {
+    /// The snapshot ends in the middle of an entry
+    Truncated,
+    /// The bytes are not a snapshot, or a snapshot written by an unsupported version of the crate
+    InvalidHeader,
+    /// The snapshot was written by a manager with other managed types
+    LayoutMismatch,
+    /// The snapshot describes a manager that is not consistent
+    Corrupted,
+}
+
+impl std::fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SnapshotError::Truncated => write!(f, "the snapshot is truncated"),
+            SnapshotError::InvalidHeader => write!(f, "the bytes are not a supported snapshot"),
+            SnapshotError::LayoutMismatch => write!(f, "the snapshot does not have the managed types of this build"),
+            SnapshotError::Corrupted => write!(f, "the snapshot is corrupted"),
+        }
+    }
+}
+
+impl std::error::Error for SnapshotError {}
+
+/// Writes an unsigned integer as a LEB128 variable-length integer
+fn write_varint(mut value: u128, out: &mut Vec<u8>) {
+    while value >= 0x80 {
+        out.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn read_varint(input: &mut &[u8]) -> Result<u128, SnapshotError> {
+    let mut value = 0u128;
+    let mut shift = 0;
+    loop {
+        let (byte, rest) = input.split_first().ok_or(SnapshotError::Truncated)?;
+        *input = rest;
+        if shift >= 128 {
+            return Err(SnapshotError::Corrupted);
+        }
+        value |= ((byte & 0x7f) as u128) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+        shift += 7;
+    }
+}
+
+pub(crate) fn write_usize(value: usize, out: &mut Vec<u8>) {
+    write_varint(value as u128, out);
+}
+
+pub(crate) fn read_usize(input: &mut &[u8]) -> Result<usize, SnapshotError> {
+    usize::try_from(read_varint(input)?).map_err(|_| SnapshotError::Corrupted)
+}
+
+/// Reads a column of `len` entries
+pub(crate) fn read_column<T>(input: &mut &[u8], len: usize, read: impl Fn(&mut &[u8]) -> Result<T, SnapshotError>) -> Result<Vec<T>, SnapshotError> {
+    // Each entry takes at least one byte, which bounds the allocation for a corrupted length
+    if len > input.len() {
+        return Err(SnapshotError::Truncated);
+    }
+    (0..len).map(|_| read(input)).collect()
+}
+
+/// A value that can be written in a snapshot
+pub(crate) trait SnapshotValue: Sized {
+    fn write(&self, out: &mut Vec<u8>);
+    fn read(input: &mut &[u8]) -> Result<Self, SnapshotError>;
+}
+
+macro_rules! snapshot_unsigned {
+    ($($t:ty),*) => {
+        $(
+            impl SnapshotValue for $t {
+                fn write(&self, out: &mut Vec<u8>) {
+                    write_varint(*self as u128, out);
+                }
+
+                fn read(input: &mut &[u8]) -> Result<Self, SnapshotError> {
+                    <$t>::try_from(read_varint(input)?).map_err(|_| SnapshotError::Corrupted)
+                }
+            }
+        )*
+    };
+}
+
+snapshot_unsigned!(u8, u16, u32, u64, u128, usize);
+
+// The signed integers are zigzag-encoded, so that small negative values are small varints
+macro_rules! snapshot_signed {
+    ($($t:ty => $unsigned:ty),*) => {
+        $(
+            impl SnapshotValue for $t {
+                fn write(&self, out: &mut Vec<u8>) {
+                    let zigzag = ((*self << 1) ^ (*self >> (<$t>::BITS - 1))) as $unsigned;
+                    zigzag.write(out);
+                }
+
+                fn read(input: &mut &[u8]) -> Result<Self, SnapshotError> {
+                    let zigzag = <$unsigned>::read(input)?;
+                    Ok(((zigzag >> 1) as $t) ^ -((zigzag & 1) as $t))
+                }
+            }
+        )*
+    };
+}
+
+snapshot_signed!(i8 => u8, i16 => u16, i32 => u32, i64 => u64, i128 => u128, isize => usize);
+
+macro_rules! snapshot_float {
+    ($($t:ty),*) => {
+        $(
+            impl SnapshotValue for $t {
+                fn write(&self, out: &mut Vec<u8>) {
+                    out.extend_from_slice(&self.to_le_bytes());
+                }
+
+                fn read(input: &mut &[u8]) -> Result<Self, SnapshotError> {
+                    let size = std::mem::size_of::<$t>();
+                    if input.len() < size {
+                        return Err(SnapshotError::Truncated);
+                    }
+                    let (bytes, rest) = input.split_at(size);
+                    *input = rest;
+                    Ok(<$t>::from_le_bytes(bytes.try_into().unwrap()))
+                }
+            }
+        )*
+    };
+}
+
+snapshot_float!(f32, f64);
+
+impl SnapshotValue for bool {
+    fn write(&self, out: &mut Vec<u8>) {
+        out.push(*self as u8);
+    }
+
+    fn read(input: &mut &[u8]) -> Result<Self, SnapshotError> {
+        match u8::read(input)? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(SnapshotError::Corrupted),
+        }
+    }
+}
+
+impl<T: SnapshotValue> SnapshotValue for Option<T> {
+    fn write(&self, out: &mut Vec<u8>) {
+        match self {
+            None => out.push(0),
+            Some(value) => {
+                out.push(1);
+                value.write(out);
+            }
+        }
+    }
+
+    fn read(input: &mut &[u8]) -> Result<Self, SnapshotError> {
+        match u8::read(input)? {
+            0 => Ok(None),
+            1 => Ok(Some(T::read(input)?)),
+            _ => Err(SnapshotError::Corrupted),
+        }
+    }
+}
+
+/// A part of a manager that can be written in a snapshot
+pub(crate) trait Snapshot: Sized {
+    fn write_snapshot(&self, out: &mut Vec<u8>);
+    fn read_snapshot(input: &mut &[u8]) -> Result<Self, SnapshotError>;
+}
+
+macro_rules! snapshots {
+    ($($u:ty => $feature:literal),*) => {
+        paste! {
+            /// Returns a bit set of the managed types and of the features changing the layout of the snapshots
+            fn layout() -> u32 {
+                let features = [$(cfg!(feature = $feature),)* cfg!(feature = "bool"), cfg!(feature = "checked-handles")];
+                features.iter().enumerate().filter(|(_, enabled)| **enabled).map(|(i, _)| 1 << i).sum()
+            }
+
+            impl StateManager<Trailing> {
+                /// Returns a compact binary snapshot of the manager, including its saved levels. The manager
+                /// can be rebuilt with `from_bytes`, by a build of the crate with the same managed types.
+                pub fn to_bytes(&self) -> Vec<u8> {
+                    let mut out = MAGIC.to_vec();
+                    out.push(VERSION);
+                    write_varint(layout() as u128, &mut out);
+                    write_usize(self.clock, &mut out);
+                    write_usize(self.max_clock, &mut out);
+                    write_usize(self.depth, &mut out);
+                    self.epsilon.write(&mut out);
+                    write_usize(self.levels.len(), &mut out);
+                    self.levels.iter().for_each(|level| write_usize(level.clock, &mut out));
+                    self.levels.iter().for_each(|level| write_usize(level.empties, &mut out));
+                    #[cfg(feature = "checked-handles")]
+                    self.tag.write_snapshot(&mut out);
+                    $(
+                        #[cfg(feature = $feature)]
+                        {
+                            self.[<numbers _ $u>].write_snapshot(&mut out);
+                            self.[<numbers_option_ $u>].write_snapshot(&mut out);
+                        }
+                    )*
+                    #[cfg(feature = "bool")]
+                    {
+                        self.bools.write_snapshot(&mut out);
+                        self.option_bools.write_snapshot(&mut out);
+                    }
+                    out
+                }
+
+                /// Rebuilds a manager from a snapshot written by `to_bytes`. The handles of the original
+                /// manager are valid for the rebuilt manager.
+                pub fn from_bytes(mut bytes: &[u8]) -> Result<Self, SnapshotError> {
+                    let input = &mut bytes;
+                    if input.len() < MAGIC.len() + 1 || &input[..MAGIC.len()] != MAGIC || input[MAGIC.len()] != VERSION {
+                        return Err(SnapshotError::InvalidHeader);
+                    }
+                    *input = &input[MAGIC.len() + 1..];
+                    if read_varint(input)? != layout() as u128 {
+                        return Err(SnapshotError::LayoutMismatch);
+                    }
+                    let mut mgr = Self::new();
+                    mgr.clock = read_usize(input)?;
+                    mgr.max_clock = read_usize(input)?;
+                    mgr.depth = read_usize(input)?;
+                    mgr.epsilon = f64::read(input)?;
+                    let len = read_usize(input)?;
+                    let clocks = read_column(input, len, read_usize)?;
+                    let empties = read_column(input, len, read_usize)?;
+                    mgr.levels = clocks.into_iter().zip(empties).map(|(clock, empties)| Level { clock, empties }).collect();
+                    #[cfg(feature = "checked-handles")]
+                    {
+                        mgr.tag = Snapshot::read_snapshot(input)?;
+                    }
+                    $(
+                        #[cfg(feature = $feature)]
+                        {
+                            mgr.[<numbers _ $u>] = Snapshot::read_snapshot(input)?;
+                            mgr.[<numbers_option_ $u>] = Snapshot::read_snapshot(input)?;
+                        }
+                    )*
+                    #[cfg(feature = "bool")]
+                    {
+                        mgr.bools = Snapshot::read_snapshot(input)?;
+                        mgr.option_bools = Snapshot::read_snapshot(input)?;
+                    }
+                    let levels_are_valid = mgr.levels.first().is_some_and(|level| level.clock == 0)
+                        && mgr.levels.windows(2).all(|pair| pair[0].clock < pair[1].clock)
+                        && mgr.levels.last().is_some_and(|level| level.clock == mgr.clock)
+                        && mgr.clock <= mgr.max_clock;
+                    if !input.is_empty() || !levels_are_valid {
+                        return Err(SnapshotError::Corrupted);
+                    }
+                    Ok(mgr)
+                }
+            }
+        }
+    };
+}
+
+for_each_managed_type!(snapshots);
+
+#[cfg(all(test, feature = "usize", feature = "i32", feature = "f64", feature = "bool"))]
+mod test_snapshot {
+    use crate::{BoolManager, F64Manager, I32Manager, OptionBoolManager, OptionUsizeManager, SaveAndRestore, SnapshotError, StateManager, UsizeManager};
+
+    #[test]
+    fn search_is_resumed() {
+        let mut mgr = StateManager::default();
+        let numbers = (0..1000).map(|i| mgr.manage_usize(i)).collect::<Vec<_>>();
+        let x = mgr.manage_i32(-3);
+        let y = mgr.manage_f64(0.5);
+        let z = mgr.manage_option_usize(None);
+        let b = mgr.manage_bool(false);
+        let o = mgr.manage_option_bool(None);
+        mgr.save_state();
+        mgr.set_usize_slice(numbers[10], &[0; 100]);
+        mgr.set_i32(x, -70000);
+        mgr.save_state();
+        mgr.save_state();
+        mgr.set_usize(numbers[500], 7);
+        mgr.set_option_usize(z, Some(4));
+        mgr.set_f64(y, 1.5);
+        mgr.flip_bool(b);
+        mgr.set_option_bool(o, true);
+
+        let bytes = mgr.to_bytes();
+        let mut resumed = StateManager::from_bytes(&bytes).unwrap();
+        resumed.debug_validate();
+        assert_eq!(mgr.to_bytes(), resumed.to_bytes());
+        for _ in 0..3 {
+            for (i, n) in numbers.iter().copied().enumerate() {
+                assert_eq!(mgr.get_usize(n), resumed.get_usize(n), "resource {}", i);
+            }
+            assert_eq!(mgr.get_i32(x), resumed.get_i32(x));
+            assert_eq!(mgr.get_f64(y), resumed.get_f64(y));
+            assert_eq!(mgr.get_option_usize(z), resumed.get_option_usize(z));
+            assert_eq!(mgr.get_bool(b), resumed.get_bool(b));
+            assert_eq!(mgr.get_option_bool(o), resumed.get_option_bool(o));
+            mgr.restore_state();
+            resumed.restore_state();
+        }
+        assert_eq!(-3, resumed.get_i32(x));
+        assert_eq!(10, resumed.get_usize(numbers[10]));
+    }
+
+    #[test]
+    fn snapshots_are_compact() {
+        let mut mgr = StateManager::default();
+        (0..10000).for_each(|i| {
+            mgr.manage_usize(i % 100);
+        });
+        // One byte for each value and one for each clock
+        assert!(mgr.to_bytes().len() < 2 * 10000 + 200);
+    }
+
+    #[test]
+    fn invalid_snapshots_are_rejected() {
+        let mut mgr = StateManager::default();
+        let x = mgr.manage_usize(300);
+        mgr.save_state();
+        mgr.set_usize(x, 1);
+        let bytes = mgr.to_bytes();
+        assert_eq!(Err(SnapshotError::InvalidHeader), StateManager::from_bytes(&bytes[1..]).map(|_| ()));
+        assert_eq!(Err(SnapshotError::Truncated), StateManager::from_bytes(&bytes[..bytes.len() - 1]).map(|_| ()));
+        let mut extended = bytes.clone();
+        extended.push(0);
+        assert_eq!(Err(SnapshotError::Corrupted), StateManager::from_bytes(&extended).map(|_| ()));
+        let mut other_layout = bytes.clone();
+        other_layout[5] ^= 1;
+        assert_eq!(Err(SnapshotError::LayoutMismatch), StateManager::from_bytes(&other_layout).map(|_| ()));
+    }
+}
@@ -0,0 +1,178 @@
+//Copyright (c) 2023 X. Gillard, A. Dubray
+//
+//Permission is hereby granted, free of charge, to any person obtaining a copy
+//of this software and associated documentation files (the "Software"), to deal
+//in the Software without restriction, including without limitation the rights
+//to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+//copies of the Software, and to permit persons to whom the Software is
+//furnished to do so, subject to the following conditions:
+//
+//The above copyright notice and this permission notice shall be included in all
+//copies or substantial portions of the Software.
+//
+//THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+//IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+//AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+//OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+//SOFTWARE.
+
+
+//! Statistics of the search, recorded by the manager when they are enabled: the number of saves and
+//! restorations, the maximum depth, the peak length of the trail, and the time spent in each band of
+//! depths. They are computed in the same way for all the solvers, e.g. to be reported in benchmarks.
+
+use std::time::{Duration, Instant};
+
+use crate::{StateManager, TrailBackend};
+
+/// The statistics recorded by a manager
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TrailStats {
+    /// The number of calls to `save_state()`
+    pub saves: u64,
+    /// The number of calls to `restore_state()`
+    pub restores: u64,
+    /// The maximum number of saved levels
+    pub max_depth: usize,
+    /// The maximum number of values on the trail, observed at the saves and restorations
+    pub trail_peak: usize,
+    /// The number of consecutive depths in a band
+    pub band_width: usize,
+    /// The time spent at the depths of each band: the first one is the time spent at the depths
+    /// `0..band_width`, the second one at the depths `band_width..2 * band_width`, ...
+    pub time_per_band: Vec<Duration>,
+}
+
+impl TrailStats {
+    /// Adds the given time to the band of the depth
+    fn spend(&mut self, depth: usize, time: Duration) {
+        let band = depth / self.band_width;
+        if self.time_per_band.len() <= band {
+            self.time_per_band.resize(band + 1, Duration::ZERO);
+        }
+        self.time_per_band[band] += time;
+    }
+}
+
+/// The statistics being recorded, with the time at which the current depth was entered
+#[derive(Debug, Clone)]
+pub(crate) struct StatsRecorder {
+    stats: TrailStats,
+    since: Instant,
+}
+
+impl<B: TrailBackend> StateManager<B> {
+    /// Starts recording the statistics of the search, discarding the ones recorded so far. The time
+    /// spent at each depth is aggregated by bands of `band_width` depths.
+    ///
+    #[cfg_attr(feature = "usize", doc = "```")]
+    #[cfg_attr(not(feature = "usize"), doc = "```ignore")]
+    /// use search_trail::{SaveAndRestore, StateManager, UsizeManager};
+    ///
+    /// let mut mgr = StateManager::default();
+    /// let n = mgr.manage_usize(0);
+    /// mgr.record_stats(10);
+    /// for i in 0..20 {
+    ///     mgr.save_state();
+    ///     mgr.set_usize(n, i + 1);
+    /// }
+    /// mgr.restore_state();
+    /// let stats = mgr.stats().unwrap();
+    /// assert_eq!((20, 1, 20), (stats.saves, stats.restores, stats.max_depth));
+    /// assert_eq!(20, stats.trail_peak);
+    /// assert_eq!(3, stats.time_per_band.len());
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if `band_width` is 0
+    pub fn record_stats(&mut self, band_width: usize) {
+        assert!(band_width > 0, "The bands must contain at least one depth");
+        self.stats = Some(StatsRecorder {
+            stats: TrailStats {
+                band_width,
+                max_depth: self.depth,
+                trail_peak: self.trail_len(),
+                ..TrailStats::default()
+            },
+            since: Instant::now(),
+        });
+    }
+
+    /// Returns the statistics recorded so far, if they are recorded
+    pub fn stats(&self) -> Option<TrailStats> {
+        self.stats.as_ref().map(|recorder| {
+            let mut stats = recorder.stats.clone();
+            stats.spend(self.depth, recorder.since.elapsed());
+            stats.trail_peak = stats.trail_peak.max(self.trail_len());
+            stats
+        })
+    }
+
+    /// Stops recording the statistics, and returns them
+    pub fn take_stats(&mut self) -> Option<TrailStats> {
+        let stats = self.stats();
+        self.stats = None;
+        stats
+    }
+
+    /// Resets the statistics to zero, if they are recorded, keeping their bands
+    pub fn reset_stats(&mut self) {
+        if let Some(band_width) = self.stats.as_ref().map(|recorder| recorder.stats.band_width) {
+            self.record_stats(band_width);
+        }
+    }
+
+    /// Records a call to `save_state()` (if `save`) or `restore_state()` in the statistics, before the
+    /// depth is modified, if they are recorded
+    pub(crate) fn count_stats(&mut self, save: bool) {
+        if self.stats.is_none() {
+            return;
+        }
+        let (depth, trail_len) = (self.depth, self.trail_len());
+        let recorder = self.stats.as_mut().unwrap();
+        let now = Instant::now();
+        recorder.stats.spend(depth, now - recorder.since);
+        recorder.since = now;
+        recorder.stats.trail_peak = recorder.stats.trail_peak.max(trail_len);
+        if save {
+            recorder.stats.saves += 1;
+            recorder.stats.max_depth = recorder.stats.max_depth.max(depth + 1);
+        } else {
+            recorder.stats.restores += 1;
+        }
+    }
+}
+
+#[cfg(all(test, feature = "u32"))]
+mod test_stats {
+    use crate::*;
+
+    #[test]
+    fn stats_are_recorded_and_reset() {
+        let mut mgr = StateManager::default();
+        let xs = (0..10).map(|i| mgr.manage_u32(i)).collect::<Vec<_>>();
+        mgr.save_state();
+        assert!(mgr.stats().is_none());
+        mgr.record_stats(2);
+        for i in 0..3 {
+            mgr.save_state();
+            mgr.set_u32_slice(xs[0], &[i + 10; 10]);
+        }
+        for _ in 0..3 {
+            mgr.restore_state();
+        }
+        mgr.save_state();
+        let stats = mgr.stats().unwrap();
+        assert_eq!((4, 3, 4), (stats.saves, stats.restores, stats.max_depth));
+        assert_eq!(30, stats.trail_peak);
+        assert_eq!(3, stats.time_per_band.len());
+        mgr.reset_stats();
+        let stats = mgr.take_stats().unwrap();
+        assert_eq!((0, 0, 2, 0), (stats.saves, stats.restores, stats.max_depth, stats.trail_peak));
+        assert_eq!(2, stats.band_width);
+        assert!(mgr.stats().is_none());
+    }
+}
@@ -0,0 +1,254 @@
+//Copyright (c) 2023 X. Gillard, A. Dubray
+//
+//Permission is hereby granted, free of charge, to any person obtaining a copy
+//of this software and associated documentation files (the "Software"), to deal
+//in the Software without restriction, including without limitation the rights
+//to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+//copies of the Software, and to permit persons to whom the Software is
+//furnished to do so, subject to the following conditions:
+//
+//The above copyright notice and this permission notice shall be included in all
+//copies or substantial portions of the Software.
+//
+//THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+//IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+//AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+//OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+//SOFTWARE.
+
+
+//! Model-based testing of the managers. Random sequences of operations (creating, setting, saving
+//! and restoring resources of every managed type) are applied both to a manager and to a naive
+//! reference model that copies all its values at each saved level. The values of the manager are
+//! compared to the ones of the model after each operation.
+
+use std::fmt::Debug;
+
+use crate::*;
+
+/// A small xorshift generator, so that the sequences of operations are reproducible from a seed
+struct Random(u64);
+
+impl Random {
+    fn new(seed: u64) -> Self {
+        // The state of a xorshift generator must not be zero
+        Self(seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    /// Returns a random number in 0..n
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+}
+
+/// A managed type whose values can be drawn at random. The values are drawn from a small range so
+/// that the same values are often set again.
+trait Sample: ScopedValue + PartialEq + Debug {
+    fn sample(random: &mut Random) -> Self;
+}
+
+macro_rules! sample_values {
+    ($($u:ty => $feature:literal),*) => {
+        $(
+            #[cfg(feature = $feature)]
+            impl Sample for $u {
+                fn sample(random: &mut Random) -> Self {
+                    random.below(8) as $u
+                }
+            }
+        )*
+    };
+}
+
+for_each_managed_type!(sample_values);
+
+#[cfg(feature = "bool")]
+impl Sample for bool {
+    fn sample(random: &mut Random) -> Self {
+        random.below(2) == 1
+    }
+}
+
+impl<T: Sample> Sample for Option<T>
+where
+    Option<T>: ScopedValue,
+{
+    fn sample(random: &mut Random) -> Self {
+        if random.below(4) == 0 {
+            None
+        } else {
+            Some(T::sample(random))
+        }
+    }
+}
+
+/// The reference model of the resources of one type. The values of every level are fully copied.
+struct Model<T: Sample> {
+    handles: Vec<T::Handle>,
+    values: Vec<T>,
+    saved: Vec<Vec<T>>,
+}
+
+impl<T: Sample> Default for Model<T> {
+    fn default() -> Self {
+        Self {
+            handles: vec![],
+            values: vec![],
+            saved: vec![],
+        }
+    }
+}
+
+/// The operations on a model, independent of the type of its resources
+trait Checked<B: TrailBackend> {
+    fn save(&mut self);
+    fn restore(&mut self);
+    /// Creates or sets a random resource, both in the manager and in the model
+    fn step(&mut self, mgr: &mut StateManager<B>, random: &mut Random);
+    /// Returns a description of the first resource whose value differs between the manager and the model
+    fn check(&self, mgr: &StateManager<B>) -> Result<(), String>;
+}
+
+impl<B: TrailBackend, T: Sample> Checked<B> for Model<T> {
+    fn save(&mut self) {
+        self.saved.push(self.values.clone());
+    }
+
+    fn restore(&mut self) {
+        self.values = self.saved.pop().unwrap();
+        // The resources created in the restored level are not used anymore
+        self.handles.truncate(self.values.len());
+    }
+
+    fn step(&mut self, mgr: &mut StateManager<B>, random: &mut Random) {
+        let value = T::sample(random);
+        if self.handles.is_empty() || random.below(4) == 0 {
+            self.handles.push(T::manage(mgr, value));
+            self.values.push(value);
+        } else {
+            let i = random.below(self.handles.len());
+            T::set(mgr, self.handles[i], value);
+            self.values[i] = value;
+        }
+    }
+
+    fn check(&self, mgr: &StateManager<B>) -> Result<(), String> {
+        for (handle, expected) in self.handles.iter().zip(self.values.iter()) {
+            let actual = T::get(mgr, *handle);
+            if actual != *expected {
+                return Err(format!("{:?} has the value {:?} instead of {:?}", handle, actual, expected));
+            }
+        }
+        Ok(())
+    }
+}
+
+macro_rules! models {
+    ($($u:ty => $feature:literal),*) => {
+        /// Returns a model for every managed type
+        fn models<B: TrailBackend>() -> Vec<Box<dyn Checked<B>>> {
+            let mut models: Vec<Box<dyn Checked<B>>> = vec![];
+            $(
+                #[cfg(feature = $feature)]
+                {
+                    models.push(Box::new(Model::<$u>::default()));
+                    models.push(Box::new(Model::<Option<$u>>::default()));
+                }
+            )*
+            #[cfg(feature = "bool")]
+            {
+                models.push(Box::new(Model::<bool>::default()));
+                models.push(Box::new(Model::<Option<bool>>::default()));
+            }
+            models
+        }
+    };
+}
+
+for_each_managed_type!(models);
+
+/// Applies `steps` random operations, drawn from the given seed, to a new manager of the backend `B`
+/// and to the reference model. Panics, with the seed and the step, as soon as the value of a resource
+/// differs between the manager and the model, or if the manager breaks one of its invariants.
+pub fn check_against_model<B: TrailBackend>(seed: u64, steps: usize) {
+    let mut mgr = StateManager::<B>::new();
+    let mut models = models::<B>();
+    let mut random = Random::new(seed);
+    let mut depth = 0;
+    for step in 0..steps {
+        match random.below(8) {
+            0 => {
+                mgr.save_state();
+                models.iter_mut().for_each(|model| model.save());
+                depth += 1;
+            }
+            1 if depth > 0 => {
+                mgr.restore_state();
+                models.iter_mut().for_each(|model| model.restore());
+                depth -= 1;
+            }
+            _ if !models.is_empty() => {
+                let i = random.below(models.len());
+                models[i].step(&mut mgr, &mut random);
+            }
+            _ => {}
+        }
+        mgr.debug_validate();
+        for model in models.iter() {
+            if let Err(message) = model.check(&mgr) {
+                panic!("Seed {}, step {} (depth {}): {}", seed, step, depth, message);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_model {
+    use super::check_against_model;
+    use crate::{CopyOnWrite, Copying, Recomputing, Trailing};
+
+    #[test]
+    fn trailing() {
+        for seed in 0..10 {
+            check_against_model::<Trailing>(seed, 2000);
+        }
+    }
+
+    #[test]
+    fn copying() {
+        for seed in 0..10 {
+            check_against_model::<Copying>(seed, 2000);
+        }
+    }
+
+    #[test]
+    fn recomputing() {
+        for seed in 0..10 {
+            check_against_model::<Recomputing<3>>(seed, 2000);
+        }
+    }
+
+    #[test]
+    fn copy_on_write() {
+        for seed in 0..10 {
+            check_against_model::<CopyOnWrite>(seed, 2000);
+        }
+    }
+
+    #[cfg(feature = "persistent")]
+    #[test]
+    fn persistent() {
+        for seed in 0..10 {
+            check_against_model::<crate::Persistent>(seed, 2000);
+        }
+    }
+}
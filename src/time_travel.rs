@@ -0,0 +1,199 @@
+//Copyright (c) 2023 X. Gillard, A. Dubray
+//
+//Permission is hereby granted, free of charge, to any person obtaining a copy
+//of this software and associated documentation files (the "Software"), to deal
+//in the Software without restriction, including without limitation the rights
+//to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+//copies of the Software, and to permit persons to whom the Software is
+//furnished to do so, subject to the following conditions:
+//
+//The above copyright notice and this permission notice shall be included in all
+//copies or substantial portions of the Software.
+//
+//THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+//IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+//AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+//OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+//SOFTWARE.
+
+
+//! Time-travel debugging. The journal of a manager records all its mutating calls, including the ones
+//! of the levels that have since been restored. A `TimeTravel` replays this journal on a replica of the
+//! manager, which can be stepped forward and backward through the past of the manager, and inspected
+//! with the handles of the manager at any point.
+//!
+//! To step backward, the replica is cloned at regular intervals of the journal: going back to a
+//! position restores the last clone before it and replays the calls from there.
+
+use crate::{Journal, JournalEntry, StateManager, TrailBackend};
+
+/// The number of journal entries between two clones of the replica
+const INTERVAL: usize = 64;
+
+/// A replica of a manager that can be moved through the calls of its journal
+///
+/// #Example
+///
+/// ```
+/// use search_trail::{SaveAndRestore, StateManager, UsizeManager};
+///
+/// let mut mgr = StateManager::default();
+/// mgr.record_journal();
+/// let x = mgr.manage_usize(0);
+/// mgr.save_state();
+/// mgr.set_usize(x, 1);
+/// mgr.restore_state();
+/// mgr.set_usize(x, 2);
+///
+/// let mut debugger = mgr.time_travel().unwrap();
+/// debugger.seek(debugger.len());
+/// assert_eq!(2, debugger.manager().get_usize(x));
+/// // Goes back to the state before the restoration
+/// debugger.step_backward();
+/// debugger.step_backward();
+/// assert_eq!(1, debugger.manager().get_usize(x));
+/// ```
+#[derive(Debug, Clone)]
+pub struct TimeTravel {
+    journal: Journal,
+    /// The replica, on which the first `position` entries of the journal have been replayed
+    mgr: StateManager,
+    position: usize,
+    /// The clones of the replica every `INTERVAL` entries
+    clones: Vec<StateManager>,
+}
+
+impl TimeTravel {
+    /// Returns a replica at the start of the journal. The journal must have been recorded from the
+    /// creation of the manager.
+    pub fn new(journal: Journal) -> Self {
+        Self::starting_from(journal, StateManager::new())
+    }
+
+    /// Returns a replica at the start of the journal, from the given empty manager
+    fn starting_from(journal: Journal, mgr: StateManager) -> Self {
+        Self {
+            journal,
+            clones: vec![mgr.clone()],
+            mgr,
+            position: 0,
+        }
+    }
+
+    /// Returns the number of entries of the journal
+    pub fn len(&self) -> usize {
+        self.journal.entries.len()
+    }
+
+    /// Returns true if the journal has no entry
+    pub fn is_empty(&self) -> bool {
+        self.journal.entries.is_empty()
+    }
+
+    /// Returns the number of entries of the journal replayed on the replica
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// Returns the next entry to replay, if any
+    pub fn next_entry(&self) -> Option<&JournalEntry> {
+        self.journal.entries.get(self.position)
+    }
+
+    /// Returns the replica, in the state of the recorded manager at the current position
+    pub fn manager(&self) -> &StateManager {
+        &self.mgr
+    }
+
+    /// Replays the next entry of the journal. Returns false if the end of the journal is reached.
+    pub fn step_forward(&mut self) -> bool {
+        let Some(entry) = self.journal.entries.get(self.position) else {
+            return false;
+        };
+        entry.replay_on(&mut self.mgr);
+        self.position += 1;
+        if self.position.is_multiple_of(INTERVAL) && self.clones.len() == self.position / INTERVAL {
+            self.clones.push(self.mgr.clone());
+        }
+        true
+    }
+
+    /// Undoes the last replayed entry. Returns false if the start of the journal is reached.
+    pub fn step_backward(&mut self) -> bool {
+        if self.position == 0 {
+            return false;
+        }
+        self.seek(self.position - 1);
+        true
+    }
+
+    /// Moves the replica to the given position of the journal (or to its end, if it is shorter)
+    pub fn seek(&mut self, position: usize) {
+        let position = position.min(self.len());
+        if position < self.position {
+            // The clones up to the current position have all been made
+            let clone = position / INTERVAL;
+            self.mgr.clone_from(&self.clones[clone]);
+            self.position = clone * INTERVAL;
+        }
+        while self.position < position {
+            self.step_forward();
+        }
+    }
+}
+
+impl<B: TrailBackend> StateManager<B> {
+    /// Returns a replica of the manager at the start of its journal, if it is recorded. The handles
+    /// of the manager can be used with the replica.
+    pub fn time_travel(&self) -> Option<TimeTravel> {
+        let journal = self.journal.clone()?;
+        #[allow(unused_mut)]
+        let mut mgr = StateManager::new();
+        #[cfg(feature = "checked-handles")]
+        {
+            // The tag of the manager when the journal started, before it was reset by the journal
+            let resets = journal.entries.iter().filter(|entry| **entry == JournalEntry::Clear).count();
+            mgr.tag = self.tag.rewind(resets as u32);
+        }
+        Some(TimeTravel::starting_from(journal, mgr))
+    }
+}
+
+#[cfg(all(test, feature = "usize", feature = "bool"))]
+mod test_time_travel {
+    use crate::*;
+
+    #[test]
+    fn steps_through_the_past() {
+        let mut mgr = StateManager::default();
+        mgr.record_journal();
+        let x = mgr.manage_usize(0);
+        let b = mgr.manage_bool(false);
+        // The position in the journal and the state at the end of each iteration
+        let mut history = vec![];
+        for i in 0..100 {
+            mgr.save_state();
+            mgr.set_usize(x, i + 1);
+            mgr.set_bool(b, i % 2 == 0);
+            if i % 3 == 0 {
+                mgr.restore_state();
+            }
+            history.push((mgr.journal().unwrap().entries.len(), mgr.get_usize(x), mgr.get_bool(b)));
+        }
+        mgr.reset();
+        let y = mgr.manage_usize(7);
+
+        let mut debugger = mgr.time_travel().unwrap();
+        assert!(!debugger.step_backward());
+        debugger.seek(usize::MAX);
+        assert!(!debugger.step_forward());
+        assert_eq!(7, debugger.manager().get_usize(y));
+        // Walks back through the loop
+        for &(position, x_value, b_value) in history.iter().rev() {
+            debugger.seek(position);
+            assert_eq!((x_value, b_value), (debugger.manager().get_usize(x), debugger.manager().get_bool(b)));
+        }
+    }
+}
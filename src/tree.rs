@@ -0,0 +1,190 @@
+//Copyright (c) 2023 X. Gillard, A. Dubray
+//
+//Permission is hereby granted, free of charge, to any person obtaining a copy
+//of this software and associated documentation files (the "Software"), to deal
+//in the Software without restriction, including without limitation the rights
+//to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+//copies of the Software, and to permit persons to whom the Software is
+//furnished to do so, subject to the following conditions:
+//
+//The above copyright notice and this permission notice shall be included in all
+//copies or substantial portions of the Software.
+//
+//THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+//IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+//AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+//OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+//SOFTWARE.
+
+
+//! Recording of the tree of the levels explored by a search, which can be exported to Graphviz. Each
+//! call to `save_state()` creates a child of the current node, labelled with the decision taken in the
+//! new level, and each call to `restore_state()` goes back to the parent node.
+
+use std::fmt::Write;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::{StateManager, TrailBackend};
+
+/// A node of the search tree, for a saved level
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SearchNode {
+    /// The parent of the node, `None` for the root
+    pub parent: Option<usize>,
+    /// The decision taken in the level, given by `StateManager::label_level`
+    pub label: Option<String>,
+}
+
+/// The tree of the levels explored by a search, recorded by a manager after a call to
+/// `StateManager::record_search_tree`
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SearchTree {
+    /// The nodes, in the order in which the levels were saved. The first node is the level in which
+    /// the recording started.
+    pub nodes: Vec<SearchNode>,
+    /// The node of the current level
+    current: usize,
+}
+
+impl Default for SearchTree {
+    fn default() -> Self {
+        Self {
+            nodes: vec![SearchNode { parent: None, label: None }],
+            current: 0,
+        }
+    }
+}
+
+impl SearchTree {
+    /// Returns the node of the current level
+    pub fn current(&self) -> usize {
+        self.current
+    }
+
+    fn save(&mut self) {
+        self.nodes.push(SearchNode {
+            parent: Some(self.current),
+            label: None,
+        });
+        self.current = self.nodes.len() - 1;
+    }
+
+    /// Goes back to the parent node. The levels below the one in which the recording started are not
+    /// recorded.
+    fn restore(&mut self) {
+        if let Some(parent) = self.nodes[self.current].parent {
+            self.current = parent;
+        }
+    }
+
+    /// Returns the tree in the DOT language of Graphviz. The nodes are numbered in the order in which
+    /// they were explored, and the edges are labelled with the decisions.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph search {\n");
+        for (i, node) in self.nodes.iter().enumerate() {
+            writeln!(dot, "    n{} [label=\"{}\"];", i, i).unwrap();
+            if let Some(parent) = node.parent {
+                match &node.label {
+                    Some(label) => writeln!(dot, "    n{} -> n{} [label=\"{}\"];", parent, i, escape(label)).unwrap(),
+                    None => writeln!(dot, "    n{} -> n{};", parent, i).unwrap(),
+                }
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+/// Escapes a label for a quoted DOT string
+fn escape(label: &str) -> String {
+    label.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+impl<B: TrailBackend> StateManager<B> {
+    /// Starts recording the tree of the levels explored from the current one, discarding the tree
+    /// recorded so far
+    pub fn record_search_tree(&mut self) {
+        self.tree = Some(SearchTree::default());
+    }
+
+    /// Stops recording the search tree, and returns the recorded tree
+    pub fn take_search_tree(&mut self) -> Option<SearchTree> {
+        self.tree.take()
+    }
+
+    /// Returns the search tree recorded so far, if the recording is enabled
+    pub fn search_tree(&self) -> Option<&SearchTree> {
+        self.tree.as_ref()
+    }
+
+    /// Labels the current level with the decision taken in it. Does nothing if the search tree is not
+    /// recorded.
+    pub fn label_level(&mut self, label: impl Into<String>) {
+        if let Some(tree) = self.tree.as_mut() {
+            let current = tree.current;
+            tree.nodes[current].label = Some(label.into());
+        }
+    }
+
+    /// Records a call to `save_state()` in the search tree, if it is recorded
+    pub(crate) fn record_save(&mut self) {
+        if let Some(tree) = self.tree.as_mut() {
+            tree.save();
+        }
+    }
+
+    /// Records a call to `restore_state()` in the search tree, if it is recorded
+    pub(crate) fn record_restore(&mut self) {
+        if let Some(tree) = self.tree.as_mut() {
+            tree.restore();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_search_tree {
+    use crate::{SaveAndRestore, StateManager};
+
+    #[test]
+    fn explored_levels_are_recorded() {
+        let mut mgr = StateManager::default();
+        mgr.save_state();
+        mgr.record_search_tree();
+        for x in 0..2 {
+            mgr.save_state();
+            mgr.label_level(format!("x = {}", x));
+            mgr.save_state();
+            mgr.label_level("y = \"a\"");
+            mgr.restore_state();
+            mgr.restore_state();
+        }
+        // Below the level in which the recording started
+        mgr.restore_state();
+        mgr.save_state();
+        let tree = mgr.take_search_tree().unwrap();
+        assert_eq!(vec![None, Some(0), Some(1), Some(0), Some(3), Some(0)], tree.nodes.iter().map(|node| node.parent).collect::<Vec<_>>());
+        assert_eq!(5, tree.current());
+        let expected = r#"digraph search {
+    n0 [label="0"];
+    n1 [label="1"];
+    n0 -> n1 [label="x = 0"];
+    n2 [label="2"];
+    n1 -> n2 [label="y = \"a\""];
+    n3 [label="3"];
+    n0 -> n3 [label="x = 1"];
+    n4 [label="4"];
+    n3 -> n4 [label="y = \"a\""];
+    n5 [label="5"];
+    n0 -> n5;
+}
+"#;
+        assert_eq!(expected, tree.to_dot());
+        assert!(mgr.search_tree().is_none());
+    }
+}
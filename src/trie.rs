@@ -0,0 +1,224 @@
+//Copyright (c) 2023 X. Gillard, A. Dubray
+//
+//Permission is hereby granted, free of charge, to any person obtaining a copy
+//of this software and associated documentation files (the "Software"), to deal
+//in the Software without restriction, including without limitation the rights
+//to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+//copies of the Software, and to permit persons to whom the Software is
+//furnished to do so, subject to the following conditions:
+//
+//The above copyright notice and this permission notice shall be included in all
+//copies or substantial portions of the Software.
+//
+//THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+//IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+//AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+//OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+//SOFTWARE.
+
+//! A trie over sequences of symbols from a small alphabet (e.g., bytes), whose insertions and removals
+//! are restored with the state of the manager. The nodes are taken from a pool that only grows: the
+//! nodes allocated in a level are given back to the pool when the level is restored, and reused by the
+//! next insertions.
+
+use crate::*;
+
+/// A set of sequences whose modifications are undone by restoring the state of the manager used to
+/// modify it. The symbols of the sequences are the integers `0..arity`.
+///
+/// #Example
+///
+/// ```
+/// use search_trail::{ReversibleTrie, SaveAndRestore, StateManager};
+///
+/// let mut mgr = StateManager::default();
+/// let mut words = ReversibleTrie::new(&mut mgr, 256);
+/// words.insert(&mut mgr, b"car");
+/// mgr.save_state();
+/// words.insert(&mut mgr, b"cat");
+/// words.remove(&mut mgr, b"car");
+/// assert_eq!(1, words.count_prefix(&mgr, b"ca"));
+/// mgr.restore_state();
+/// assert!(words.contains(&mgr, b"car"));
+/// assert!(!words.contains(&mgr, b"cat"));
+/// ```
+#[derive(Debug, Clone)]
+pub struct ReversibleTrie {
+    /// Number of symbols of the alphabet
+    arity: usize,
+    /// The child of each node for each symbol, 0 if it has none (the root, node 0, is nobody's child)
+    children: Vec<ReversibleUsize>,
+    /// Number of sequences of the set going through each node
+    counts: Vec<ReversibleUsize>,
+    /// 1 if a sequence of the set ends at the node, 0 otherwise
+    ends: Vec<ReversibleUsize>,
+    /// Number of nodes of the pool in use
+    allocated: ReversibleUsize,
+}
+
+impl ReversibleTrie {
+    /// Creates an empty set of sequences over the symbols `0..arity`, trailed by the given manager
+    pub fn new<B: TrailBackend>(mgr: &mut StateManager<B>, arity: usize) -> Self {
+        let allocated = mgr.manage(0usize);
+        let mut trie = Self {
+            arity,
+            children: vec![],
+            counts: vec![],
+            ends: vec![],
+            allocated,
+        };
+        trie.allocate(mgr);
+        trie
+    }
+
+    /// Returns the number of symbols of the alphabet
+    pub fn arity(&self) -> usize {
+        self.arity
+    }
+
+    /// Returns the number of sequences in the set
+    pub fn len<B: TrailBackend>(&self, mgr: &StateManager<B>) -> usize {
+        mgr.get(self.counts[0])
+    }
+
+    /// Returns true if the set is empty
+    pub fn is_empty<B: TrailBackend>(&self, mgr: &StateManager<B>) -> bool {
+        self.len(mgr) == 0
+    }
+
+    /// Returns true if the sequence is in the set
+    pub fn contains<B: TrailBackend, S: Copy + Into<usize>>(&self, mgr: &StateManager<B>, sequence: &[S]) -> bool {
+        self.find(mgr, sequence).is_some_and(|node| mgr.get(self.ends[node]) == 1)
+    }
+
+    /// Returns the number of sequences of the set starting with the given prefix
+    pub fn count_prefix<B: TrailBackend, S: Copy + Into<usize>>(&self, mgr: &StateManager<B>, prefix: &[S]) -> usize {
+        self.find(mgr, prefix).map_or(0, |node| mgr.get(self.counts[node]))
+    }
+
+    /// Adds the sequence to the set. Returns false if it was already in the set.
+    pub fn insert<B: TrailBackend, S: Copy + Into<usize>>(&mut self, mgr: &mut StateManager<B>, sequence: &[S]) -> bool {
+        if self.contains(mgr, sequence) {
+            return false;
+        }
+        let mut node = 0;
+        mgr.set(self.counts[0], mgr.get(self.counts[0]) + 1);
+        for &symbol in sequence.iter() {
+            let edge = self.edge(node, symbol.into());
+            node = match mgr.get(self.children[edge]) {
+                0 => {
+                    let child = self.allocate(mgr);
+                    mgr.set(self.children[edge], child);
+                    child
+                }
+                child => child,
+            };
+            mgr.set(self.counts[node], mgr.get(self.counts[node]) + 1);
+        }
+        mgr.set(self.ends[node], 1);
+        true
+    }
+
+    /// Removes the sequence from the set. Returns false if it was not in the set.
+    pub fn remove<B: TrailBackend, S: Copy + Into<usize>>(&self, mgr: &mut StateManager<B>, sequence: &[S]) -> bool {
+        if !self.contains(mgr, sequence) {
+            return false;
+        }
+        let mut node = 0;
+        mgr.set(self.counts[0], mgr.get(self.counts[0]) - 1);
+        for &symbol in sequence.iter() {
+            let edge = self.edge(node, symbol.into());
+            let child = mgr.get(self.children[edge]);
+            let count = mgr.get(self.counts[child]) - 1;
+            mgr.set(self.counts[child], count);
+            if count == 0 {
+                // The subtree of the child only held the sequence, and is detached
+                mgr.set(self.children[edge], 0);
+                return true;
+            }
+            node = child;
+        }
+        mgr.set(self.ends[node], 0);
+        true
+    }
+
+    /// Returns the node reached by the sequence from the root, if any
+    fn find<B: TrailBackend, S: Copy + Into<usize>>(&self, mgr: &StateManager<B>, sequence: &[S]) -> Option<usize> {
+        let mut node = 0;
+        for &symbol in sequence.iter() {
+            node = match mgr.get(self.children[self.edge(node, symbol.into())]) {
+                0 => return None,
+                child => child,
+            };
+        }
+        Some(node)
+    }
+
+    /// Returns the index in `children` of the child of the node for the symbol
+    fn edge(&self, node: usize, symbol: usize) -> usize {
+        assert!(symbol < self.arity, "The symbol {} is not in the alphabet of {} symbols", symbol, self.arity);
+        node * self.arity + symbol
+    }
+
+    /// Takes an empty node from the pool, growing it if all its nodes are in use, and returns its index
+    fn allocate<B: TrailBackend>(&mut self, mgr: &mut StateManager<B>) -> usize {
+        let node = mgr.get(self.allocated);
+        if node < self.counts.len() {
+            // The node was released by a restoration, and may still hold the values it had then
+            for edge in node * self.arity..(node + 1) * self.arity {
+                mgr.set(self.children[edge], 0);
+            }
+            mgr.set(self.counts[node], 0);
+            mgr.set(self.ends[node], 0);
+        } else {
+            self.children.extend((0..self.arity).map(|_| mgr.manage(0usize)));
+            self.counts.push(mgr.manage(0usize));
+            self.ends.push(mgr.manage(0usize));
+        }
+        mgr.set(self.allocated, node + 1);
+        node
+    }
+}
+
+#[cfg(test)]
+mod test_trie {
+    use std::collections::BTreeSet;
+
+    use crate::*;
+
+    #[test]
+    fn same_sequences_as_a_set() {
+        let mut mgr = StateManager::default();
+        let mut trie = ReversibleTrie::new(&mut mgr, 3);
+        let mut set = BTreeSet::new();
+        let mut saved = vec![];
+        let sequences: [&[usize]; 7] = [&[0, 1], &[0, 1, 2], &[], &[2], &[0], &[0, 1], &[2, 2, 2]];
+        for (i, &sequence) in sequences.iter().enumerate() {
+            mgr.save_state();
+            saved.push(set.clone());
+            if i % 3 == 2 {
+                assert_eq!(set.remove(sequence), trie.remove(&mut mgr, sequence));
+            }
+            assert_eq!(set.insert(sequence.to_vec()), trie.insert(&mut mgr, sequence));
+            assert_eq!(set.remove([0, 1].as_slice()), trie.remove(&mut mgr, &[0usize, 1]));
+            for prefix in [&[][..], &[0usize], &[0, 1], &[2], &[1]] {
+                assert_eq!(set.iter().filter(|s| s.starts_with(prefix)).count(), trie.count_prefix(&mgr, prefix));
+            }
+        }
+        while let Some(previous) = saved.pop() {
+            mgr.restore_state();
+            assert_eq!(previous.len(), trie.len(&mgr));
+            for sequence in sequences.iter() {
+                assert_eq!(previous.contains(*sequence), trie.contains(&mgr, sequence));
+            }
+        }
+        assert!(trie.is_empty(&mgr));
+        // The pool is reused after the restorations
+        let pool = trie.counts.len();
+        trie.insert(&mut mgr, &[2u8, 2, 2]);
+        assert_eq!(pool, trie.counts.len());
+        assert!(trie.contains(&mgr, &[2usize, 2, 2]) && !trie.contains(&mgr, &[2usize, 2]));
+    }
+}
@@ -0,0 +1,174 @@
+//Copyright (c) 2023 X. Gillard, A. Dubray
+//
+//Permission is hereby granted, free of charge, to any person obtaining a copy
+//of this software and associated documentation files (the "Software"), to deal
+//in the Software without restriction, including without limitation the rights
+//to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+//copies of the Software, and to permit persons to whom the Software is
+//furnished to do so, subject to the following conditions:
+//
+//The above copyright notice and this permission notice shall be included in all
+//copies or substantial portions of the Software.
+//
+//THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+//IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+//AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+//OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+//SOFTWARE.
+
+
+//! A value of any of the managed types, used by the operations of the fuzzers, by the journal and by the
+//! type-erased managers
+
+#[cfg(feature = "fuzz")]
+use arbitrary::Arbitrary;
+use paste::paste;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::*;
+
+macro_rules! values {
+    ($($u:ty => $feature:literal),*) => {
+        paste! {
+            /// A value of any managed type
+            #[derive(Debug, Clone, Copy, PartialEq)]
+            #[cfg_attr(feature = "fuzz", derive(Arbitrary))]
+            #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+            pub enum Value {
+                $(
+                    #[cfg(feature = $feature)]
+                    [<$u:camel>]($u),
+                    #[cfg(feature = $feature)]
+                    [<Option $u:camel>](Option<$u>),
+                )*
+                #[cfg(feature = "bool")]
+                Bool(bool),
+                #[cfg(feature = "bool")]
+                OptionBool(Option<bool>),
+            }
+
+            impl Value {
+                /// Returns false if the value is an empty option
+                pub(crate) fn is_assigned(&self) -> bool {
+                    match self {
+                        $(
+                            #[cfg(feature = $feature)]
+                            Value::[<Option $u:camel>](None) => false,
+                        )*
+                        #[cfg(feature = "bool")]
+                        Value::OptionBool(None) => false,
+                        #[allow(unreachable_patterns)]
+                        _ => true,
+                    }
+                }
+
+                /// Creates a new managed resource with this value
+                pub(crate) fn manage<B: TrailBackend>(self, mgr: &mut StateManager<B>) -> AnyHandle {
+                    match self {
+                        $(
+                            #[cfg(feature = $feature)]
+                            Value::[<$u:camel>](v) => mgr.[<manage_ $u>](v).into(),
+                            #[cfg(feature = $feature)]
+                            Value::[<Option $u:camel>](v) => mgr.[<manage_option_ $u>](v).into(),
+                        )*
+                        #[cfg(feature = "bool")]
+                        Value::Bool(v) => mgr.manage_bool(v).into(),
+                        #[cfg(feature = "bool")]
+                        Value::OptionBool(v) => mgr.manage_option_bool(v).into(),
+                    }
+                }
+
+                /// Sets the given resource to this value. Returns false, without modifying the
+                /// resource, if its type is not the type of the value.
+                #[cfg(feature = "fuzz")]
+                pub(crate) fn set<B: TrailBackend>(self, mgr: &mut StateManager<B>, handle: AnyHandle) -> bool {
+                    match (self, handle) {
+                        $(
+                            #[cfg(feature = $feature)]
+                            (Value::[<$u:camel>](v), AnyHandle::[<$u:camel>](h)) => {
+                                mgr.[<set_ $u>](h, v);
+                            }
+                            #[cfg(feature = $feature)]
+                            (Value::[<Option $u:camel>](v), AnyHandle::[<Option $u:camel>](h)) => {
+                                mgr.[<set_option_ $u>](h, v);
+                            }
+                        )*
+                        #[cfg(feature = "bool")]
+                        (Value::Bool(v), AnyHandle::Bool(h)) => {
+                            mgr.set_bool(h, v);
+                        }
+                        #[cfg(feature = "bool")]
+                        (Value::OptionBool(Some(v)), AnyHandle::OptionBool(h)) => {
+                            mgr.set_option_bool(h, v);
+                        }
+                        #[cfg(feature = "bool")]
+                        (Value::OptionBool(None), AnyHandle::OptionBool(h)) => mgr.set_option_bool_none(h),
+                        #[allow(unreachable_patterns)]
+                        _ => return false,
+                    }
+                    true
+                }
+
+                /// Sets the resource of the type of this value at the given index
+                #[cfg(feature = "journal")]
+                pub(crate) fn set_at<B: TrailBackend>(self, mgr: &mut StateManager<B>, id: usize) {
+                    match self {
+                        $(
+                            #[cfg(feature = $feature)]
+                            Value::[<$u:camel>](v) => {
+                                mgr.set_value(id, v);
+                            }
+                            #[cfg(feature = $feature)]
+                            Value::[<Option $u:camel>](v) => {
+                                mgr.set_value(id, v);
+                            }
+                        )*
+                        #[cfg(feature = "bool")]
+                        Value::Bool(v) => {
+                            mgr.set_bool(ReversibleBool::new(mgr, id), v);
+                        }
+                        #[cfg(feature = "bool")]
+                        Value::OptionBool(Some(v)) => {
+                            mgr.set_option_bool(ReversibleOptionBool::new(mgr, id), v);
+                        }
+                        #[cfg(feature = "bool")]
+                        Value::OptionBool(None) => mgr.set_option_bool_none(ReversibleOptionBool::new(mgr, id)),
+                    }
+                }
+
+                /// Sets the resources from `first` to the given values, which are all of the same numeric type
+                #[cfg(feature = "journal")]
+                pub(crate) fn set_range<B: TrailBackend>(mgr: &mut StateManager<B>, first: usize, values: &[Value]) {
+                    match values.first() {
+                        $(
+                            #[cfg(feature = $feature)]
+                            Some(Value::[<$u:camel>](_)) => {
+                                let values = values.iter().map(|value| match value {
+                                    Value::[<$u:camel>](v) => *v,
+                                    _ => panic!("The range mixes values of different types"),
+                                }).collect::<Vec<$u>>();
+                                mgr.set_values(first, &values);
+                            }
+                            #[cfg(feature = $feature)]
+                            Some(Value::[<Option $u:camel>](_)) => {
+                                let values = values.iter().map(|value| match value {
+                                    Value::[<Option $u:camel>](v) => *v,
+                                    _ => panic!("The range mixes values of different types"),
+                                }).collect::<Vec<Option<$u>>>();
+                                mgr.set_values(first, &values);
+                            }
+                        )*
+                        None => {}
+                        #[allow(unreachable_patterns)]
+                        _ => panic!("The booleans can not be set by ranges"),
+                    }
+                }
+            }
+        }
+    };
+}
+
+for_each_managed_type!(values);
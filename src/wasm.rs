@@ -0,0 +1,245 @@
+//Copyright (c) 2023 X. Gillard, A. Dubray
+//
+//Permission is hereby granted, free of charge, to any person obtaining a copy
+//of this software and associated documentation files (the "Software"), to deal
+//in the Software without restriction, including without limitation the rights
+//to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+//copies of the Software, and to permit persons to whom the Software is
+//furnished to do so, subject to the following conditions:
+//
+//The above copyright notice and this permission notice shall be included in all
+//copies or substantial portions of the Software.
+//
+//THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+//IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+//AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+//OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+//SOFTWARE.
+
+
+//! JavaScript bindings of the default manager, so that backtracking searches can run client-side. The
+//! module is compiled to WebAssembly and its JavaScript glue is generated with `wasm-bindgen` (see the
+//! README).
+//!
+//! The handles are the indexes of the resources. The resources created together from a typed array
+//! have consecutive handles, and can be read and written at once with the `*Array` methods.
+//!
+//! ```js
+//! import { StateManager } from "search_trail";
+//!
+//! const mgr = new StateManager();
+//! const first = mgr.manageI32Array(new Int32Array([1, 2, 3]));
+//! mgr.saveState();
+//! mgr.setI32Array(first, new Int32Array([4, 5, 6]));
+//! mgr.restoreState();
+//! console.log(mgr.getI32Array(first, 3)); // Int32Array [1, 2, 3]
+//! ```
+
+use wasm_bindgen::prelude::*;
+
+use crate::*;
+
+/// The default manager, seen from JavaScript
+#[wasm_bindgen(js_name = StateManager)]
+#[derive(Default)]
+pub struct WasmStateManager {
+    mgr: StateManager,
+}
+
+impl WasmStateManager {
+    /// Returns an error if there are not `len` resources of type `T` from `first`
+    fn check_range<T: Managed>(&self, first: usize, len: usize) -> Result<(), TrailError> {
+        match len.checked_sub(1) {
+            None => Ok(()),
+            Some(last) => self.mgr.check_handle::<T>(first.saturating_add(last)),
+        }
+    }
+
+    /// Returns an error if the bool handle is not in the manager, for which `get_bool` would panic
+    fn check_bool(&self, handle: usize) -> Result<(), TrailError> {
+        let len = self.mgr.bools.len();
+        if handle < len {
+            Ok(())
+        } else {
+            Err(TrailError::InvalidHandle { id: handle, len })
+        }
+    }
+}
+
+#[wasm_bindgen(js_class = StateManager)]
+impl WasmStateManager {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The number of saved states
+    #[wasm_bindgen(getter)]
+    pub fn depth(&self) -> usize {
+        self.mgr.depth
+    }
+
+    #[wasm_bindgen(js_name = saveState)]
+    pub fn save_state(&mut self) {
+        self.mgr.save_state();
+    }
+
+    /// Restores the last saved state, or throws if there is none
+    #[wasm_bindgen(js_name = restoreState)]
+    pub fn restore_state(&mut self) -> Result<(), JsError> {
+        if self.mgr.depth == 0 {
+            return Err(JsError::new("no saved state to restore"));
+        }
+        self.mgr.restore_state();
+        Ok(())
+    }
+
+    #[wasm_bindgen(js_name = manageI32)]
+    pub fn manage_i32(&mut self, value: i32) -> usize {
+        self.mgr.manage_value(value)
+    }
+
+    #[wasm_bindgen(js_name = getI32)]
+    pub fn get_i32(&self, handle: usize) -> Result<i32, JsError> {
+        Ok(self.mgr.try_get_value(handle)?)
+    }
+
+    #[wasm_bindgen(js_name = setI32)]
+    pub fn set_i32(&mut self, handle: usize, value: i32) -> Result<i32, JsError> {
+        Ok(self.mgr.try_set_value(handle, value)?)
+    }
+
+    /// Creates a resource for each value, and returns the handle of the first one
+    #[wasm_bindgen(js_name = manageI32Array)]
+    pub fn manage_i32_array(&mut self, values: &[i32]) -> usize {
+        let first = self.mgr.numbers_i32.len();
+        for &value in values {
+            self.mgr.manage_value(value);
+        }
+        first
+    }
+
+    /// Returns the values of the `len` resources from the given handle
+    #[wasm_bindgen(js_name = getI32Array)]
+    pub fn get_i32_array(&self, first: usize, len: usize) -> Result<Vec<i32>, JsError> {
+        self.check_range::<i32>(first, len)?;
+        Ok((first..first + len).map(|id| self.mgr.get_value(id)).collect())
+    }
+
+    /// Sets the resources from the given handle to the given values
+    #[wasm_bindgen(js_name = setI32Array)]
+    pub fn set_i32_array(&mut self, first: usize, values: &[i32]) -> Result<(), JsError> {
+        self.check_range::<i32>(first, values.len())?;
+        self.mgr.set_values(first, values);
+        Ok(())
+    }
+
+    #[wasm_bindgen(js_name = manageF64)]
+    pub fn manage_f64(&mut self, value: f64) -> usize {
+        self.mgr.manage_value(value)
+    }
+
+    #[wasm_bindgen(js_name = getF64)]
+    pub fn get_f64(&self, handle: usize) -> Result<f64, JsError> {
+        Ok(self.mgr.try_get_value(handle)?)
+    }
+
+    #[wasm_bindgen(js_name = setF64)]
+    pub fn set_f64(&mut self, handle: usize, value: f64) -> Result<f64, JsError> {
+        Ok(self.mgr.try_set_value(handle, value)?)
+    }
+
+    /// Creates a resource for each value, and returns the handle of the first one
+    #[wasm_bindgen(js_name = manageF64Array)]
+    pub fn manage_f64_array(&mut self, values: &[f64]) -> usize {
+        let first = self.mgr.numbers_f64.len();
+        for &value in values {
+            self.mgr.manage_value(value);
+        }
+        first
+    }
+
+    /// Returns the values of the `len` resources from the given handle
+    #[wasm_bindgen(js_name = getF64Array)]
+    pub fn get_f64_array(&self, first: usize, len: usize) -> Result<Vec<f64>, JsError> {
+        self.check_range::<f64>(first, len)?;
+        Ok((first..first + len).map(|id| self.mgr.get_value(id)).collect())
+    }
+
+    /// Sets the resources from the given handle to the given values
+    #[wasm_bindgen(js_name = setF64Array)]
+    pub fn set_f64_array(&mut self, first: usize, values: &[f64]) -> Result<(), JsError> {
+        self.check_range::<f64>(first, values.len())?;
+        self.mgr.set_values(first, values);
+        Ok(())
+    }
+
+    #[wasm_bindgen(js_name = manageBool)]
+    pub fn manage_bool(&mut self, value: bool) -> usize {
+        self.mgr.manage_bool(value).0
+    }
+
+    #[wasm_bindgen(js_name = getBool)]
+    pub fn get_bool(&self, handle: usize) -> Result<bool, JsError> {
+        self.check_bool(handle)?;
+        Ok(self.mgr.get_bool(ReversibleBool::new(&self.mgr, handle)))
+    }
+
+    #[wasm_bindgen(js_name = setBool)]
+    pub fn set_bool(&mut self, handle: usize, value: bool) -> Result<bool, JsError> {
+        self.check_bool(handle)?;
+        Ok(self.mgr.set_bool(ReversibleBool::new(&self.mgr, handle), value))
+    }
+
+    #[wasm_bindgen(js_name = flipBool)]
+    pub fn flip_bool(&mut self, handle: usize) -> Result<bool, JsError> {
+        self.check_bool(handle)?;
+        Ok(self.mgr.flip_bool(ReversibleBool::new(&self.mgr, handle)))
+    }
+
+    #[wasm_bindgen(js_name = toString)]
+    pub fn to_js_string(&self) -> String {
+        self.mgr.to_string()
+    }
+}
+
+#[cfg(test)]
+mod test_wasm {
+    use super::*;
+
+    // The errors are JavaScript objects, which can only be created on wasm targets: only the valid
+    // operations are tested natively.
+    #[test]
+    fn arrays_are_restored() {
+        let mut mgr = WasmStateManager::new();
+        let x = mgr.manage_i32(7);
+        let first = mgr.manage_i32_array(&[1, 2, 3]);
+        let y = mgr.manage_f64_array(&[0.5, 1.5]);
+        let b = mgr.manage_bool(false);
+        assert_eq!(1, first);
+        mgr.save_state();
+        mgr.set_i32_array(first, &[4, 5, 6]).unwrap();
+        mgr.set_f64_array(y, &[2.5, 3.5]).unwrap();
+        mgr.flip_bool(b).unwrap();
+        assert_eq!(vec![7, 4, 5, 6], mgr.get_i32_array(x, 4).unwrap());
+        assert_eq!(vec![2.5, 3.5], mgr.get_f64_array(y, 2).unwrap());
+        assert!(mgr.get_bool(b).unwrap());
+        mgr.restore_state().unwrap();
+        assert_eq!(vec![1, 2, 3], mgr.get_i32_array(first, 3).unwrap());
+        assert_eq!(vec![0.5, 1.5], mgr.get_f64_array(y, 2).unwrap());
+        assert!(!mgr.get_bool(b).unwrap());
+        assert_eq!(Vec::<i32>::new(), mgr.get_i32_array(10, 0).unwrap());
+    }
+
+    #[test]
+    fn ranges_are_checked() {
+        let mut mgr = WasmStateManager::new();
+        let first = mgr.manage_i32_array(&[1, 2]);
+        assert!(mgr.check_range::<i32>(first, 2).is_ok());
+        assert_eq!(Err(TrailError::InvalidHandle { id: 2, len: 2 }), mgr.check_range::<i32>(first, 3));
+        assert!(mgr.check_range::<i32>(usize::MAX, 2).is_err());
+        assert!(mgr.check_bool(0).is_err());
+    }
+}
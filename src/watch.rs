@@ -0,0 +1,207 @@
+//Copyright (c) 2023 X. Gillard, A. Dubray
+//
+//Permission is hereby granted, free of charge, to any person obtaining a copy
+//of this software and associated documentation files (the "Software"), to deal
+//in the Software without restriction, including without limitation the rights
+//to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+//copies of the Software, and to permit persons to whom the Software is
+//furnished to do so, subject to the following conditions:
+//
+//The above copyright notice and this permission notice shall be included in all
+//copies or substantial portions of the Software.
+//
+//THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+//IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+//AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+//OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+//SOFTWARE.
+
+
+//! Watch lists whose modifications are undone by restoring the state of the manager. Each list holds
+//! the watchers of something (e.g., a resource or a value of a domain), and the watchers can be added,
+//! removed or moved from one list to another during the search: restoring a level puts them back in
+//! the lists they were in when the level was saved. This is the storage needed by the watched literals
+//! of clauses and by the watched supports of table constraints.
+//!
+//! The modifications are recorded in a log whose length is managed by the manager. The modifications
+//! beyond the managed length have been undone by a restoration, and are reverted the next time the
+//! lists are accessed. The modifications done at the root, which is never restored, are not recorded.
+
+use crate::{ReversibleUsize, StateManager, TrailBackend, UsizeManager};
+
+/// A modification of the watch lists
+#[derive(Debug, Clone)]
+enum WatchOp<W> {
+    /// A watcher has been pushed at the end of the list
+    Push { list: usize },
+    /// The watcher at the given index has been swapped with the last one of the list, and removed
+    Remove { list: usize, index: usize, watcher: W },
+}
+
+/// Lists of watchers, identified by their index, whose modifications are undone by restoring the
+/// state of the manager used to modify them. The order of the watchers in a list is not preserved by
+/// the removals.
+///
+/// #Example
+///
+/// ```
+/// use search_trail::{SaveAndRestore, StateManager, WatchLists};
+///
+/// let mut mgr = StateManager::default();
+/// let mut watches = WatchLists::new(&mut mgr, 3);
+/// watches.watch(&mut mgr, 0, "c1");
+/// mgr.save_state();
+/// watches.move_watcher(&mut mgr, 0, 2, &"c1");
+/// assert_eq!(&["c1"], watches.watchers(&mgr, 2));
+/// mgr.restore_state();
+/// assert_eq!(&["c1"], watches.watchers(&mgr, 0));
+/// assert!(watches.watchers(&mgr, 2).is_empty());
+/// ```
+#[derive(Debug, Clone)]
+pub struct WatchLists<W> {
+    lists: Vec<Vec<W>>,
+    /// The modifications of the lists, including the ones beyond `ops` that have been undone by a
+    /// restoration but not reverted yet
+    log: Vec<WatchOp<W>>,
+    /// Number of modifications in the current state
+    ops: ReversibleUsize,
+}
+
+impl<W: Clone + PartialEq> WatchLists<W> {
+    /// Creates `count` empty lists, trailed by the given manager
+    pub fn new<B: TrailBackend>(mgr: &mut StateManager<B>, count: usize) -> Self {
+        Self {
+            lists: vec![vec![]; count],
+            log: vec![],
+            ops: mgr.manage_usize(0),
+        }
+    }
+
+    /// Adds an empty list and returns its index. The list is not removed by the restorations.
+    pub fn add_list(&mut self) -> usize {
+        self.lists.push(vec![]);
+        self.lists.len() - 1
+    }
+
+    /// Returns the number of lists
+    pub fn len(&self) -> usize {
+        self.lists.len()
+    }
+
+    /// Returns true if there is no list
+    pub fn is_empty(&self) -> bool {
+        self.lists.is_empty()
+    }
+
+    /// Returns the watchers of a list in the current state
+    pub fn watchers<B: TrailBackend>(&mut self, mgr: &StateManager<B>, list: usize) -> &[W] {
+        self.revert(mgr);
+        &self.lists[list]
+    }
+
+    /// Adds a watcher at the end of a list
+    pub fn watch<B: TrailBackend>(&mut self, mgr: &mut StateManager<B>, list: usize, watcher: W) {
+        self.revert(mgr);
+        self.lists[list].push(watcher);
+        self.record(mgr, WatchOp::Push { list });
+    }
+
+    /// Removes a watcher from a list, and returns false if it was not in the list. The last watcher of
+    /// the list takes its place.
+    pub fn unwatch<B: TrailBackend>(&mut self, mgr: &mut StateManager<B>, list: usize, watcher: &W) -> bool {
+        self.revert(mgr);
+        let Some(index) = self.lists[list].iter().position(|w| w == watcher) else {
+            return false;
+        };
+        let watcher = self.lists[list].swap_remove(index);
+        self.record(mgr, WatchOp::Remove { list, index, watcher });
+        true
+    }
+
+    /// Moves a watcher from a list to the end of another one, and returns false if it was not in the
+    /// first list
+    pub fn move_watcher<B: TrailBackend>(&mut self, mgr: &mut StateManager<B>, from: usize, to: usize, watcher: &W) -> bool {
+        if !self.unwatch(mgr, from, watcher) {
+            return false;
+        }
+        self.watch(mgr, to, watcher.clone());
+        true
+    }
+
+    /// Records a modification in the log, unless it is done at the root
+    fn record<B: TrailBackend>(&mut self, mgr: &mut StateManager<B>, op: WatchOp<W>) {
+        if mgr.depth == 0 {
+            return;
+        }
+        self.log.push(op);
+        mgr.set_usize(self.ops, self.log.len());
+    }
+
+    /// Reverts the modifications undone by the restorations of the manager, from the most recent one
+    fn revert<B: TrailBackend>(&mut self, mgr: &StateManager<B>) {
+        let ops = mgr.get_usize(self.ops);
+        while self.log.len() > ops {
+            match self.log.pop().unwrap() {
+                WatchOp::Push { list } => {
+                    self.lists[list].pop();
+                }
+                WatchOp::Remove { list, index, watcher } => {
+                    let list = &mut self.lists[list];
+                    list.push(watcher);
+                    let last = list.len() - 1;
+                    list.swap(index, last);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_watch_lists {
+    use crate::{SaveAndRestore, StateManager, WatchLists};
+
+    #[test]
+    fn moved_watchers_are_restored() {
+        let mut mgr = StateManager::default();
+        let mut watches = WatchLists::new(&mut mgr, 4);
+        for clause in 0..3 {
+            watches.watch(&mut mgr, 0, clause);
+            watches.watch(&mut mgr, 1, clause);
+        }
+        mgr.save_state();
+        assert!(watches.move_watcher(&mut mgr, 0, 2, &0));
+        assert!(!watches.move_watcher(&mut mgr, 0, 2, &0));
+        mgr.save_state();
+        assert!(watches.move_watcher(&mut mgr, 0, 3, &1));
+        assert!(watches.unwatch(&mut mgr, 1, &2));
+        assert_eq!(&[2], watches.watchers(&mgr, 0));
+        assert_eq!(&[0, 1], watches.watchers(&mgr, 1));
+        mgr.restore_state();
+        assert_eq!(&[2, 1], watches.watchers(&mgr, 0));
+        assert_eq!(&[0, 1, 2], watches.watchers(&mgr, 1));
+        watches.watch(&mut mgr, 3, 5);
+        mgr.restore_state();
+        assert_eq!(&[0, 1, 2], watches.watchers(&mgr, 0));
+        assert!(watches.watchers(&mgr, 2).is_empty());
+        assert!(watches.watchers(&mgr, 3).is_empty());
+    }
+
+    #[test]
+    fn moves_at_the_root_are_not_recorded() {
+        let mut mgr = StateManager::default();
+        let mut watches = WatchLists::new(&mut mgr, 2);
+        watches.watch(&mut mgr, 0, 0);
+        for i in 0..100 {
+            assert!(watches.move_watcher(&mut mgr, i % 2, (i + 1) % 2, &0));
+        }
+        assert!(watches.log.is_empty());
+        mgr.save_state();
+        assert!(watches.move_watcher(&mut mgr, 0, 1, &0));
+        assert_eq!(2, watches.log.len());
+        mgr.restore_state();
+        assert_eq!(&[0], watches.watchers(&mgr, 0));
+        assert!(watches.log.is_empty());
+    }
+}